@@ -53,6 +53,9 @@ pub struct Extensions {
 	pub fork_blocks: sc_client::ForkBlocks<Block>,
 	/// Known bad block hashes.
 	pub bad_blocks: sc_client::BadBlocks<Block>,
+	/// GRANDPA voting rules enabled on top of the default ones.
+	#[serde(default)]
+	pub grandpa_voting_rules: grandpa::VotingRulesConfig,
 }
 
 /// Specialized `ChainSpec`.
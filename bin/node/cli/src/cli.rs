@@ -24,6 +24,7 @@ use structopt::StructOpt;
 use sc_cli::{display_role, parse_and_prepare, GetSharedParams, ParseAndPrepare};
 use crate::{service, ChainSpec, load_spec};
 use crate::factory_impl::FactoryState;
+use sp_core::Bytes;
 use node_transaction_factory::RuntimeAdapter;
 
 /// Custom subcommands.
@@ -46,6 +47,61 @@ impl GetSharedParams for CustomSubcommands {
 	}
 }
 
+/// Custom parameters of the `run` command.
+#[derive(Debug, StructOpt, Clone)]
+pub struct RunParams {
+	/// Never vote in GRANDPA past the block with the given number.
+	///
+	/// Overrides the height configured in the chain spec, useful to halt
+	/// finality at a known height.
+	#[structopt(long = "grandpa-pause-at", value_name = "BLOCK")]
+	pub grandpa_pause_at: Option<u64>,
+
+	/// Only vote in GRANDPA on blocks whose height has been known for the given number of milliseconds.
+	#[structopt(long = "grandpa-vote-lag", value_name = "MILLIS")]
+	pub grandpa_vote_lag: Option<u64>,
+
+	/// Hex-encoded BABE public key of a trusted block author.
+	///
+	/// Can be passed multiple times. Used together with `--grandpa-trusted-confirmations`.
+	#[structopt(
+		long = "grandpa-trusted-author",
+		value_name = "PUBLIC_KEY",
+		parse(try_from_str = parse_public_key),
+		requires = "grandpa-trusted-confirmations"
+	)]
+	pub grandpa_trusted_authors: Vec<Vec<u8>>,
+
+	/// Never vote in GRANDPA past a block that isn't built upon by the given
+	/// number of distinct trusted authors.
+	#[structopt(
+		long = "grandpa-trusted-confirmations",
+		value_name = "COUNT",
+		requires = "grandpa-trusted-author"
+	)]
+	pub grandpa_trusted_confirmations: Option<u32>,
+}
+
+impl RunParams {
+	/// GRANDPA voting rules enabled on the command line.
+	pub fn voting_rules(&self) -> grandpa::VotingRulesConfig {
+		grandpa::VotingRulesConfig {
+			pause_at: self.grandpa_pause_at,
+			lag_by_millis: self.grandpa_vote_lag,
+			trusted_confirmations: self.grandpa_trusted_confirmations.map(|confirmations|
+				grandpa::TrustedConfirmationsConfig {
+					authors: self.grandpa_trusted_authors.iter().cloned().map(Bytes).collect(),
+					confirmations,
+				}
+			),
+		}
+	}
+}
+
+fn parse_public_key(key: &str) -> Result<Vec<u8>, String> {
+	sp_core::bytes::from_hex(key).map_err(|e| format!("Invalid public key {}: {:?}", key, e))
+}
+
 /// The `factory` command used to generate transactions.
 /// Please note: this command currently only works on an empty database!
 #[derive(Debug, StructOpt, Clone)]
@@ -96,9 +152,9 @@ pub fn run<I, T, E>(args: I, exit: E, version: sc_cli::VersionInfo) -> error::Re
 {
 	type Config<A, B> = Configuration<(), A, B>;
 
-	match parse_and_prepare::<CustomSubcommands, NoCustom, _>(&version, "substrate-node", args) {
+	match parse_and_prepare::<CustomSubcommands, RunParams, _>(&version, "substrate-node", args) {
 		ParseAndPrepare::Run(cmd) => cmd.run(load_spec, exit,
-		|exit, _cli_args, _custom_args, config: Config<_, _>| {
+		|exit, _cli_args, custom_args, config: Config<_, _>| {
 			info!("{}", version.name);
 			info!("  version {}", config.full_version());
 			info!("  by Parity Technologies, 2017-2019");
@@ -115,7 +171,7 @@ pub fn run<I, T, E>(args: I, exit: E, version: sc_cli::VersionInfo) -> error::Re
 				),
 				_ => run_until_exit(
 					runtime,
-					service::new_full(config)?,
+					service::new_full_with_voting_rules(config, custom_args.voting_rules())?,
 					exit
 				),
 			}
@@ -130,6 +186,8 @@ pub fn run<I, T, E>(args: I, exit: E, version: sc_cli::VersionInfo) -> error::Re
 		ParseAndPrepare::PurgeChain(cmd) => cmd.run(load_spec),
 		ParseAndPrepare::RevertChain(cmd) => cmd.run_with_builder(|config: Config<_, _>|
 			Ok(new_full_start!(config).0), load_spec),
+		ParseAndPrepare::TryRuntime(cmd) => cmd.run_with_builder(|config: Config<_, _>|
+			Ok(new_full_start!(config).0), load_spec),
 		ParseAndPrepare::CustomCommand(CustomSubcommands::Factory(cli_args)) => {
 			let mut config: Config<_, _> = sc_cli::create_config_with_db_path(
 				load_spec,
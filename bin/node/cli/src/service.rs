@@ -65,6 +65,8 @@ macro_rules! new_full_start {
 			.with_transaction_pool(|config, client, _fetcher| {
 				let pool_api = sc_transaction_pool::FullChainApi::new(client.clone());
 				let pool = sc_transaction_pool::BasicPool::new(config, pool_api);
+				let best_block = sp_runtime::generic::BlockId::hash(client.chain_info().best_hash);
+				futures::executor::block_on(pool.reload_journal(&best_block));
 				let maintainer = sc_transaction_pool::FullBasicPoolMaintainer::new(pool.pool().clone(), client);
 				let maintainable_pool = sp_transaction_pool::MaintainableTransactionPool::new(pool, maintainer);
 				Ok(maintainable_pool)
@@ -98,9 +100,23 @@ macro_rules! new_full_start {
 
 				import_setup = Some((block_import, grandpa_link, babe_link));
 				Ok(import_queue)
-			})?
-			.with_rpc_extensions(|client, pool, _backend, fetcher, _remote_blockchain| -> Result<RpcExtension, _> {
-				Ok(node_rpc::create(client, pool, node_rpc::LightDeps::none(fetcher)))
+			})?;
+
+		let spawn_handle = builder.spawn_task_handle();
+		let builder = builder
+			.with_rpc_extensions(|client, pool, backend, fetcher, _remote_blockchain| -> Result<RpcExtension, _> {
+				let (_, grandpa_link, _) = import_setup.as_ref()
+					.expect("Link Half is present for Full Services or setup failed before. qed");
+				let grandpa_deps = node_rpc::GrandpaDeps {
+					shared_voter_state: grandpa_link.shared_voter_state(),
+					justification_stream: grandpa_link.justification_stream(),
+					finality_proof_provider: std::sync::Arc::new(
+						grandpa::FinalityProofProvider::new(backend, client.clone())
+					),
+					subscriptions: sc_rpc::Subscriptions::new(std::sync::Arc::new(spawn_handle)),
+				};
+
+				Ok(node_rpc::create(client, pool, node_rpc::LightDeps::none(fetcher), Some(grandpa_deps)))
 			})?;
 
 		(builder, import_setup, inherent_data_providers)
@@ -112,7 +128,7 @@ macro_rules! new_full_start {
 /// We need to use a macro because the test suit doesn't work with an opaque service. It expects
 /// concrete types instead.
 macro_rules! new_full {
-	($config:expr, $with_startup_data: expr) => {{
+	($config:expr, $with_startup_data: expr, $voting_rules: expr) => {{
 		use futures01::Stream;
 		use futures::{
 			compat::Stream01CompatExt,
@@ -127,12 +143,21 @@ macro_rules! new_full {
 			name,
 			disable_grandpa,
 			sentry_nodes,
+			voting_rules,
 		) = (
 			$config.roles.is_authority(),
 			$config.force_authoring,
 			$config.name.clone(),
 			$config.disable_grandpa,
 			$config.network.sentry_nodes.clone(),
+			{
+				use sc_chain_spec::Extension;
+				$config.chain_spec.extensions()
+					.get::<grandpa::VotingRulesConfig>()
+					.cloned()
+					.unwrap_or_default()
+					.merge($voting_rules)
+			},
 		);
 
 		// sentry nodes announce themselves as authorities to the network
@@ -153,6 +178,9 @@ macro_rules! new_full {
 
 		($with_startup_data)(&block_import, &babe_link);
 
+		// the authors of the blocks are looked up by the voting rules
+		let author_link = babe_link.clone();
+
 		if participates_in_consensus {
 			let proposer = sc_basic_authority::ProposerFactory {
 				client: service.client(),
@@ -240,7 +268,14 @@ macro_rules! new_full {
 					inherent_data_providers: inherent_data_providers.clone(),
 					on_exit: service.on_exit(),
 					telemetry_on_connect: Some(service.telemetry_on_connect_stream()),
-					voting_rule: grandpa::VotingRulesBuilder::default().build(),
+					voting_rule: {
+						let client = service.client();
+						grandpa::VotingRulesBuilder::default()
+							.add_config(&voting_rules, move |header: &node_primitives::Header| {
+								author_link.block_author(&*client, header).map(|author| codec::Encode::encode(&author))
+							})
+							.build()
+					},
 					executor: service.spawn_task_handle(),
 				};
 				// the GRANDPA voter task is considered infallible, i.e.
@@ -258,6 +293,9 @@ macro_rules! new_full {
 
 		Ok((service, inherent_data_providers))
 	}};
+	($config:expr, $with_startup_data: expr) => {{
+		new_full!($config, $with_startup_data, grandpa::VotingRulesConfig::default())
+	}};
 	($config:expr) => {{
 		new_full!($config, |_, _| {})
 	}}
@@ -310,7 +348,33 @@ pub fn new_full<C: Send + Default + 'static>(config: NodeConfiguration<C>)
 	ServiceError,
 >
 {
-	new_full!(config).map(|(service, _)| service)
+	new_full_with_voting_rules(config, Default::default())
+}
+
+/// Builds a new service for a full client, enabling the given GRANDPA voting
+/// rules on top of the ones configured in the chain spec.
+pub fn new_full_with_voting_rules<C: Send + Default + 'static>(
+	config: NodeConfiguration<C>,
+	voting_rules: grandpa::VotingRulesConfig,
+)
+-> Result<
+	Service<
+		ConcreteBlock,
+		ConcreteClient,
+		LongestChain<ConcreteBackend, ConcreteBlock>,
+		NetworkStatus<ConcreteBlock>,
+		NetworkService<ConcreteBlock, crate::service::NodeProtocol, <ConcreteBlock as BlockT>::Hash>,
+		ConcreteTransactionPool,
+		OffchainWorkers<
+			ConcreteClient,
+			<ConcreteBackend as sc_client_api::backend::Backend<Block, Blake2Hasher>>::OffchainStorage,
+			ConcreteBlock,
+		>
+	>,
+	ServiceError,
+>
+{
+	new_full!(config, |_, _| {}, voting_rules).map(|(service, _)| service)
 }
 
 /// Builds a new service for a light client.
@@ -318,6 +382,9 @@ pub fn new_light<C: Send + Default + 'static>(config: NodeConfiguration<C>)
 -> Result<impl AbstractService, ServiceError> {
 	type RpcExtension = jsonrpc_core::IoHandler<sc_rpc::Metadata>;
 	let inherent_data_providers = InherentDataProviders::new();
+	let grandpa_observer = config.grandpa_observer;
+	let name = config.name.clone();
+	let mut light_import_setup = None;
 
 	let service = ServiceBuilder::new_light::<Block, RuntimeApi, node_executor::Executor>(config)?
 		.with_select_chain(|_config, backend| {
@@ -344,6 +411,7 @@ pub fn new_light<C: Send + Default + 'static>(config: NodeConfiguration<C>)
 			)?;
 
 			let finality_proof_import = grandpa_block_import.clone();
+			light_import_setup = Some(grandpa_block_import.clone());
 			let finality_proof_request_builder =
 				finality_proof_import.create_finality_proof_request_builder();
 
@@ -370,17 +438,40 @@ pub fn new_light<C: Send + Default + 'static>(config: NodeConfiguration<C>)
 		.with_finality_proof_provider(|client, backend|
 			Ok(Arc::new(GrandpaFinalityProofProvider::new(backend, client)) as _)
 		)?
-		.with_rpc_extensions(|client, pool, _backend, fetcher, remote_blockchain| -> Result<RpcExtension, _> {
+		.with_rpc_extensions(|client, pool, backend, fetcher, remote_blockchain| -> Result<RpcExtension, _> {
 			let fetcher = fetcher
 				.ok_or_else(|| "Trying to start node RPC without active fetcher")?;
 			let remote_blockchain = remote_blockchain
 				.ok_or_else(|| "Trying to start node RPC without active remote blockchain")?;
 
 			let light_deps = node_rpc::LightDeps { remote_blockchain, fetcher };
-			Ok(node_rpc::create(client, pool, Some(light_deps)))
+			Ok(node_rpc::create(client, pool, Some(light_deps), node_rpc::GrandpaDeps::none(backend)))
 		})?
 		.build()?;
 
+	if grandpa_observer {
+		let light_import = light_import_setup.take()
+			.expect("Light block import is present for Light Services or setup failed before. qed");
+
+		let config = grandpa::Config {
+			gossip_duration: std::time::Duration::from_millis(333),
+			justification_period: 512,
+			name: Some(name),
+			observer_enabled: true,
+			keystore: None,
+			is_authority: false,
+		};
+
+		// follow finality by observing GRANDPA commits on top of the light client
+		service.spawn_task(grandpa::run_light_grandpa_observer(
+			config,
+			light_import,
+			service.network(),
+			service.on_exit(),
+			service.spawn_task_handle(),
+		)?);
+	}
+
 	Ok(service)
 }
 
@@ -634,4 +725,36 @@ mod tests {
 			],
 		)
 	}
+
+	#[test]
+	fn full_node_should_serve_grandpa_rpc() {
+		use futures01::Future;
+
+		// given
+		let root = tempfile::tempdir().expect("Creates the node directory");
+		let chain_spec = crate::chain_spec::tests::integration_test_config_with_single_authority();
+		let config = sc_service_test::node_config(
+			0,
+			&chain_spec,
+			sc_service::Roles::FULL,
+			None,
+			30600,
+			&root,
+		);
+		let service = new_full(config).expect("Creates the full node");
+		let (sender, _receiver) = futures01::sync::mpsc::channel(1);
+		let session = sc_service::RpcSession::new(sender);
+		let request = r#"{"jsonrpc":"2.0","method":"grandpa_roundState","params":[],"id":1}"#;
+
+		// when
+		let response = service.rpc_query(&session, request).wait()
+			.expect("The RPC query does not fail")
+			.expect("The request has a response");
+
+		// then
+		let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+		assert_eq!(response["result"]["setId"], 0);
+		assert_eq!(response["result"]["round"], 1);
+		assert_eq!(response["result"]["prevotes"]["missing"].as_array().unwrap().len(), 1);
+	}
 }
@@ -113,7 +113,7 @@ mod tests {
 	}
 
 	fn executor() -> NativeExecutor<Executor> {
-		NativeExecutor::new(WasmExecutionMethod::Interpreted, None)
+		NativeExecutor::new(WasmExecutionMethod::Interpreted, None, 8)
 	}
 
 	fn set_heap_pages<E: Externalities>(ext: &mut E, heap_pages: u64) {
@@ -949,6 +949,32 @@ mod tests {
 		assert!(t.ext().storage_changes_root(&GENESIS_HASH.encode()).unwrap().is_some());
 	}
 
+	#[test]
+	fn wasm_block_execution_enters_spans() {
+		let mut t = new_test_ext(COMPACT_CODE, false);
+		let (block1, _) = blocks();
+
+		let collector = sc_tracing::SpanCollector::new("frame_executive,pallet_balances");
+		collector.collect(|| executor_call::<NeverNativeValue, fn() -> _>(
+			&mut t,
+			"Core_execute_block",
+			&block1.0,
+			false,
+			None,
+		).0.unwrap());
+		let (spans, _) = collector.finish();
+
+		let spans = spans.into_iter()
+			.map(|span| (span.target, span.name))
+			.collect::<Vec<_>>();
+		// the timestamp inherent and the transfer
+		assert_eq!(
+			spans.iter().filter(|(target, name)| target == "frame_executive" && name == "apply_extrinsic").count(),
+			2,
+		);
+		assert!(spans.contains(&("pallet_balances".to_string(), "transfer".to_string())));
+	}
+
 	#[test]
 	fn should_import_block_with_test_client() {
 		use node_testing::client::{
@@ -33,6 +33,8 @@ use std::sync::Arc;
 
 use node_primitives::{Block, AccountId, Index, Balance};
 use node_runtime::UncheckedExtrinsic;
+use sc_finality_grandpa::{FinalityProofProvider, GrandpaJustificationStream, SharedVoterState};
+use sp_core::Blake2Hasher;
 use sp_runtime::traits::ProvideRuntimeApi;
 use sp_transaction_pool::TransactionPool;
 
@@ -54,33 +56,59 @@ impl<F> LightDeps<F> {
 	}
 }
 
+/// Dependencies of the GRANDPA RPC, only available on full nodes.
+pub struct GrandpaDeps<B> {
+	/// State of the voter, reported by `grandpa_roundState`.
+	pub shared_voter_state: SharedVoterState<Block>,
+	/// Stream of the justifications of the finalized blocks.
+	pub justification_stream: GrandpaJustificationStream<Block>,
+	/// Provider of the finality proofs.
+	pub finality_proof_provider: Arc<FinalityProofProvider<B, Block>>,
+	/// Subscriptions to the justifications.
+	pub subscriptions: sc_rpc::Subscriptions,
+}
+
+impl<B> GrandpaDeps<B> {
+	/// Create empty `GrandpaDeps` with the given backend type `B`.
+	///
+	/// This is a convenience method to be used in the service builder of light clients,
+	/// to make sure the type of the `GrandpaDeps<B>` is matching.
+	pub fn none(_: Arc<B>) -> Option<Self> {
+		None
+	}
+}
+
 /// Instantiate all RPC extensions.
 ///
-/// If you provide `LightDeps`, the system is configured for light client.
-pub fn create<C, P, M, F>(
+/// If you provide `LightDeps`, the system is configured for light client. The GRANDPA RPC is
+/// only registered when `GrandpaDeps` are provided.
+pub fn create<C, P, F, B>(
 	client: Arc<C>,
 	pool: Arc<P>,
 	light_deps: Option<LightDeps<F>>,
-) -> jsonrpc_core::IoHandler<M> where
+	grandpa_deps: Option<GrandpaDeps<B>>,
+) -> jsonrpc_core::IoHandler<sc_rpc::Metadata> where
 	C: ProvideRuntimeApi,
 	C: sc_client::blockchain::HeaderBackend<Block>,
 	C: Send + Sync + 'static,
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
+	C::Api: substrate_frame_rpc_system::BlockBuilderApi<Block>,
 	C::Api: pallet_contracts_rpc::ContractsRuntimeApi<Block, AccountId, Balance>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance, UncheckedExtrinsic>,
 	F: sc_client::light::fetcher::Fetcher<Block> + 'static,
 	P: TransactionPool + 'static,
-	M: jsonrpc_core::Metadata + Default,
+	B: sc_client_api::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
 {
 	use substrate_frame_rpc_system::{FullSystem, LightSystem, SystemApi};
 	use pallet_contracts_rpc::{Contracts, ContractsApi};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
+	use sc_finality_grandpa_rpc::{GrandpaApi, GrandpaRpcHandler};
 
 	let mut io = jsonrpc_core::IoHandler::default();
 
 	if let Some(LightDeps { remote_blockchain, fetcher }) = light_deps {
 		io.extend_with(
-			SystemApi::<AccountId, Index>::to_delegate(LightSystem::new(client, remote_blockchain, fetcher, pool))
+			SystemApi::<_, AccountId, Index>::to_delegate(LightSystem::new(client, remote_blockchain, fetcher, pool))
 		);
 	} else {
 		io.extend_with(
@@ -97,5 +125,21 @@ pub fn create<C, P, M, F>(
 			TransactionPaymentApi::to_delegate(TransactionPayment::new(client))
 		);
 	}
+
+	if let Some(GrandpaDeps {
+		shared_voter_state,
+		justification_stream,
+		finality_proof_provider,
+		subscriptions,
+	}) = grandpa_deps {
+		io.extend_with(
+			GrandpaApi::to_delegate(GrandpaRpcHandler::new(
+				shared_voter_state,
+				justification_stream,
+				finality_proof_provider,
+				subscriptions,
+			))
+		);
+	}
 	io
 }
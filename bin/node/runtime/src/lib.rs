@@ -79,7 +79,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	// implementation changes and behavior does not, then leave spec_version as
 	// is and increment impl_version.
 	spec_version: 199,
-	impl_version: 199,
+	impl_version: 200,
 	apis: RUNTIME_API_VERSIONS,
 };
 
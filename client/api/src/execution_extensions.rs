@@ -20,18 +20,18 @@
 //! strategy for the runtime calls and provide the right `Externalities`
 //! extensions to support APIs for particular execution context & capabilities.
 
-use std::sync::{Weak, Arc};
+use std::{path::PathBuf, sync::{Weak, Arc}, time::{Duration, Instant}};
 use codec::Decode;
 use sp_core::{
 	ExecutionContext,
 	offchain::{self, OffchainExt, TransactionPoolExt},
-	traits::{BareCryptoStorePtr, KeystoreExt},
+	traits::{BareCryptoStorePtr, KeystoreExt, ExecutionLimits, ExecutionLimitsExt},
 };
 use sp_runtime::{
 	generic::BlockId,
 	traits,
 };
-use sp_state_machine::{ExecutionStrategy, ExecutionManager, DefaultHandler};
+use sp_state_machine::{ExecutionStrategy, ExecutionManager, DefaultHandler, ExecutionMismatchDump};
 use sp_externalities::Extensions;
 use parking_lot::RwLock;
 
@@ -62,6 +62,30 @@ impl Default for ExecutionStrategies {
 	}
 }
 
+/// Limits on the resources consumed by the calls made outside of consensus, i.e. by RPC, offchain
+/// workers and transaction validation.
+///
+/// Only enforced when the executor meters the Wasm execution.
+#[derive(Debug, Clone, Default)]
+pub struct CallLimits {
+	/// Maximum duration of a call.
+	pub timeout: Option<Duration>,
+	/// Maximum fuel consumed by a call, roughly the number of executed Wasm instructions.
+	pub fuel: Option<u64>,
+}
+
+impl CallLimits {
+	/// Create the extension enforcing the limits on a call starting now, if there is any limit.
+	pub fn extension(&self) -> Option<ExecutionLimitsExt> {
+		if self.timeout.is_none() && self.fuel.is_none() {
+			return None;
+		}
+
+		let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+		Some(ExecutionLimitsExt(ExecutionLimits::new(deadline, self.fuel)))
+	}
+}
+
 /// A producer of execution extensions for offchain calls.
 ///
 /// This crate aggregates extensions available for the offchain calls
@@ -69,6 +93,8 @@ impl Default for ExecutionStrategies {
 /// for each call, based on required `Capabilities`.
 pub struct ExecutionExtensions<Block: traits::Block> {
 	strategies: ExecutionStrategies,
+	call_limits: CallLimits,
+	mismatch_dump: Option<PathBuf>,
 	keystore: Option<BareCryptoStorePtr>,
 	transaction_pool: RwLock<Option<Weak<dyn sp_transaction_pool::OffchainSubmitTransaction<Block>>>>,
 }
@@ -77,6 +103,8 @@ impl<Block: traits::Block> Default for ExecutionExtensions<Block> {
 	fn default() -> Self {
 		Self {
 			strategies: Default::default(),
+			call_limits: Default::default(),
+			mismatch_dump: None,
 			keystore: None,
 			transaction_pool: RwLock::new(None),
 		}
@@ -90,7 +118,26 @@ impl<Block: traits::Block> ExecutionExtensions<Block> {
 		keystore: Option<BareCryptoStorePtr>,
 	) -> Self {
 		let transaction_pool = RwLock::new(None);
-		Self { strategies, keystore, transaction_pool }
+		Self {
+			strategies,
+			call_limits: Default::default(),
+			mismatch_dump: None,
+			keystore,
+			transaction_pool,
+		}
+	}
+
+	/// Set the limits on the resources consumed by the calls made outside of consensus.
+	pub fn with_call_limits(mut self, call_limits: CallLimits) -> Self {
+		self.call_limits = call_limits;
+		self
+	}
+
+	/// Dump the mismatches between the native and wasm executions of the calls made with
+	/// `ExecutionStrategy::Both` to files in `directory`.
+	pub fn with_mismatch_dump(mut self, directory: Option<PathBuf>) -> Self {
+		self.mismatch_dump = directory;
+		self
 	}
 
 	/// Get a reference to the execution strategies.
@@ -98,6 +145,16 @@ impl<Block: traits::Block> ExecutionExtensions<Block> {
 		&self.strategies
 	}
 
+	/// Get a reference to the limits on the calls made outside of consensus.
+	pub fn call_limits(&self) -> &CallLimits {
+		&self.call_limits
+	}
+
+	/// The extension dumping the execution mismatches of a call identified by `label`, if enabled.
+	pub fn mismatch_dump(&self, label: impl Into<String>) -> Option<ExecutionMismatchDump> {
+		self.mismatch_dump.as_ref().map(|directory| ExecutionMismatchDump::new(directory.clone(), label))
+	}
+
 	/// Register transaction pool extension.
 	///
 	/// To break retain cycle between `Client` and `TransactionPool` we require this
@@ -152,6 +209,16 @@ impl<Block: traits::Block> ExecutionExtensions<Block> {
 			}
 		}
 
+		if let ExecutionContext::OffchainCall(_) = context {
+			if let Some(limits) = self.call_limits.extension() {
+				extensions.register(limits);
+			}
+		}
+
+		if let Some(dump) = self.mismatch_dump(format!("call at {:?}", at)) {
+			extensions.register(dump);
+		}
+
 		if let ExecutionContext::OffchainCall(Some(ext)) = context {
 			extensions.register(
 				OffchainExt::new(offchain::LimitedExternalities::new(capabilities, ext.0))
@@ -26,7 +26,7 @@ mod execution_strategy;
 pub mod error;
 pub mod informant;
 
-use sc_client_api::execution_extensions::ExecutionStrategies;
+use sc_client_api::execution_extensions::{ExecutionStrategies, CallLimits};
 use sc_service::{
 	config::{Configuration, DatabaseConfig, KeystoreConfig},
 	ServiceBuilderCommand,
@@ -36,14 +36,16 @@ use sc_network::{
 	self,
 	multiaddr::Protocol,
 	config::{
-		NetworkConfiguration, TransportConfig, NonReservedPeerMode, NodeKeyConfig, build_multiaddr
+		NetworkConfiguration, TransportConfig, NonReservedPeerMode, NodeKeyConfig, BandwidthConfig,
+		build_multiaddr,
 	},
 };
-use sp_core::H256;
+use sp_core::{H256, storage::{StorageKey, StorageData}};
 
 use std::{
 	io::{Write, Read, Seek, Cursor, stdin, stdout, ErrorKind}, iter, fmt::Debug, fs::{self, File},
-	net::{Ipv4Addr, SocketAddr}, path::{Path, PathBuf}, str::FromStr, pin::Pin, task::Poll
+	net::{Ipv4Addr, SocketAddr}, path::{Path, PathBuf}, str::FromStr, pin::Pin, task::Poll,
+	time::Duration, collections::BTreeMap,
 };
 
 use names::{Generator, Name};
@@ -54,7 +56,7 @@ pub use structopt::clap::App;
 use params::{
 	RunCmd, PurgeChainCmd, RevertCmd, ImportBlocksCmd, ExportBlocksCmd, BuildSpecCmd,
 	NetworkConfigurationParams, MergeParameters, TransactionPoolParams,
-	NodeKeyParams, NodeKeyType, Cors, CheckBlockCmd,
+	NodeKeyParams, NodeKeyType, Cors, CheckBlockCmd, TryRuntimeCmd,
 };
 pub use params::{NoCustom, CoreParams, SharedParams, ImportParams, ExecutionStrategy};
 pub use traits::GetSharedParams;
@@ -70,8 +72,15 @@ use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
 const DEFAULT_NETWORK_CONFIG_PATH : &'static str = "network";
 /// default sub directory to store database
 const DEFAULT_DB_CONFIG_PATH : &'static str = "db";
+/// default sub directory to store transaction pool journal
+const DEFAULT_TXPOOL_CONFIG_PATH : &'static str = "txpool";
 /// default sub directory for the key store
 const DEFAULT_KEYSTORE_CONFIG_PATH : &'static str =  "keystore";
+/// default sub directory to cache the compiled Wasm runtimes
+const DEFAULT_WASM_CACHE_CONFIG_PATH : &'static str = "wasm-cache";
+
+/// default file permissions of the IPC RPC socket
+const DEFAULT_IPC_PERMISSIONS: u32 = 0o600;
 
 /// The maximum number of characters for a node name.
 const NODE_NAME_MAX_LENGTH: usize = 32;
@@ -241,6 +250,9 @@ where
 		params::CoreParams::Revert(params) => ParseAndPrepare::RevertChain(
 			ParseAndPrepareRevert { params, version }
 		),
+		params::CoreParams::TryRuntime(params) => ParseAndPrepare::TryRuntime(
+			TryRuntime { params, version }
+		),
 		params::CoreParams::Custom(params) => ParseAndPrepare::CustomCommand(params),
 	};
 	init_logger(args.shared_params().and_then(|p| p.log.as_ref()).map(|v| v.as_ref()).unwrap_or(""));
@@ -275,6 +287,8 @@ pub enum ParseAndPrepare<'a, CC, RP> {
 	PurgeChain(ParseAndPreparePurge<'a>),
 	/// Command ready to revert the chain.
 	RevertChain(ParseAndPrepareRevert<'a>),
+	/// Command to check a runtime upgrade.
+	TryRuntime(TryRuntime<'a>),
 	/// An additional custom command passed to `parse_and_prepare`.
 	CustomCommand(CC),
 }
@@ -290,6 +304,7 @@ impl<'a, CC, RP> ParseAndPrepare<'a, CC, RP> where CC: GetSharedParams {
 			ParseAndPrepare::CheckBlock(c) => Some(&c.params.shared_params),
 			ParseAndPrepare::PurgeChain(c) => Some(&c.params.shared_params),
 			ParseAndPrepare::RevertChain(c) => Some(&c.params.shared_params),
+			ParseAndPrepare::TryRuntime(c) => Some(&c.params.shared_params),
 			ParseAndPrepare::CustomCommand(c) => c.shared_params(),
 		}
 	}
@@ -523,14 +538,7 @@ impl<'a> CheckBlock<'a> {
 		let mut config = create_config_with_db_path(spec_factory, &self.params.shared_params, self.version)?;
 		fill_import_params(&mut config, &self.params.import_params, sc_service::Roles::FULL)?;
 
-		let input = if self.params.input.starts_with("0x") { &self.params.input[2..] } else { &self.params.input[..] };
-		let block_id = match FromStr::from_str(input) {
-			Ok(hash) => BlockId::hash(hash),
-			Err(_) => match self.params.input.parse::<u32>() {
-				Ok(n) => BlockId::number((n as u32).into()),
-				Err(_) => return Err(error::Error::Input("Invalid hash or number specified".into())),
-			}
-		};
+		let block_id = parse_block_id(&self.params.input)?;
 
 		let start = std::time::Instant::now();
 		let check = builder(config)?
@@ -543,6 +551,59 @@ impl<'a> CheckBlock<'a> {
 	}
 }
 
+/// Parse a block hash or number.
+fn parse_block_id<B: BlockT>(input: &str) -> error::Result<BlockId<B>> where B::Hash: FromStr {
+	let hash = if input.starts_with("0x") { &input[2..] } else { input };
+	match FromStr::from_str(hash) {
+		Ok(hash) => Ok(BlockId::hash(hash)),
+		Err(_) => match input.parse::<u32>() {
+			Ok(n) => Ok(BlockId::number(n.into())),
+			Err(_) => Err(error::Error::Input("Invalid hash or number specified".into())),
+		}
+	}
+}
+
+/// Command to check a runtime upgrade.
+pub struct TryRuntime<'a> {
+	params: TryRuntimeCmd,
+	version: &'a VersionInfo,
+}
+
+impl<'a> TryRuntime<'a> {
+	/// Runs the command and checks the runtime upgrade.
+	pub fn run_with_builder<C, G, E, F, B, S>(
+		self,
+		builder: F,
+		spec_factory: S,
+	) -> error::Result<()>
+		where S: FnOnce(&str) -> Result<Option<ChainSpec<G, E>>, String>,
+			F: FnOnce(Configuration<C, G, E>) -> Result<B, error::Error>,
+			B: ServiceBuilderCommand,
+			<<B as ServiceBuilderCommand>::Block as BlockT>::Hash: FromStr,
+			C: Default,
+			G: RuntimeGenesis,
+			E: ChainSpecExtension,
+	{
+		let mut config = create_config_with_db_path(spec_factory, &self.params.shared_params, self.version)?;
+		fill_import_params(&mut config, &self.params.import_params, sc_service::Roles::FULL)?;
+
+		let block_id = self.params.at.as_ref().map(|at| parse_block_id(at)).transpose()?;
+		let code = fs::read(&self.params.runtime)?;
+		let snapshot = match self.params.snapshot {
+			Some(ref path) => {
+				let snapshot: BTreeMap<StorageKey, StorageData> = serde_json::from_reader(File::open(path)?)
+					.map_err(|e| error::Error::Input(format!("Invalid snapshot: {}", e)))?;
+				Some(snapshot.into_iter().map(|(key, value)| (key.0, value.0)).collect())
+			},
+			None => None,
+		};
+
+		builder(config)?.try_runtime(block_id, code, snapshot)?;
+		info!("The runtime upgrade can be enacted.");
+		Ok(())
+	}
+}
+
 /// Command ready to purge the chain.
 pub struct ParseAndPreparePurge<'a> {
 	params: PurgeChainCmd,
@@ -684,6 +745,22 @@ fn fill_transaction_pool_configuration<C, G, E>(
 	options.transaction_pool.future.count = params.pool_limit / factor;
 	options.transaction_pool.future.total_bytes = params.pool_kbytes * 1024 / factor;
 
+	options.transaction_pool.sender_limit = params.pool_sender_limit
+		.map(|count| sc_service::TransactionPoolSenderLimit {
+			prefix_len: params.pool_sender_tag_prefix,
+			count,
+		});
+	options.transaction_pool.min_priority_bump = params.pool_min_priority_bump;
+	options.transaction_pool.retracted_watch_blocks = params.pool_retracted_watch_blocks;
+
+	if params.pool_journal {
+		options.transaction_pool.journal = options.in_chain_config_dir(DEFAULT_TXPOOL_CONFIG_PATH)
+			.map(|path| sc_service::TransactionPoolJournalOptions {
+				path: path.join("journal"),
+				max_transactions: params.pool_journal_limit,
+			});
+	}
+
 	Ok(())
 }
 
@@ -740,6 +817,15 @@ fn fill_network_configuration(
 
 	config.max_parallel_downloads = cli.max_parallel_downloads;
 
+	let kib = |rate: Option<u64>| rate.map(|r| r.saturating_mul(1024));
+	config.bandwidth = BandwidthConfig {
+		max_download_rate: kib(cli.in_bandwidth),
+		max_upload_rate: kib(cli.out_bandwidth),
+		max_download_rate_per_peer: kib(cli.in_peer_bandwidth),
+		max_upload_rate_per_peer: kib(cli.out_peer_bandwidth),
+		max_sync_download_rate: kib(cli.sync_bandwidth),
+	};
+
 	Ok(())
 }
 
@@ -816,6 +902,21 @@ pub fn fill_import_params<C, G, E>(
 	};
 
 	config.wasm_method = cli.wasm_method.into();
+	config.sandbox_backend = cli.sandbox_backend.into();
+	config.max_runtime_instances = cli.max_runtime_instances;
+	config.wasm_compilation_cache = if cli.no_wasm_cache {
+		None
+	} else {
+		config.in_chain_config_dir(DEFAULT_WASM_CACHE_CONFIG_PATH)
+	};
+
+	config.call_limits = CallLimits {
+		timeout: cli.call_timeout.map(Duration::from_millis),
+		fuel: cli.call_fuel_limit,
+	};
+	config.wasm_metering = cli.wasm_metering
+		|| config.call_limits.timeout.is_some()
+		|| config.call_limits.fuel.is_some();
 
 	let exec = &cli.execution_strategies;
 	let exec_all_or = |strat: ExecutionStrategy| exec.execution.unwrap_or(strat).into();
@@ -826,6 +927,7 @@ pub fn fill_import_params<C, G, E>(
 		offchain_worker: exec_all_or(exec.execution_offchain_worker),
 		other: exec_all_or(exec.execution_other),
 	};
+	config.execution_mismatch_dump = exec.execution_mismatch_dump.clone();
 	Ok(())
 }
 
@@ -893,6 +995,7 @@ where
 
 	config.roles = role;
 	config.disable_grandpa = cli.no_grandpa;
+	config.grandpa_observer = cli.grandpa_observer;
 
 	let client_id = config.client_id();
 	fill_network_configuration(
@@ -925,6 +1028,16 @@ where
 	);
 
 	config.rpc_ws_max_connections = cli.ws_max_connections;
+	config.rpc_ipc = cli.ipc_path;
+	config.rpc_ipc_permissions = Some(cli.ipc_permissions.unwrap_or(DEFAULT_IPC_PERMISSIONS));
+	if let Some(path) = cli.rpc_policy {
+		config.rpc_policies = File::open(&path)
+			.map_err(|e| e.to_string())
+			.and_then(|file| serde_json::from_reader(file).map_err(|e| e.to_string()))
+			.map_err(|e| error::Error::Input(
+				format!("Invalid RPC policy file {}: {}", path.display(), e)
+			))?;
+	}
 	config.rpc_cors = cli.rpc_cors.unwrap_or_else(|| if is_dev {
 		log::warn!("Running in --dev mode, RPC CORS has been disabled.");
 		Cors::All
@@ -1095,6 +1208,12 @@ mod tests {
 	use super::*;
 	use sc_network::config::identity::ed25519;
 
+	#[test]
+	fn grandpa_observer_requires_light_client() {
+		assert!(RunCmd::from_iter_safe(&["node", "--grandpa-observer"]).is_err());
+		assert!(RunCmd::from_iter_safe(&["node", "--grandpa-observer", "--light"]).is_ok());
+	}
+
 	#[test]
 	fn tests_node_name_good() {
 		assert!(is_node_name_valid("short name").is_ok());
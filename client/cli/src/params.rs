@@ -69,6 +69,43 @@ impl Into<sc_service::config::WasmExecutionMethod> for WasmExecutionMethod {
 	}
 }
 
+arg_enum! {
+	/// Engine executing the sandboxed Wasm modules
+	#[allow(missing_docs)]
+	#[derive(Debug, Clone, Copy)]
+	pub enum SandboxBackend {
+		// Uses the wasmi interpreter.
+		Wasmi,
+		// Uses the wasmtime compiler.
+		Wasmtime,
+	}
+}
+
+impl SandboxBackend {
+	/// Returns list of variants that are not disabled by feature flags.
+	fn enabled_variants() -> Vec<&'static str> {
+		Self::variants()
+			.iter()
+			.cloned()
+			.filter(|&name| cfg!(feature = "wasmtime") || name != "Wasmtime")
+			.collect()
+	}
+}
+
+impl Into<sc_service::config::SandboxBackend> for SandboxBackend {
+	fn into(self) -> sc_service::config::SandboxBackend {
+		match self {
+			SandboxBackend::Wasmi => sc_service::config::SandboxBackend::Wasmi,
+			#[cfg(feature = "wasmtime")]
+			SandboxBackend::Wasmtime => sc_service::config::SandboxBackend::Wasmtime,
+			#[cfg(not(feature = "wasmtime"))]
+			SandboxBackend::Wasmtime => panic!(
+				"Substrate must be compiled with \"wasmtime\" feature for the wasmtime sandbox"
+			),
+		}
+	}
+}
+
 arg_enum! {
 	/// Whether off-chain workers are enabled.
 	#[allow(missing_docs)]
@@ -129,6 +166,53 @@ pub struct ImportParams {
 	)]
 	pub wasm_method: WasmExecutionMethod,
 
+	/// Engine executing the Wasm modules sandboxed by the runtime, e.g. the smart contracts.
+	///
+	/// Only applies when the runtime is executed in Wasm: the native runtime always sandboxes
+	/// the modules with the wasmi interpreter.
+	#[structopt(
+		long = "sandbox-execution",
+		value_name = "BACKEND",
+		possible_values = &SandboxBackend::enabled_variants(),
+		case_insensitive = true,
+		default_value = "Wasmi"
+	)]
+	pub sandbox_backend: SandboxBackend,
+
+	/// Specify the maximum number of idle instances kept per Wasm runtime by every thread.
+	///
+	/// Every thread calling into a runtime uses its own instances. Nested calls beyond this
+	/// number are executed by temporary instances.
+	#[structopt(long = "max-runtime-instances", value_name = "COUNT", default_value = "8")]
+	pub max_runtime_instances: usize,
+
+	/// Disable caching the runtimes compiled with `--wasm-execution Compiled` on disk.
+	#[structopt(long = "no-wasm-cache")]
+	pub no_wasm_cache: bool,
+
+	/// Meter the Wasm execution, allowing to interrupt runtime calls.
+	///
+	/// Only the calls made by RPC, offchain workers and transaction validation are metered, block
+	/// import and authoring run at full speed. Implied by `--call-timeout` and `--call-fuel-limit`.
+	#[structopt(long = "wasm-metering")]
+	pub wasm_metering: bool,
+
+	/// Interrupt the runtime calls made by RPC, offchain workers and transaction validation
+	/// after the given number of milliseconds.
+	///
+	/// Only applies to calls executed in Wasm, see `--execution-other` and
+	/// `--execution-offchain-worker`. The `state_call` RPC is always executed in Wasm when limited.
+	#[structopt(long = "call-timeout", value_name = "MILLISECONDS")]
+	pub call_timeout: Option<u64>,
+
+	/// Interrupt the runtime calls made by RPC, offchain workers and transaction validation
+	/// after they executed roughly the given number of Wasm instructions.
+	///
+	/// Only applies to calls executed in Wasm, see `--execution-other` and
+	/// `--execution-offchain-worker`. The `state_call` RPC is always executed in Wasm when limited.
+	#[structopt(long = "call-fuel-limit", value_name = "FUEL")]
+	pub call_fuel_limit: Option<u64>,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub execution_strategies: ExecutionStrategies,
@@ -206,6 +290,29 @@ pub struct NetworkConfigurationParams {
 	#[structopt(long = "max-parallel-downloads", value_name = "COUNT", default_value = "5")]
 	pub max_parallel_downloads: u32,
 
+	/// Maximum download rate, in KiB/s, all peers combined.
+	#[structopt(long = "in-bandwidth", value_name = "KIB_PER_SEC")]
+	pub in_bandwidth: Option<u64>,
+
+	/// Maximum upload rate, in KiB/s, all peers combined.
+	#[structopt(long = "out-bandwidth", value_name = "KIB_PER_SEC")]
+	pub out_bandwidth: Option<u64>,
+
+	/// Maximum download rate, in KiB/s, from each individual peer.
+	#[structopt(long = "in-peer-bandwidth", value_name = "KIB_PER_SEC")]
+	pub in_peer_bandwidth: Option<u64>,
+
+	/// Maximum upload rate, in KiB/s, to each individual peer.
+	#[structopt(long = "out-peer-bandwidth", value_name = "KIB_PER_SEC")]
+	pub out_peer_bandwidth: Option<u64>,
+
+	/// Maximum rate, in KiB/s, at which blocks are downloaded while syncing.
+	///
+	/// Keeping this below `--in-bandwidth` leaves room for consensus gossip while the node
+	/// catches up with the chain.
+	#[structopt(long = "sync-bandwidth", value_name = "KIB_PER_SEC")]
+	pub sync_bandwidth: Option<u64>,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub node_key_params: NodeKeyParams,
@@ -290,6 +397,29 @@ pub struct TransactionPoolParams {
 	/// Maximum number of kilobytes of all transactions stored in the pool.
 	#[structopt(long = "pool-kbytes", value_name = "COUNT", default_value = "10240")]
 	pub pool_kbytes: usize,
+	/// Maximum number of transactions a single sender can have in the transaction pool.
+	#[structopt(long = "pool-sender-limit", value_name = "COUNT")]
+	pub pool_sender_limit: Option<usize>,
+	/// Number of leading bytes of the tags provided by a transaction that identify its sender.
+	///
+	/// Defaults to the length of a 32-byte account id.
+	#[structopt(long = "pool-sender-tag-prefix", value_name = "BYTES", default_value = "32")]
+	pub pool_sender_tag_prefix: usize,
+	/// Priority increase required for a transaction to replace another one in the pool.
+	#[structopt(long = "pool-min-priority-bump", value_name = "PRIORITY", default_value = "0")]
+	pub pool_min_priority_bump: u64,
+	/// Number of recent blocks for which transaction watchers are kept open after inclusion.
+	///
+	/// Watchers of transactions included in these blocks are notified if the block is
+	/// retracted. By default watchers end once the transaction is included in a block.
+	#[structopt(long = "pool-retracted-watch-blocks", value_name = "COUNT", default_value = "0")]
+	pub pool_retracted_watch_blocks: usize,
+	/// Persist locally submitted transactions in a journal, so that they survive restarts.
+	#[structopt(long = "pool-journal")]
+	pub pool_journal: bool,
+	/// Maximum number of transactions kept in the transaction pool journal.
+	#[structopt(long = "pool-journal-limit", value_name = "COUNT", default_value = "1024")]
+	pub pool_journal_limit: usize,
 }
 
 arg_enum! {
@@ -380,6 +510,14 @@ pub struct ExecutionStrategies {
 		]
 	)]
 	pub execution: Option<ExecutionStrategy>,
+
+	/// Write the details of every mismatch between the native and wasm executions to a file in
+	/// the given directory, and continue with the wasm result.
+	///
+	/// Applies to the execution contexts using the `Both` strategy. Diverging storage changes
+	/// are reported as a mismatch as well.
+	#[structopt(long = "execution-mismatch-dump", value_name = "PATH", parse(from_os_str))]
+	pub execution_mismatch_dump: Option<PathBuf>,
 }
 
 /// The `run` command used to run a node.
@@ -415,6 +553,17 @@ pub struct RunCmd {
 	#[structopt(long = "no-grandpa")]
 	pub no_grandpa: bool,
 
+	/// Follow GRANDPA finality on a light client by observing commits.
+	///
+	/// Only supported by light clients: full nodes that aren't validators always run the
+	/// GRANDPA observer (unless `--no-grandpa` is passed).
+	#[structopt(
+		long = "grandpa-observer",
+		requires = "light",
+		conflicts_with_all = &[ "validator", "no-grandpa" ]
+	)]
+	pub grandpa_observer: bool,
+
 	/// Experimental: Run in light client mode.
 	#[structopt(long = "light")]
 	pub light: bool,
@@ -465,6 +614,31 @@ pub struct RunCmd {
 	#[structopt(long = "ws-max-connections", value_name = "COUNT")]
 	pub ws_max_connections: Option<usize>,
 
+	/// Specify the path of the IPC RPC server socket.
+	///
+	/// The IPC server is disabled unless a path is given. It serves the same methods and
+	/// subscriptions as the HTTP & WS servers to processes on the same host.
+	#[structopt(long = "ipc-path", value_name = "PATH")]
+	pub ipc_path: Option<String>,
+
+	/// Specify the file permissions of the IPC RPC server socket, in octal.
+	///
+	/// Default is `600`, allowing only the user running the node to connect.
+	#[structopt(long = "ipc-permissions", value_name = "MODE", parse(try_from_str = parse_permissions))]
+	pub ipc_permissions: Option<u32>,
+
+	/// Specify a JSON file with the access control policies of the RPC servers.
+	///
+	/// The file has an optional `http`, `ws` and `ipc` policy, each of which may set
+	/// `allowedNamespaces`, `maxRequestsPerMinute`, `maxConnections`,
+	/// `maxSubscriptionsPerConnection`, `maxRequestSize`, `maxResponseSize`,
+	/// `allowUnsafeMethods` and `trustedProxies`. By default no limit other than the maximum
+	/// number of WS connections applies, and unsafe methods such as `state_traceBlock` are not
+	/// allowed. Rate limiting the HTTP & WS servers requires them to listen on a loopback
+	/// address, behind a reverse proxy forwarding the client addresses.
+	#[structopt(long = "rpc-policy", value_name = "PATH", parse(from_os_str))]
+	pub rpc_policy: Option<PathBuf>,
+
 	/// Specify browser Origins allowed to access the HTTP & WS RPC servers.
 	///
 	/// A comma-separated list of origins (protocol://domain or special `null`
@@ -699,6 +873,14 @@ fn parse_cors(s: &str) -> Result<Cors, Box<dyn std::error::Error>> {
 	Ok(if is_all { Cors::All } else { Cors::List(origins) })
 }
 
+/// Parse octal file permissions
+fn parse_permissions(s: &str) -> Result<u32, String> {
+	u32::from_str_radix(s, 8)
+		.ok()
+		.filter(|mode| *mode <= 0o777)
+		.ok_or_else(|| format!("Invalid file permissions `{}`, expected an octal mode like `600`", s))
+}
+
 /// The `build-spec` command used to build a specification.
 #[derive(Debug, StructOpt, Clone)]
 pub struct BuildSpecCmd {
@@ -829,6 +1011,36 @@ pub struct CheckBlockCmd {
 	pub import_params: ImportParams,
 }
 
+/// The `try-runtime` command used to check a runtime upgrade before enacting it.
+#[derive(Debug, StructOpt, Clone)]
+pub struct TryRuntimeCmd {
+	/// Path to the Wasm blob of the new runtime.
+	#[structopt(parse(from_os_str))]
+	pub runtime: PathBuf,
+
+	/// Block hash or number on top of which the upgrade is checked.
+	///
+	/// Default is best block.
+	#[structopt(long = "at", value_name = "HASH or NUMBER")]
+	pub at: Option<String>,
+
+	/// Check the upgrade against a snapshot of the state instead of the state of the block.
+	///
+	/// The snapshot is a JSON object mapping the hex encoded storage keys to their hex encoded
+	/// values, like the `top` storage of a raw chain spec. The block of the snapshot must be
+	/// given with `--at`.
+	#[structopt(long = "snapshot", value_name = "PATH", parse(from_os_str), requires = "at")]
+	pub snapshot: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub import_params: ImportParams,
+}
+
 /// The `revert` command used revert the chain to a previous state.
 #[derive(Debug, StructOpt, Clone)]
 pub struct RevertCmd {
@@ -878,6 +1090,9 @@ pub enum CoreParams<CC, RP> {
 	/// Revert chain to the previous state.
 	Revert(RevertCmd),
 
+	/// Check a runtime upgrade before enacting it.
+	TryRuntime(TryRuntimeCmd),
+
 	/// Remove the whole chain data.
 	PurgeChain(PurgeChainCmd),
 
@@ -916,6 +1131,13 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 			RevertCmd::augment_clap(SubCommand::with_name("revert"))
 				.about("Revert chain to the previous state.")
 		)
+		.subcommand(
+			TryRuntimeCmd::augment_clap(SubCommand::with_name("try-runtime"))
+				.about("Check that a new runtime can be upgraded to: its version, its metadata and \
+						its migrations are checked on top of an existing state, which is not \
+						modified."
+					)
+		)
 		.subcommand(
 			PurgeChainCmd::augment_clap(SubCommand::with_name("purge-chain"))
 				.about("Remove the whole chain data.")
@@ -933,6 +1155,8 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 			("check-block", Some(matches)) =>
 				CoreParams::CheckBlock(CheckBlockCmd::from_clap(matches)),
 			("revert", Some(matches)) => CoreParams::Revert(RevertCmd::from_clap(matches)),
+			("try-runtime", Some(matches)) =>
+				CoreParams::TryRuntime(TryRuntimeCmd::from_clap(matches)),
 			("purge-chain", Some(matches)) =>
 				CoreParams::PurgeChain(PurgeChainCmd::from_clap(matches)),
 			(_, None) => CoreParams::Run(MergeParameters::from_clap(matches)),
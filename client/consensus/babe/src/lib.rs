@@ -586,6 +586,29 @@ pub struct BabeLink<Block: BlockT> {
 	epoch_changes: SharedEpochChanges<Block>,
 	config: Config,
 }
+
+impl<Block: BlockT<Hash=H256>> BabeLink<Block> {
+	/// Return the authority that has authored the given block.
+	///
+	/// The authority is looked up in the epoch the block has been authored in, which is
+	/// determined from its parent, so the parent must be known to `client`.
+	pub fn block_author<H>(&self, client: &H, header: &Block::Header) -> Option<AuthorityId> where
+		H: HeaderBackend<Block> + HeaderMetadata<Block, Error=ClientError>,
+	{
+		let pre_digest = find_pre_digest::<Block>(header).ok()?;
+		let parent = client.header(BlockId::Hash(*header.parent_hash())).ok()??;
+		let epoch = self.epoch_changes.lock().epoch_for_child_of(
+			descendent_query(client),
+			&parent.hash(),
+			*parent.number(),
+			pre_digest.slot_number(),
+			|slot| self.config.genesis_epoch(slot),
+		).ok()??;
+
+		epoch.as_ref().authorities.get(pre_digest.authority_index() as usize).map(|(id, _)| id.clone())
+	}
+}
+
 /// A verifier for Babe blocks.
 pub struct BabeVerifier<B, E, Block: BlockT, RA, PRA> {
 	client: Arc<Client<B, E, Block, RA>>,
@@ -631,6 +631,37 @@ fn importing_block_one_sets_genesis_epoch() {
 	assert_eq!(epoch_for_second_block, genesis_epoch);
 }
 
+#[test]
+fn block_author_is_looked_up_in_epoch_of_block() {
+	let mut net = BabeTestNet::new(1);
+
+	let peer = net.peer(0);
+	let data = peer.data.as_ref().expect("babe link set up during initialization");
+	let client = peer.client().as_full().expect("Only full clients are used in tests").clone();
+
+	let mut proposer_factory = DummyFactory {
+		client: client.clone(),
+		config: data.link.config.clone(),
+		epoch_changes: data.link.epoch_changes.clone(),
+		mutator: Arc::new(|_, _| ()),
+	};
+
+	let mut block_import = data.block_import.lock().take().expect("import set up during init");
+
+	let genesis_header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+	let block_hash = propose_and_import_block(
+		&genesis_header,
+		Some(999),
+		&mut proposer_factory,
+		&mut block_import,
+	);
+	let header = client.header(&BlockId::Hash(block_hash)).unwrap().unwrap();
+
+	let genesis_epoch = data.link.config.genesis_epoch(999);
+	assert_eq!(data.link.block_author(&*client, &header), Some(genesis_epoch.authorities[0].0.clone()));
+	assert_eq!(data.link.block_author(&*client, &genesis_header), None);
+}
+
 #[test]
 fn importing_epoch_change_block_prunes_tree() {
 	use sc_client_api::Finalizer;
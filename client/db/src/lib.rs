@@ -201,6 +201,26 @@ impl<B: BlockT> StateBackend<Blake2Hasher> for RefTrackingState<B> {
 		self.state.for_child_keys_with_prefix(storage_key, child_info, prefix, f)
 	}
 
+	fn apply_to_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		self.state.apply_to_key_values_while(prefix, start_at, f)
+	}
+
+	fn apply_to_child_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		self.state.apply_to_child_key_values_while(storage_key, child_info, prefix, start_at, f)
+	}
+
 	fn storage_root<I>(&self, delta: I) -> (H256, Self::Transaction)
 		where
 			I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>
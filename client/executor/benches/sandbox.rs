@@ -0,0 +1,75 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use codec::{Decode, Encode};
+use criterion::{Criterion, criterion_group, criterion_main};
+use sc_executor::{WasmExecutionMethod, SandboxBackend, call_in_wasm_with_sandbox_backend};
+use sc_runtime_test::WASM_BINARY;
+use sp_io::TestExternalities;
+
+criterion_group!(benches, bench_instantiate_sandbox);
+criterion_main!(benches);
+
+const HEAP_PAGES: u64 = 8;
+
+/// The guest module instantiated by the runtime, the same one for every instantiation so that
+/// the compiled module is reused by the engines caching it.
+const GUEST: &str = r#"
+	(module
+		(func $fib (param i32) (result i32)
+			(if (result i32) (i32.lt_u (get_local 0) (i32.const 2))
+				(then (get_local 0))
+				(else
+					(i32.add
+						(call $fib (i32.sub (get_local 0) (i32.const 1)))
+						(call $fib (i32.sub (get_local 0) (i32.const 2)))
+					)
+				)
+			)
+		)
+		(func (export "call") (drop (call $fib (i32.const 10))))
+	)
+"#;
+
+fn bench_instantiate_sandbox(c: &mut Criterion) {
+	let guest = wabt::wat2wasm(GUEST).unwrap().encode();
+
+	c.bench_function_over_inputs(
+		"instantiate a sandboxed module repeatedly",
+		move |b, backend| {
+			let mut ext = TestExternalities::default();
+			let mut ext = ext.ext();
+
+			b.iter(|| {
+				let res = call_in_wasm_with_sandbox_backend::<_, sp_io::SubstrateHostFunctions>(
+					"test_sandbox_instantiate_repeatedly",
+					&guest,
+					WasmExecutionMethod::Interpreted,
+					*backend,
+					&mut ext,
+					WASM_BINARY,
+					HEAP_PAGES,
+				).unwrap();
+				assert!(bool::decode(&mut &res[..]).unwrap());
+			})
+		},
+		vec![
+			SandboxBackend::Wasmi,
+			#[cfg(feature = "wasmtime")]
+			SandboxBackend::Wasmtime,
+		],
+	);
+}
@@ -47,7 +47,7 @@
 //! back the allocation into the linked list from the head.
 
 use crate::error::{Error, Result};
-use log::trace;
+use log::{debug, trace, warn};
 use std::convert::{TryFrom, TryInto};
 use std::ops::Range;
 use sp_wasm_interface::{Pointer, WordSize};
@@ -69,6 +69,73 @@ const MIN_POSSIBLE_ALLOCATION: u32 = 8;
 // to which it belongs.
 const PREFIX_SIZE: u32 = 8;
 
+/// Statistics of the allocations made by a [`FreeingBumpHeapAllocator`].
+///
+/// As a new allocator is used for every call into the runtime, these are per-call statistics.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AllocationStats {
+	/// The peak number of bytes allocated at once, including the prefixes.
+	pub peak_usage: u32,
+	/// The number of successful allocations.
+	pub allocations: u32,
+	/// The size in bytes of the largest allocation requested.
+	pub largest_allocation: u32,
+	/// The size in bytes of the first allocation which failed for lack of space, if any.
+	pub failed_allocation: Option<u32>,
+}
+
+impl AllocationStats {
+	/// Report the statistics of the call to `method` as a log and a tracing event, and as metrics
+	/// with the `metrics` feature.
+	///
+	/// Calls which ran out of heap space are reported as warnings.
+	pub fn report(&self, method: &str) {
+		match self.failed_allocation {
+			Some(failed_allocation) => warn!(
+				target: "wasm-heap",
+				"{} ran out of heap space allocating {} bytes: peak usage {} bytes, {} allocations, \
+				largest allocation {} bytes",
+				method,
+				failed_allocation,
+				self.peak_usage,
+				self.allocations,
+				self.largest_allocation,
+			),
+			None => debug!(
+				target: "wasm-heap",
+				"{}: peak usage {} bytes, {} allocations, largest allocation {} bytes",
+				method,
+				self.peak_usage,
+				self.allocations,
+				self.largest_allocation,
+			),
+		}
+
+		tracing::event!(
+			target: "wasm-heap",
+			tracing::Level::DEBUG,
+			method,
+			peak_usage = self.peak_usage,
+			allocations = self.allocations,
+			largest_allocation = self.largest_allocation,
+			out_of_space = self.failed_allocation.is_some(),
+		);
+
+		#[cfg(feature = "metrics")]
+		{
+			let key = |name: &str| grafana_data_source::labeled_key(name, &[("method", method)]);
+			let result = grafana_data_source::record_metrics!(
+				&key("wasm_heap_peak_usage") => self.peak_usage,
+				&key("wasm_heap_allocations") => self.allocations,
+				&key("wasm_heap_largest_allocation") => self.largest_allocation,
+			);
+			if let Err(e) = result {
+				warn!(target: "wasm-heap", "Unable to record the heap metrics: {:?}", e);
+			}
+		}
+	}
+}
+
 /// An implementation of freeing bump allocator.
 ///
 /// Refer to the module-level documentation for further details.
@@ -77,6 +144,7 @@ pub struct FreeingBumpHeapAllocator {
 	heads: [u32; N],
 	ptr_offset: u32,
 	total_size: u32,
+	stats: AllocationStats,
 }
 
 /// Create an allocator error.
@@ -100,9 +168,15 @@ impl FreeingBumpHeapAllocator {
 			heads: [0; N],
 			ptr_offset,
 			total_size: 0,
+			stats: AllocationStats::default(),
 		}
 	}
 
+	/// Returns the statistics of the allocations made so far.
+	pub fn stats(&self) -> &AllocationStats {
+		&self.stats
+	}
+
 	/// Gets requested number of bytes to allocate and returns a pointer.
 	/// The maximum size which can be allocated at once is 16 MiB.
 	/// There is no minimum size, but whatever size is passed into
@@ -118,14 +192,16 @@ impl FreeingBumpHeapAllocator {
 			.expect("size of Wasm linear memory is <2^32");
 		let max_heap_size = mem_size - self.ptr_offset;
 
+		self.stats.largest_allocation = self.stats.largest_allocation.max(size);
 		if size > MAX_POSSIBLE_ALLOCATION {
 			return Err(Error::RequestedAllocationTooLarge);
 		}
 
+		let requested = size;
 		let size = size.max(MIN_POSSIBLE_ALLOCATION);
 		let item_size = size.next_power_of_two();
 		if item_size + PREFIX_SIZE + self.total_size > max_heap_size {
-			return Err(Error::AllocatorOutOfSpace);
+			return Err(self.out_of_space(requested, max_heap_size));
 		}
 
 		let list_index = (item_size.trailing_zeros() - 3) as usize;
@@ -145,12 +221,17 @@ impl FreeingBumpHeapAllocator {
 			ptr
 		} else {
 			// Nothing to be freed. Bump.
-			self.bump(item_size, max_heap_size)? + PREFIX_SIZE
+			match self.bump(item_size, max_heap_size) {
+				Some(ptr) => ptr + PREFIX_SIZE,
+				None => return Err(self.out_of_space(requested, max_heap_size)),
+			}
 		};
 
 		self.set_heap_u64(mem, ptr - PREFIX_SIZE, list_index as u64)?;
 
 		self.total_size = self.total_size + item_size + PREFIX_SIZE;
+		self.stats.peak_usage = self.stats.peak_usage.max(self.total_size);
+		self.stats.allocations += 1;
 		trace!(target: "wasm-heap", "Heap size is {} bytes after allocation", self.total_size);
 
 		Ok(Pointer::new(self.ptr_offset + ptr))
@@ -188,16 +269,25 @@ impl FreeingBumpHeapAllocator {
 	/// Increases the `bumper` by `item_size + PREFIX_SIZE`.
 	///
 	/// Returns the `bumper` from before the increase.
-	/// Returns an `Error::AllocatorOutOfSpace` if the operation
-	/// would exhaust the heap.
-	fn bump(&mut self, item_size: u32, max_heap_size: u32) -> Result<u32> {
+	/// Returns `None` if the operation would exhaust the heap.
+	fn bump(&mut self, item_size: u32, max_heap_size: u32) -> Option<u32> {
 		if self.bumper + PREFIX_SIZE + item_size > max_heap_size {
-			return Err(Error::AllocatorOutOfSpace);
+			return None;
 		}
 
 		let res = self.bumper;
 		self.bumper += item_size + PREFIX_SIZE;
-		Ok(res)
+		Some(res)
+	}
+
+	/// Record the failure of an allocation of `requested` bytes for lack of space.
+	fn out_of_space(&mut self, requested: u32, max_heap_size: u32) -> Error {
+		self.stats.failed_allocation.get_or_insert(requested);
+		Error::AllocatorOutOfSpace {
+			requested,
+			allocated: self.total_size,
+			heap_size: max_heap_size,
+		}
 	}
 
 	fn get_item_size_from_index(index: usize) -> usize {
@@ -386,7 +476,7 @@ mod tests {
 
 		// then
 		match ptr.unwrap_err() {
-			Error::AllocatorOutOfSpace => {},
+			Error::AllocatorOutOfSpace { .. } => {},
 			e => panic!("Expected allocator out of space error, got: {:?}", e),
 		}
 	}
@@ -405,7 +495,7 @@ mod tests {
 		// then
 		// there is no room for another half page incl. its 8 byte prefix
 		match ptr2.unwrap_err() {
-			Error::AllocatorOutOfSpace => {},
+			Error::AllocatorOutOfSpace { .. } => {},
 			e => panic!("Expected allocator out of space error, got: {:?}", e),
 		}
 	}
@@ -466,7 +556,7 @@ mod tests {
 
 		// then
 		match ptr.unwrap_err() {
-			Error::AllocatorOutOfSpace => {},
+			Error::AllocatorOutOfSpace { .. } => {},
 			e => panic!("Expected allocator out of space error, got: {:?}", e),
 		}
 	}
@@ -554,4 +644,47 @@ mod tests {
 		assert_eq!(item_size as u32, MAX_POSSIBLE_ALLOCATION);
 	}
 
+	#[test]
+	fn should_record_allocation_stats() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = FreeingBumpHeapAllocator::new(0);
+
+		// when
+		let ptr1 = heap.allocate(&mut mem[..], 1).unwrap();
+		let ptr2 = heap.allocate(&mut mem[..], 9).unwrap();
+		heap.deallocate(&mut mem[..], ptr1).unwrap();
+		heap.deallocate(&mut mem[..], ptr2).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
+
+		// then
+		assert_eq!(heap.stats(), &AllocationStats {
+			peak_usage: (PREFIX_SIZE + 8) + (PREFIX_SIZE + 16),
+			allocations: 3,
+			largest_allocation: 9,
+			failed_allocation: None,
+		});
+	}
+
+	#[test]
+	fn should_report_requested_size_and_usage_when_out_of_space() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = FreeingBumpHeapAllocator::new(0);
+		heap.allocate(&mut mem[..], (PAGE_SIZE / 2) - PREFIX_SIZE).unwrap();
+
+		// when
+		let result = heap.allocate(&mut mem[..], PAGE_SIZE / 2);
+
+		// then
+		match result.unwrap_err() {
+			Error::AllocatorOutOfSpace { requested, allocated, heap_size } => {
+				assert_eq!(requested, PAGE_SIZE / 2);
+				assert_eq!(allocated, PAGE_SIZE / 2 + PREFIX_SIZE);
+				assert_eq!(heap_size, PAGE_SIZE);
+			},
+			e => panic!("Expected allocator out of space error, got: {:?}", e),
+		}
+		assert_eq!(heap.stats().failed_allocation, Some(PAGE_SIZE / 2));
+	}
 }
@@ -18,6 +18,7 @@
 
 use sp_serializer;
 use wasmi;
+use sp_core::traits::LimitExceeded;
 
 /// Result type alias.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -74,14 +75,31 @@ pub enum Error {
 	#[display(fmt="Error in allocator: {}", _0)]
 	Allocator(&'static str),
 	/// The allocator ran out of space.
-	#[display(fmt="Allocator ran out of space")]
-	AllocatorOutOfSpace,
+	#[display(
+		fmt="Allocator ran out of space: requested {} bytes with {} of {} bytes of the heap in use",
+		requested, allocated, heap_size,
+	)]
+	#[from(ignore)]
+	AllocatorOutOfSpace {
+		/// The number of bytes requested.
+		requested: u32,
+		/// The number of bytes allocated when the allocation failed, including the prefixes.
+		allocated: u32,
+		/// The size of the heap.
+		heap_size: u32,
+	},
 	/// Someone tried to allocate more memory than the allowed maximum per allocation.
 	#[display(fmt="Requested allocation size is too large")]
 	RequestedAllocationTooLarge,
 	/// Execution of a host function failed.
 	#[display(fmt="Host function {} execution failed with: {}", _0, _1)]
 	FunctionExecution(String, String),
+	/// The call was interrupted because it did not complete before its deadline.
+	#[display(fmt="Execution timed out")]
+	Timeout,
+	/// The call was interrupted because it consumed all of its fuel.
+	#[display(fmt="Execution ran out of fuel")]
+	OutOfFuel,
 }
 
 impl std::error::Error for Error {
@@ -97,6 +115,15 @@ impl std::error::Error for Error {
 
 impl wasmi::HostError for Error {}
 
+impl From<LimitExceeded> for Error {
+	fn from(limit: LimitExceeded) -> Error {
+		match limit {
+			LimitExceeded::Deadline => Error::Timeout,
+			LimitExceeded::Fuel => Error::OutOfFuel,
+		}
+	}
+}
+
 impl From<String> for Error {
 	fn from(err: String) -> Error {
 		Error::Other(err)
@@ -0,0 +1,141 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Resolution of the host functions imported by the runtime.
+//!
+//! Host functions declared by a runtime interface are exported as `<name>_version_<N>`. A runtime
+//! imports the version it was built against, so a missing import usually means that the runtime
+//! expects a version of a host function this node does not provide (yet).
+
+use crate::error::WasmError;
+use sp_wasm_interface::Function;
+use parity_wasm::elements::{deserialize_buffer, External, Module};
+
+/// Separator between the name of a host function and its version.
+const VERSION_SEPARATOR: &str = "_version_";
+
+/// Split `name` into the name of the host function and its version, if it is versioned.
+fn split_version(name: &str) -> Option<(&str, u32)> {
+	let index = name.rfind(VERSION_SEPARATOR)?;
+	let version = name[index + VERSION_SEPARATOR.len()..].parse().ok()?;
+	Some((&name[..index], version))
+}
+
+/// Returns the message reporting that the host function `name` imported by the runtime is not
+/// provided by `host_functions`.
+///
+/// The message lists the versions of the host function that are provided, if any.
+pub fn missing_import_message(name: &str, host_functions: &[&dyn Function]) -> String {
+	let mut message = format!("Export {} not found", name);
+
+	if let Some((function, _)) = split_version(name) {
+		let mut versions = host_functions
+			.iter()
+			.filter_map(|f| split_version(f.name()))
+			.filter(|(f, _)| *f == function)
+			.map(|(_, version)| version)
+			.collect::<Vec<_>>();
+		versions.sort();
+
+		if !versions.is_empty() {
+			let versions = versions.iter().map(ToString::to_string).collect::<Vec<_>>();
+			message.push_str(&format!(", the host provides version(s) {}", versions.join(", ")));
+		}
+	}
+
+	message
+}
+
+/// Check that every function the given `code` imports from the `env` module is provided by
+/// `host_functions`.
+pub fn check_imports(code: &[u8], host_functions: &[&dyn Function]) -> Result<(), WasmError> {
+	let module: Module = deserialize_buffer(code).map_err(|_| WasmError::CantDeserializeWasm)?;
+	let imports = match module.import_section() {
+		Some(section) => section.entries(),
+		None => return Ok(()),
+	};
+
+	for import in imports {
+		match import.external() {
+			External::Function(_) if import.module() == "env" => {
+				let name = import.field();
+				if host_functions.iter().all(|f| f.name() != name) {
+					return Err(WasmError::Other(missing_import_message(name, host_functions)));
+				}
+			},
+			_ => {},
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_wasm_interface::{FunctionContext, Result, Signature, Value};
+
+	struct Dummy(&'static str);
+
+	impl Function for Dummy {
+		fn name(&self) -> &str {
+			self.0
+		}
+
+		fn signature(&self) -> Signature {
+			Signature::new_with_args(&[][..])
+		}
+
+		fn execute(
+			&self,
+			_: &mut dyn FunctionContext,
+			_: &mut dyn Iterator<Item = Value>,
+		) -> Result<Option<Value>> {
+			Ok(None)
+		}
+	}
+
+	#[test]
+	fn should_list_provided_versions() {
+		// given
+		let host_functions: &[&dyn Function] = &[
+			&Dummy("ext_misc_print_version_2"),
+			&Dummy("ext_misc_print_version_1"),
+			&Dummy("ext_misc_print_num_version_1"),
+		];
+
+		// when
+		let message = missing_import_message("ext_misc_print_version_3", host_functions);
+
+		// then
+		assert_eq!(
+			message,
+			"Export ext_misc_print_version_3 not found, the host provides version(s) 1, 2",
+		);
+	}
+
+	#[test]
+	fn should_only_report_the_name_of_unknown_functions() {
+		// given
+		let host_functions: &[&dyn Function] = &[&Dummy("ext_misc_print_version_1")];
+
+		// when
+		let message = missing_import_message("ext_misc_unknown_version_1", host_functions);
+
+		// then
+		assert_eq!(message, "Export ext_misc_unknown_version_1 not found");
+	}
+}
@@ -21,4 +21,7 @@
 pub mod sandbox;
 pub mod allocator;
 pub mod error;
+pub mod imports;
+pub mod metering;
+pub mod snapshot;
 pub mod wasm_runtime;
@@ -0,0 +1,152 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Metering of the Wasm execution.
+//!
+//! The runtime code is instrumented to call the `gas` host function with the number of
+//! instructions of every basic block before executing it. This works the same way with every
+//! backend, and allows to interrupt a call which exceeds its [`ExecutionLimits`].
+
+use std::time::Instant;
+use crate::error::WasmError;
+use sp_core::traits::{ExecutionLimits, LimitExceeded};
+use sp_wasm_interface::{Function, FunctionContext, Signature, Value, ValueType};
+use parity_wasm::elements::{deserialize_buffer, serialize};
+
+/// Amount of fuel consumed between two checks of the deadline.
+///
+/// Avoids reading the clock at every basic block.
+const DEADLINE_CHECK_INTERVAL: u64 = 100_000;
+
+/// Instrument `wasm_code` to call the [`GasFunction`] before every basic block.
+pub fn instrument(wasm_code: &[u8]) -> Result<Vec<u8>, WasmError> {
+	let module = deserialize_buffer(wasm_code).map_err(|_| WasmError::CantDeserializeWasm)?;
+	let module = pwasm_utils::inject_gas_counter(module, &Default::default())
+		.map_err(|_| WasmError::Other("failed to instrument the code for metering".into()))?;
+	serialize(module).map_err(|_| WasmError::Other("failed to serialize the instrumented code".into()))
+}
+
+/// The state of the metering of a call.
+struct Meter {
+	limits: ExecutionLimits,
+	fuel_left: Option<u64>,
+	until_deadline_check: u64,
+}
+
+impl Meter {
+	fn charge(&mut self, fuel: u64) -> Result<(), LimitExceeded> {
+		if let Some(ref mut fuel_left) = self.fuel_left {
+			*fuel_left = fuel_left.checked_sub(fuel).ok_or(LimitExceeded::Fuel)?;
+		}
+
+		if let Some(deadline) = self.limits.deadline {
+			match self.until_deadline_check.checked_sub(fuel) {
+				Some(until_deadline_check) => self.until_deadline_check = until_deadline_check,
+				None => {
+					self.until_deadline_check = DEADLINE_CHECK_INTERVAL;
+					if Instant::now() >= deadline {
+						return Err(LimitExceeded::Deadline);
+					}
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+environmental::environmental!(meter: Meter);
+
+/// Execute `f`, interrupting the metered Wasm code it calls when it exceeds `limits`.
+///
+/// The exceeded limit is recorded in `limits`.
+pub fn with_limits<R>(limits: Option<&ExecutionLimits>, f: impl FnOnce() -> R) -> R {
+	match limits {
+		Some(limits) => {
+			let mut meter = Meter {
+				limits: limits.clone(),
+				fuel_left: limits.fuel,
+				until_deadline_check: 0,
+			};
+			meter::using(&mut meter, f)
+		},
+		None => f(),
+	}
+}
+
+/// The `gas` host function called by the instrumented code.
+///
+/// Does nothing outside of [`with_limits`].
+pub struct GasFunction;
+
+/// The `gas` host function, to register along with the other host functions.
+pub static GAS_FUNCTION: GasFunction = GasFunction;
+
+impl Function for GasFunction {
+	fn name(&self) -> &str {
+		"gas"
+	}
+
+	fn signature(&self) -> Signature {
+		Signature::new_with_args(&[ValueType::I32][..])
+	}
+
+	fn execute(
+		&self,
+		_: &mut dyn FunctionContext,
+		args: &mut dyn Iterator<Item = Value>,
+	) -> sp_wasm_interface::Result<Option<Value>> {
+		let fuel = match args.next() {
+			Some(Value::I32(fuel)) => fuel as u32 as u64,
+			_ => return Err("`gas` expects a single i32 argument".into()),
+		};
+
+		meter::with(|meter| meter.charge(fuel).map_err(|limit| {
+			meter.limits.set_exceeded(limit);
+			format!("{:?} limit exceeded", limit)
+		})).unwrap_or(Ok(()))?;
+
+		Ok(None)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn should_run_out_of_fuel() {
+		// given
+		let limits = ExecutionLimits::new(None, Some(10));
+		let mut meter = Meter { limits: limits.clone(), fuel_left: limits.fuel, until_deadline_check: 0 };
+
+		// then
+		assert_eq!(meter.charge(6), Ok(()));
+		assert_eq!(meter.charge(4), Ok(()));
+		assert_eq!(meter.charge(1), Err(LimitExceeded::Fuel));
+	}
+
+	#[test]
+	fn should_check_deadline_periodically() {
+		// given
+		let limits = ExecutionLimits::new(Some(Instant::now()), None);
+		let mut meter = Meter { limits, fuel_left: None, until_deadline_check: DEADLINE_CHECK_INTERVAL };
+
+		// then
+		assert_eq!(meter.charge(DEADLINE_CHECK_INTERVAL), Ok(()));
+		assert_eq!(meter.charge(1), Err(LimitExceeded::Deadline));
+	}
+}
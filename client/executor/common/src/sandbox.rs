@@ -16,8 +16,8 @@
 
 //! This module implements sandboxing support in the runtime.
 //!
-//! Sandboxing is baked by wasmi at the moment. In future, however, we would like to add/switch to
-//! a compiled execution engine.
+//! Sandboxing is backed by wasmi by default. With the `wasmtime` feature, the guest modules can
+//! be compiled by wasmtime instead, see [`SandboxBackend`].
 
 use crate::error::{Result, Error};
 use std::{collections::HashMap, rc::Rc};
@@ -25,10 +25,32 @@ use codec::{Decode, Encode};
 use sp_core::sandbox as sandbox_primitives;
 use wasmi::{
 	Externals, ImportResolver, MemoryInstance, MemoryRef, Module, ModuleInstance,
-	ModuleRef, RuntimeArgs, RuntimeValue, Trap, TrapKind, memory_units::Pages,
+	ModuleRef, RuntimeArgs, RuntimeValue, Trap, memory_units::Pages,
 };
 use sp_wasm_interface::{Pointer, WordSize};
 
+#[cfg(feature = "wasmtime")]
+mod wasmtime_backend;
+
+/// The engine executing the sandboxed guest modules.
+///
+/// The engine only applies to the runtimes executed in Wasm: a native runtime sandboxes the
+/// guest modules itself, with the wasmi interpreter of `sp-sandbox`.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum SandboxBackend {
+	/// Guest modules are interpreted by wasmi.
+	Wasmi,
+	/// Guest modules are compiled by wasmtime.
+	#[cfg(feature = "wasmtime")]
+	Wasmtime,
+}
+
+impl Default for SandboxBackend {
+	fn default() -> Self {
+		SandboxBackend::Wasmi
+	}
+}
+
 /// Index of a function inside the supervisor.
 ///
 /// This is a typically an index in the default table of the supervisor, however
@@ -71,7 +93,7 @@ impl GuestToSupervisorFunctionMapping {
 
 struct Imports {
 	func_map: HashMap<(Vec<u8>, Vec<u8>), GuestFuncIndex>,
-	memories_map: HashMap<(Vec<u8>, Vec<u8>), MemoryRef>,
+	memories_map: HashMap<(Vec<u8>, Vec<u8>), Memory>,
 }
 
 impl ImportResolver for Imports {
@@ -104,16 +126,13 @@ impl ImportResolver for Imports {
 			module_name.as_bytes().to_vec(),
 			field_name.as_bytes().to_vec(),
 		);
-		let mem = self.memories_map
-			.get(&key)
-			.ok_or_else(|| {
-				wasmi::Error::Instantiation(format!(
-					"Export {}:{} not found",
-					module_name, field_name
-				))
-			})?
-			.clone();
-		Ok(mem)
+		match self.memories_map.get(&key) {
+			Some(Memory::Wasmi(memory_ref)) => Ok(memory_ref.clone()),
+			_ => Err(wasmi::Error::Instantiation(format!(
+				"Export {}:{} not found",
+				module_name, field_name
+			))),
+		}
 	}
 
 	fn resolve_global(
@@ -219,22 +238,54 @@ pub struct GuestExternals<'a, FE: SandboxCapabilities + 'a> {
 	state: u32,
 }
 
-fn trap(msg: &'static str) -> Trap {
-	TrapKind::Host(Box::new(Error::Other(msg.into()))).into()
-}
-
-fn deserialize_result(serialized_result: &[u8]) -> std::result::Result<Option<RuntimeValue>, Trap> {
+/// Invoke the supervisor function `func_idx` with `args` on behalf of a guest.
+///
+/// The arguments are serialized into the supervisor memory and passed to the dispatch thunk, which
+/// returns the serialized result of the call.
+fn invoke_supervisor<FE: SandboxCapabilities>(
+	supervisor_externals: &mut FE,
+	dispatch_thunk: &FE::SupervisorFuncRef,
+	func_idx: SupervisorFuncIndex,
+	args: &[sandbox_primitives::TypedValue],
+	state: u32,
+) -> Result<sandbox_primitives::ReturnValue> {
 	use self::sandbox_primitives::{HostError, ReturnValue};
-	let result_val = std::result::Result::<ReturnValue, HostError>::decode(&mut &serialized_result[..])
-		.map_err(|_| trap("Decoding Result<ReturnValue, HostError> failed!"))?;
-
-	match result_val {
-		Ok(return_value) => Ok(match return_value {
-			ReturnValue::Unit => None,
-			ReturnValue::Value(typed_value) => Some(RuntimeValue::from(typed_value)),
-		}),
-		Err(HostError) => Err(trap("Supervisor function returned sandbox::HostError")),
-	}
+
+	// Serialize arguments into a byte vector.
+	let invoke_args_data: Vec<u8> = args.encode();
+
+	// Move serialized arguments inside the memory and invoke dispatch thunk and
+	// then free allocated memory.
+	let invoke_args_len = invoke_args_data.len() as WordSize;
+	let invoke_args_ptr = supervisor_externals.allocate(invoke_args_len)?;
+	supervisor_externals.write_memory(invoke_args_ptr, &invoke_args_data)?;
+	let result = supervisor_externals.invoke(
+		dispatch_thunk,
+		invoke_args_ptr,
+		invoke_args_len,
+		state,
+		func_idx,
+	)?;
+	supervisor_externals.deallocate(invoke_args_ptr)?;
+
+	// dispatch_thunk returns pointer to serialized arguments.
+	// Unpack pointer and len of the serialized result data.
+	let (serialized_result_val_ptr, serialized_result_val_len) = {
+		// Cast to u64 to use zero-extension.
+		let v = result as u64;
+		let ptr = (v as u64 >> 32) as u32;
+		let len = (v & 0xFFFFFFFF) as u32;
+		(Pointer::new(ptr), len)
+	};
+
+	let serialized_result_val = supervisor_externals
+		.read_memory(serialized_result_val_ptr, serialized_result_val_len)?;
+	supervisor_externals
+		.deallocate(serialized_result_val_ptr)?;
+
+	std::result::Result::<ReturnValue, HostError>::decode(&mut &serialized_result_val[..])
+		.map_err(|_| Error::Other("Decoding Result<ReturnValue, HostError> failed!".into()))?
+		.map_err(|HostError| Error::Other("Supervisor function returned sandbox::HostError".into()))
 }
 
 impl<'a, FE: SandboxCapabilities + 'a> Externals for GuestExternals<'a, FE> {
@@ -256,46 +307,24 @@ impl<'a, FE: SandboxCapabilities + 'a> Externals for GuestExternals<'a, FE> {
 					qed"
 			);
 
-		// Serialize arguments into a byte vector.
-		let invoke_args_data: Vec<u8> = args.as_ref()
+		let args = args.as_ref()
 			.iter()
 			.cloned()
 			.map(sandbox_primitives::TypedValue::from)
-			.collect::<Vec<_>>()
-			.encode();
-
-		let state = self.state;
+			.collect::<Vec<_>>();
 
-		// Move serialized arguments inside the memory and invoke dispatch thunk and
-		// then free allocated memory.
-		let invoke_args_len = invoke_args_data.len() as WordSize;
-		let invoke_args_ptr = self.supervisor_externals.allocate(invoke_args_len)?;
-		self.supervisor_externals.write_memory(invoke_args_ptr, &invoke_args_data)?;
-		let result = self.supervisor_externals.invoke(
+		let return_value = invoke_supervisor(
+			self.supervisor_externals,
 			&self.sandbox_instance.dispatch_thunk,
-			invoke_args_ptr,
-			invoke_args_len,
-			state,
 			func_idx,
+			&args,
+			self.state,
 		)?;
-		self.supervisor_externals.deallocate(invoke_args_ptr)?;
-
-		// dispatch_thunk returns pointer to serialized arguments.
-		// Unpack pointer and len of the serialized result data.
-		let (serialized_result_val_ptr, serialized_result_val_len) = {
-			// Cast to u64 to use zero-extension.
-			let v = result as u64;
-			let ptr = (v as u64 >> 32) as u32;
-			let len = (v & 0xFFFFFFFF) as u32;
-			(Pointer::new(ptr), len)
-		};
 
-		let serialized_result_val = self.supervisor_externals
-			.read_memory(serialized_result_val_ptr, serialized_result_val_len)?;
-		self.supervisor_externals
-			.deallocate(serialized_result_val_ptr)?;
-
-		deserialize_result(&serialized_result_val)
+		Ok(match return_value {
+			sandbox_primitives::ReturnValue::Unit => None,
+			sandbox_primitives::ReturnValue::Value(typed_value) => Some(RuntimeValue::from(typed_value)),
+		})
 	}
 }
 
@@ -332,11 +361,18 @@ where
 ///
 /// [`invoke`]: #method.invoke
 pub struct SandboxInstance<FR> {
-	instance: ModuleRef,
+	instance: BackendInstance,
 	dispatch_thunk: FR,
 	guest_to_supervisor_mapping: GuestToSupervisorFunctionMapping,
 }
 
+/// An instance of a guest module, specific to the sandbox backend.
+enum BackendInstance {
+	Wasmi(ModuleRef),
+	#[cfg(feature = "wasmtime")]
+	Wasmtime(wasmtime_backend::Instance),
+}
+
 impl<FR> SandboxInstance<FR> {
 	/// Invoke an exported function by a name.
 	///
@@ -351,16 +387,27 @@ impl<FR> SandboxInstance<FR> {
 		args: &[RuntimeValue],
 		supervisor_externals: &mut FE,
 		state: u32,
-	) -> std::result::Result<Option<wasmi::RuntimeValue>, wasmi::Error> {
-		with_guest_externals(
-			supervisor_externals,
-			self,
-			state,
-			|guest_externals| {
-				self.instance
-					.invoke_export(export_name, args, guest_externals)
-			},
-		)
+	) -> Result<Option<RuntimeValue>> {
+		match self.instance {
+			BackendInstance::Wasmi(ref instance) => with_guest_externals(
+				supervisor_externals,
+				self,
+				state,
+				|guest_externals| {
+					instance
+						.invoke_export(export_name, args, guest_externals)
+						.map_err(Into::into)
+				},
+			),
+			#[cfg(feature = "wasmtime")]
+			BackendInstance::Wasmtime(ref instance) => {
+				let dispatch_thunk = &self.dispatch_thunk;
+				let mut dispatch = |func_idx, guest_args: &[sandbox_primitives::TypedValue]| {
+					invoke_supervisor(supervisor_externals, dispatch_thunk, func_idx, guest_args, state)
+				};
+				instance.invoke(export_name, args, &mut dispatch)
+			}
+		}
 	}
 }
 
@@ -381,7 +428,7 @@ pub enum InstantiationError {
 
 fn decode_environment_definition(
 	raw_env_def: &[u8],
-	memories: &[Option<MemoryRef>],
+	memories: &[Option<Memory>],
 ) -> std::result::Result<(Imports, GuestToSupervisorFunctionMapping), InstantiationError> {
 	let env_def = sandbox_primitives::EnvironmentDefinition::decode(&mut &raw_env_def[..])
 		.map_err(|_| InstantiationError::EnvironmentDefinitionCorrupted)?;
@@ -401,12 +448,12 @@ fn decode_environment_definition(
 				func_map.insert((module, field), externals_idx);
 			}
 			sandbox_primitives::ExternEntity::Memory(memory_idx) => {
-				let memory_ref = memories
+				let memory = memories
 					.get(memory_idx as usize)
 					.cloned()
 					.ok_or_else(|| InstantiationError::EnvironmentDefinitionCorrupted)?
 					.ok_or_else(|| InstantiationError::EnvironmentDefinitionCorrupted)?;
-				memories_map.insert((module, field), memory_ref);
+				memories_map.insert((module, field), memory);
 			}
 		}
 	}
@@ -444,14 +491,59 @@ pub fn instantiate<FE: SandboxCapabilities>(
 	let (imports, guest_to_supervisor_mapping) =
 		decode_environment_definition(raw_env_def, &supervisor_externals.store().memories)?;
 
+	let sandbox_instance = match supervisor_externals.store().backend {
+		BackendStore::Wasmi => instantiate_wasmi(
+			supervisor_externals,
+			dispatch_thunk,
+			guest_to_supervisor_mapping,
+			wasm,
+			&imports,
+			state,
+		)?,
+		#[cfg(feature = "wasmtime")]
+		BackendStore::Wasmtime(ref engine) => {
+			let engine = engine.clone();
+			let mut dispatch = |func_idx, guest_args: &[sandbox_primitives::TypedValue]| {
+				invoke_supervisor(&mut *supervisor_externals, &dispatch_thunk, func_idx, guest_args, state)
+			};
+			let instance = wasmtime_backend::Instance::new(
+				&engine,
+				wasm,
+				&imports,
+				&guest_to_supervisor_mapping,
+				&mut dispatch,
+			)?;
+			Rc::new(SandboxInstance {
+				instance: BackendInstance::Wasmtime(instance),
+				dispatch_thunk,
+				guest_to_supervisor_mapping,
+			})
+		}
+	};
+
+	// At last, register the instance.
+	let instance_idx = supervisor_externals
+		.store_mut()
+		.register_sandbox_instance(sandbox_instance);
+	Ok(instance_idx)
+}
+
+fn instantiate_wasmi<FE: SandboxCapabilities>(
+	supervisor_externals: &mut FE,
+	dispatch_thunk: FE::SupervisorFuncRef,
+	guest_to_supervisor_mapping: GuestToSupervisorFunctionMapping,
+	wasm: &[u8],
+	imports: &Imports,
+	state: u32,
+) -> std::result::Result<Rc<SandboxInstance<FE::SupervisorFuncRef>>, InstantiationError> {
 	let module = Module::from_buffer(wasm).map_err(|_| InstantiationError::ModuleDecoding)?;
-	let instance = ModuleInstance::new(&module, &imports).map_err(|_| InstantiationError::Instantiation)?;
+	let instance = ModuleInstance::new(&module, imports).map_err(|_| InstantiationError::Instantiation)?;
 
 	let sandbox_instance = Rc::new(SandboxInstance {
 		// In general, it's not a very good idea to use `.not_started_instance()` for anything
 		// but for extracting memory and tables. But in this particular case, we are extracting
 		// for the purpose of running `start` function which should be ok.
-		instance: instance.not_started_instance().clone(),
+		instance: BackendInstance::Wasmi(instance.not_started_instance().clone()),
 		dispatch_thunk,
 		guest_to_supervisor_mapping,
 	});
@@ -467,11 +559,44 @@ pub fn instantiate<FE: SandboxCapabilities>(
 		},
 	)?;
 
-	// At last, register the instance.
-	let instance_idx = supervisor_externals
-		.store_mut()
-		.register_sandbox_instance(sandbox_instance);
-	Ok(instance_idx)
+	Ok(sandbox_instance)
+}
+
+/// A sandboxed linear memory.
+#[derive(Clone)]
+pub enum Memory {
+	/// A memory of the wasmi backend.
+	Wasmi(MemoryRef),
+	/// A memory of the wasmtime backend.
+	#[cfg(feature = "wasmtime")]
+	Wasmtime(wasmtime_backend::Memory),
+}
+
+impl Memory {
+	/// Provides direct access to the contents of the memory.
+	pub fn with_direct_access<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+		match self {
+			Memory::Wasmi(memory_ref) => memory_ref.with_direct_access(f),
+			#[cfg(feature = "wasmtime")]
+			Memory::Wasmtime(memory) => memory.with_direct_access(f),
+		}
+	}
+
+	/// Provides direct mutable access to the contents of the memory.
+	pub fn with_direct_access_mut<R, F: FnOnce(&mut [u8]) -> R>(&self, f: F) -> R {
+		match self {
+			Memory::Wasmi(memory_ref) => memory_ref.with_direct_access_mut(f),
+			#[cfg(feature = "wasmtime")]
+			Memory::Wasmtime(memory) => memory.with_direct_access_mut(f),
+		}
+	}
+}
+
+/// The part of the [`Store`] specific to the sandbox backend.
+enum BackendStore {
+	Wasmi,
+	#[cfg(feature = "wasmtime")]
+	Wasmtime(Rc<wasmtime_backend::Engine>),
 }
 
 /// This struct keeps track of all sandboxed components.
@@ -480,15 +605,21 @@ pub fn instantiate<FE: SandboxCapabilities>(
 pub struct Store<FR> {
 	// Memories and instances are `Some` untill torndown.
 	instances: Vec<Option<Rc<SandboxInstance<FR>>>>,
-	memories: Vec<Option<MemoryRef>>,
+	memories: Vec<Option<Memory>>,
+	backend: BackendStore,
 }
 
 impl<FR> Store<FR> {
-	/// Create a new empty sandbox store.
-	pub fn new() -> Self {
+	/// Create a new empty sandbox store, executing the guest modules with `backend`.
+	pub fn new(backend: SandboxBackend) -> Self {
 		Store {
 			instances: Vec::new(),
 			memories: Vec::new(),
+			backend: match backend {
+				SandboxBackend::Wasmi => BackendStore::Wasmi,
+				#[cfg(feature = "wasmtime")]
+				SandboxBackend::Wasmtime => BackendStore::Wasmtime(Rc::new(wasmtime_backend::Engine::new())),
+			},
 		}
 	}
 
@@ -501,14 +632,18 @@ impl<FR> Store<FR> {
 	pub fn new_memory(&mut self, initial: u32, maximum: u32) -> Result<u32> {
 		let maximum = match maximum {
 			sandbox_primitives::MEM_UNLIMITED => None,
-			specified_limit => Some(Pages(specified_limit as usize)),
+			specified_limit => Some(specified_limit),
 		};
 
-		let mem =
-			MemoryInstance::alloc(
+		let mem = match self.backend {
+			BackendStore::Wasmi => Memory::Wasmi(MemoryInstance::alloc(
 				Pages(initial as usize),
-				maximum,
-			)?;
+				maximum.map(|maximum| Pages(maximum as usize)),
+			)?),
+			#[cfg(feature = "wasmtime")]
+			BackendStore::Wasmtime(ref engine) =>
+				Memory::Wasmtime(wasmtime_backend::Memory::new(engine.store(), initial, maximum)?),
+		};
 
 		let mem_idx = self.memories.len();
 		self.memories.push(Some(mem));
@@ -535,7 +670,7 @@ impl<FR> Store<FR> {
 	///
 	/// Returns `Err` If `memory_idx` isn't a valid index of an memory or
 	/// if memory has been torn down.
-	pub fn memory(&self, memory_idx: u32) -> Result<MemoryRef> {
+	pub fn memory(&self, memory_idx: u32) -> Result<Memory> {
 		self.memories
 			.get(memory_idx as usize)
 			.cloned()
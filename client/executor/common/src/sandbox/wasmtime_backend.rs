@@ -0,0 +1,403 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The sandbox backend compiling the guest modules with wasmtime.
+//!
+//! Guest modules behave as with the wasmi backend: imports are resolved by name only, calls of
+//! the guest into the supervisor go through the dispatch thunk and values of an unexpected type
+//! trap. Unlike wasmi, wasmtime has no limit of its own on the depth of the calls, so the stack
+//! height of the guest modules is limited by instrumentation, see [`limit_stack_height`].
+
+use super::{GuestToSupervisorFunctionMapping, Imports, InstantiationError, SupervisorFuncIndex};
+use crate::error::{Error, Result};
+use std::{cell::{Cell, RefCell}, collections::HashMap, rc::Rc};
+use sp_core::{blake2_256, sandbox::{ReturnValue, TypedValue}};
+use wasmi::RuntimeValue;
+use wasmtime::{
+	Callable, Extern, ExternType, Func, HostRef, Limits, MemoryType, Module, Store, Trap, Val,
+	ValType,
+};
+
+/// The maximum number of pages of a linear memory.
+const MAX_PAGES: u32 = 65536;
+
+/// The maximum number of compiled guest modules kept by an [`Engine`].
+const MAX_MODULES: usize = 64;
+
+/// Maximum height of the stack of the guest modules, see [`limit_stack_height`].
+///
+/// The height of a call is the number of its locals and parameters plus the maximum height of
+/// its operand stack, and at least one.
+const STACK_HEIGHT_LIMIT: u32 = 16 * 1024;
+
+/// Instrument the guest module `code` to trap once its stack exceeds [`STACK_HEIGHT_LIMIT`],
+/// before the native stack of the supervisor overflows.
+fn limit_stack_height(code: &[u8]) -> std::result::Result<Vec<u8>, InstantiationError> {
+	use parity_wasm::elements::{self, Local, ValueType};
+
+	let mut module = elements::deserialize_buffer::<elements::Module>(code)
+		.map_err(|_| InstantiationError::ModuleDecoding)?;
+	// The limiter ignores the calls of functions without locals nor operands, which would
+	// otherwise recurse without bound.
+	if let Some(code) = module.code_section_mut() {
+		for body in code.bodies_mut() {
+			body.locals_mut().push(Local::new(1, ValueType::I32));
+		}
+	}
+
+	let module = pwasm_utils::stack_height::inject_limiter(module, STACK_HEIGHT_LIMIT)
+		.map_err(|_| InstantiationError::ModuleDecoding)?;
+	elements::serialize(module).map_err(|_| InstantiationError::ModuleDecoding)
+}
+
+/// A guest module compiled by an [`Engine`].
+struct CompiledModule {
+	module: Module,
+	has_start: bool,
+	last_used: u64,
+}
+
+/// The wasmtime store of a sandbox, along with the guest modules it compiled.
+///
+/// The modules are cached by the hash of their code, so that a module instantiated repeatedly,
+/// e.g. a contract called by many extrinsics of a block, is only compiled once.
+pub struct Engine {
+	store: Store,
+	modules: RefCell<HashMap<[u8; 32], CompiledModule>>,
+	uses: Cell<u64>,
+}
+
+impl Engine {
+	/// Create an engine with an empty store.
+	pub fn new() -> Self {
+		Engine {
+			store: Store::default(),
+			modules: RefCell::new(HashMap::new()),
+			uses: Cell::new(0),
+		}
+	}
+
+	/// The store the guest modules and memories are created in.
+	pub fn store(&self) -> &Store {
+		&self.store
+	}
+
+	/// Compile `wasm` with its stack height limited, unless it was compiled already.
+	///
+	/// Returns the compiled module and whether it has a start function. The least recently used
+	/// module is evicted when [`MAX_MODULES`] are cached.
+	fn compile(&self, wasm: &[u8]) -> std::result::Result<(Module, bool), InstantiationError> {
+		let last_used = self.uses.get();
+		self.uses.set(last_used + 1);
+
+		let hash = blake2_256(wasm);
+		let mut modules = self.modules.borrow_mut();
+		if let Some(compiled) = modules.get_mut(&hash) {
+			compiled.last_used = last_used;
+			return Ok((compiled.module.clone(), compiled.has_start));
+		}
+
+		let wasm = limit_stack_height(wasm)?;
+		let has_start = parity_wasm::elements::deserialize_buffer::<parity_wasm::elements::Module>(&wasm)
+			.map_err(|_| InstantiationError::ModuleDecoding)?
+			.start_section()
+			.is_some();
+		let module = Module::new(&self.store, &wasm).map_err(|_| InstantiationError::ModuleDecoding)?;
+
+		if modules.len() >= MAX_MODULES {
+			let evicted = modules.iter()
+				.min_by_key(|(_, compiled)| compiled.last_used)
+				.map(|(hash, _)| *hash);
+			if let Some(evicted) = evicted {
+				modules.remove(&evicted);
+			}
+		}
+		modules.insert(hash, CompiledModule { module: module.clone(), has_start, last_used });
+
+		Ok((module, has_start))
+	}
+}
+
+/// Dispatches the calls of a guest into the supervisor.
+pub type Dispatch<'a> = dyn FnMut(SupervisorFuncIndex, &[TypedValue]) -> Result<ReturnValue> + 'a;
+
+/// The dispatcher of the ongoing call into an instance, shared with the functions it imports.
+#[derive(Default)]
+struct DispatchSlot(Cell<Option<*mut Dispatch<'static>>>);
+
+impl DispatchSlot {
+	/// Execute `f`, dispatching the calls of the guest to `dispatch`.
+	///
+	/// Calls may be nested, in which case the previous dispatcher is restored when `f` returns.
+	fn using<'a, R>(&self, dispatch: &mut Dispatch<'a>, f: impl FnOnce() -> R) -> R {
+		struct Restore<'s>(&'s DispatchSlot, Option<*mut Dispatch<'static>>);
+
+		impl<'s> Drop for Restore<'s> {
+			fn drop(&mut self) {
+				(self.0).0.set(self.1);
+			}
+		}
+
+		// The pointer is only dereferenced by `dispatch` while `f` runs, i.e. while the
+		// dispatcher is borrowed, so its lifetime can be erased.
+		let dispatch = unsafe {
+			std::mem::transmute::<*mut Dispatch<'a>, *mut Dispatch<'static>>(dispatch)
+		};
+		let _restore = Restore(self, self.0.replace(Some(dispatch)));
+		f()
+	}
+
+	fn dispatch(&self, func_idx: SupervisorFuncIndex, args: &[TypedValue]) -> Result<ReturnValue> {
+		let dispatch = self.0.get()
+			.ok_or_else(|| Error::Other("Guest function called outside of an invocation".into()))?;
+		// Set by `using` for the duration of the call into the instance; see above.
+		unsafe { (*dispatch)(func_idx, args) }
+	}
+}
+
+/// A function imported by a guest, implemented by the supervisor.
+struct GuestFunction {
+	func_idx: SupervisorFuncIndex,
+	return_type: Option<ValType>,
+	dispatch: Rc<DispatchSlot>,
+}
+
+impl Callable for GuestFunction {
+	fn call(&self, params: &[Val], results: &mut [Val]) -> std::result::Result<(), HostRef<Trap>> {
+		let args = params.iter()
+			.map(val_to_typed_value)
+			.collect::<Option<Vec<_>>>()
+			.ok_or_else(|| trap("Unsupported type of argument"))?;
+
+		let return_value = self.dispatch.dispatch(self.func_idx, &args)
+			.map_err(|e| trap(&e.to_string()))?;
+
+		match (return_value, &self.return_type, results.first_mut()) {
+			(ReturnValue::Unit, None, None) => Ok(()),
+			(ReturnValue::Value(value), Some(ty), Some(result)) if has_type(&value, ty) => {
+				*result = typed_value_to_val(value);
+				Ok(())
+			}
+			_ => Err(trap("Supervisor function returned a value of an unexpected type")),
+		}
+	}
+}
+
+/// A sandboxed memory backed by wasmtime.
+#[derive(Clone)]
+pub struct Memory(HostRef<wasmtime::Memory>);
+
+impl Memory {
+	/// Create a memory of `initial` pages, growable up to `maximum` pages.
+	pub fn new(store: &Store, initial: u32, maximum: Option<u32>) -> Result<Self> {
+		if initial > MAX_PAGES || maximum.map_or(false, |maximum| maximum < initial || maximum > MAX_PAGES) {
+			return Err(Error::Other(format!(
+				"Invalid limits of a sandboxed memory: initial {}, maximum {:?}",
+				initial,
+				maximum,
+			)));
+		}
+
+		let ty = MemoryType::new(Limits::new(initial, maximum));
+		Ok(Memory(HostRef::new(wasmtime::Memory::new(store, ty))))
+	}
+
+	/// Provides direct access to the contents of the memory.
+	pub fn with_direct_access<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+		let memory = self.0.borrow();
+		// The memory is neither grown nor dropped while borrowed.
+		let data = unsafe { std::slice::from_raw_parts(memory.data_ptr(), memory.data_size()) };
+		f(data)
+	}
+
+	/// Provides direct mutable access to the contents of the memory.
+	pub fn with_direct_access_mut<R, F: FnOnce(&mut [u8]) -> R>(&self, f: F) -> R {
+		let memory = self.0.borrow_mut();
+		// The memory is neither grown nor dropped while borrowed.
+		let data = unsafe { std::slice::from_raw_parts_mut(memory.data_ptr(), memory.data_size()) };
+		f(data)
+	}
+}
+
+/// An instance of a guest module compiled by wasmtime.
+pub struct Instance {
+	instance: wasmtime::Instance,
+	dispatch: Rc<DispatchSlot>,
+}
+
+impl Instance {
+	/// Instantiate `wasm` with `imports`, running its start function with `dispatch`.
+	///
+	/// wasmtime runs the start function as part of the instantiation. Since all imports are
+	/// resolved beforehand, an instantiation failure of a module with a start function is
+	/// reported as [`InstantiationError::StartTrapped`].
+	pub(super) fn new(
+		engine: &Engine,
+		wasm: &[u8],
+		imports: &Imports,
+		guest_to_supervisor_mapping: &GuestToSupervisorFunctionMapping,
+		dispatch: &mut Dispatch,
+	) -> std::result::Result<Self, InstantiationError> {
+		let store = engine.store();
+		let (module, has_start) = engine.compile(wasm)?;
+
+		let dispatch_slot = Rc::new(DispatchSlot::default());
+		let mut externs = Vec::with_capacity(module.imports().len());
+		for import in module.imports() {
+			let key = (import.module().as_bytes().to_vec(), import.name().as_bytes().to_vec());
+			let external = match import.ty() {
+				ExternType::Func(func_ty) => {
+					let guest_func_idx = imports.func_map.get(&key)
+						.ok_or(InstantiationError::Instantiation)?;
+					let func_idx = guest_to_supervisor_mapping.func_by_guest_index(*guest_func_idx)
+						.ok_or(InstantiationError::Instantiation)?;
+					let function = GuestFunction {
+						func_idx,
+						return_type: func_ty.results().first().cloned(),
+						dispatch: dispatch_slot.clone(),
+					};
+					Extern::Func(HostRef::new(Func::new(store, func_ty.clone(), Rc::new(function))))
+				}
+				ExternType::Memory(_) => match imports.memories_map.get(&key) {
+					Some(super::Memory::Wasmtime(memory)) => Extern::Memory(memory.0.clone()),
+					_ => return Err(InstantiationError::Instantiation),
+				},
+				_ => return Err(InstantiationError::Instantiation),
+			};
+			externs.push(external);
+		}
+
+		let instance = dispatch_slot.using(dispatch, || wasmtime::Instance::new(store, &module, &externs))
+			.map_err(|_| if has_start {
+				InstantiationError::StartTrapped
+			} else {
+				InstantiationError::Instantiation
+			})?;
+
+		Ok(Instance { instance, dispatch: dispatch_slot })
+	}
+
+	/// Invoke the exported function `export_name`, dispatching the calls of the guest with
+	/// `dispatch`.
+	pub fn invoke(
+		&self,
+		export_name: &str,
+		args: &[RuntimeValue],
+		dispatch: &mut Dispatch,
+	) -> Result<Option<RuntimeValue>> {
+		let func = self.instance.find_export_by_name(export_name)
+			.and_then(|export| export.func())
+			.ok_or_else(|| Error::Other(format!("Export {} is not a function", export_name)))?
+			.borrow();
+
+		let args = args.iter().cloned().map(TypedValue::from).collect::<Vec<_>>();
+		let params = func.r#type().params();
+		if params.len() != args.len() || args.iter().zip(params).any(|(arg, ty)| !has_type(arg, ty)) {
+			return Err(Error::Other(format!("Invalid arguments for the export {}", export_name)));
+		}
+		let params = args.into_iter().map(typed_value_to_val).collect::<Vec<_>>();
+
+		let results = self.dispatch.using(dispatch, || func.call(&params))
+			.map_err(|trap| Error::Other(format!("Sandboxed code trapped: {}", trap.borrow().message())))?;
+
+		results.first()
+			.map(|result| val_to_typed_value(result)
+				.map(RuntimeValue::from)
+				.ok_or_else(|| Error::Other("Unsupported type of return value".into()))
+			)
+			.transpose()
+	}
+}
+
+fn trap(message: &str) -> HostRef<Trap> {
+	HostRef::new(Trap::new(message.to_string()))
+}
+
+fn has_type(value: &TypedValue, ty: &ValType) -> bool {
+	match (value, ty) {
+		(TypedValue::I32(_), ValType::I32) |
+		(TypedValue::I64(_), ValType::I64) |
+		(TypedValue::F32(_), ValType::F32) |
+		(TypedValue::F64(_), ValType::F64) => true,
+		_ => false,
+	}
+}
+
+fn typed_value_to_val(value: TypedValue) -> Val {
+	match value {
+		TypedValue::I32(v) => Val::I32(v),
+		TypedValue::I64(v) => Val::I64(v),
+		TypedValue::F32(bits) => Val::F32(bits as u32),
+		TypedValue::F64(bits) => Val::F64(bits as u64),
+	}
+}
+
+fn val_to_typed_value(value: &Val) -> Option<TypedValue> {
+	match *value {
+		Val::I32(v) => Some(TypedValue::I32(v)),
+		Val::I64(v) => Some(TypedValue::I64(v)),
+		Val::F32(bits) => Some(TypedValue::F32(bits as i32)),
+		Val::F64(bits) => Some(TypedValue::F64(bits as i64)),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// An empty module, told apart from the others by the name of its custom section.
+	fn module(name: &str) -> Vec<u8> {
+		let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+		wasm.extend_from_slice(&[0, name.len() as u8 + 1, name.len() as u8]);
+		wasm.extend_from_slice(name.as_bytes());
+		wasm
+	}
+
+	#[test]
+	fn should_compile_modules_once() {
+		// given
+		let engine = Engine::new();
+
+		// when
+		for _ in 0..3 {
+			engine.compile(&module("first")).map_err(|_| ()).unwrap();
+		}
+		engine.compile(&module("second")).map_err(|_| ()).unwrap();
+
+		// then
+		assert_eq!(engine.modules.borrow().len(), 2);
+	}
+
+	#[test]
+	fn should_evict_the_least_recently_used_module() {
+		// given
+		let engine = Engine::new();
+		for index in 0..MAX_MODULES {
+			engine.compile(&module(&index.to_string())).map_err(|_| ()).unwrap();
+		}
+		engine.compile(&module("0")).map_err(|_| ()).unwrap();
+
+		// when
+		engine.compile(&module("new")).map_err(|_| ()).unwrap();
+
+		// then
+		let modules = engine.modules.borrow();
+		assert_eq!(modules.len(), MAX_MODULES);
+		assert!(modules.contains_key(&blake2_256(&module("0"))));
+		assert!(!modules.contains_key(&blake2_256(&module("1"))));
+	}
+}
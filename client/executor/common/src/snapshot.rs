@@ -0,0 +1,84 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Snapshots of the initial linear memory of a Wasm module.
+
+use crate::error::WasmError;
+use parity_wasm::elements::{deserialize_buffer, Instruction, Module as RawModule};
+
+/// The data segments of a module, i.e. the content of its linear memory just after
+/// instantiation, apart from the zeroed areas.
+#[derive(Clone)]
+pub struct DataSegmentsSnapshot {
+	/// The offset and the content of the data segments.
+	data_segments: Vec<(u32, Vec<u8>)>,
+}
+
+impl DataSegmentsSnapshot {
+	/// Extract the data segments from the given wasm code.
+	///
+	/// `global` returns the value of the `i32` global with the given index, which may be used as
+	/// the offset of a segment.
+	///
+	/// Returns `Err` if the code cannot be deserialized or the offset of a segment cannot be
+	/// determined, e.g. because the segment is passive.
+	pub fn take(
+		wasm_code: &[u8],
+		mut global: impl FnMut(u32) -> Option<u32>,
+	) -> Result<Self, WasmError> {
+		let raw_module: RawModule = deserialize_buffer(wasm_code)
+			.map_err(|_| WasmError::CantDeserializeWasm)?;
+
+		let data_segments = raw_module
+			.data_section()
+			.map(|ds| ds.entries())
+			.unwrap_or(&[])
+			.iter()
+			.map(|segment| {
+				let init_expr = match segment.offset() {
+					Some(offset) => offset.code(),
+					None => return Err(WasmError::InvalidModule),
+				};
+
+				// [op, End]
+				if init_expr.len() != 2 {
+					return Err(WasmError::InvalidModule);
+				}
+				let offset = match init_expr[0] {
+					Instruction::I32Const(v) => v as u32,
+					Instruction::GetGlobal(idx) => global(idx).ok_or(WasmError::InvalidModule)?,
+					_ => return Err(WasmError::InvalidModule),
+				};
+
+				Ok((offset, segment.value().to_vec()))
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(DataSegmentsSnapshot { data_segments })
+	}
+
+	/// Restore the data segments by writing each of them with `write`.
+	///
+	/// The rest of the memory is expected to be zeroed beforehand.
+	pub fn apply<E>(
+		&self,
+		mut write: impl FnMut(u32, &[u8]) -> Result<(), E>,
+	) -> Result<(), E> {
+		self.data_segments
+			.iter()
+			.try_for_each(|(offset, contents)| write(*offset, contents))
+	}
+}
@@ -22,7 +22,8 @@ use sp_wasm_interface::Function;
 
 /// A trait that defines an abstract wasm runtime.
 ///
-/// This can be implemented by an execution engine.
+/// This can be implemented by an execution engine. The Wasm engines are single threaded, so
+/// runtimes are not `Send` and must stay on the thread that created them.
 pub trait WasmRuntime {
 	/// Attempt to update the number of heap pages available during execution.
 	///
@@ -18,7 +18,46 @@ use sp_runtime::{print, traits::{BlakeTwo256, Hash}};
 #[cfg(not(feature = "std"))]
 use sp_core::{ed25519, sr25519};
 
+/// A static in the data segment, incremented by `test_mutable_static`.
+#[cfg(not(feature = "std"))]
+static mut MUTABLE_STATIC: u64 = 32;
+
+/// A zero initialized static, outside of the data segments, incremented by `test_zeroed_static`.
+#[cfg(not(feature = "std"))]
+static mut ZEROED_STATIC: u64 = 0;
+
 sp_core::wasm_export_functions! {
+	fn test_mutable_static() -> u64 {
+		unsafe {
+			MUTABLE_STATIC += 1;
+			MUTABLE_STATIC
+		}
+	}
+
+	fn test_zeroed_static() -> u64 {
+		unsafe {
+			ZEROED_STATIC += 1;
+			ZEROED_STATIC
+		}
+	}
+
+	fn test_grow_memory() -> u32 {
+		core::arch::wasm32::memory_grow(0, 1);
+		core::arch::wasm32::memory_size(0) as u32
+	}
+
+	fn test_memory_size() -> u32 {
+		core::arch::wasm32::memory_size(0) as u32
+	}
+
+	fn test_infinite_loop() {
+		let mut counter: u64 = 0;
+		loop {
+			// Volatile, so that the loop is not optimized out.
+			unsafe { core::ptr::write_volatile(&mut counter, counter.wrapping_add(1)) }
+		}
+	}
+
 	fn test_data_in(input: Vec<u8>) -> Vec<u8> {
 		print("set_storage");
 		storage::set(b"input", &input);
@@ -132,6 +171,10 @@ sp_core::wasm_export_functions! {
 		ok
 	}
 
+	fn test_sandbox_recursion_depth(code: Vec<u8>) -> u32 {
+		sandbox_recursion_depth(&code)
+	}
+
 	fn test_sandbox_instantiate(code: Vec<u8>) -> u8 {
 		let env_builder = sp_sandbox::EnvironmentDefinitionBuilder::new();
 		let code = match sp_sandbox::Instance::new(&code, &env_builder, &mut ()) {
@@ -144,6 +187,11 @@ sp_core::wasm_export_functions! {
 		code
 	}
 
+	fn test_sandbox_instantiate_repeatedly(code: Vec<u8>) -> bool {
+		let env_builder = sp_sandbox::EnvironmentDefinitionBuilder::new();
+		(0..100).all(|_| sp_sandbox::Instance::new(&code, &env_builder, &mut ()).is_ok())
+	}
+
 	fn test_offchain_local_storage() -> bool {
 		let kind = sp_core::offchain::StorageKind::PERSISTENT;
 		assert_eq!(sp_io::offchain::local_storage_get(kind, b"test"), None);
@@ -198,6 +246,28 @@ sp_core::wasm_export_functions! {
 	}
  }
 
+/// The depth reached by the recursion of the exported `call` of the sandboxed `code`, counted by
+/// the calls of the imported `env.inc_counter` until the recursion traps.
+#[cfg(not(feature = "std"))]
+fn sandbox_recursion_depth(code: &[u8]) -> u32 {
+	fn env_inc_counter(
+		depth: &mut u32,
+		_args: &[sp_sandbox::TypedValue],
+	) -> Result<sp_sandbox::ReturnValue, sp_sandbox::HostError> {
+		*depth += 1;
+		Ok(sp_sandbox::ReturnValue::Unit)
+	}
+
+	let mut env_builder = sp_sandbox::EnvironmentDefinitionBuilder::new();
+	env_builder.add_host_func("env", "inc_counter", env_inc_counter);
+
+	let mut depth = 0;
+	if let Ok(mut instance) = sp_sandbox::Instance::new(code, &env_builder, &mut depth) {
+		let _ = instance.invoke("call", &[], &mut depth);
+	}
+	depth
+}
+
 #[cfg(not(feature = "std"))]
 fn execute_sandboxed(
 	code: &[u8],
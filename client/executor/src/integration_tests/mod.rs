@@ -21,8 +21,9 @@ use hex_literal::hex;
 use sp_core::{
 	Blake2Hasher, blake2_128, blake2_256, ed25519, sr25519, map, Pair,
 	offchain::{OffchainExt, testing},
-	traits::Externalities,
+	traits::{Externalities, ExecutionLimits, LimitExceeded},
 };
+use sc_executor_common::metering;
 use sc_runtime_test::WASM_BINARY;
 use sp_state_machine::TestExternalities as CoreTestExternalities;
 use test_case::test_case;
@@ -498,3 +499,133 @@ fn offchain_http_should_work(wasm_method: WasmExecutionMethod) {
 		true.encode(),
 	);
 }
+
+fn create_runtime(
+	wasm_method: WasmExecutionMethod,
+) -> Box<dyn sc_executor_common::wasm_runtime::WasmRuntime> {
+	use sp_wasm_interface::HostFunctions;
+
+	crate::wasm_runtime::create_wasm_runtime_with_code(
+		wasm_method,
+		8,
+		&WASM_BINARY[..],
+		sp_io::SubstrateHostFunctions::host_functions(),
+		crate::sandbox::SandboxBackend::Wasmi,
+	).expect("Creates runtime")
+}
+
+fn create_metered_runtime(
+	wasm_method: WasmExecutionMethod,
+) -> Box<dyn sc_executor_common::wasm_runtime::WasmRuntime> {
+	use sp_wasm_interface::HostFunctions;
+
+	let mut host_functions = sp_io::SubstrateHostFunctions::host_functions();
+	host_functions.push(&metering::GAS_FUNCTION);
+	crate::wasm_runtime::create_wasm_runtime_with_code(
+		wasm_method,
+		8,
+		&metering::instrument(&WASM_BINARY[..]).expect("Instruments code"),
+		host_functions,
+		crate::sandbox::SandboxBackend::Wasmi,
+	).expect("Creates runtime")
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn data_segments_are_restored_between_calls(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let mut runtime = create_runtime(wasm_method);
+
+	for _ in 0..3 {
+		let output = runtime.call(&mut ext, "test_mutable_static", &[]).unwrap();
+		assert_eq!(u64::decode(&mut &output[..]).unwrap(), 33);
+	}
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn memory_is_zeroed_between_calls(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let mut runtime = create_runtime(wasm_method);
+
+	for _ in 0..3 {
+		let output = runtime.call(&mut ext, "test_zeroed_static", &[]).unwrap();
+		assert_eq!(u64::decode(&mut &output[..]).unwrap(), 1);
+	}
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn memory_grown_by_a_call_is_shrunk_for_the_next_call(wasm_method: WasmExecutionMethod) {
+	// given
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let mut runtime = create_runtime(wasm_method);
+	let mut call = |method| {
+		let output = runtime.call(&mut ext, method, &[]).unwrap();
+		u32::decode(&mut &output[..]).unwrap()
+	};
+	let initial_size = call("test_memory_size");
+
+	// when
+	let grown_size = call("test_grow_memory");
+
+	// then
+	assert_eq!(grown_size, initial_size + 1);
+	assert_eq!(call("test_memory_size"), initial_size);
+	assert_eq!(call("test_grow_memory"), initial_size + 1);
+	assert_eq!(call("test_memory_size"), initial_size);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn state_is_restored_after_failed_calls(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let mut runtime = create_runtime(wasm_method);
+
+	runtime.call(&mut ext, "test_mutable_static", &[]).unwrap();
+	assert!(runtime.call(&mut ext, "test_panic", &[]).is_err());
+
+	let output = runtime.call(&mut ext, "test_mutable_static", &[]).unwrap();
+	assert_eq!(u64::decode(&mut &output[..]).unwrap(), 33);
+	let output = runtime.call(&mut ext, "test_zeroed_static", &[]).unwrap();
+	assert_eq!(u64::decode(&mut &output[..]).unwrap(), 1);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn metered_calls_run_out_of_fuel(wasm_method: WasmExecutionMethod) {
+	// given
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let mut runtime = create_metered_runtime(wasm_method);
+	let limited = || ExecutionLimits::new(None, Some(1_000_000));
+	let (first, second, enough) = (limited(), limited(), limited());
+
+	// when
+	let first_result = metering::with_limits(
+		Some(&first),
+		|| runtime.call(&mut ext, "test_infinite_loop", &[]),
+	);
+	let second_result = metering::with_limits(
+		Some(&second),
+		|| runtime.call(&mut ext, "test_infinite_loop", &[]),
+	);
+	let within_fuel = metering::with_limits(
+		Some(&enough),
+		|| runtime.call(&mut ext, "test_mutable_static", &[]),
+	).unwrap();
+	let unlimited = runtime.call(&mut ext, "test_mutable_static", &[]).unwrap();
+
+	// then
+	assert!(first_result.is_err());
+	assert_eq!(first.exceeded(), Some(LimitExceeded::Fuel));
+	assert!(second_result.is_err());
+	assert_eq!(second.exceeded(), Some(LimitExceeded::Fuel));
+	assert_eq!(enough.exceeded(), None);
+	assert_eq!(u64::decode(&mut &within_fuel[..]).unwrap(), 33);
+	assert_eq!(u64::decode(&mut &unlimited[..]).unwrap(), 33);
+}
@@ -14,17 +14,41 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use super::{TestExternalities, call_in_wasm};
-use crate::WasmExecutionMethod;
+use super::TestExternalities;
+use crate::{WasmExecutionMethod, sandbox::SandboxBackend};
 
 use codec::Encode;
 use sc_runtime_test::WASM_BINARY;
+use sp_core::traits::Externalities;
 use test_case::test_case;
 use wabt;
 
-#[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
-fn sandbox_should_work(wasm_method: WasmExecutionMethod) {
+fn call_in_wasm<E: Externalities>(
+	function: &str,
+	call_data: &[u8],
+	execution_method: WasmExecutionMethod,
+	sandbox_backend: SandboxBackend,
+	ext: &mut E,
+	code: &[u8],
+	heap_pages: u64,
+) -> crate::error::Result<Vec<u8>> {
+	use sp_wasm_interface::HostFunctions;
+
+	let mut instance = crate::wasm_runtime::create_wasm_runtime_with_code(
+		execution_method,
+		heap_pages,
+		code,
+		sp_io::SubstrateHostFunctions::host_functions(),
+		sandbox_backend,
+	)?;
+	instance.call(ext, function, call_data)
+}
+
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
+fn sandbox_should_work(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -55,6 +79,7 @@ fn sandbox_should_work(wasm_method: WasmExecutionMethod) {
 			"test_sandbox",
 			&code,
 			wasm_method,
+			sandbox_backend,
 			&mut ext,
 			&test_code[..],
 			8,
@@ -63,9 +88,46 @@ fn sandbox_should_work(wasm_method: WasmExecutionMethod) {
 	);
 }
 
+#[cfg(feature = "wasmtime")]
 #[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
-fn sandbox_trap(wasm_method: WasmExecutionMethod) {
+#[test_case(WasmExecutionMethod::Compiled)]
+fn wasmtime_sandbox_traps_on_unbounded_recursion(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+
+	let code = wabt::wat2wasm(r#"
+		(module
+			(import "env" "inc_counter" (func $inc_counter))
+			(func $recurse
+				(call $inc_counter)
+				(call $recurse)
+			)
+			(func (export "call")
+				(call $recurse)
+			)
+		)
+		"#).unwrap();
+
+	let depth = call_in_wasm(
+		"test_sandbox_recursion_depth",
+		&code.encode(),
+		wasm_method,
+		SandboxBackend::Wasmtime,
+		&mut ext,
+		&WASM_BINARY[..],
+		8,
+	).unwrap();
+
+	// the recursion traps at the stack height limit instead of overflowing the native stack
+	let depth: u32 = codec::Decode::decode(&mut &depth[..]).unwrap();
+	assert!(depth > 0 && depth < 16 * 1024, "Unexpected depth {}", depth);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
+fn sandbox_trap(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -85,6 +147,7 @@ fn sandbox_trap(wasm_method: WasmExecutionMethod) {
 			"test_sandbox",
 			&code,
 			wasm_method,
+			sandbox_backend,
 			&mut ext,
 			&test_code[..],
 			8,
@@ -93,10 +156,12 @@ fn sandbox_trap(wasm_method: WasmExecutionMethod) {
 	);
 }
 
-#[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
 #[should_panic(expected = "Allocator ran out of space")]
-fn sandbox_should_trap_when_heap_exhausted(wasm_method: WasmExecutionMethod) {
+fn sandbox_should_trap_when_heap_exhausted(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -115,15 +180,18 @@ fn sandbox_should_trap_when_heap_exhausted(wasm_method: WasmExecutionMethod) {
 		"test_exhaust_heap",
 		&code,
 		wasm_method,
+		sandbox_backend,
 		&mut ext,
 		&test_code[..],
 		8,
 	).unwrap();
 }
 
-#[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
-fn start_called(wasm_method: WasmExecutionMethod) {
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
+fn start_called(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -160,6 +228,7 @@ fn start_called(wasm_method: WasmExecutionMethod) {
 			"test_sandbox",
 			&code,
 			wasm_method,
+			sandbox_backend,
 			&mut ext,
 			&test_code[..],
 			8,
@@ -168,9 +237,11 @@ fn start_called(wasm_method: WasmExecutionMethod) {
 	);
 }
 
-#[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
-fn invoke_args(wasm_method: WasmExecutionMethod) {
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
+fn invoke_args(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -203,6 +274,7 @@ fn invoke_args(wasm_method: WasmExecutionMethod) {
 			"test_sandbox_args",
 			&code,
 			wasm_method,
+			sandbox_backend,
 			&mut ext,
 			&test_code[..],
 			8,
@@ -211,9 +283,11 @@ fn invoke_args(wasm_method: WasmExecutionMethod) {
 	);
 }
 
-#[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
-fn return_val(wasm_method: WasmExecutionMethod) {
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
+fn return_val(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -234,6 +308,7 @@ fn return_val(wasm_method: WasmExecutionMethod) {
 			"test_sandbox_return_val",
 			&code,
 			wasm_method,
+			sandbox_backend,
 			&mut ext,
 			&test_code[..],
 			8,
@@ -242,9 +317,11 @@ fn return_val(wasm_method: WasmExecutionMethod) {
 	);
 }
 
-#[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
-fn unlinkable_module(wasm_method: WasmExecutionMethod) {
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
+fn unlinkable_module(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -263,6 +340,7 @@ fn unlinkable_module(wasm_method: WasmExecutionMethod) {
 			"test_sandbox_instantiate",
 			&code,
 			wasm_method,
+			sandbox_backend,
 			&mut ext,
 			&test_code[..],
 			8,
@@ -271,9 +349,11 @@ fn unlinkable_module(wasm_method: WasmExecutionMethod) {
 	);
 }
 
-#[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
-fn corrupted_module(wasm_method: WasmExecutionMethod) {
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
+fn corrupted_module(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -286,6 +366,7 @@ fn corrupted_module(wasm_method: WasmExecutionMethod) {
 			"test_sandbox_instantiate",
 			&code,
 			wasm_method,
+			sandbox_backend,
 			&mut ext,
 			&test_code[..],
 			8,
@@ -294,9 +375,11 @@ fn corrupted_module(wasm_method: WasmExecutionMethod) {
 	);
 }
 
-#[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
-fn start_fn_ok(wasm_method: WasmExecutionMethod) {
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
+fn start_fn_ok(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -318,6 +401,7 @@ fn start_fn_ok(wasm_method: WasmExecutionMethod) {
 			"test_sandbox_instantiate",
 			&code,
 			wasm_method,
+			sandbox_backend,
 			&mut ext,
 			&test_code[..],
 			8,
@@ -326,9 +410,11 @@ fn start_fn_ok(wasm_method: WasmExecutionMethod) {
 	);
 }
 
-#[test_case(WasmExecutionMethod::Interpreted)]
-#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
-fn start_fn_traps(wasm_method: WasmExecutionMethod) {
+#[test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmi)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Interpreted, SandboxBackend::Wasmtime))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmi))]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled, SandboxBackend::Wasmtime))]
+fn start_fn_traps(wasm_method: WasmExecutionMethod, sandbox_backend: SandboxBackend) {
 	let mut ext = TestExternalities::default();
 	let mut ext = ext.ext();
 	let test_code = WASM_BINARY;
@@ -351,6 +437,7 @@ fn start_fn_traps(wasm_method: WasmExecutionMethod) {
 			"test_sandbox_instantiate",
 			&code,
 			wasm_method,
+			sandbox_backend,
 			&mut ext,
 			&test_code[..],
 			8,
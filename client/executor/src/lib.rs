@@ -39,16 +39,20 @@ mod wasm_runtime;
 mod integration_tests;
 
 pub use wasmi;
-pub use native_executor::{with_native_environment, NativeExecutor, NativeExecutionDispatch};
+pub use native_executor::{
+	with_native_environment, default_host_functions, NativeExecutor, NativeExecutionDispatch,
+	WasmExecutor,
+};
 pub use sp_version::{RuntimeVersion, NativeVersion};
 pub use codec::Codec;
 #[doc(hidden)]
 pub use sp_core::traits::Externalities;
 #[doc(hidden)]
 pub use sp_wasm_interface;
-pub use wasm_runtime::WasmExecutionMethod;
+pub use wasm_runtime::{WasmExecutionMethod, enable_wasm_compilation_cache};
+pub use sc_executor_common::sandbox::SandboxBackend;
 
-pub use sc_executor_common::{error, allocator, sandbox};
+pub use sc_executor_common::{error, allocator, imports, sandbox};
 
 /// Call the given `function` in the given wasm `code`.
 ///
@@ -67,12 +71,35 @@ pub fn call_in_wasm<E: Externalities, HF: sp_wasm_interface::HostFunctions>(
 	ext: &mut E,
 	code: &[u8],
 	heap_pages: u64,
+) -> error::Result<Vec<u8>> {
+	call_in_wasm_with_sandbox_backend::<E, HF>(
+		function,
+		call_data,
+		execution_method,
+		SandboxBackend::Wasmi,
+		ext,
+		code,
+		heap_pages,
+	)
+}
+
+/// Call the given `function` in the given wasm `code`, like [`call_in_wasm`], with the modules
+/// sandboxed by the function executed by `sandbox_backend`.
+pub fn call_in_wasm_with_sandbox_backend<E: Externalities, HF: sp_wasm_interface::HostFunctions>(
+	function: &str,
+	call_data: &[u8],
+	execution_method: WasmExecutionMethod,
+	sandbox_backend: SandboxBackend,
+	ext: &mut E,
+	code: &[u8],
+	heap_pages: u64,
 ) -> error::Result<Vec<u8>> {
 	let mut instance = wasm_runtime::create_wasm_runtime_with_code(
 		execution_method,
 		heap_pages,
 		code,
 		HF::host_functions(),
+		sandbox_backend,
 	)?;
 	instance.call(ext, function, call_data)
 }
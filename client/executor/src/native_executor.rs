@@ -20,19 +20,31 @@ use crate::{
 };
 use sp_version::{NativeVersion, RuntimeVersion};
 use codec::{Decode, Encode};
-use sp_core::{NativeOrEncoded, traits::{CodeExecutor, Externalities}};
+use sp_core::{
+	NativeOrEncoded,
+	traits::{CodeExecutor, Externalities, ExternalitiesExt, ExecutionLimits, ExecutionLimitsExt},
+};
 use log::trace;
-use std::{result, cell::RefCell, panic::{UnwindSafe, AssertUnwindSafe}};
+use std::{result, sync::Arc, panic::{UnwindSafe, AssertUnwindSafe}};
 use sp_wasm_interface::{HostFunctions, Function};
-use sc_executor_common::wasm_runtime::WasmRuntime;
-
-thread_local! {
-	static RUNTIMES_CACHE: RefCell<RuntimesCache> = RefCell::new(RuntimesCache::new());
-}
+use sc_executor_common::{metering, sandbox::SandboxBackend, wasm_runtime::WasmRuntime};
 
 /// Default num of pages for the heap
 const DEFAULT_HEAP_PAGES: u64 = 1024;
 
+/// The host functions provided to every runtime.
+///
+/// Nodes may provide additional host functions through
+/// [`NativeExecutionDispatch::ExtendHostFunctions`].
+pub fn default_host_functions() -> Vec<&'static dyn Function> {
+	let mut host_functions = sp_io::SubstrateHostFunctions::host_functions();
+	// Add the old and deprecated host functions as well, so that we support old wasm runtimes.
+	host_functions.extend(
+		crate::deprecated_host_interface::SubstrateExternals::host_functions(),
+	);
+	host_functions
+}
+
 pub(crate) fn safe_call<F, U>(f: F) -> Result<U>
 	where F: UnwindSafe + FnOnce() -> U
 {
@@ -42,6 +54,25 @@ pub(crate) fn safe_call<F, U>(f: F) -> Result<U>
 	std::panic::catch_unwind(f).map_err(|_| Error::Runtime)
 }
 
+/// Call `method` of the Wasm `runtime`, interrupting it if it exceeds `limits`.
+fn call_wasm<E: Externalities>(
+	mut runtime: AssertUnwindSafe<&mut (dyn WasmRuntime + 'static)>,
+	mut ext: AssertUnwindSafe<&mut E>,
+	method: &str,
+	data: &[u8],
+	limits: Option<&ExecutionLimits>,
+) -> Result<Result<Vec<u8>>> {
+	let result = safe_call(move || metering::with_limits(
+		limits,
+		move || runtime.call(&mut **ext, method, data),
+	))?;
+
+	Ok(match limits.and_then(ExecutionLimits::exceeded) {
+		Some(limit) => Err(limit.into()),
+		None => result,
+	})
+}
+
 /// Set up the externalities and safe calling environment to execute calls to a native runtime.
 ///
 /// If the inner closure panics, it will be caught and return an error.
@@ -79,8 +110,15 @@ pub struct NativeExecutor<D> {
 	native_version: NativeVersion,
 	/// The number of 64KB pages to allocate for Wasm execution.
 	default_heap_pages: u64,
+	/// Whether the Wasm code of the calls executed with [`ExecutionLimitsExt`] is instrumented
+	/// for metering.
+	metering: bool,
+	/// The engine executing the modules sandboxed by the runtime.
+	sandbox_backend: SandboxBackend,
 	/// The host functions registered with this instance.
 	host_functions: Vec<&'static dyn Function>,
+	/// WASM runtimes cache, shared by all clones of this executor.
+	wasm: Arc<RuntimesCache>,
 }
 
 impl<D: NativeExecutionDispatch> NativeExecutor<D> {
@@ -92,12 +130,15 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 	///
 	/// `default_heap_pages` - Number of 64KB pages to allocate for Wasm execution.
 	/// 	Defaults to `DEFAULT_HEAP_PAGES` if `None` is provided.
-	pub fn new(fallback_method: WasmExecutionMethod, default_heap_pages: Option<u64>) -> Self {
-		let mut host_functions = sp_io::SubstrateHostFunctions::host_functions();
-		// Add the old and deprecated host functions as well, so that we support old wasm runtimes.
-		host_functions.extend(
-			crate::deprecated_host_interface::SubstrateExternals::host_functions(),
-		);
+	///
+	/// `max_runtime_instances` - Number of idle instances kept per runtime by every thread
+	/// 	calling into it, bounding the number of nested calls executed without instantiating it.
+	pub fn new(
+		fallback_method: WasmExecutionMethod,
+		default_heap_pages: Option<u64>,
+		max_runtime_instances: usize,
+	) -> Self {
+		let mut host_functions = default_host_functions();
 
 		// Add the custom host functions provided by the user.
 		host_functions.extend(D::ExtendHostFunctions::host_functions());
@@ -107,10 +148,40 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 			fallback_method,
 			native_version: D::native_version(),
 			default_heap_pages: default_heap_pages.unwrap_or(DEFAULT_HEAP_PAGES),
+			metering: false,
+			sandbox_backend: SandboxBackend::default(),
 			host_functions,
+			wasm: Arc::new(RuntimesCache::new(max_runtime_instances)),
 		}
 	}
 
+	/// Enable or disable the metering of the Wasm execution.
+	///
+	/// Metering is required to enforce the [`ExecutionLimits`] of the calls, at the cost of slower
+	/// execution. Only the calls executed with [`ExecutionLimitsExt`] registered run the metered
+	/// code, in instances of their own; the other calls, e.g. the ones importing blocks, keep
+	/// running the code as it is on chain.
+	pub fn with_wasm_metering(mut self, metering: bool) -> Self {
+		let gas_function: &'static dyn Function = &metering::GAS_FUNCTION;
+		self.host_functions.retain(|function| function.name() != gas_function.name());
+		if metering {
+			self.host_functions.push(gas_function);
+		}
+		self.metering = metering;
+		self
+	}
+
+	/// Set the engine executing the modules sandboxed by the runtime, e.g. the smart contracts.
+	pub fn with_sandbox_backend(mut self, sandbox_backend: SandboxBackend) -> Self {
+		self.sandbox_backend = sandbox_backend;
+		self
+	}
+
+	/// The host functions provided to the runtime.
+	pub fn host_functions(&self) -> &[&'static dyn Function] {
+		&self.host_functions
+	}
+
 	/// Execute the given closure `f` with the latest runtime (based on the `CODE` key in `ext`).
 	///
 	/// The closure `f` is expected to return `Err(_)` when there happened a `panic!` in native code
@@ -124,35 +195,27 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 	/// runtime is invalidated on any `panic!` to prevent a poisoned state. `ext` is already
 	/// implicitly handled as unwind safe, as we store it in a global variable while executing the
 	/// native runtime.
+	///
+	/// The code is instrumented for metering if `metered` is `true` and metering is enabled.
 	fn with_runtime<E, R>(
 		&self,
 		ext: &mut E,
+		metered: bool,
 		f: impl for<'a> FnOnce(
 			AssertUnwindSafe<&'a mut (dyn WasmRuntime + 'static)>,
 			&'a RuntimeVersion,
 			AssertUnwindSafe<&'a mut E>,
 		) -> Result<Result<R>>,
 	) -> Result<R> where E: Externalities {
-		RUNTIMES_CACHE.with(|cache| {
-			let mut cache = cache.borrow_mut();
-			let (runtime, version, code_hash) = cache.fetch_runtime(
-				ext,
-				self.fallback_method,
-				self.default_heap_pages,
-				&self.host_functions,
-			)?;
-
-			let runtime = AssertUnwindSafe(runtime);
-			let ext = AssertUnwindSafe(ext);
-
-			match f(runtime, version, ext) {
-				Ok(res) => res,
-				Err(e) => {
-					cache.invalidate_runtime(self.fallback_method, code_hash);
-					Err(e)
-				}
-			}
-		})
+		self.wasm.with_instance(
+			ext,
+			self.fallback_method,
+			self.default_heap_pages,
+			self.metering && metered,
+			self.sandbox_backend,
+			&self.host_functions,
+			f,
+		)
 	}
 }
 
@@ -163,7 +226,10 @@ impl<D: NativeExecutionDispatch> Clone for NativeExecutor<D> {
 			fallback_method: self.fallback_method,
 			native_version: D::native_version(),
 			default_heap_pages: self.default_heap_pages,
+			metering: self.metering,
+			sandbox_backend: self.sandbox_backend,
 			host_functions: self.host_functions.clone(),
+			wasm: self.wasm.clone(),
 		}
 	}
 }
@@ -177,7 +243,7 @@ impl<D: NativeExecutionDispatch> RuntimeInfo for NativeExecutor<D> {
 		&self,
 		ext: &mut E,
 	) -> Result<RuntimeVersion> {
-		self.with_runtime(ext, |_runtime, version, _ext| Ok(Ok(version.clone())))
+		self.with_runtime(ext, false, |_runtime, version, _ext| Ok(Ok(version.clone())))
 	}
 }
 
@@ -198,7 +264,10 @@ impl<D: NativeExecutionDispatch> CodeExecutor for NativeExecutor<D> {
 		native_call: Option<NC>,
 	) -> (Result<NativeOrEncoded<R>>, bool){
 		let mut used_native = false;
-		let result = self.with_runtime(ext, |mut runtime, onchain_version, mut ext| {
+		let limits = (&mut *ext as &mut dyn Externalities)
+			.extension::<ExecutionLimitsExt>()
+			.map(|limits| limits.0.clone());
+		let result = self.with_runtime(ext, limits.is_some(), |runtime, onchain_version, mut ext| {
 			match (
 				use_native,
 				onchain_version.can_call_with(&self.native_version.runtime_version),
@@ -212,14 +281,12 @@ impl<D: NativeExecutionDispatch> CodeExecutor for NativeExecutor<D> {
 						onchain_version,
 					);
 
-					safe_call(
-						move || runtime.call(&mut **ext, method, data).map(NativeOrEncoded::Encoded)
-					)
+					call_wasm(runtime, ext, method, data, limits.as_ref())
+						.map(|res| res.map(NativeOrEncoded::Encoded))
 				}
 				(false, _, _) => {
-					safe_call(
-						move || runtime.call(&mut **ext, method, data).map(NativeOrEncoded::Encoded)
-					)
+					call_wasm(runtime, ext, method, data, limits.as_ref())
+						.map(|res| res.map(NativeOrEncoded::Encoded))
 				},
 				(true, true, Some(call)) => {
 					trace!(
@@ -255,6 +322,120 @@ impl<D: NativeExecutionDispatch> CodeExecutor for NativeExecutor<D> {
 	}
 }
 
+/// A `CodeExecutor` that always executes the on-chain Wasm runtime, never a native one.
+///
+/// Unlike the clones of a [`NativeExecutor`], every `WasmExecutor` has its own runtimes. Calls
+/// executed with it neither wait for nor take the runtime instances used to import blocks.
+pub struct WasmExecutor {
+	/// Method used to execute the Wasm code.
+	method: WasmExecutionMethod,
+	/// Version reported as the native one, with no API as there is no native runtime.
+	native_version: NativeVersion,
+	/// The number of 64KB pages to allocate for Wasm execution.
+	default_heap_pages: u64,
+	/// The host functions registered with this instance.
+	host_functions: Vec<&'static dyn Function>,
+	/// WASM runtimes cache, shared by all clones of this executor.
+	wasm: Arc<RuntimesCache>,
+}
+
+impl WasmExecutor {
+	/// Create new instance, providing the [`default_host_functions`] to the runtime.
+	///
+	/// The parameters are the same as the ones of [`NativeExecutor::new`].
+	pub fn new(
+		method: WasmExecutionMethod,
+		default_heap_pages: Option<u64>,
+		max_runtime_instances: usize,
+	) -> Self {
+		WasmExecutor {
+			method,
+			native_version: NativeVersion {
+				runtime_version: Default::default(),
+				can_author_with: Default::default(),
+			},
+			default_heap_pages: default_heap_pages.unwrap_or(DEFAULT_HEAP_PAGES),
+			host_functions: default_host_functions(),
+			wasm: Arc::new(RuntimesCache::new(max_runtime_instances)),
+		}
+	}
+
+	fn with_runtime<E, R>(
+		&self,
+		ext: &mut E,
+		f: impl for<'a> FnOnce(
+			AssertUnwindSafe<&'a mut (dyn WasmRuntime + 'static)>,
+			&'a RuntimeVersion,
+			AssertUnwindSafe<&'a mut E>,
+		) -> Result<Result<R>>,
+	) -> Result<R> where E: Externalities {
+		self.wasm.with_instance(
+			ext,
+			self.method,
+			self.default_heap_pages,
+			false,
+			SandboxBackend::default(),
+			&self.host_functions,
+			f,
+		)
+	}
+}
+
+impl Clone for WasmExecutor {
+	fn clone(&self) -> Self {
+		WasmExecutor {
+			method: self.method,
+			native_version: NativeVersion {
+				runtime_version: self.native_version.runtime_version.clone(),
+				can_author_with: self.native_version.can_author_with.clone(),
+			},
+			default_heap_pages: self.default_heap_pages,
+			host_functions: self.host_functions.clone(),
+			wasm: self.wasm.clone(),
+		}
+	}
+}
+
+impl RuntimeInfo for WasmExecutor {
+	fn native_version(&self) -> &NativeVersion {
+		&self.native_version
+	}
+
+	fn runtime_version<E: Externalities>(
+		&self,
+		ext: &mut E,
+	) -> Result<RuntimeVersion> {
+		self.with_runtime(ext, |_runtime, version, _ext| Ok(Ok(version.clone())))
+	}
+}
+
+impl CodeExecutor for WasmExecutor {
+	type Error = Error;
+
+	fn call
+	<
+		E: Externalities,
+		R: Decode + Encode + PartialEq,
+		NC: FnOnce() -> result::Result<R, String> + UnwindSafe,
+	>(
+		&self,
+		ext: &mut E,
+		method: &str,
+		data: &[u8],
+		_use_native: bool,
+		_native_call: Option<NC>,
+	) -> (Result<NativeOrEncoded<R>>, bool) {
+		let limits = (&mut *ext as &mut dyn Externalities)
+			.extension::<ExecutionLimitsExt>()
+			.map(|limits| limits.0.clone());
+		let result = self.with_runtime(ext, |runtime, _version, ext| {
+			call_wasm(runtime, ext, method, data, limits.as_ref())
+				.map(|res| res.map(NativeOrEncoded::Encoded))
+		});
+		(result, false)
+	}
+}
+
 /// Implements a `NativeExecutionDispatch` for provided parameters.
 ///
 /// # Example
@@ -350,7 +531,7 @@ mod tests {
 
 	#[test]
 	fn native_executor_registers_custom_interface() {
-		let executor = NativeExecutor::<MyExecutor>::new(WasmExecutionMethod::Interpreted, None);
+		let executor = NativeExecutor::<MyExecutor>::new(WasmExecutionMethod::Interpreted, None, 8);
 		my_interface::HostFunctions::host_functions().iter().for_each(|function| {
 			assert_eq!(
 				executor.host_functions.iter().filter(|f| f == &function).count(),
@@ -19,13 +19,19 @@
 //! The primary means of accessing the runtimes is through a cache which saves the reusable
 //! components of the runtime that are expensive to initialize.
 
-use crate::error::{Error, WasmError};
-use log::{trace, warn};
+use crate::error::{Error, Result, WasmError};
+use log::{debug, trace, warn};
 use codec::Decode;
+use parking_lot::Mutex;
 use sp_core::{storage::well_known_keys, traits::Externalities};
 use sp_version::RuntimeVersion;
-use std::{collections::hash_map::{Entry, HashMap}, panic::AssertUnwindSafe};
-use sc_executor_common::wasm_runtime::WasmRuntime;
+use std::{
+	cell::RefCell,
+	collections::HashMap,
+	panic::AssertUnwindSafe,
+	sync::{Arc, Weak, atomic::{AtomicU64, Ordering}},
+};
+use sc_executor_common::{sandbox::SandboxBackend, wasm_runtime::WasmRuntime};
 
 use sp_wasm_interface::Function;
 
@@ -39,48 +45,97 @@ pub enum WasmExecutionMethod {
 	Compiled,
 }
 
-/// A Wasm runtime object along with its cached runtime version.
+/// Maximum number of runtimes kept in the cache, the least recently used being evicted first.
+///
+/// A runtime is cached per code, heap pages, metering and sandbox backend, so a node usually
+/// only uses a few of them at a time.
+const MAX_RUNTIMES: usize = 8;
+
+/// A Wasm runtime along with its cached runtime version.
+///
+/// The instances of the runtime can not be sent to other threads, so they are pooled by every
+/// thread using the runtime, see `LOCAL_INSTANCES`.
 struct VersionedRuntime {
-	runtime: Box<dyn WasmRuntime>,
 	/// Runtime version according to `Core_version`.
 	version: RuntimeVersion,
 }
 
+/// The idle instances of a runtime, owned by a single thread.
+struct LocalInstances {
+	/// The runtime, whose instances are dropped once it is evicted from its cache.
+	runtime: Weak<VersionedRuntime>,
+	/// The instances ready to be reused, reset before every call.
+	idle: Vec<Box<dyn WasmRuntime>>,
+}
+
+thread_local! {
+	/// The idle instances of the runtimes used by this thread, by address of their runtime.
+	///
+	/// The runtime is kept allocated by the weak reference, so its address can not be reused
+	/// while the entry exists.
+	static LOCAL_INSTANCES: RefCell<HashMap<usize, LocalInstances>> = RefCell::new(HashMap::new());
+}
+
+/// Executes `f` with the idle instances of this thread, once the instances of the runtimes
+/// evicted from their cache are dropped.
+fn with_local_instances<R>(f: impl FnOnce(&mut HashMap<usize, LocalInstances>) -> R) -> R {
+	LOCAL_INSTANCES.with(|local| {
+		let mut local = local.borrow_mut();
+		local.retain(|_, instances| instances.runtime.upgrade().is_some());
+		f(&mut local)
+	})
+}
+
+/// A cached runtime, or the error of its creation.
+struct CachedRuntime {
+	runtime: std::result::Result<Arc<VersionedRuntime>, WasmError>,
+	/// Sequence number of the last use, for evicting the least recently used runtimes.
+	last_used: u64,
+}
+
+/// Key of a runtime in the cache: the Wasm execution method, the number of heap pages, whether
+/// the code is metered, the sandbox backend and the hash of the runtime code.
+type RuntimeKey = (WasmExecutionMethod, u64, bool, SandboxBackend, Vec<u8>);
+
 /// Cache for the runtimes.
 ///
-/// When an instance is requested for the first time it is added to this cache. Metadata is kept
-/// with the instance so that it can be efficiently reinitialized.
-///
-/// When using the Wasmi interpreter execution method, the metadata includes the initial memory and
-/// values of mutable globals. Follow-up requests to fetch a runtime return this one instance with
-/// the memory reset to the initial memory. So, one runtime instance is reused for every fetch
-/// request.
+/// When a runtime is requested for the first time it is added to this cache, keeping at most
+/// `MAX_RUNTIMES` runtimes. The Wasm engines are single threaded, so the instances of a
+/// runtime never leave the thread that created them: every thread keeps up to
+/// `max_runtime_instances` idle instances per runtime, and calls on different threads are
+/// executed in parallel by different instances. Instances are created lazily and reset to their
+/// initial memory and globals before every call.
 ///
-/// For now the cache grows indefinitely, but that should be fine for now since runtimes can only be
-/// upgraded rarely and there are no other ways to make the node to execute some other runtime.
+/// Nested calls on the same thread are executed by distinct instances, which are only kept for
+/// later calls while the thread has fewer than `max_runtime_instances` idle instances.
 pub struct RuntimesCache {
-	/// A cache of runtime instances along with metadata, ready to be reused.
-	///
-	/// Instances are keyed by the Wasm execution method and the hash of their code.
-	instances: HashMap<(WasmExecutionMethod, Vec<u8>), Result<VersionedRuntime, WasmError>>,
+	/// A cache of runtimes along with their versions.
+	runtimes: Mutex<HashMap<RuntimeKey, CachedRuntime>>,
+	/// Sequence number of the last use of a runtime.
+	uses: AtomicU64,
+	/// The maximum number of idle instances kept per runtime and thread.
+	max_runtime_instances: usize,
 }
 
 impl RuntimesCache {
-	/// Creates a new instance of a runtimes cache.
-	pub fn new() -> RuntimesCache {
+	/// Creates a new instance of a runtimes cache, keeping at most `max_runtime_instances` idle
+	/// instances per runtime and thread.
+	pub fn new(max_runtime_instances: usize) -> RuntimesCache {
 		RuntimesCache {
-			instances: HashMap::new(),
+			runtimes: Mutex::new(HashMap::new()),
+			uses: AtomicU64::new(0),
+			max_runtime_instances: max_runtime_instances.max(1),
 		}
 	}
 
-	/// Fetches an instance of the runtime.
+	/// Executes `f` with an instance of the runtime found in `ext`.
 	///
-	/// On first use we create a new runtime instance, save it to the cache
-	/// and persist its initial memory.
+	/// On first use of the runtime, a first instance is created to determine its version and the
+	/// runtime is saved to the cache.
 	///
-	/// Each subsequent request will return this instance, with its memory restored
-	/// to the persisted initial memory. Thus, we reuse one single runtime instance
-	/// for every `fetch_runtime` invocation.
+	/// `f` is expected to return `Err(_)` when there happened a `panic!` in native code while
+	/// executing the runtime in Wasm, in which case the instance is dropped and a new one is
+	/// created on next use.
 	///
 	/// # Parameters
 	///
@@ -89,26 +144,35 @@ impl RuntimesCache {
 	///
 	/// `default_heap_pages` - Number of 64KB pages to allocate for Wasm execution.
 	///
+	/// `metering` - Whether the runtime code is instrumented for metering, in which case
+	/// `host_functions` must contain the `gas` function.
+	///
+	/// `sandbox_backend` - The engine executing the modules sandboxed by the runtime.
+	///
 	/// `host_functions` - The host functions that should be registered for the Wasm runtime.
 	///
 	/// # Return value
 	///
-	/// If no error occurred a tuple `(&mut WasmRuntime, H256)` is
-	/// returned. `H256` is the hash of the runtime code.
-	///
-	/// In case of failure one of two errors can be returned:
+	/// The result of `f`, or one of two errors:
 	///
 	/// `Err::InvalidCode` is returned for runtime code issues.
 	///
 	/// `Error::InvalidMemoryReference` is returned if no memory export with the
 	/// identifier `memory` can be found in the runtime.
-	pub fn fetch_runtime<E: Externalities>(
-		&mut self,
+	pub fn with_instance<E, R>(
+		&self,
 		ext: &mut E,
 		wasm_method: WasmExecutionMethod,
 		default_heap_pages: u64,
+		metering: bool,
+		sandbox_backend: SandboxBackend,
 		host_functions: &[&'static dyn Function],
-	) -> Result<(&mut (dyn WasmRuntime + 'static), &RuntimeVersion, Vec<u8>), Error> {
+		f: impl for<'a> FnOnce(
+			AssertUnwindSafe<&'a mut (dyn WasmRuntime + 'static)>,
+			&'a RuntimeVersion,
+			AssertUnwindSafe<&'a mut E>,
+		) -> Result<Result<R>>,
+	) -> Result<R> where E: Externalities {
 		let code_hash = ext
 			.original_storage_hash(well_known_keys::CODE)
 			.ok_or(Error::InvalidCode("`CODE` not found in storage.".into()))?;
@@ -118,70 +182,88 @@ impl RuntimesCache {
 			.and_then(|pages| u64::decode(&mut &pages[..]).ok())
 			.unwrap_or(default_heap_pages);
 
-		let result = match self.instances.entry((wasm_method, code_hash.clone())) {
-			Entry::Occupied(o) => {
-				let result = o.into_mut();
-				if let Ok(ref mut cached_runtime) = result {
-					let heap_pages_changed = !cached_runtime.runtime.update_heap_pages(heap_pages);
-					let host_functions_changed = cached_runtime.runtime.host_functions()
-						!= host_functions;
-					if heap_pages_changed || host_functions_changed {
-						let changed = if heap_pages_changed {
-							"heap_pages"
-						} else {
-							"host functions"
-						};
-
-						trace!(
-							target: "runtimes_cache",
-							"{} were changed. Reinstantiating the instance",
-							changed,
-						);
-						*result = create_versioned_wasm_runtime(
-							ext,
-							wasm_method,
-							heap_pages,
-							host_functions.into(),
-						);
-						if let Err(ref err) = result {
-							warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
-						}
-					}
-				}
-				result
-			},
-			Entry::Vacant(v) => {
-				trace!(target: "runtimes_cache", "no instance found in cache, creating now.");
+		let create_instance = |ext: &mut E| {
+			let code = runtime_code(ext, metering)
+				.and_then(|code| create_wasm_runtime_with_code(
+					wasm_method,
+					heap_pages,
+					&code,
+					host_functions.into(),
+					sandbox_backend,
+				));
+			code.map_err(|e| Error::InvalidCode(format!("{:?}", e)))
+		};
+
+		let mut first_instance = None;
+		let runtime = {
+			let mut runtimes = self.runtimes.lock();
+			let last_used = self.uses.fetch_add(1, Ordering::Relaxed);
+			let key = (wasm_method, heap_pages, metering, sandbox_backend, code_hash);
+			if !runtimes.contains_key(&key) {
+				trace!(target: "runtimes_cache", "no runtime found in cache, creating now.");
 				let result = create_versioned_wasm_runtime(
 					ext,
 					wasm_method,
 					heap_pages,
+					metering,
+					sandbox_backend,
 					host_functions.into(),
-				);
+				).map(|(runtime, instance)| {
+					first_instance = Some(instance);
+					Arc::new(runtime)
+				});
 				if let Err(ref err) = result {
 					warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
 				}
-				v.insert(result)
+
+				if runtimes.len() >= MAX_RUNTIMES {
+					let evicted = runtimes.iter()
+						.min_by_key(|(_, cached)| cached.last_used)
+						.map(|(key, _)| key.clone());
+					if let Some(evicted) = evicted {
+						debug!(target: "runtimes_cache", "evicting the least recently used runtime.");
+						runtimes.remove(&evicted);
+					}
+				}
+				runtimes.insert(key.clone(), CachedRuntime { runtime: result, last_used });
 			}
+
+			let cached = runtimes.get_mut(&key).expect("runtime is inserted above; qed");
+			cached.last_used = last_used;
+			cached.runtime.as_ref()
+				.map(Clone::clone)
+				.map_err(|e| Error::InvalidCode(format!("{:?}", e)))?
 		};
 
-		result.as_mut()
-			.map(|entry| (entry.runtime.as_mut(), &entry.version, code_hash))
-			.map_err(|ref e| Error::InvalidCode(format!("{:?}", e)))
-	}
+		let address = &*runtime as *const VersionedRuntime as usize;
+		let instance = match first_instance {
+			Some(instance) => Some(instance),
+			None => with_local_instances(|local| {
+				local.get_mut(&address).and_then(|instances| instances.idle.pop())
+			}),
+		};
+		let mut instance = match instance {
+			Some(instance) => instance,
+			None => create_instance(ext)?,
+		};
 
-	/// Invalidate the runtime for the given `wasm_method` and `code_hash`.
-	///
-	/// Invalidation of a runtime is useful when there was a `panic!` in native while executing it.
-	/// The `panic!` maybe have brought the runtime into a poisoned state and so, it is better to
-	/// invalidate this runtime instance.
-	pub fn invalidate_runtime(
-		&mut self,
-		wasm_method: WasmExecutionMethod,
-		code_hash: Vec<u8>,
-	) {
-		// Just remove the instance, it will be re-created the next time it is requested.
-		self.instances.remove(&(wasm_method, code_hash));
+		match f(AssertUnwindSafe(instance.as_mut()), &runtime.version, AssertUnwindSafe(ext)) {
+			Ok(res) => {
+				let max_runtime_instances = self.max_runtime_instances;
+				with_local_instances(|local| {
+					let instances = local.entry(address).or_insert_with(|| LocalInstances {
+						runtime: Arc::downgrade(&runtime),
+						idle: Vec::new(),
+					});
+					if instances.idle.len() < max_runtime_instances {
+						instances.idle.push(instance);
+					}
+				});
+				res
+			},
+			// The `panic!` may have brought the instance into a poisoned state, so it is dropped.
+			Err(e) => Err(e),
+		}
 	}
 }
 
@@ -191,28 +273,70 @@ pub fn create_wasm_runtime_with_code(
 	heap_pages: u64,
 	code: &[u8],
 	host_functions: Vec<&'static dyn Function>,
-) -> Result<Box<dyn WasmRuntime>, WasmError> {
+	sandbox_backend: SandboxBackend,
+) -> std::result::Result<Box<dyn WasmRuntime>, WasmError> {
 	match wasm_method {
 		WasmExecutionMethod::Interpreted =>
-			sc_executor_wasmi::create_instance(code, heap_pages, host_functions)
+			sc_executor_wasmi::create_instance(code, heap_pages, host_functions, sandbox_backend)
 				.map(|runtime| -> Box<dyn WasmRuntime> { Box::new(runtime) }),
 		#[cfg(feature = "wasmtime")]
 		WasmExecutionMethod::Compiled =>
-			sc_executor_wasmtime::create_instance(code, heap_pages, host_functions)
+			sc_executor_wasmtime::create_instance(code, heap_pages, host_functions, sandbox_backend)
 				.map(|runtime| -> Box<dyn WasmRuntime> { Box::new(runtime) }),
 	}
 }
 
+/// Enable caching the code compiled by [`WasmExecutionMethod::Compiled`] against
+/// `host_functions` in `directory`, so that runtimes are not recompiled from scratch on every
+/// start.
+///
+/// The cache is global to the process. Does nothing without the `wasmtime` feature.
+pub fn enable_wasm_compilation_cache(
+	directory: &std::path::Path,
+	host_functions: &[&'static dyn Function],
+) -> std::result::Result<(), WasmError> {
+	#[cfg(feature = "wasmtime")]
+	{
+		sc_executor_wasmtime::enable_cache(directory, host_functions)
+	}
+	#[cfg(not(feature = "wasmtime"))]
+	{
+		let _ = (directory, host_functions);
+		Ok(())
+	}
+}
+
+/// Read the runtime code from `ext`, instrumenting it if `metering` is enabled.
+fn runtime_code<E: Externalities>(
+	ext: &mut E,
+	metering: bool,
+) -> std::result::Result<Vec<u8>, WasmError> {
+	let code = ext
+		.original_storage(well_known_keys::CODE)
+		.ok_or(WasmError::CodeNotFound)?;
+	if metering {
+		sc_executor_common::metering::instrument(&code)
+	} else {
+		Ok(code)
+	}
+}
+
 fn create_versioned_wasm_runtime<E: Externalities>(
 	ext: &mut E,
 	wasm_method: WasmExecutionMethod,
 	heap_pages: u64,
+	metering: bool,
+	sandbox_backend: SandboxBackend,
 	host_functions: Vec<&'static dyn Function>,
-) -> Result<VersionedRuntime, WasmError> {
-	let code = ext
-		.original_storage(well_known_keys::CODE)
-		.ok_or(WasmError::CodeNotFound)?;
-	let mut runtime = create_wasm_runtime_with_code(wasm_method, heap_pages, &code, host_functions)?;
+) -> std::result::Result<(VersionedRuntime, Box<dyn WasmRuntime>), WasmError> {
+	let code = runtime_code(ext, metering)?;
+	let mut runtime = create_wasm_runtime_with_code(
+		wasm_method,
+		heap_pages,
+		&code,
+		host_functions,
+		sandbox_backend,
+	)?;
 
 	// Call to determine runtime version.
 	let version_result = {
@@ -231,16 +355,154 @@ fn create_versioned_wasm_runtime<E: Externalities>(
 	let version = RuntimeVersion::decode(&mut encoded_version.as_slice())
 		.map_err(|_| WasmError::Instantiation("failed to decode \"Core_version\" result".into()))?;
 
-	Ok(VersionedRuntime {
-		runtime,
-		version,
-	})
+	Ok((VersionedRuntime { version }, runtime))
 }
 
 #[cfg(test)]
 mod tests {
+	use super::*;
+	use sp_core::Blake2Hasher;
+	use sp_state_machine::TestExternalities;
 	use sp_wasm_interface::HostFunctions;
 
+	fn test_externalities() -> TestExternalities<Blake2Hasher, u64> {
+		let mut ext = TestExternalities::default();
+		ext.insert(well_known_keys::CODE.to_vec(), substrate_test_runtime::WASM_BINARY.to_vec());
+		ext
+	}
+
+	fn address(runtime: &mut (dyn WasmRuntime + 'static)) -> usize {
+		runtime as *mut dyn WasmRuntime as *mut u8 as usize
+	}
+
+	#[test]
+	fn nested_calls_use_distinct_instances() {
+		// given
+		let cache = RuntimesCache::new(2);
+		let host_functions = sp_io::SubstrateHostFunctions::host_functions();
+		let mut ext = test_externalities();
+		let mut ext = ext.ext();
+		let method = WasmExecutionMethod::Interpreted;
+
+		// when
+		let (outer, middle, inner, version) = cache.with_instance(
+			&mut ext, method, 8, false, SandboxBackend::Wasmi, &host_functions,
+			|mut outer, _, mut ext| {
+				let outer = address(&mut **outer);
+				Ok(cache.with_instance(&mut **ext, method, 8, false, SandboxBackend::Wasmi, &host_functions, |mut middle, _, mut ext| {
+					let middle = address(&mut **middle);
+					Ok(cache.with_instance(&mut **ext, method, 8, false, SandboxBackend::Wasmi, &host_functions, |mut inner, version, _| {
+						Ok(Ok((outer, middle, address(&mut **inner), version.clone())))
+					}))
+				}))
+			},
+		).unwrap();
+		let reused = cache.with_instance(
+			&mut ext, method, 8, false, SandboxBackend::Wasmi, &host_functions,
+			|mut runtime, _, _| Ok(Ok(address(&mut **runtime))),
+		).unwrap();
+
+		// then
+		assert_eq!(version, substrate_test_runtime::VERSION);
+		assert_ne!(outer, middle);
+		assert_ne!(outer, inner);
+		assert_ne!(middle, inner);
+		// Only two instances are kept, the last one released being reused first.
+		assert_eq!(reused, middle);
+	}
+
+	#[test]
+	fn failed_calls_invalidate_the_instance() {
+		// given
+		let cache = RuntimesCache::new(1);
+		let host_functions = sp_io::SubstrateHostFunctions::host_functions();
+		let mut ext = test_externalities();
+		let mut ext = ext.ext();
+		let method = WasmExecutionMethod::Interpreted;
+
+		// when
+		let result = cache.with_instance(
+			&mut ext, method, 8, false, SandboxBackend::Wasmi, &host_functions,
+			|_, _, _| -> Result<Result<()>> { Err(Error::Runtime) },
+		);
+		let called = cache.with_instance(
+			&mut ext, method, 8, false, SandboxBackend::Wasmi, &host_functions,
+			|mut runtime, _, mut ext| Ok(runtime.call(&mut **ext, "Core_version", &[])),
+		).unwrap();
+
+		// then
+		assert!(result.is_err());
+		assert_eq!(RuntimeVersion::decode(&mut &called[..]).unwrap(), substrate_test_runtime::VERSION);
+		assert_eq!(idle_instances(), vec![1]);
+	}
+
+	fn idle_instances() -> Vec<usize> {
+		LOCAL_INSTANCES.with(|local| local.borrow().values().map(|instances| instances.idle.len()).collect())
+	}
+
+	#[test]
+	fn calls_on_different_threads_use_their_own_instances() {
+		// given
+		let cache = Arc::new(RuntimesCache::new(1));
+		let barrier = Arc::new(std::sync::Barrier::new(4));
+
+		// when
+		let threads = (0..4).map(|_| {
+			let cache = cache.clone();
+			let barrier = barrier.clone();
+			std::thread::spawn(move || {
+				let host_functions = sp_io::SubstrateHostFunctions::host_functions();
+				let mut ext = test_externalities();
+				let mut ext = ext.ext();
+				(0..2).map(|_| cache.with_instance(
+					&mut ext, WasmExecutionMethod::Interpreted, 8, false, SandboxBackend::Wasmi, &host_functions,
+					|mut runtime, _, mut ext| {
+						// Keep the instances of all threads busy at the same time.
+						barrier.wait();
+						Ok(runtime.call(&mut **ext, "Core_version", &[]))
+					},
+				).unwrap()).collect::<Vec<_>>()
+			})
+		}).collect::<Vec<_>>();
+
+		// then
+		for thread in threads {
+			for version in thread.join().unwrap() {
+				assert_eq!(RuntimeVersion::decode(&mut &version[..]).unwrap(), substrate_test_runtime::VERSION);
+			}
+		}
+		assert_eq!(cache.runtimes.lock().len(), 1);
+		assert!(idle_instances().is_empty());
+	}
+
+	#[test]
+	fn least_recently_used_runtimes_are_evicted() {
+		// given
+		let cache = RuntimesCache::new(1);
+		let host_functions = sp_io::SubstrateHostFunctions::host_functions();
+		let mut ext = test_externalities();
+		let mut ext = ext.ext();
+		let call = |ext: &mut _, heap_pages| cache.with_instance(
+			ext, WasmExecutionMethod::Interpreted, heap_pages, false, SandboxBackend::Wasmi, &host_functions,
+			|_, _, _| Ok(Ok(())),
+		).unwrap();
+
+		// when
+		for heap_pages in 0..MAX_RUNTIMES as u64 {
+			call(&mut ext, 8 + heap_pages);
+		}
+		call(&mut ext, 8);
+		call(&mut ext, 8 + MAX_RUNTIMES as u64);
+
+		// then
+		let runtimes = cache.runtimes.lock();
+		assert_eq!(runtimes.len(), MAX_RUNTIMES);
+		assert!(runtimes.keys().any(|key| key.1 == 8));
+		assert!(runtimes.keys().all(|key| key.1 != 9));
+		drop(runtimes);
+		assert_eq!(idle_instances().len(), MAX_RUNTIMES);
+	}
+
 	#[test]
 	fn host_functions_are_equal() {
 		let host_functions = sp_io::SubstrateHostFunctions::host_functions();
@@ -20,16 +20,16 @@ use sc_executor_common::{
 	error::{Error, WasmError},
 	sandbox,
 	allocator,
+	snapshot::DataSegmentsSnapshot,
 };
-use std::{str, mem};
+use std::{ops::Range, str};
 use wasmi::{
-	Module, ModuleInstance, MemoryInstance, MemoryRef, TableRef, ImportsBuilder, ModuleRef,
+	Module, ModuleInstance, MemoryRef, TableRef, ImportsBuilder, ModuleRef,
 	memory_units::Pages, RuntimeValue::{I32, I64, self},
 };
 use codec::{Encode, Decode};
 use sp_core::{sandbox as sandbox_primitives, traits::Externalities};
 use log::{error, trace};
-use parity_wasm::elements::{deserialize_buffer, DataSegment, Instruction, Module as RawModule};
 use sp_wasm_interface::{
 	FunctionContext, Pointer, WordSize, Sandbox, MemoryId, Result as WResult, Function,
 };
@@ -50,9 +50,10 @@ impl<'a> FunctionExecutor<'a> {
 		heap_base: u32,
 		t: Option<TableRef>,
 		host_functions: &'a [&'static dyn Function],
+		sandbox_backend: sandbox::SandboxBackend,
 	) -> Result<Self, Error> {
 		Ok(FunctionExecutor {
-			sandbox_store: sandbox::Store::new(),
+			sandbox_store: sandbox::Store::new(sandbox_backend),
 			heap: allocator::FreeingBumpHeapAllocator::new(heap_base),
 			memory: m,
 			table: t,
@@ -153,17 +154,22 @@ impl<'a> Sandbox for FunctionExecutor<'a> {
 		buf_len: WordSize,
 	) -> WResult<u32> {
 		let sandboxed_memory = self.sandbox_store.memory(memory_id).map_err(|e| e.to_string())?;
+		let len = buf_len as usize;
 
-		match MemoryInstance::transfer(
-			&sandboxed_memory,
-			offset as usize,
-			&self.memory,
-			buf_ptr.into(),
-			buf_len as usize,
-		) {
-			Ok(()) => Ok(sandbox_primitives::ERR_OK),
-			Err(_) => Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
-		}
+		sandboxed_memory.with_direct_access(|sandboxed_memory| {
+			self.memory.with_direct_access_mut(|memory| {
+				let src_range = match checked_range(offset as usize, len, sandboxed_memory.len()) {
+					Some(range) => range,
+					None => return Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
+				};
+				let dst_range = match checked_range(buf_ptr.into(), len, memory.len()) {
+					Some(range) => range,
+					None => return Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
+				};
+				memory[dst_range].copy_from_slice(&sandboxed_memory[src_range]);
+				Ok(sandbox_primitives::ERR_OK)
+			})
+		})
 	}
 
 	fn memory_set(
@@ -174,17 +180,22 @@ impl<'a> Sandbox for FunctionExecutor<'a> {
 		val_len: WordSize,
 	) -> WResult<u32> {
 		let sandboxed_memory = self.sandbox_store.memory(memory_id).map_err(|e| e.to_string())?;
+		let len = val_len as usize;
 
-		match MemoryInstance::transfer(
-			&self.memory,
-			val_ptr.into(),
-			&sandboxed_memory,
-			offset as usize,
-			val_len as usize,
-		) {
-			Ok(()) => Ok(sandbox_primitives::ERR_OK),
-			Err(_) => Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
-		}
+		sandboxed_memory.with_direct_access_mut(|sandboxed_memory| {
+			self.memory.with_direct_access(|memory| {
+				let src_range = match checked_range(val_ptr.into(), len, memory.len()) {
+					Some(range) => range,
+					None => return Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
+				};
+				let dst_range = match checked_range(offset as usize, len, sandboxed_memory.len()) {
+					Some(range) => range,
+					None => return Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
+				};
+				sandboxed_memory[dst_range].copy_from_slice(&memory[src_range]);
+				Ok(sandbox_primitives::ERR_OK)
+			})
+		})
 	}
 
 	fn memory_teardown(&mut self, memory_id: MemoryId) -> WResult<()> {
@@ -296,7 +307,7 @@ impl<'a> wasmi::ModuleImportResolver for Resolver<'a> {
 		}
 
 		Err(wasmi::Error::Instantiation(
-			format!("Export {} not found", name),
+			sc_executor_common::imports::missing_import_message(name, self.0),
 		))
 	}
 }
@@ -319,6 +330,17 @@ impl<'a> wasmi::Externals for FunctionExecutor<'a> {
 	}
 }
 
+/// Construct a range from an offset to a data length after the offset.
+/// Returns None if the end of the range would exceed some maximum offset.
+fn checked_range(offset: usize, len: usize, max: usize) -> Option<Range<usize>> {
+	let end = offset.checked_add(len)?;
+	if end <= max {
+		Some(offset..end)
+	} else {
+		None
+	}
+}
+
 fn get_mem_instance(module: &ModuleRef) -> Result<MemoryRef, Error> {
 	Ok(module
 		.export_by_name("memory")
@@ -351,6 +373,7 @@ fn call_in_wasm_module(
 	method: &str,
 	data: &[u8],
 	host_functions: &[&'static dyn Function],
+	sandbox_backend: sandbox::SandboxBackend,
 ) -> Result<Vec<u8>, Error> {
 	// extract a reference to a linear memory, optional reference to a table
 	// and then initialize FunctionExecutor.
@@ -360,7 +383,13 @@ fn call_in_wasm_module(
 		.and_then(|e| e.as_table().cloned());
 	let heap_base = get_heap_base(module_instance)?;
 
-	let mut fec = FunctionExecutor::new(memory.clone(), heap_base, table, host_functions)?;
+	let mut fec = FunctionExecutor::new(
+		memory.clone(),
+		heap_base,
+		table,
+		host_functions,
+		sandbox_backend,
+	)?;
 
 	// Write the call data
 	let offset = fec.allocate_memory(data.len() as u32)?;
@@ -374,6 +403,7 @@ fn call_in_wasm_module(
 			&mut fec,
 		),
 	);
+	fec.heap.stats().report(method);
 
 	match result {
 		Ok(Some(I64(r))) => {
@@ -425,52 +455,28 @@ fn instantiate_module(
 /// It is used for restoring the state of the module after execution.
 #[derive(Clone)]
 struct StateSnapshot {
-	/// The offset and the content of the memory segments that should be used to restore the snapshot
-	data_segments: Vec<(u32, Vec<u8>)>,
+	/// The memory segments that should be used to restore the snapshot
+	data_segments: DataSegmentsSnapshot,
 	/// The list of all global mutable variables of the module in their sequential order.
 	global_mut_values: Vec<RuntimeValue>,
 	heap_pages: u64,
+	/// The initial size of the memory.
+	memory_size: Pages,
 }
 
 impl StateSnapshot {
-	// Returns `None` if instance is not valid.
+	// Returns `Err` if the data segments of the instance cannot be determined.
 	fn take(
 		module_instance: &ModuleRef,
-		data_segments: Vec<DataSegment>,
+		wasm_code: &[u8],
 		heap_pages: u64,
-	) -> Option<Self> {
-		let prepared_segments = data_segments
-			.into_iter()
-			.map(|mut segment| {
-				// Just replace contents of the segment since the segments will be discarded later
-				// anyway.
-				let contents = mem::replace(segment.value_mut(), vec![]);
-
-				let init_expr = match segment.offset() {
-					Some(offset) => offset.code(),
-					// Return if the segment is passive
-					None => return None
-				};
-
-				// [op, End]
-				if init_expr.len() != 2 {
-					return None;
-				}
-				let offset = match init_expr[0] {
-					Instruction::I32Const(v) => v as u32,
-					Instruction::GetGlobal(idx) => {
-						let global_val = module_instance.globals().get(idx as usize)?.get();
-						match global_val {
-							RuntimeValue::I32(v) => v as u32,
-							_ => return None,
-						}
-					}
-					_ => return None,
-				};
-
-				Some((offset, contents))
-			})
-			.collect::<Option<Vec<_>>>()?;
+	) -> Result<Self, WasmError> {
+		let data_segments = DataSegmentsSnapshot::take(wasm_code, |idx| {
+			match module_instance.globals().get(idx as usize)?.get() {
+				RuntimeValue::I32(v) => Some(v as u32),
+				_ => None,
+			}
+		})?;
 
 		// Collect all values of mutable globals.
 		let global_mut_values = module_instance
@@ -480,10 +486,15 @@ impl StateSnapshot {
 			.map(|g| g.get())
 			.collect();
 
-		Some(Self {
-			data_segments: prepared_segments,
+		let memory_size = get_mem_instance(module_instance)
+			.map_err(|_| WasmError::InvalidMemory)?
+			.current_size();
+
+		Ok(Self {
+			data_segments,
 			global_mut_values,
 			heap_pages,
+			memory_size,
 		})
 	}
 
@@ -503,11 +514,11 @@ impl StateSnapshot {
 		memory
 			.erase()
 			.map_err(|e| WasmError::ErasingFailed(e.to_string()))?;
-		for (offset, contents) in &self.data_segments {
+		self.data_segments.apply(|offset, contents| {
 			memory
-				.set(*offset, contents)
-				.map_err(|_| WasmError::ApplySnapshotFailed)?;
-		}
+				.set(offset, contents)
+				.map_err(|_| WasmError::ApplySnapshotFailed)
+		})?;
 
 		// Second, restore the values of mutable globals.
 		for (global_ref, global_val) in instance
@@ -528,14 +539,19 @@ impl StateSnapshot {
 }
 
 /// A runtime along with its initial state snapshot.
-#[derive(Clone)]
+///
+/// Memories can not shrink, so the module is instantiated again when a call grew the memory.
 pub struct WasmiRuntime {
+	/// The wasm module.
+	module: Module,
 	/// A wasm module instance.
 	instance: ModuleRef,
 	/// The snapshot of the instance's state taken just after the instantiation.
 	state_snapshot: StateSnapshot,
 	/// The host functions registered for this instance.
 	host_functions: Vec<&'static dyn Function>,
+	/// The engine executing the sandboxed guest modules.
+	sandbox_backend: sandbox::SandboxBackend,
 }
 
 impl WasmRuntime for WasmiRuntime {
@@ -553,7 +569,7 @@ impl WasmRuntime for WasmiRuntime {
 		method: &str,
 		data: &[u8],
 	) -> Result<Vec<u8>, Error> {
-		self.state_snapshot.apply(&self.instance)
+		self.restore()
 			.map_err(|e| {
 				// Snapshot restoration failed. This is pretty unexpected since this can happen
 				// if some invariant is broken or if the system is under extreme memory pressure
@@ -561,7 +577,34 @@ impl WasmRuntime for WasmiRuntime {
 				error!(target: "wasm-executor", "snapshot restoration failed: {}", e);
 				e
 			})?;
-		call_in_wasm_module(ext, &self.instance, method, data, &self.host_functions)
+		call_in_wasm_module(
+			ext,
+			&self.instance,
+			method,
+			data,
+			&self.host_functions,
+			self.sandbox_backend,
+		)
+	}
+}
+
+impl WasmiRuntime {
+	/// Restore the instance to its initial state, instantiating the module again if its memory
+	/// grew.
+	fn restore(&mut self) -> Result<(), WasmError> {
+		let memory_size = get_mem_instance(&self.instance)
+			.map_err(|_| WasmError::InvalidMemory)?
+			.current_size();
+		if memory_size == self.state_snapshot.memory_size {
+			return self.state_snapshot.apply(&self.instance);
+		}
+
+		self.instance = instantiate_module(
+			self.state_snapshot.heap_pages as usize,
+			&self.module,
+			&self.host_functions,
+		).map_err(|e| WasmError::Instantiation(e.to_string()))?;
+		Ok(())
 	}
 }
 
@@ -569,46 +612,22 @@ pub fn create_instance(
 	code: &[u8],
 	heap_pages: u64,
 	host_functions: Vec<&'static dyn Function>,
+	sandbox_backend: sandbox::SandboxBackend,
 ) -> Result<WasmiRuntime, WasmError> {
 	let module = Module::from_buffer(&code).map_err(|_| WasmError::InvalidModule)?;
 
-	// Extract the data segments from the wasm code.
-	//
-	// A return of this error actually indicates that there is a problem in logic, since
-	// we just loaded and validated the `module` above.
-	let data_segments = extract_data_segments(&code)?;
-
 	// Instantiate this module.
 	let instance = instantiate_module(heap_pages as usize, &module, &host_functions)
 		.map_err(|e| WasmError::Instantiation(e.to_string()))?;
 
 	// Take state snapshot before executing anything.
-	let state_snapshot = StateSnapshot::take(&instance, data_segments, heap_pages)
-		.expect(
-			"`take` returns `Err` if the module is not valid;
-				we already loaded module above, thus the `Module` is proven to be valid at this point;
-				qed
-				",
-		);
+	let state_snapshot = StateSnapshot::take(&instance, code, heap_pages)?;
 
 	Ok(WasmiRuntime {
+		module,
 		instance,
 		state_snapshot,
 		host_functions,
+		sandbox_backend,
 	})
 }
-
-/// Extract the data segments from the given wasm code.
-///
-/// Returns `Err` if the given wasm code cannot be deserialized.
-fn extract_data_segments(wasm_code: &[u8]) -> Result<Vec<DataSegment>, WasmError> {
-	let raw_module: RawModule = deserialize_buffer(wasm_code)
-		.map_err(|_| WasmError::CantDeserializeWasm)?;
-
-	let segments = raw_module
-		.data_section()
-		.map(|ds| ds.entries())
-		.unwrap_or(&[])
-		.to_vec();
-	Ok(segments)
-}
@@ -0,0 +1,244 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! On-disk cache of the compiled runtime code.
+//!
+//! Relies on the compilation cache of wasmtime, whose entries are keyed by the hash of the module
+//! together with the compiler version and the target ISA flags, i.e. the CPU features. Entries are
+//! checksummed and discarded when they fail to load, and a background worker evicts the least
+//! recently used ones once the cache grows past its limits.
+//!
+//! Artifacts are stored in a sub-directory specific to the version of wasmtime and to the
+//! signatures of the host functions, so that upgrading the executor never picks up code compiled
+//! by another version of wasmtime or against other host functions. Directories of other versions
+//! are removed when the cache is enabled.
+
+use std::{fs, io, path::{Path, PathBuf}, sync::Mutex};
+use sc_executor_common::error::WasmError;
+use sp_wasm_interface::Function;
+
+/// Prefix of the versioned sub-directories of the cache.
+const VERSION_DIR_PREFIX: &str = "wasmtime-";
+
+/// Name of the wasmtime cache configuration file.
+const CONFIG_FILE: &str = "config.toml";
+
+/// Soft limit on the total size of the cached artifacts.
+const TOTAL_SIZE_SOFT_LIMIT: &str = "512Mi";
+
+/// Soft limit on the number of cached artifacts.
+const FILE_COUNT_SOFT_LIMIT: u64 = 64;
+
+lazy_static::lazy_static! {
+	/// The directory the cache was enabled with, if any.
+	///
+	/// The wasmtime cache is global to the process and may only be initialized once.
+	static ref ENABLED_DIRECTORY: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+/// Enable caching the runtime code compiled against `host_functions` in `directory`.
+///
+/// The cache is global to the process: enabling it again with the same directory does nothing and
+/// enabling it with another directory fails.
+pub fn enable_cache(
+	directory: &Path,
+	host_functions: &[&'static dyn Function],
+) -> Result<(), WasmError> {
+	let mut enabled = ENABLED_DIRECTORY.lock().unwrap_or_else(|e| e.into_inner());
+	match *enabled {
+		Some(ref current) if current == directory => return Ok(()),
+		Some(ref current) => return Err(WasmError::Other(format!(
+			"the compilation cache is already enabled in {}",
+			current.display(),
+		))),
+		None => {}
+	}
+
+	let versioned_dir = versioned_dir_name(host_functions);
+	let cache_dir = directory.join(&versioned_dir);
+	fs::create_dir_all(&cache_dir).map_err(io_error)?;
+	prune_stale_versions(directory, &versioned_dir).map_err(io_error)?;
+
+	let config_path = cache_dir.join(CONFIG_FILE);
+	fs::write(&config_path, config(&cache_dir)).map_err(io_error)?;
+
+	let errors = wasmtime_environ::cache_init(true, Some(&config_path), None);
+	if !errors.is_empty() {
+		return Err(WasmError::Other(format!(
+			"failed to enable the compilation cache: {}",
+			errors.join(", "),
+		)));
+	}
+
+	*enabled = Some(directory.to_path_buf());
+	Ok(())
+}
+
+/// Name of the sub-directory storing the artifacts compiled by this version of wasmtime against
+/// `host_functions`.
+fn versioned_dir_name(host_functions: &[&'static dyn Function]) -> String {
+	let mut signatures = host_functions.iter()
+		.map(|function| format!("{}{:?}", function.name(), function.signature()))
+		.collect::<Vec<_>>();
+	signatures.sort();
+
+	format!(
+		"{}{}-{:016x}",
+		VERSION_DIR_PREFIX,
+		wasmtime_environ::VERSION,
+		u64::from_le_bytes(sp_core::hashing::twox_64(signatures.join(";").as_bytes())),
+	)
+}
+
+/// Remove the artifacts of the versioned sub-directories other than `current`.
+fn prune_stale_versions(directory: &Path, current: &str) -> io::Result<()> {
+	for entry in fs::read_dir(directory)? {
+		let entry = entry?;
+		let name = entry.file_name();
+		let name = name.to_string_lossy();
+		if name.starts_with(VERSION_DIR_PREFIX) && name != current && entry.file_type()?.is_dir() {
+			log::info!(
+				target: "wasm-runtime",
+				"Removing stale compilation cache {}",
+				entry.path().display(),
+			);
+			fs::remove_dir_all(entry.path())?;
+		}
+	}
+	Ok(())
+}
+
+/// The wasmtime cache configuration storing artifacts in `directory`.
+fn config(directory: &Path) -> String {
+	format!(
+		"[cache]\n\
+		enabled = true\n\
+		directory = {:?}\n\
+		file-count-soft-limit = \"{}\"\n\
+		files-total-size-soft-limit = \"{}\"\n",
+		directory.display().to_string(),
+		FILE_COUNT_SOFT_LIMIT,
+		TOTAL_SIZE_SOFT_LIMIT,
+	)
+}
+
+fn io_error(error: io::Error) -> WasmError {
+	WasmError::Other(format!("compilation cache: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::SystemTime;
+	use sp_wasm_interface::{FunctionContext, Result, Signature, Value, ValueType};
+
+	/// A module with a single empty function.
+	const EMPTY_MODULE: &[u8] = &[
+		0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+		0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+		0x03, 0x02, 0x01, 0x00,
+		0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+	];
+
+	struct HostFunction(Signature);
+
+	impl Function for HostFunction {
+		fn name(&self) -> &str {
+			"ext_function"
+		}
+
+		fn signature(&self) -> Signature {
+			self.0.clone()
+		}
+
+		fn execute(
+			&self,
+			_: &mut dyn FunctionContext,
+			_: &mut dyn Iterator<Item = Value>,
+		) -> Result<Option<Value>> {
+			Ok(None)
+		}
+	}
+
+	/// The artifacts found in `directory` along with their modification time.
+	fn artifacts(directory: &Path) -> Vec<(PathBuf, SystemTime)> {
+		let mut artifacts = Vec::new();
+		for entry in fs::read_dir(directory).unwrap() {
+			let entry = entry.unwrap();
+			let name = entry.file_name().to_string_lossy().into_owned();
+			if entry.file_type().unwrap().is_dir() {
+				artifacts.extend(artifacts(&entry.path()));
+			} else if name != CONFIG_FILE && !name.contains(".stats") && !name.contains(".wip") {
+				artifacts.push((entry.path(), entry.metadata().unwrap().modified().unwrap()));
+			}
+		}
+		artifacts.sort();
+		artifacts
+	}
+
+	#[test]
+	fn should_prune_other_versions_only() {
+		// given
+		let dir = tempfile::tempdir().unwrap();
+		let current_name = versioned_dir_name(&[]);
+		let stale = dir.path().join(format!("{}0.0.1", VERSION_DIR_PREFIX));
+		let current = dir.path().join(&current_name);
+		let unrelated = dir.path().join("other");
+		for path in &[&stale, &current, &unrelated] {
+			fs::create_dir_all(path).unwrap();
+		}
+
+		// when
+		prune_stale_versions(dir.path(), &current_name).unwrap();
+
+		// then
+		assert!(!stale.exists());
+		assert!(current.exists());
+		assert!(unrelated.exists());
+	}
+
+	#[test]
+	fn should_key_versions_on_host_function_signatures() {
+		// given
+		let function = HostFunction(Signature::new_with_args(&[ValueType::I32][..]));
+		let other_function = HostFunction(Signature::new_with_args(&[ValueType::I64][..]));
+		let function: &'static dyn Function = Box::leak(Box::new(function));
+		let other_function: &'static dyn Function = Box::leak(Box::new(other_function));
+
+		// then
+		let prefix = format!("{}{}-", VERSION_DIR_PREFIX, wasmtime_environ::VERSION);
+		assert!(versioned_dir_name(&[]).starts_with(&prefix));
+		assert_eq!(versioned_dir_name(&[function]), versioned_dir_name(&[function]));
+		assert_ne!(versioned_dir_name(&[function]), versioned_dir_name(&[other_function]));
+		assert_ne!(versioned_dir_name(&[function]), versioned_dir_name(&[]));
+	}
+
+	#[test]
+	fn should_reuse_cached_artifacts() {
+		// given
+		let dir = tempfile::tempdir().unwrap();
+		enable_cache(dir.path(), &[]).unwrap();
+		crate::runtime::create_compiled_unit(EMPTY_MODULE, &[]).unwrap();
+		let compiled = artifacts(dir.path());
+
+		// when
+		crate::runtime::create_compiled_unit(EMPTY_MODULE, &[]).unwrap();
+
+		// then
+		assert!(!compiled.is_empty());
+		assert_eq!(artifacts(dir.path()), compiled);
+	}
+}
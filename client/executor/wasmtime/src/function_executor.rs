@@ -53,10 +53,11 @@ pub struct FunctionExecutorState {
 }
 
 impl FunctionExecutorState {
-	/// Constructs a new `FunctionExecutorState`.
-	pub fn new(heap_base: u32) -> Self {
+	/// Constructs a new `FunctionExecutorState`, executing the sandboxed guest modules with
+	/// `sandbox_backend`.
+	pub fn new(heap_base: u32, sandbox_backend: sandbox::SandboxBackend) -> Self {
 		FunctionExecutorState {
-			sandbox_store: sandbox::Store::new(),
+			sandbox_store: sandbox::Store::new(sandbox_backend),
 			heap: FreeingBumpHeapAllocator::new(heap_base),
 		}
 	}
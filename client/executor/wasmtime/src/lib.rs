@@ -16,10 +16,12 @@
 
 ///! Defines a `WasmRuntime` that uses the Wasmtime JIT to execute.
 
+mod cache;
 mod function_executor;
 mod runtime;
 mod trampoline;
 mod util;
 
+pub use cache::enable_cache;
 pub use runtime::create_instance;
 
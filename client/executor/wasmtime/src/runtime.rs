@@ -22,6 +22,8 @@ use crate::util::{cranelift_ir_signature, read_memory_into, write_memory_from};
 
 use sc_executor_common::{
 	error::{Error, Result, WasmError},
+	sandbox::SandboxBackend,
+	snapshot::DataSegmentsSnapshot,
 	wasm_runtime::WasmRuntime,
 };
 use sp_core::traits::Externalities;
@@ -37,33 +39,36 @@ use cranelift_codegen::ir;
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_entity::{EntityRef, PrimaryMap};
 use cranelift_frontend::FunctionBuilderContext;
-use cranelift_wasm::DefinedFuncIndex;
+use cranelift_wasm::{DefinedFuncIndex, GlobalIndex};
 use wasmtime_environ::{Module, translate_signature};
 use wasmtime_jit::{
 	ActionOutcome, CodeMemory, CompilationStrategy, CompiledModule, Compiler, Context, RuntimeValue,
 };
-use wasmtime_runtime::{Export, Imports, InstanceHandle, VMFunctionBody};
+use wasmtime_runtime::{Export, Imports, InstanceHandle, VMFunctionBody, VMGlobalDefinition};
 
 /// A `WasmRuntime` implementation using the Wasmtime JIT to compile the runtime module to native
 /// and execute the compiled code.
+///
+/// The module is instantiated once and the instance is restored to its initial state from a
+/// snapshot before every call. Memories can not shrink, so the module is instantiated again when
+/// a call grew the memory.
 pub struct WasmtimeRuntime {
+	instance: InstanceHandle,
+	/// The snapshot of the instance's state taken just after the instantiation.
+	snapshot: InstanceSnapshot,
+	/// The compiled module, whose code is used by the instance.
 	module: CompiledModule,
 	context: Context,
-	max_heap_pages: Option<u32>,
 	heap_pages: u32,
 	/// The host functions registered for this instance.
 	host_functions: Vec<&'static dyn Function>,
+	/// The engine executing the sandboxed guest modules.
+	sandbox_backend: SandboxBackend,
 }
 
 impl WasmRuntime for WasmtimeRuntime {
 	fn update_heap_pages(&mut self, heap_pages: u64) -> bool {
-		match heap_pages_valid(heap_pages, self.max_heap_pages) {
-			Some(heap_pages) => {
-				self.heap_pages = heap_pages;
-				true
-			}
-			None => false,
-		}
+		u64::from(self.heap_pages) == heap_pages
 	}
 
 	fn host_functions(&self) -> &[&'static dyn Function] {
@@ -71,25 +76,105 @@ impl WasmRuntime for WasmtimeRuntime {
 	}
 
 	fn call(&mut self, ext: &mut dyn Externalities, method: &str, data: &[u8]) -> Result<Vec<u8>> {
+		if get_memory_mut(&mut self.instance)?.len() == self.snapshot.memory_size {
+			self.snapshot.apply(&mut self.instance)?;
+		} else {
+			self.instance = instantiate(&mut self.module, &mut self.context, self.heap_pages)?;
+		}
 		call_method(
 			&mut self.context,
-			&mut self.module,
+			&mut self.instance,
 			ext,
 			method,
 			data,
-			self.heap_pages,
+			self.sandbox_backend,
 		)
 	}
 }
 
+/// A state snapshot of an instance taken just after instantiation.
+struct InstanceSnapshot {
+	/// The memory segments that should be used to restore the snapshot.
+	data_segments: DataSegmentsSnapshot,
+	/// The definitions of the mutable globals of the instance.
+	mutable_globals: Vec<(GlobalIndex, VMGlobalDefinition)>,
+	/// The size in bytes of the memory of the instance.
+	memory_size: usize,
+}
+
+impl InstanceSnapshot {
+	/// Take a snapshot of the given freshly instantiated `code`.
+	fn take(
+		code: &[u8],
+		module: &Module,
+		instance: &mut InstanceHandle,
+	) -> std::result::Result<Self, WasmError> {
+		let mutable_globals = module.globals
+			.iter()
+			.filter(|(_, global)| global.mutability)
+			.map(|(index, _)| {
+				let export = wasmtime_environ::Export::Global(index);
+				match instance.lookup_by_declaration(&export) {
+					// This is safe as the definition pointer is returned by a lookup on a valid
+					// instance.
+					Export::Global { definition, .. } =>
+						Ok((index, unsafe { std::ptr::read(definition) })),
+					_ => Err(WasmError::Instantiation(format!("cannot find global {:?}", index))),
+				}
+			})
+			.collect::<std::result::Result<Vec<_>, _>>()?;
+
+		let data_segments = DataSegmentsSnapshot::take(code, |index| {
+			let index = GlobalIndex::new(index as usize);
+			match instance.lookup_by_declaration(&wasmtime_environ::Export::Global(index)) {
+				// This is safe as the definition pointer is returned by a lookup on a valid instance
+				// and the global is checked to be an I32, which can be read safely as a u32.
+				Export::Global { definition, vmctx: _, global } if global.ty == ir::types::I32 =>
+					Some(unsafe { *(*definition).as_u32() }),
+				_ => None,
+			}
+		})?;
+
+		let memory_size = get_memory_mut(instance).map_err(|_| WasmError::InvalidMemory)?.len();
+
+		Ok(InstanceSnapshot { data_segments, mutable_globals, memory_size })
+	}
+
+	/// Reset the instance to its initial state by zeroing its memory, then restoring the data
+	/// segments and the mutable globals.
+	///
+	/// The memory of the instance is expected to have its initial size.
+	fn apply(&self, instance: &mut InstanceHandle) -> Result<()> {
+		let memory = get_memory_mut(instance)?;
+		zero_memory(memory);
+		self.data_segments.apply(|offset, contents| {
+			write_memory_from(memory, Pointer::new(offset), contents)
+		})?;
+
+		for (index, value) in &self.mutable_globals {
+			match instance.lookup_by_declaration(&wasmtime_environ::Export::Global(*index)) {
+				// This is safe as the definition pointer is returned by a lookup on a valid instance
+				// and points to a definition of the same global, hence of the same type.
+				Export::Global { definition, .. } => unsafe {
+					std::ptr::copy_nonoverlapping(value, definition, 1);
+				},
+				_ => return Err(WasmError::ApplySnapshotFailed.into()),
+			}
+		}
+
+		Ok(())
+	}
+}
+
 /// Create a new `WasmtimeRuntime` given the code. This function performs translation from Wasm to
 /// machine code, which can be computationally heavy.
 pub fn create_instance(
 	code: &[u8],
 	heap_pages: u64,
 	host_functions: Vec<&'static dyn Function>,
+	sandbox_backend: SandboxBackend,
 ) -> std::result::Result<WasmtimeRuntime, WasmError> {
-	let (compiled_module, context) = create_compiled_unit(code, &host_functions)?;
+	let (mut compiled_module, mut context) = create_compiled_unit(code, &host_functions)?;
 
 	// Inspect the module for the min and max memory sizes.
 	let (min_memory_size, max_memory_size) = {
@@ -108,19 +193,54 @@ pub fn create_instance(
 	let heap_pages = heap_pages_valid(heap_pages, max_heap_pages)
 		.ok_or_else(|| WasmError::InvalidHeapPages)?;
 
+	let mut instance = instantiate(&mut compiled_module, &mut context, heap_pages)?;
+
+	// Take state snapshot before executing anything.
+	let snapshot = InstanceSnapshot::take(code, compiled_module.module_ref(), &mut instance)?;
+
 	Ok(WasmtimeRuntime {
+		instance,
+		snapshot,
 		module: compiled_module,
 		context,
-		max_heap_pages,
 		heap_pages,
 		host_functions,
+		sandbox_backend,
 	})
 }
 
-fn create_compiled_unit(
+/// Instantiate the compiled `module`, with `heap_pages` pages in addition to its initial memory.
+fn instantiate(
+	module: &mut CompiledModule,
+	context: &mut Context,
+	heap_pages: u32,
+) -> std::result::Result<InstanceHandle, WasmError> {
+	// Old exports get clobbered in `InstanceHandle::new` if we don't explicitly remove them first.
+	//
+	// The global exports mechanism is temporary in Wasmtime and expected to be removed.
+	// https://github.com/CraneStation/wasmtime/issues/332
+	clear_globals(&mut *context.get_global_exports().borrow_mut());
+
+	let mut instance = module.instantiate()
+		.map_err(|e| WasmError::Instantiation(e.to_string()))?;
+
+	// Ideally there would be a way to set the heap pages during instantiation rather than
+	// growing the memory after the fact. Currently this may require an additional mmap and copy.
+	// However, the wasmtime API doesn't support modifying the size of memory on instantiation
+	// at this time.
+	grow_memory(&mut instance, heap_pages)
+		.map_err(|e| WasmError::Instantiation(e.to_string()))?;
+
+	Ok(instance)
+}
+
+pub(crate) fn create_compiled_unit(
 	code: &[u8],
 	host_functions: &[&'static dyn Function],
 ) -> std::result::Result<(CompiledModule, Context), WasmError> {
+	// Report missing host functions with their name and version before compiling the code.
+	sc_executor_common::imports::check_imports(code, host_functions)?;
+
 	let compilation_strategy = CompilationStrategy::Cranelift;
 
 	let compiler = new_compiler(compilation_strategy)?;
@@ -142,46 +262,34 @@ fn create_compiled_unit(
 	Ok((module, context))
 }
 
-/// Call a function inside a precompiled Wasm module.
+/// Call a function inside an instance of a precompiled Wasm module.
 fn call_method(
 	context: &mut Context,
-	module: &mut CompiledModule,
+	instance: &mut InstanceHandle,
 	ext: &mut dyn Externalities,
 	method: &str,
 	data: &[u8],
-	heap_pages: u32,
+	sandbox_backend: SandboxBackend,
 ) -> Result<Vec<u8>> {
-	// Old exports get clobbered in `InstanceHandle::new` if we don't explicitly remove them first.
-	//
-	// The global exports mechanism is temporary in Wasmtime and expected to be removed.
-	// https://github.com/CraneStation/wasmtime/issues/332
-	clear_globals(&mut *context.get_global_exports().borrow_mut());
-
-	let mut instance = module.instantiate()
-		.map_err(|e| Error::Other(e.to_string()))?;
-
-	// Ideally there would be a way to set the heap pages during instantiation rather than
-	// growing the memory after the fact. Currently this may require an additional mmap and copy.
-	// However, the wasmtime API doesn't support modifying the size of memory on instantiation
-	// at this time.
-	grow_memory(&mut instance, heap_pages)?;
-
 	// Initialize the function executor state.
-	let heap_base = get_heap_base(&instance)?;
-	let executor_state = FunctionExecutorState::new(heap_base);
+	let heap_base = get_heap_base(instance)?;
+	let executor_state = FunctionExecutorState::new(heap_base, sandbox_backend);
 	reset_env_state_and_take_trap(context, Some(executor_state))?;
 
 	// Write the input data into guest memory.
-	let (data_ptr, data_len) = inject_input_data(context, &mut instance, data)?;
+	let (data_ptr, data_len) = inject_input_data(context, instance, data)?;
 	let args = [RuntimeValue::I32(u32::from(data_ptr) as i32), RuntimeValue::I32(data_len as i32)];
 
 	// Invoke the function in the runtime.
 	let outcome = sp_externalities::set_and_run_with_externalities(ext, || {
 		context
-			.invoke(&mut instance, method, &args[..])
+			.invoke(instance, method, &args[..])
 			.map_err(|e| Error::Other(format!("error calling runtime: {}", e)))
 	})?;
-	let trap_error = reset_env_state_and_take_trap(context, None)?;
+	let (executor_state, trap_error) = reset_env_state_and_take_trap(context, None)?;
+	if let Some(mut executor_state) = executor_state {
+		executor_state.heap().stats().report(method);
+	}
 	let (output_ptr, output_len) = match outcome {
 		ActionOutcome::Returned { values } => match values.as_slice() {
 			[RuntimeValue::I64(retval)] => unpack_ptr_and_len(*retval as u64),
@@ -194,7 +302,7 @@ fn call_method(
 
 	// Read the output data from guest memory.
 	let mut output = vec![0; output_len as usize];
-	let memory = get_memory_mut(&mut instance)?;
+	let memory = get_memory_mut(instance)?;
 	read_memory_into(memory, Pointer::new(output_ptr), &mut output)?;
 	Ok(output)
 }
@@ -303,11 +411,11 @@ fn get_env_state(context: &mut Context) -> Result<&mut EnvState> {
 fn reset_env_state_and_take_trap(
 	context: &mut Context,
 	executor_state: Option<FunctionExecutorState>,
-) -> Result<Option<Error>>
+) -> Result<(Option<FunctionExecutorState>, Option<Error>)>
 {
 	let env_state = get_env_state(context)?;
-	env_state.executor_state = executor_state;
-	Ok(env_state.take_trap())
+	let previous_state = std::mem::replace(&mut env_state.executor_state, executor_state);
+	Ok((previous_state, env_state.take_trap()))
 }
 
 fn inject_input_data(
@@ -343,6 +451,28 @@ fn get_memory_mut(instance: &mut InstanceHandle) -> Result<&mut [u8]> {
 	}
 }
 
+/// Zero `memory`.
+///
+/// On Linux, the pages of the memory are given back to the kernel, which maps them to the zero
+/// page until they are written again: only the pages dirtied since the instantiation cost
+/// anything to restore. Elsewhere, the whole memory is zeroed.
+fn zero_memory(memory: &mut [u8]) {
+	#[cfg(target_os = "linux")]
+	{
+		// This is safe as the memory is a private anonymous mapping owned by the instance, whose
+		// released pages read as zeroes. `madvise` fails without side effects if the memory is
+		// not page aligned, in which case it is zeroed below.
+		let released = unsafe {
+			libc::madvise(memory.as_mut_ptr() as *mut libc::c_void, memory.len(), libc::MADV_DONTNEED)
+		};
+		if released == 0 {
+			return;
+		}
+	}
+
+	memory.iter_mut().for_each(|byte| *byte = 0);
+}
+
 fn get_heap_base(instance: &InstanceHandle) -> Result<u32> {
 	// This is safe to wrap in an unsafe block as:
 	// - The result of the `lookup_immutable` call is not mutated
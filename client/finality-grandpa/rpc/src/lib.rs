@@ -0,0 +1,368 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC API for GRANDPA.
+//!
+//! Exposes the state of the current voting round (who has and hasn't voted
+//! yet), finality proofs for block ranges and a subscription to the
+//! justifications of finalized blocks.
+
+#![warn(missing_docs)]
+
+use std::sync::Arc;
+
+use codec::Encode;
+use finality_grandpa::BlockNumberOps;
+use futures::{StreamExt as _, TryStreamExt as _};
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_core::futures::{Future, Sink, Stream};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use sc_client_api::backend::Backend;
+use sc_finality_grandpa::{
+	FinalityProofProvider, GrandpaJustification, GrandpaJustificationStream,
+	RoundVotesState, SharedVoterState, VoterState,
+};
+use sc_rpc::Subscriptions;
+use sp_core::{Blake2Hasher, Bytes, H256};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+pub use self::gen_client::Client as GrandpaClient;
+
+/// The voter is paused, e.g. waiting for an authority set change to be finalized.
+const VOTER_PAUSED: i64 = 1;
+/// Finality proof could not be generated.
+const FINALITY_PROOF_FAILED: i64 = 2;
+
+/// Something that reports the state of the GRANDPA voter.
+pub trait ReportVoterState {
+	/// Returns the state of the best round of the voter, or `None` if the voter is paused.
+	fn voter_state(&self) -> Option<VoterState>;
+}
+
+impl<Block: BlockT> ReportVoterState for SharedVoterState<Block> {
+	fn voter_state(&self) -> Option<VoterState> {
+		SharedVoterState::voter_state(self)
+	}
+}
+
+/// Votes of a single kind cast in a round.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Votes {
+	/// Total weight of the voters that have cast the vote.
+	pub current_weight: u64,
+	/// Voters that haven't cast the vote yet.
+	pub missing: Vec<String>,
+}
+
+impl From<RoundVotesState> for Votes {
+	fn from(votes: RoundVotesState) -> Self {
+		Votes {
+			current_weight: votes.current_weight,
+			missing: votes.missing.iter().map(ToString::to_string).collect(),
+		}
+	}
+}
+
+/// State of the best round of the voter.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundState {
+	/// The current authority set id.
+	pub set_id: u64,
+	/// The best round number.
+	pub round: u64,
+	/// Total weight of the authority set.
+	pub total_weight: u64,
+	/// Weight required to reach supermajority.
+	pub threshold_weight: u64,
+	/// The prevotes cast in the round.
+	pub prevotes: Votes,
+	/// The precommits cast in the round.
+	pub precommits: Votes,
+}
+
+impl From<VoterState> for RoundState {
+	fn from(state: VoterState) -> Self {
+		RoundState {
+			set_id: state.set_id,
+			round: state.round,
+			total_weight: state.total_weight,
+			threshold_weight: state.threshold_weight,
+			prevotes: state.prevotes.into(),
+			precommits: state.precommits.into(),
+		}
+	}
+}
+
+/// SCALE-encoded justification of a finalized block.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JustificationNotification(Bytes);
+
+impl<Block: BlockT> From<GrandpaJustification<Block>> for JustificationNotification {
+	fn from(justification: GrandpaJustification<Block>) -> Self {
+		JustificationNotification(justification.encode().into())
+	}
+}
+
+/// GRANDPA RPC methods.
+#[rpc]
+pub trait GrandpaApi<Hash> {
+	/// RPC metadata
+	type Metadata;
+
+	/// Returns the state of the best round of the voter: the prevote and
+	/// precommit weights and the voters that haven't voted yet.
+	#[rpc(name = "grandpa_roundState")]
+	fn round_state(&self) -> Result<RoundState>;
+
+	/// Returns the SCALE-encoded proof-of-finality of the best possible block
+	/// in the range (begin; end], given that the caller knows the authority
+	/// set `authorities_set_id` that finalized `begin`.
+	///
+	/// Returns `None` if no block in the range has been finalized yet.
+	#[rpc(name = "grandpa_proveFinality")]
+	fn prove_finality(&self, begin: Hash, end: Hash, authorities_set_id: u64) -> Result<Option<Bytes>>;
+
+	/// Subscribe to the justifications of finalized blocks.
+	#[pubsub(
+		subscription = "grandpa_justifications",
+		subscribe,
+		name = "grandpa_subscribeJustifications"
+	)]
+	fn subscribe_justifications(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<JustificationNotification>,
+	);
+
+	/// Unsubscribe from justifications of finalized blocks.
+	#[pubsub(
+		subscription = "grandpa_justifications",
+		unsubscribe,
+		name = "grandpa_unsubscribeJustifications"
+	)]
+	fn unsubscribe_justifications(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool>;
+}
+
+/// Implementation of the GRANDPA RPC methods.
+pub struct GrandpaRpcHandler<VS, B, Block: BlockT<Hash=H256>> {
+	voter_state: VS,
+	justification_stream: GrandpaJustificationStream<Block>,
+	finality_proof_provider: Arc<FinalityProofProvider<B, Block>>,
+	subscriptions: Subscriptions,
+}
+
+impl<VS, B, Block: BlockT<Hash=H256>> GrandpaRpcHandler<VS, B, Block> {
+	/// Create new GRANDPA RPC handler.
+	pub fn new(
+		voter_state: VS,
+		justification_stream: GrandpaJustificationStream<Block>,
+		finality_proof_provider: Arc<FinalityProofProvider<B, Block>>,
+		subscriptions: Subscriptions,
+	) -> Self {
+		GrandpaRpcHandler {
+			voter_state,
+			justification_stream,
+			finality_proof_provider,
+			subscriptions,
+		}
+	}
+}
+
+impl<VS, B, Block> GrandpaApi<Block::Hash> for GrandpaRpcHandler<VS, B, Block> where
+	VS: ReportVoterState + Send + Sync + 'static,
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	Block: BlockT<Hash=H256>,
+	NumberFor<Block>: BlockNumberOps,
+{
+	type Metadata = sc_rpc::Metadata;
+
+	fn round_state(&self) -> Result<RoundState> {
+		self.voter_state.voter_state()
+			.map(Into::into)
+			.ok_or_else(|| Error {
+				code: ErrorCode::ServerError(VOTER_PAUSED),
+				message: "GRANDPA voter is paused.".into(),
+				data: None,
+			})
+	}
+
+	fn prove_finality(&self, begin: Block::Hash, end: Block::Hash, authorities_set_id: u64) -> Result<Option<Bytes>> {
+		self.finality_proof_provider
+			.prove_finality_for_range(begin, end, authorities_set_id)
+			.map(|proof| proof.map(Into::into))
+			.map_err(|e| Error {
+				code: ErrorCode::ServerError(FINALITY_PROOF_FAILED),
+				message: "Unable to prove finality.".into(),
+				data: Some(format!("{:?}", e).into()),
+			})
+	}
+
+	fn subscribe_justifications(
+		&self,
+		_metadata: Self::Metadata,
+		subscriber: Subscriber<JustificationNotification>,
+	) {
+		let stream = self.justification_stream.subscribe()
+			.map(|justification| Ok::<_, ()>(JustificationNotification::from(justification)))
+			.compat();
+
+		self.subscriptions.add(subscriber, |sink| {
+			sink
+				.sink_map_err(|e| warn!("Error sending justification notifications: {:?}", e))
+				.send_all(stream.map(Ok))
+				// we ignore the resulting Stream (if the first stream is over we are unsubscribed)
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_justifications(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jsonrpc_core::IoHandler;
+	use sc_finality_grandpa::{AuthorityId, GrandpaJustificationSender};
+	use sp_keyring::Ed25519Keyring;
+	use substrate_test_runtime_client::{
+		Backend, TestClientBuilder, TestClientBuilderExt, runtime::Block,
+	};
+
+	struct TestVoterState(Option<VoterState>);
+
+	impl ReportVoterState for TestVoterState {
+		fn voter_state(&self) -> Option<VoterState> {
+			self.0.clone()
+		}
+	}
+
+	fn setup_io_handler(
+		voter_state: TestVoterState,
+		runtime: &tokio::runtime::Runtime,
+	) -> (IoHandler<sc_rpc::Metadata>, H256) {
+		let builder = TestClientBuilder::new();
+		let backend = builder.backend();
+		let client = Arc::new(builder.build());
+		let genesis_hash = client.chain_info().genesis_hash;
+		let finality_proof_provider = Arc::new(FinalityProofProvider::<Backend, Block>::new(backend, client));
+
+		let (_, justification_stream) = GrandpaJustificationSender::<Block>::channel();
+		let handler = GrandpaRpcHandler::new(
+			voter_state,
+			justification_stream,
+			finality_proof_provider,
+			Subscriptions::new(Arc::new(runtime.executor())),
+		);
+
+		let mut io = IoHandler::default();
+		io.extend_with(GrandpaApi::to_delegate(handler));
+		(io, genesis_hash)
+	}
+
+	fn voter_state() -> VoterState {
+		VoterState {
+			set_id: 1,
+			round: 2,
+			total_weight: 3,
+			threshold_weight: 3,
+			prevotes: RoundVotesState {
+				current_weight: 2,
+				missing: vec![AuthorityId::from(Ed25519Keyring::Charlie.public())],
+			},
+			precommits: RoundVotesState {
+				current_weight: 0,
+				missing: vec![
+					AuthorityId::from(Ed25519Keyring::Bob.public()),
+					AuthorityId::from(Ed25519Keyring::Charlie.public()),
+				],
+			},
+		}
+	}
+
+	#[test]
+	fn round_state_reports_missing_voters() {
+		// given
+		let runtime = tokio::runtime::Runtime::new().unwrap();
+		let (io, _) = setup_io_handler(TestVoterState(Some(voter_state())), &runtime);
+		let request = r#"{"jsonrpc":"2.0","method":"grandpa_roundState","params":[],"id":1}"#;
+
+		// when
+		let response: serde_json::Value = serde_json::from_str(
+			&io.handle_request_sync(request, Default::default()).unwrap()
+		).unwrap();
+
+		// then
+		let result = &response["result"];
+		assert_eq!(result["setId"], 1);
+		assert_eq!(result["round"], 2);
+		assert_eq!(result["thresholdWeight"], 3);
+		assert_eq!(result["prevotes"]["currentWeight"], 2);
+		assert_eq!(
+			result["prevotes"]["missing"],
+			serde_json::json!([AuthorityId::from(Ed25519Keyring::Charlie.public()).to_string()]),
+		);
+		assert_eq!(result["precommits"]["missing"].as_array().unwrap().len(), 2);
+	}
+
+	#[test]
+	fn round_state_fails_when_voter_is_paused() {
+		// given
+		let runtime = tokio::runtime::Runtime::new().unwrap();
+		let (io, _) = setup_io_handler(TestVoterState(None), &runtime);
+		let request = r#"{"jsonrpc":"2.0","method":"grandpa_roundState","params":[],"id":1}"#;
+
+		// when
+		let response = io.handle_request_sync(request, Default::default()).unwrap();
+
+		// then
+		let expected = r#"{"jsonrpc":"2.0","error":{"code":1,"message":"GRANDPA voter is paused."},"id":1}"#;
+		assert_eq!(response, expected);
+	}
+
+	#[test]
+	fn prove_finality_returns_nothing_when_range_is_not_finalized() {
+		// given
+		let runtime = tokio::runtime::Runtime::new().unwrap();
+		let (io, genesis) = setup_io_handler(TestVoterState(Some(voter_state())), &runtime);
+		let request = format!(
+			r#"{{"jsonrpc":"2.0","method":"grandpa_proveFinality","params":["{:?}","{:?}",0],"id":1}}"#,
+			genesis,
+			genesis,
+		);
+
+		// when
+		let response = io.handle_request_sync(&request, Default::default()).unwrap();
+
+		// then
+		assert_eq!(response, r#"{"jsonrpc":"2.0","result":null,"id":1}"#);
+	}
+}
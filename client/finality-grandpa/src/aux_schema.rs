@@ -39,6 +39,12 @@ const SET_STATE_KEY: &[u8] = b"grandpa_completed_round";
 const CONCLUDED_ROUNDS: &[u8] = b"grandpa_concluded_rounds";
 const AUTHORITY_SET_KEY: &[u8] = b"grandpa_voters";
 const CONSENSUS_CHANGES_KEY: &[u8] = b"grandpa_consensus_changes";
+/// The standard authority set changes finalized by the node.
+///
+/// Changes are only recorded when they are finalized, so the changes finalized by a node before
+/// it started to record them are missing: there is no backfill, as it would require walking the
+/// whole finalized chain. The proof of authority set changes stops at such gaps.
+const AUTHORITY_SET_CHANGES_KEY: &[u8] = b"grandpa_authority_set_changes";
 
 const CURRENT_VERSION: u32 = 2;
 
@@ -395,6 +401,33 @@ pub(crate) fn update_authority_set<Block: BlockT, F, R>(
 	}
 }
 
+/// An authority set change enacted by a finalized block.
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub(crate) struct AuthoritySetChange<N> {
+	/// The id of the authority set enacted by the block.
+	pub(crate) set_id: SetId,
+	/// The number of the (finalized) block that enacted the set.
+	pub(crate) block_number: N,
+}
+
+/// Load the ordered list of standard authority set changes that have been
+/// finalized by this node. Returns an empty list if none are stored.
+pub(crate) fn load_authority_set_changes<Block: BlockT, B: AuxStore>(
+	backend: &B,
+) -> ClientResult<Vec<AuthoritySetChange<NumberFor<Block>>>> {
+	load_decode(backend, AUTHORITY_SET_CHANGES_KEY).map(|changes| changes.unwrap_or_default())
+}
+
+/// Update the list of finalized authority set changes on disk.
+pub(crate) fn update_authority_set_changes<N: Encode, F, R>(
+	changes: &[AuthoritySetChange<N>],
+	write_aux: F
+) -> R where
+	F: FnOnce(&[(&'static [u8], &[u8])]) -> R,
+{
+	write_aux(&[(AUTHORITY_SET_CHANGES_KEY, &changes.encode()[..])])
+}
+
 /// Write voter set state.
 pub(crate) fn write_voter_set_state<Block: BlockT, B: AuxStore>(
 	backend: &B,
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::iter::FromIterator;
 use std::sync::Arc;
 use std::time::Duration;
@@ -59,6 +59,7 @@ use crate::authorities::{AuthoritySet, SharedAuthoritySet};
 use crate::communication::Network as NetworkT;
 use crate::consensus_changes::SharedConsensusChanges;
 use crate::justification::GrandpaJustification;
+use crate::notification::GrandpaJustificationSender;
 use crate::until_imported::UntilVoteTargetImported;
 use crate::voting_rule::VotingRule;
 use sp_finality_grandpa::{AuthorityId, AuthoritySignature, SetId, RoundNumber};
@@ -376,6 +377,56 @@ impl<Block: BlockT> SharedVoterSetState<Block> {
 	}
 }
 
+/// Voters observed prevoting and precommitting in a single round.
+#[derive(Debug, Clone, Default)]
+pub struct RoundVotes {
+	/// Voters that have cast a prevote.
+	pub prevotes: HashSet<AuthorityId>,
+	/// Voters that have cast a precommit.
+	pub precommits: HashSet<AuthorityId>,
+}
+
+/// Votes observed in the live rounds of the current voter set, meant to be
+/// shared with anyone reporting on the progress of the voter.
+#[derive(Clone, Default)]
+pub struct SharedRoundVotes {
+	inner: Arc<RwLock<BTreeMap<RoundNumber, RoundVotes>>>,
+}
+
+impl SharedRoundVotes {
+	/// Note a vote cast in the given round. Primary proposals are ignored.
+	pub(crate) fn note_vote<H, N>(
+		&self,
+		round: RoundNumber,
+		voter: &AuthorityId,
+		message: &finality_grandpa::Message<H, N>,
+	) {
+		let mut rounds = self.inner.write();
+		let votes = rounds.entry(round).or_default();
+		match message {
+			finality_grandpa::Message::Prevote(_) => { votes.prevotes.insert(voter.clone()); },
+			finality_grandpa::Message::Precommit(_) => { votes.precommits.insert(voter.clone()); },
+			finality_grandpa::Message::PrimaryPropose(_) => {},
+		}
+	}
+
+	/// Forget the votes of all rounds before the given one.
+	pub(crate) fn prune(&self, round: RoundNumber) {
+		let mut rounds = self.inner.write();
+		*rounds = rounds.split_off(&round);
+	}
+
+	/// Forget the votes of all rounds, e.g. when the voter set changes.
+	pub(crate) fn clear(&self) {
+		self.inner.write().clear();
+	}
+
+	/// Returns the votes observed in the given round.
+	pub fn round(&self, round: RoundNumber) -> RoundVotes {
+		self.inner.read().get(&round).cloned().unwrap_or_default()
+	}
+}
+
 /// The environment we run GRANDPA in.
 pub(crate) struct Environment<B, E, Block: BlockT, N: NetworkT<Block>, RA, SC, VR> {
 	pub(crate) client: Arc<Client<B, E, Block, RA>>,
@@ -388,6 +439,8 @@ pub(crate) struct Environment<B, E, Block: BlockT, N: NetworkT<Block>, RA, SC, V
 	pub(crate) set_id: SetId,
 	pub(crate) voter_set_state: SharedVoterSetState<Block>,
 	pub(crate) voting_rule: VR,
+	pub(crate) round_votes: SharedRoundVotes,
+	pub(crate) justification_sender: GrandpaJustificationSender<Block>,
 }
 
 impl<B, E, Block: BlockT, N: NetworkT<Block>, RA, SC, VR> Environment<B, E, Block, N, RA, SC, VR> {
@@ -608,14 +661,18 @@ where
 		);
 
 		// schedule incoming messages from the network to be held until
-		// corresponding blocks are imported.
+		// corresponding blocks are imported. our own votes are looped back
+		// through the incoming stream as well, so all the votes of the round
+		// are noted.
+		let round_votes = self.round_votes.clone();
 		let incoming = Box::new(UntilVoteTargetImported::new(
 			self.client.import_notification_stream(),
 			self.network.clone(),
 			self.client.clone(),
 			incoming,
 			"round",
-		).map_err(Into::into));
+		).inspect(move |signed| round_votes.note_vote(round, &signed.id, &signed.message))
+			.map_err(Into::into));
 
 		// schedule network message cleanup when sink drops.
 		let outgoing = Box::new(outgoing.sink_map_err(Into::into));
@@ -815,6 +872,9 @@ where
 			Ok(Some(set_state))
 		})?;
 
+		// votes of the completed round are still reported until the next one completes.
+		self.round_votes.prune(round);
+
 		Ok(())
 	}
 
@@ -886,6 +946,7 @@ where
 			&self.authority_set,
 			&self.consensus_changes,
 			Some(self.config.justification_period.into()),
+			Some(&self.justification_sender),
 			hash,
 			number,
 			(round, commit).into(),
@@ -945,6 +1006,7 @@ pub(crate) fn finalize_block<B, Block: BlockT<Hash=H256>, E, RA>(
 	authority_set: &SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
 	consensus_changes: &SharedConsensusChanges<Block::Hash, NumberFor<Block>>,
 	justification_period: Option<NumberFor<Block>>,
+	justification_sender: Option<&GrandpaJustificationSender<Block>>,
 	hash: Block::Hash,
 	number: NumberFor<Block>,
 	justification_or_commit: JustificationOrCommit<Block>,
@@ -984,6 +1046,10 @@ pub(crate) fn finalize_block<B, Block: BlockT<Hash=H256>, E, RA>(
 		canonical_at_height(client, (hash, number), true, canon_number)
 	};
 
+	// the justification (or the commit to build it from) that subscribers
+	// are notified with once the block is finalized.
+	let mut notification = None;
+
 	let update_res: Result<_, Error> = client.lock_import_and_run(|import_op| {
 		let status = authority_set.apply_standard_changes(
 			hash,
@@ -1018,7 +1084,11 @@ pub(crate) fn finalize_block<B, Block: BlockT<Hash=H256>, E, RA>(
 		// justifications for transition blocks which will be requested by
 		// syncing clients.
 		let justification = match justification_or_commit {
-			JustificationOrCommit::Justification(justification) => Some(justification.encode()),
+			JustificationOrCommit::Justification(justification) => {
+				let encoded = justification.encode();
+				notification = Some(JustificationOrCommit::Justification(justification));
+				Some(encoded)
+			},
 			JustificationOrCommit::Commit((round_number, commit)) => {
 				let mut justification_required =
 					// justification is always required when block that enacts new authorities
@@ -1045,8 +1115,11 @@ pub(crate) fn finalize_block<B, Block: BlockT<Hash=H256>, E, RA>(
 						commit,
 					)?;
 
-					Some(justification.encode())
+					let encoded = justification.encode();
+					notification = Some(JustificationOrCommit::Justification(justification));
+					Some(encoded)
 				} else {
+					notification = Some(JustificationOrCommit::Commit((round_number, commit)));
 					None
 				}
 			},
@@ -1104,9 +1177,43 @@ pub(crate) fn finalize_block<B, Block: BlockT<Hash=H256>, E, RA>(
 			}
 		}
 
+		if let Some(ref new_set) = new_authorities {
+			// remember where the set has changed, so that the handoff can be
+			// proven to light clients later on.
+			let mut set_changes = crate::aux_schema::load_authority_set_changes::<Block, _>(client)?;
+			set_changes.push(crate::aux_schema::AuthoritySetChange {
+				set_id: new_set.set_id,
+				block_number: new_set.canon_number,
+			});
+
+			let write_result = crate::aux_schema::update_authority_set_changes(
+				&set_changes,
+				|insert| apply_aux(import_op, insert, &[]),
+			);
+
+			if let Err(e) = write_result {
+				warn!(target: "afg", "Failed to write authority set changes to disk. Bailing.");
+				warn!(target: "afg", "Node is in a potentially inconsistent state.");
+
+				return Err(e.into());
+			}
+		}
+
 		Ok(new_authorities.map(VoterCommand::ChangeAuthorities))
 	});
 
+	if let (true, Some(sender), Some(notification)) = (update_res.is_ok(), justification_sender, notification) {
+		let result = sender.notify(|| match notification {
+			JustificationOrCommit::Justification(justification) => Ok(justification),
+			JustificationOrCommit::Commit((round_number, commit)) =>
+				GrandpaJustification::from_commit(client, round_number, commit),
+		});
+
+		if let Err(e) = result {
+			warn!(target: "afg", "Failed to notify subscribers about justification of block {:?}: {:?}", hash, e);
+		}
+	}
+
 	match update_res {
 		Ok(Some(command)) => Err(CommandOrError::VoterCommand(command)),
 		Ok(None) => Ok(()),
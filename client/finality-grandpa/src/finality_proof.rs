@@ -33,6 +33,21 @@
 //! Finality proof provider can choose how to provide finality proof on its own. The incomplete
 //! finality proof (that finalizes some block C that is ancestor of the B and descendant
 //! of the U) could be returned.
+//!
+//! Handoff from the authority set N to the set N + 1 is proved by providing:
+//! 1) the header S that schedules the change, along with the headers sub-chain (S; E],
+//!    where E is the block that enacts the change;
+//! 2) the justification for the block E, signed by the set N.
+//!
+//! Proofs of all the handoffs since the set known to the caller are returned in bulk, so
+//! that the caller only downloads one fragment per set change to reach the current set.
+//! Forced changes are not signed by the previous set and can't be proved this way: the
+//! proof stops at the last standard change before the forced one.
+//!
+//! Full nodes only know the changes they have finalized since they started to record them, so
+//! they can't prove the older handoffs. Light clients request the proof of authority set changes
+//! for the first block that needs finality proof, and fall back to the finality proofs of single
+//! blocks when the proof is missing or doesn't cover the whole range.
 
 use std::iter;
 use std::sync::Arc;
@@ -48,16 +63,20 @@ use parity_scale_codec::{Encode, Decode};
 use finality_grandpa::BlockNumberOps;
 use sp_runtime::{
 	Justification, generic::BlockId,
-	traits::{NumberFor, Block as BlockT, Header as HeaderT, One},
+	traits::{NumberFor, Block as BlockT, Header as HeaderT, One, Zero},
 };
 use sp_core::{H256, Blake2Hasher, storage::StorageKey};
 use sc_telemetry::{telemetry, CONSENSUS_INFO};
 use sp_finality_grandpa::{AuthorityId, AuthorityList, VersionedAuthorityList, GRANDPA_AUTHORITIES_KEY};
 
+use crate::aux_schema::{AuthoritySetChange, load_authority_set_changes};
+use crate::import::find_scheduled_change;
 use crate::justification::GrandpaJustification;
 
 /// Maximum number of fragments that we want to return in a single prove_finality call.
 const MAX_FRAGMENTS_IN_PROOF: usize = 8;
+/// Maximum number of authority set changes that we want to prove in a single response.
+const MAX_SET_CHANGES_IN_PROOF: usize = 64;
 
 /// GRANDPA authority set related methods for the finality proof provider.
 pub trait AuthoritySetForFinalityProver<Block: BlockT>: Send + Sync {
@@ -152,6 +171,28 @@ impl<B, Block: BlockT<Hash=H256>> FinalityProofProvider<B, Block>
 	) -> Self {
 		FinalityProofProvider { backend, authority_provider }
 	}
+
+	/// Prepare SCALE-encoded proof-of-finality for the best possible block in
+	/// the range (begin; end], given that the caller knows the justification of
+	/// `begin` and the authority set with id `authorities_set_id`.
+	///
+	/// Returns `None` if there are no finalized blocks unknown to the caller.
+	pub fn prove_finality_for_range(
+		&self,
+		begin: Block::Hash,
+		end: Block::Hash,
+		authorities_set_id: u64,
+	) -> Result<Option<Vec<u8>>, ClientError> where
+		NumberFor<Block>: BlockNumberOps,
+	{
+		prove_finality::<_, _, GrandpaJustification<Block>>(
+			&*self.backend.blockchain(),
+			&*self.authority_provider,
+			authorities_set_id,
+			begin,
+			end,
+		)
+	}
 }
 
 impl<B, Block> sc_network::FinalityProofProvider<Block> for FinalityProofProvider<B, Block>
@@ -178,6 +219,11 @@ impl<B, Block> sc_network::FinalityProofProvider<Block> for FinalityProofProvide
 				request.last_finalized,
 				for_block,
 			),
+			FinalityProofRequest::AuthoritySetChanges(request) => prove_authority_set_changes::<Block, _>(
+				&*self.backend.blockchain(),
+				&load_authority_set_changes::<Block, _>(&*self.backend)?,
+				request.authorities_set_id,
+			),
 		}
 	}
 }
@@ -197,6 +243,19 @@ pub struct FinalityEffects<Header: HeaderT> {
 	pub new_authorities: AuthorityList,
 }
 
+/// The effects of a single handoff of authority set change proof.
+#[derive(Debug, PartialEq)]
+pub struct AuthoritySetChangeEffects<Header: HeaderT> {
+	/// The headers from the block that schedules the change to the block that enacts it.
+	pub headers: Vec<Header>,
+	/// The justification for the last header.
+	pub justification: Vec<u8>,
+	/// New authorities set id that should be applied starting from block.
+	pub new_set_id: u64,
+	/// New authorities set that should be applied starting from block.
+	pub new_authorities: AuthorityList,
+}
+
 /// Single fragment of proof-of-finality.
 ///
 /// Finality for block B is proved by providing:
@@ -220,11 +279,31 @@ struct FinalityProofFragment<Header: HeaderT> {
 /// - all other fragments provide justifications for GRANDPA authorities set changes within requested range.
 type FinalityProof<Header> = Vec<FinalityProofFragment<Header>>;
 
+/// Single fragment of authority set change proof.
+///
+/// Handoff from the set N to the set N + 1 is proved by providing:
+/// 1) the header S that schedules the change, followed by the headers sub-chain (S; E],
+///    where E is the block that enacts the change;
+/// 2) the justification of the block E, signed by the set N.
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub(crate) struct AuthoritySetChangeFragment<Header: HeaderT> {
+	/// The headers in the range [S; E]. Ordered.
+	pub headers: Vec<Header>,
+	/// Justification of the block E.
+	pub justification: Vec<u8>,
+}
+
+/// Proof of authority set changes is the ordered set of handoff fragments, starting
+/// from the set known to the caller.
+type AuthoritySetChangeProof<Header> = Vec<AuthoritySetChangeFragment<Header>>;
+
 /// Finality proof request data.
 #[derive(Debug, Encode, Decode)]
 enum FinalityProofRequest<H: Encode + Decode> {
 	/// Original version of the request.
 	Original(OriginalFinalityProofRequest<H>),
+	/// Request for the proof of authority set changes.
+	AuthoritySetChanges(AuthoritySetChangesRequest),
 }
 
 /// Original version of finality proof request.
@@ -238,6 +317,15 @@ struct OriginalFinalityProofRequest<H: Encode + Decode> {
 	pub last_finalized: H,
 }
 
+/// Authority set changes proof request.
+#[derive(Debug, Encode, Decode)]
+struct AuthoritySetChangesRequest {
+	/// The authorities set id known to the caller.
+	///
+	/// The first justification in the proof must be signed by this authority set.
+	pub authorities_set_id: u64,
+}
+
 /// Prepare data blob associated with finality proof request.
 pub(crate) fn make_finality_proof_request<H: Encode + Decode>(last_finalized: H, authorities_set_id: u64) -> Vec<u8> {
 	FinalityProofRequest::Original(OriginalFinalityProofRequest {
@@ -246,6 +334,101 @@ pub(crate) fn make_finality_proof_request<H: Encode + Decode>(last_finalized: H,
 	}).encode()
 }
 
+/// Prepare data blob associated with authority set changes proof request.
+pub(crate) fn make_authority_set_changes_request<H: Encode + Decode>(authorities_set_id: u64) -> Vec<u8> {
+	FinalityProofRequest::<H>::AuthoritySetChanges(AuthoritySetChangesRequest {
+		authorities_set_id,
+	}).encode()
+}
+
+/// Prepare proof of the authority set changes enacted after the set `authorities_set_id`.
+///
+/// `set_changes` is the ordered list of standard changes finalized by this node.
+///
+/// Returns None if there are no changes that could be proved to the caller.
+pub(crate) fn prove_authority_set_changes<Block: BlockT<Hash=H256>, B: BlockchainBackend<Block>>(
+	blockchain: &B,
+	set_changes: &[AuthoritySetChange<NumberFor<Block>>],
+	authorities_set_id: u64,
+) -> ::sp_blockchain::Result<Option<Vec<u8>>> {
+	let mut proof = Vec::new();
+	let mut expected_set_id = authorities_set_id + 1;
+	let mut previous_enact_number = Zero::zero();
+	for change in set_changes {
+		if change.set_id <= authorities_set_id {
+			previous_enact_number = change.block_number;
+			continue;
+		}
+
+		// set ids that are skipped have been enacted by forced changes, which
+		// can't be proved using the justification of the previous set
+		if change.set_id != expected_set_id {
+			trace!(
+				target: "afg",
+				"Authority set #{} has been enacted by forced change. Stopping authority set changes proof.",
+				expected_set_id,
+			);
+
+			break;
+		}
+
+		if proof.len() == MAX_SET_CHANGES_IN_PROOF {
+			break;
+		}
+
+		let enact_id = BlockId::Number(change.block_number);
+		let justification = match blockchain.justification(enact_id)? {
+			Some(justification) => justification,
+			None => {
+				trace!(
+					target: "afg",
+					"No justification for block #{} that enacts authority set #{}. Stopping authority set changes proof.",
+					change.block_number,
+					change.set_id,
+				);
+
+				break;
+			},
+		};
+
+		// walk back to the block that has scheduled the change
+		let mut headers = vec![blockchain.expect_header(enact_id)?];
+		loop {
+			let header = headers.last().expect("headers are never empty; qed");
+			if let Some(scheduled_change) = find_scheduled_change::<Block>(header) {
+				if *header.number() + scheduled_change.delay == change.block_number {
+					break;
+				}
+			}
+
+			if *header.number() <= previous_enact_number {
+				return Err(ClientError::Backend(format!(
+					"Cannot find the block scheduling authority set change enacted at #{}",
+					change.block_number,
+				)));
+			}
+
+			let parent_hash = *header.parent_hash();
+			headers.push(blockchain.expect_header(BlockId::Hash(parent_hash))?);
+		}
+		headers.reverse();
+
+		proof.push(AuthoritySetChangeFragment::<Block::Header> {
+			headers,
+			justification,
+		});
+
+		previous_enact_number = change.block_number;
+		expected_set_id += 1;
+	}
+
+	if proof.is_empty() {
+		return Ok(None);
+	}
+
+	Ok(Some(proof.encode()))
+}
+
 /// Prepare proof-of-finality for the best possible block in the range: (begin; end].
 ///
 /// It is assumed that the caller already have a proof-of-finality for the block 'begin'.
@@ -529,6 +712,78 @@ fn check_finality_proof_fragment<Block: BlockT<Hash=H256>, B, J>(
 	}))
 }
 
+/// Check GRANDPA proof of authority set changes, starting from the given set.
+///
+/// Returns the effects of every proved handoff, in order. The headers of each handoff are
+/// carried by the proof, so they don't need to be known to the caller.
+pub(crate) fn check_authority_set_change_proof<Block: BlockT<Hash=H256>, J>(
+	mut current_set_id: u64,
+	mut current_authorities: AuthorityList,
+	remote_proof: Vec<u8>,
+) -> ClientResult<Vec<AuthoritySetChangeEffects<Block::Header>>>
+	where
+		NumberFor<Block>: BlockNumberOps,
+		J: ProvableJustification<Block::Header>,
+{
+	// decode authority set change proof
+	let proof = AuthoritySetChangeProof::<Block::Header>::decode(&mut &remote_proof[..])
+		.map_err(|_| ClientError::BadJustification("failed to decode authority set change proof".into()))?;
+
+	let mut effects: Vec<AuthoritySetChangeEffects<Block::Header>> = Vec::with_capacity(proof.len());
+	for fragment in proof {
+		let (signal, enact) = match (fragment.headers.first(), fragment.headers.last()) {
+			(Some(signal), Some(enact)) => (signal, enact),
+			_ => return Err(ClientError::BadJustification("authority set change without headers".into())),
+		};
+
+		// handoffs must be proved in-order
+		if let Some(previous_enact) = effects.last().and_then(|effects| effects.headers.last()) {
+			if enact.number() <= previous_enact.number() {
+				return Err(ClientError::BadJustification("authority set changes are not ordered".into()));
+			}
+		}
+
+		// the first header must schedule the change that is enacted by the last header
+		let scheduled_change = find_scheduled_change::<Block>(signal)
+			.ok_or_else(|| ClientError::BadJustification("authority set change is not scheduled".into()))?;
+		if *signal.number() + scheduled_change.delay != *enact.number() {
+			return Err(ClientError::BadJustification("authority set change is not enacted by the last header".into()));
+		}
+
+		let is_chain = fragment.headers.windows(2).all(|pair|
+			*pair[1].parent_hash() == pair[0].hash() && *pair[1].number() == *pair[0].number() + One::one()
+		);
+		if !is_chain {
+			return Err(ClientError::BadJustification("authority set change headers are not a chain".into()));
+		}
+
+		// verify justification of the enacting block using previous authorities set
+		let justification = J::decode_and_verify(&fragment.justification, current_set_id, &current_authorities)?;
+		if justification.target_hash().map_or(false, |target_hash| target_hash != enact.hash()) {
+			return Err(ClientError::BadJustification("justification is not for the enacting block".into()));
+		}
+
+		current_set_id += 1;
+		current_authorities = scheduled_change.next_authorities;
+
+		effects.push(AuthoritySetChangeEffects {
+			headers: fragment.headers,
+			justification: fragment.justification,
+			new_set_id: current_set_id,
+			new_authorities: current_authorities.clone(),
+		});
+	}
+
+	let last_enact = effects.last()
+		.and_then(|effects| effects.headers.last())
+		.ok_or_else(|| ClientError::BadJustification("empty proof of authority set changes".into()))?;
+
+	telemetry!(CONSENSUS_INFO; "afg.authority_set_change_proof_ok";
+		"set_id" => ?current_set_id, "enacting_header_hash" => ?last_enact.hash());
+
+	Ok(effects)
+}
+
 /// Authorities set from initial authorities set or finality effects.
 enum AuthoritiesOrEffects<Header: HeaderT> {
 	Authorities(u64, AuthorityList),
@@ -556,6 +811,11 @@ pub(crate) trait ProvableJustification<Header: HeaderT>: Encode + Decode {
 	/// Verify justification with respect to authorities set and authorities set id.
 	fn verify(&self, set_id: u64, authorities: &[(AuthorityId, u64)]) -> ClientResult<()>;
 
+	/// Hash of the block that is finalized by the justification, if it is known.
+	fn target_hash(&self) -> Option<Header::Hash> {
+		None
+	}
+
 	/// Decode and verify justification.
 	fn decode_and_verify(
 		justification: &Justification,
@@ -576,6 +836,10 @@ impl<Block: BlockT<Hash=H256>> ProvableJustification<Block::Header> for GrandpaJ
 	fn verify(&self, set_id: u64, authorities: &[(AuthorityId, u64)]) -> ClientResult<()> {
 		GrandpaJustification::verify(self, set_id, &authorities.iter().cloned().collect())
 	}
+
+	fn target_hash(&self) -> Option<H256> {
+		Some(self.commit.target_hash)
+	}
 }
 
 #[cfg(test)]
@@ -585,6 +849,8 @@ pub(crate) mod tests {
 	use substrate_test_runtime_client::sc_client::in_mem::Blockchain as InMemoryBlockchain;
 	use super::*;
 	use sp_core::crypto::Public;
+	use sp_finality_grandpa::{ConsensusLog, ScheduledChange, GRANDPA_ENGINE_ID};
+	use sp_runtime::generic::DigestItem;
 
 	type FinalityProof = super::FinalityProof<Header>;
 
@@ -1023,4 +1289,160 @@ pub(crate) mod tests {
 		).unwrap();
 		assert!(proof_of_4.is_none());
 	}
+
+	fn authorities(seed: u8) -> AuthorityList {
+		vec![(AuthorityId::from_slice(&[seed; 32]), 1u64)]
+	}
+
+	fn header_with_change(number: u64, parent_hash: H256, change: Option<(u64, AuthorityList)>) -> Header {
+		let mut header = Header::new(
+			number,
+			H256::from_low_u64_be(0),
+			H256::from_low_u64_be(0),
+			parent_hash,
+			Default::default(),
+		);
+		if let Some((delay, next_authorities)) = change {
+			header.digest_mut().push(DigestItem::Consensus(
+				GRANDPA_ENGINE_ID,
+				ConsensusLog::ScheduledChange(ScheduledChange { next_authorities, delay }).encode(),
+			));
+		}
+		header
+	}
+
+	/// Chain where block #1 schedules and enacts set 1 and block #3 schedules set 2,
+	/// which is enacted by block #4.
+	fn set_changes_blockchain() -> (InMemoryBlockchain<Block>, Vec<Header>) {
+		let blockchain = InMemoryBlockchain::<Block>::new();
+		let mut headers = vec![header(0)];
+		for number in 1..5 {
+			let change = match number {
+				1 => Some((0, authorities(1))),
+				3 => Some((1, authorities(2))),
+				_ => None,
+			};
+			let parent_hash = headers.last().unwrap().hash();
+			headers.push(header_with_change(number, parent_hash, change));
+		}
+
+		for header in &headers {
+			let justification = match *header.number() {
+				1 => Some(TestJustification((0, authorities(0)), vec![1]).encode()),
+				4 => Some(TestJustification((1, authorities(1)), vec![4]).encode()),
+				_ => None,
+			};
+			blockchain.insert(header.hash(), header.clone(), justification, None, NewBlockState::Final).unwrap();
+		}
+
+		(blockchain, headers)
+	}
+
+	fn set_change(set_id: u64, block_number: u64) -> AuthoritySetChange<u64> {
+		AuthoritySetChange { set_id, block_number }
+	}
+
+	#[test]
+	fn authority_set_change_proof_works() {
+		// given
+		let (blockchain, headers) = set_changes_blockchain();
+		let set_changes = vec![set_change(1, 1), set_change(2, 4)];
+
+		// when
+		let proof = prove_authority_set_changes::<Block, _>(&blockchain, &set_changes, 0).unwrap().unwrap();
+
+		// then
+		assert_eq!(
+			AuthoritySetChangeProof::<Header>::decode(&mut &proof[..]).unwrap(),
+			vec![
+				AuthoritySetChangeFragment {
+					headers: vec![headers[1].clone()],
+					justification: TestJustification((0, authorities(0)), vec![1]).encode(),
+				},
+				AuthoritySetChangeFragment {
+					headers: vec![headers[3].clone(), headers[4].clone()],
+					justification: TestJustification((1, authorities(1)), vec![4]).encode(),
+				},
+			],
+		);
+		let effects = check_authority_set_change_proof::<Block, TestJustification>(
+			0,
+			authorities(0),
+			proof,
+		).unwrap();
+		assert_eq!(effects, vec![
+			AuthoritySetChangeEffects {
+				headers: vec![headers[1].clone()],
+				justification: TestJustification((0, authorities(0)), vec![1]).encode(),
+				new_set_id: 1,
+				new_authorities: authorities(1),
+			},
+			AuthoritySetChangeEffects {
+				headers: vec![headers[3].clone(), headers[4].clone()],
+				justification: TestJustification((1, authorities(1)), vec![4]).encode(),
+				new_set_id: 2,
+				new_authorities: authorities(2),
+			},
+		]);
+	}
+
+	#[test]
+	fn authority_set_change_proof_starts_at_set_known_to_caller() {
+		let (blockchain, _) = set_changes_blockchain();
+		let set_changes = vec![set_change(1, 1), set_change(2, 4)];
+
+		let proof = prove_authority_set_changes::<Block, _>(&blockchain, &set_changes, 1).unwrap().unwrap();
+		assert_eq!(AuthoritySetChangeProof::<Header>::decode(&mut &proof[..]).unwrap().len(), 1);
+
+		assert!(prove_authority_set_changes::<Block, _>(&blockchain, &set_changes, 2).unwrap().is_none());
+	}
+
+	#[test]
+	fn authority_set_change_proof_stops_at_forced_change() {
+		// set 2 has been enacted by forced change, so only the handoff to set 1 is proved
+		let (blockchain, _) = set_changes_blockchain();
+		let set_changes = vec![set_change(1, 1), set_change(3, 4)];
+
+		let proof = prove_authority_set_changes::<Block, _>(&blockchain, &set_changes, 0).unwrap().unwrap();
+		assert_eq!(AuthoritySetChangeProof::<Header>::decode(&mut &proof[..]).unwrap().len(), 1);
+
+		assert!(prove_authority_set_changes::<Block, _>(&blockchain, &set_changes, 1).unwrap().is_none());
+	}
+
+	#[test]
+	fn authority_set_change_proof_check_fails_when_signed_by_unknown_set() {
+		let (blockchain, _) = set_changes_blockchain();
+		let set_changes = vec![set_change(1, 1), set_change(2, 4)];
+		let proof = prove_authority_set_changes::<Block, _>(&blockchain, &set_changes, 0).unwrap().unwrap();
+
+		assert!(check_authority_set_change_proof::<Block, TestJustification>(
+			1,
+			authorities(1),
+			proof,
+		).is_err());
+	}
+
+	#[test]
+	fn authority_set_change_proof_check_fails_when_headers_are_not_chain() {
+		let (_, headers) = set_changes_blockchain();
+		let proof = vec![AuthoritySetChangeFragment {
+			headers: vec![headers[3].clone(), header(4)],
+			justification: TestJustification((0, authorities(0)), vec![4]).encode(),
+		}].encode();
+
+		assert!(check_authority_set_change_proof::<Block, TestJustification>(
+			0,
+			authorities(0),
+			proof,
+		).is_err());
+	}
+
+	#[test]
+	fn authority_set_change_proof_check_fails_when_proof_is_empty() {
+		assert!(check_authority_set_change_proof::<Block, TestJustification>(
+			0,
+			authorities(0),
+			Vec::<AuthoritySetChangeFragment<Header>>::new().encode(),
+		).is_err());
+	}
 }
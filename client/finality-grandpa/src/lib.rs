@@ -86,20 +86,25 @@ mod finality_proof;
 mod import;
 mod justification;
 mod light_import;
+mod notification;
 mod observer;
 mod until_imported;
+mod voter_state;
 mod voting_rule;
 
 pub use finality_proof::FinalityProofProvider;
 pub use justification::GrandpaJustification;
 pub use light_import::light_block_import;
-pub use observer::run_grandpa_observer;
+pub use notification::{GrandpaJustificationSender, GrandpaJustificationStream};
+pub use observer::{run_grandpa_observer, run_light_grandpa_observer};
+pub use voter_state::{RoundVotesState, SharedVoterState, VoterState};
 pub use voting_rule::{
-	BeforeBestBlock, ThreeQuartersOfTheUnfinalizedChain, VotingRule, VotingRulesBuilder
+	BeforeBestBlock, BlockAuthor, LagByDuration, PauseAtBlock, ThreeQuartersOfTheUnfinalizedChain,
+	TrustedConfirmations, TrustedConfirmationsConfig, VotingRule, VotingRulesBuilder, VotingRulesConfig,
 };
 
 use aux_schema::PersistentData;
-use environment::{Environment, VoterSetState};
+use environment::{Environment, SharedRoundVotes, VoterSetState};
 use import::GrandpaBlockImport;
 use until_imported::UntilGlobalMessageBlocksImported;
 use communication::{NetworkBridge, Network as NetworkT};
@@ -360,6 +365,26 @@ pub struct LinkHalf<B, E, Block: BlockT<Hash=H256>, RA, SC> {
 	select_chain: SC,
 	persistent_data: PersistentData<Block>,
 	voter_commands_rx: mpsc::UnboundedReceiver<VoterCommand<Block::Hash, NumberFor<Block>>>,
+	round_votes: SharedRoundVotes,
+	justification_sender: GrandpaJustificationSender<Block>,
+	justification_stream: GrandpaJustificationStream<Block>,
+}
+
+impl<B, E, Block: BlockT<Hash=H256>, RA, SC> LinkHalf<B, E, Block, RA, SC> {
+	/// Get a handle to the state of the voter, used for reporting on its
+	/// progress. Must be taken before the link is given to the voter.
+	pub fn shared_voter_state(&self) -> SharedVoterState<Block> {
+		SharedVoterState::new(
+			self.persistent_data.authority_set.clone(),
+			self.persistent_data.set_state.clone(),
+			self.round_votes.clone(),
+		)
+	}
+
+	/// Get the receiving end of justification notifications.
+	pub fn justification_stream(&self) -> GrandpaJustificationStream<Block> {
+		self.justification_stream.clone()
+	}
 }
 
 /// Provider for the Grandpa authority set configured on the genesis block.
@@ -428,6 +453,7 @@ where
 	)?;
 
 	let (voter_commands_tx, voter_commands_rx) = mpsc::unbounded();
+	let (justification_sender, justification_stream) = GrandpaJustificationSender::channel();
 
 	Ok((
 		GrandpaBlockImport::new(
@@ -436,12 +462,16 @@ where
 			persistent_data.authority_set.clone(),
 			voter_commands_tx,
 			persistent_data.consensus_changes.clone(),
+			justification_sender.clone(),
 		),
 		LinkHalf {
 			client,
 			select_chain,
 			persistent_data,
 			voter_commands_rx,
+			round_votes: SharedRoundVotes::default(),
+			justification_sender,
+			justification_stream,
 		},
 	))
 }
@@ -574,6 +604,9 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, VR, X, Sp>(
 		select_chain,
 		persistent_data,
 		voter_commands_rx,
+		round_votes,
+		justification_sender,
+		..
 	} = link;
 
 	let network = NetworkBridge::new(
@@ -622,6 +655,8 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, VR, X, Sp>(
 		voting_rule,
 		persistent_data,
 		voter_commands_rx,
+		round_votes,
+		justification_sender,
 	);
 
 	let voter_work = voter_work
@@ -667,6 +702,8 @@ where
 		voting_rule: VR,
 		persistent_data: PersistentData<Block>,
 		voter_commands_rx: mpsc::UnboundedReceiver<VoterCommand<Block::Hash, NumberFor<Block>>>,
+		round_votes: SharedRoundVotes,
+		justification_sender: GrandpaJustificationSender<Block>,
 	) -> Self {
 
 		let voters = persistent_data.authority_set.current_authorities();
@@ -681,6 +718,8 @@ where
 			authority_set: persistent_data.authority_set.clone(),
 			consensus_changes: persistent_data.consensus_changes.clone(),
 			voter_set_state: persistent_data.set_state.clone(),
+			round_votes,
+			justification_sender,
 		});
 
 		let mut work = VoterWork {
@@ -787,6 +826,9 @@ where
 					Ok(Some(set_state))
 				})?;
 
+				// rounds of the new set start over.
+				self.env.round_votes.clear();
+
 				self.env = Arc::new(Environment {
 					voters: Arc::new(new.authorities.into_iter().collect()),
 					set_id: new.set_id,
@@ -799,6 +841,8 @@ where
 					consensus_changes: self.env.consensus_changes.clone(),
 					network: self.env.network.clone(),
 					voting_rule: self.env.voting_rule.clone(),
+					round_votes: self.env.round_votes.clone(),
+					justification_sender: self.env.justification_sender.clone(),
 				});
 
 				self.rebuild_voter();
@@ -30,7 +30,7 @@ use sp_consensus::{
 use sc_network::config::{BoxFinalityProofRequestBuilder, FinalityProofRequestBuilder};
 use sp_runtime::Justification;
 use sp_runtime::traits::{NumberFor, Block as BlockT, Header as HeaderT, DigestFor};
-use sp_finality_grandpa::{self, AuthorityList};
+use sp_finality_grandpa::{self, AuthorityList, ScheduledChange};
 use sp_runtime::generic::BlockId;
 use sp_core::{H256, Blake2Hasher};
 
@@ -38,8 +38,10 @@ use crate::GenesisAuthoritySetProvider;
 use crate::aux_schema::load_decode;
 use crate::consensus_changes::ConsensusChanges;
 use crate::environment::canonical_at_height;
+use crate::import::find_scheduled_change;
 use crate::finality_proof::{
 	AuthoritySetForFinalityChecker, ProvableJustification, make_finality_proof_request,
+	make_authority_set_changes_request, check_authority_set_change_proof,
 };
 use crate::justification::GrandpaJustification;
 
@@ -47,6 +49,10 @@ use crate::justification::GrandpaJustification;
 const LIGHT_AUTHORITY_SET_KEY: &[u8] = b"grandpa_voters";
 /// ConsensusChanges is saver under this key in aux storage.
 const LIGHT_CONSENSUS_CHANGES_KEY: &[u8] = b"grandpa_consensus_changes";
+/// The handoffs proved ahead of the local chain are saved under this key in aux storage.
+const LIGHT_PROVED_SET_CHANGES_KEY: &[u8] = b"grandpa_proved_set_changes";
+/// The scheduled change not enacted yet is saved under this key in aux storage.
+const LIGHT_SCHEDULED_CHANGE_KEY: &[u8] = b"grandpa_scheduled_change";
 
 /// Create light block importer.
 pub fn light_block_import<B, E, Block: BlockT<Hash=H256>, RA>(
@@ -102,6 +108,23 @@ struct LightImportData<Block: BlockT<Hash=H256>> {
 	last_finalized: Block::Hash,
 	authority_set: LightAuthoritySet,
 	consensus_changes: ConsensusChanges<Block::Hash, NumberFor<Block>>,
+	/// The block for which the proof of authority set changes has been requested instead
+	/// of the finality proof, if any.
+	authority_set_changes_request: Option<Block::Hash>,
+	/// Whether the authority set has caught up with the changes proved by the remote nodes.
+	///
+	/// Until then, the blocks that need finality proof trigger the request for the proof of
+	/// all authority set changes, so that they are fetched in bulk. It is set once a request
+	/// isn't answered with any change.
+	authority_set_changes_synced: bool,
+	/// The handoffs applied to the authority set before the local chain reached the blocks
+	/// enacting them: the number and hash of each enacting block, along with its justification.
+	///
+	/// These blocks are finalized with the justification once imported.
+	proved_set_changes: Vec<(NumberFor<Block>, Block::Hash, Justification)>,
+	/// The authority set change scheduled by a block finalized with a justification of the
+	/// current set, along with the number of this block, until the change is enacted.
+	scheduled_change: Option<(NumberFor<Block>, ScheduledChange<NumberFor<Block>>)>,
 }
 
 /// Latest authority set tracker.
@@ -118,6 +141,43 @@ impl<B, E, Block: BlockT<Hash=H256>, RA> GrandpaLightBlockImport<B, E, Block, RA
 	}
 }
 
+impl<B, E, Block: BlockT<Hash=H256>, RA> GrandpaLightBlockImport<B, E, Block, RA> where
+	NumberFor<Block>: finality_grandpa::BlockNumberOps,
+	B: Backend<Block, Blake2Hasher> + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
+	RA: Send + Sync,
+{
+	/// Get the client of the light block importer.
+	pub(crate) fn client(&self) -> Arc<Client<B, E, Block, RA>> {
+		self.client.clone()
+	}
+
+	/// Get the id and the authorities of the current light authority set.
+	pub(crate) fn authority_set(&self) -> (u64, AuthorityList) {
+		let data = self.data.read();
+		(data.authority_set.set_id(), data.authority_set.authorities())
+	}
+
+	/// Finalize the block using the justification built from a commit that has
+	/// been validated against the current light authority set.
+	///
+	/// The authority set changes enacted by the finalized blocks are applied.
+	pub(crate) fn finalize_with_justification(
+		&self,
+		hash: Block::Hash,
+		number: NumberFor<Block>,
+		justification: GrandpaJustification<Block>,
+	) -> Result<(), ConsensusError> {
+		do_finalize_block_enacting_changes(
+			&*self.client,
+			&mut *self.data.write(),
+			hash,
+			number,
+			justification.encode(),
+		).map(|_| ())
+	}
+}
+
 impl<B, E, Block: BlockT<Hash=H256>, RA> BlockImport<Block>
 	for GrandpaLightBlockImport<B, E, Block, RA> where
 		NumberFor<Block>: finality_grandpa::BlockNumberOps,
@@ -177,11 +237,31 @@ impl<B, E, Block: BlockT<Hash=H256>, RA> FinalityProofImport<Block>
 		finality_proof: Vec<u8>,
 		verifier: &mut dyn Verifier<Block>,
 	) -> Result<(Block::Hash, NumberFor<Block>), Self::Error> {
+		let mut data = self.data.write();
+		if data.authority_set_changes_request == Some(hash) {
+			// the changes enacted after the imported ones are requested again, until the remote
+			// nodes have none. If no change could be applied, fall back to the finality proofs
+			data.authority_set_changes_request = None;
+
+			let set_id = data.authority_set.set_id();
+			let result = do_import_authority_set_change_proof::<_, _, _, GrandpaJustification<Block>>(
+				&*self.client,
+				&mut *data,
+				finality_proof,
+				verifier,
+			);
+			if data.authority_set.set_id() == set_id {
+				data.authority_set_changes_synced = true;
+			}
+
+			return result;
+		}
+
 		do_import_finality_proof::<_, _, _, GrandpaJustification<Block>>(
 			&*self.client,
 			self.backend.clone(),
 			&*self.authority_set_provider,
-			&mut *self.data.write(),
+			&mut *data,
 			hash,
 			number,
 			finality_proof,
@@ -219,8 +299,28 @@ impl LightAuthoritySet {
 struct GrandpaFinalityProofRequestBuilder<B: BlockT<Hash=H256>>(Arc<RwLock<LightImportData<B>>>);
 
 impl<B: BlockT<Hash=H256>> FinalityProofRequestBuilder<B> for GrandpaFinalityProofRequestBuilder<B> {
-	fn build_request_data(&mut self, _hash: &B::Hash) -> Vec<u8> {
-		let data = self.0.read();
+	fn build_request_data(&mut self, hash: &B::Hash) -> Vec<u8> {
+		let mut data = self.0.write();
+
+		// catch up with the authority set changes in bulk before requesting the finality
+		// proofs of single blocks. Only one block at a time is used for this, so that the
+		// import of the response knows which proof it is.
+		if !data.authority_set_changes_synced {
+			match data.authority_set_changes_request {
+				None => {
+					data.authority_set_changes_request = Some(*hash);
+					return make_authority_set_changes_request::<B::Hash>(data.authority_set.set_id());
+				},
+				Some(requested) if requested == *hash => {
+					// the proof hasn't been provided, e.g. because the remote nodes haven't
+					// recorded the changes: fall back to the finality proofs
+					data.authority_set_changes_request = None;
+					data.authority_set_changes_synced = true;
+				},
+				Some(_) => (),
+			}
+		}
+
 		make_finality_proof_request(
 			data.last_finalized,
 			data.authority_set.set_id(),
@@ -260,6 +360,25 @@ fn do_import_block<B, C, Block: BlockT<Hash=H256>, J>(
 		Err(e) => return Err(ConsensusError::ClientImport(e.to_string()).into()),
 	};
 
+	// the block enacts a handoff that has already been proved: finalize it with the
+	// justification of the proof, that has been verified against the previous set
+	if let Some(position) = data.proved_set_changes.iter().position(|(_, proved, _)| *proved == hash) {
+		trace!(
+			target: "afg",
+			"Imported block {} which enacts proved authority set change. Finalizing the block.",
+			hash,
+		);
+
+		let (_, _, justification) = data.proved_set_changes.remove(position);
+		require_insert_aux(
+			&client,
+			LIGHT_PROVED_SET_CHANGES_KEY,
+			&data.proved_set_changes,
+			"proved authority set changes",
+		)?;
+		return do_finalize_block(client, data, hash, number, justification);
+	}
+
 	match justification {
 		Some(justification) => {
 			trace!(
@@ -351,10 +470,110 @@ fn do_import_finality_proof<B, C, Block: BlockT<Hash=H256>, J>(
 		finality_effects.new_set_id,
 		finality_effects.new_authorities,
 	);
+	forget_scheduled_change(&client, data)?;
 
 	Ok((finalized_block_hash, finalized_block_number))
 }
 
+/// Try to import proof of authority set changes.
+///
+/// Every proved handoff is applied in order. The headers carried by the proof that extend the
+/// local chain are imported, and the blocks enacting the handoffs are finalized. The handoffs
+/// enacted by blocks the local chain hasn't reached yet are kept, and their blocks are finalized
+/// once imported.
+///
+/// Returns the last finalized block.
+fn do_import_authority_set_change_proof<B, C, Block: BlockT<Hash=H256>, J>(
+	client: C,
+	data: &mut LightImportData<Block>,
+	proof: Vec<u8>,
+	verifier: &mut dyn Verifier<Block>,
+) -> Result<(Block::Hash, NumberFor<Block>), ConsensusError>
+	where
+		C: HeaderBackend<Block>
+			+ AuxStore
+			+ Finalizer<Block, Blake2Hasher, B>
+			+ BlockImport<Block>
+			+ Clone,
+		B: Backend<Block, Blake2Hasher> + 'static,
+		DigestFor<Block>: Encode,
+		NumberFor<Block>: finality_grandpa::BlockNumberOps,
+		J: ProvableJustification<Block::Header>,
+{
+	let set_changes = check_authority_set_change_proof::<Block, J>(
+		data.authority_set.set_id(),
+		data.authority_set.authorities(),
+		proof,
+	).map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+
+	let is_known = |hash: Block::Hash| client.header(BlockId::Hash(hash))
+		.map(|header| header.is_some())
+		.map_err(|e| ConsensusError::ClientImport(e.to_string()));
+
+	let mut finalized = None;
+	let mut extends_chain = true;
+	for set_change in set_changes {
+		let (hash, number) = {
+			let enact = set_change.headers.last().expect("proved handoffs always have headers; qed");
+			(enact.hash(), *enact.number())
+		};
+
+		for header in set_change.headers {
+			if !extends_chain {
+				break;
+			}
+
+			if is_known(header.hash())? {
+				continue;
+			}
+
+			if !is_known(*header.parent_hash())? {
+				trace!(
+					target: "afg",
+					"Block #{} that enacts authority set #{} is ahead of the chain. Finalizing once imported.",
+					number,
+					set_change.new_set_id,
+				);
+
+				extends_chain = false;
+				break;
+			}
+
+			let (block_to_import, new_authorities) = verifier.verify(BlockOrigin::NetworkBroadcast, header, None, None)
+				.map_err(|e| ConsensusError::ClientImport(e))?;
+
+			let mut cache = HashMap::new();
+			if let Some(authorities) = new_authorities {
+				cache.insert(well_known_cache_keys::AUTHORITIES, authorities.encode());
+			}
+			do_import_block::<_, _, _, J>(client.clone(), data, block_to_import, cache)?;
+		}
+
+		if extends_chain {
+			do_finalize_block(client.clone(), data, hash, number, set_change.justification)?;
+			finalized = Some((hash, number));
+		} else {
+			data.proved_set_changes.push((number, hash, set_change.justification));
+		}
+
+		// apply new authorities set
+		data.authority_set.update(set_change.new_set_id, set_change.new_authorities);
+	}
+
+	require_insert_aux(&client, LIGHT_AUTHORITY_SET_KEY, &data.authority_set, "authority set")?;
+	require_insert_aux(
+		&client,
+		LIGHT_PROVED_SET_CHANGES_KEY,
+		&data.proved_set_changes,
+		"proved authority set changes",
+	)?;
+	forget_scheduled_change(&client, data)?;
+
+	finalized.ok_or_else(|| ConsensusError::ClientImport(
+		"None of the proved authority set changes extends the chain".into()
+	))
+}
+
 /// Try to import justification.
 fn do_import_justification<B, C, Block: BlockT<Hash=H256>, J>(
 	client: C,
@@ -391,6 +610,19 @@ fn do_import_justification<B, C, Block: BlockT<Hash=H256>, J>(
 	// BadJustification error means that justification has been successfully decoded, but
 	// it isn't valid within current authority set
 	let justification = match justification {
+		// the blocks preceding a handoff that has already been proved are signed by the
+		// previous sets, they are finalized along with the block enacting the handoff
+		Err(ClientError::BadJustification(_))
+			if data.proved_set_changes.last().map_or(false, |(enact, _, _)| number < *enact) =>
+		{
+			trace!(
+				target: "afg",
+				"Justification for {} is signed by a previous authorities set. Ignoring.",
+				hash,
+			);
+
+			return Ok(ImportResult::Imported(ImportedAux::default()));
+		},
 		Err(ClientError::BadJustification(_)) => {
 			trace!(
 				target: "afg",
@@ -423,7 +655,92 @@ fn do_import_justification<B, C, Block: BlockT<Hash=H256>, J>(
 	};
 
 	// finalize the block
-	do_finalize_block(client, data, hash, number, justification.encode())
+	do_finalize_block_enacting_changes(client, data, hash, number, justification.encode())
+}
+
+/// Finalize the block with a justification of the current authority set, and apply the
+/// authority set changes enacted by the finalized blocks.
+///
+/// The changes are found in the headers finalized by the block, so they can be followed
+/// without finality proofs. A change that is scheduled but not enacted yet is kept until a
+/// block enacting it is finalized.
+fn do_finalize_block_enacting_changes<B, C, Block: BlockT<Hash=H256>>(
+	client: C,
+	data: &mut LightImportData<Block>,
+	hash: Block::Hash,
+	number: NumberFor<Block>,
+	justification: Justification,
+) -> Result<ImportResult, ConsensusError>
+	where
+		C: HeaderBackend<Block>
+			+ AuxStore
+			+ Finalizer<Block, Blake2Hasher, B>
+			+ Clone,
+		B: Backend<Block, Blake2Hasher> + 'static,
+		NumberFor<Block>: finality_grandpa::BlockNumberOps,
+{
+	let previous_finalized_number = client.info().finalized_number;
+	let import_result = do_finalize_block(client.clone(), data, hash, number, justification)?;
+
+	// collect the changes scheduled by the newly finalized blocks, in order
+	let mut scheduled_changes = Vec::new();
+	let mut current = hash;
+	loop {
+		let header = client.header(BlockId::Hash(current))
+			.map_err(|e| ConsensusError::ClientImport(e.to_string()))?
+			.ok_or_else(|| ConsensusError::ClientImport(format!("Missing header of finalized block {}", current)))?;
+		if *header.number() <= previous_finalized_number {
+			break;
+		}
+
+		if let Some(change) = find_scheduled_change::<Block>(&header) {
+			scheduled_changes.push((*header.number(), change));
+		}
+		current = *header.parent_hash();
+	}
+	scheduled_changes.reverse();
+
+	let pending_changes = data.scheduled_change.take().into_iter().chain(scheduled_changes);
+	let mut enacted = false;
+	for (signal_number, change) in pending_changes {
+		if data.scheduled_change.is_some() {
+			break;
+		}
+
+		if signal_number + change.delay <= number {
+			trace!(
+				target: "afg",
+				"Finalized block #{} enacts authority set #{}.",
+				signal_number + change.delay,
+				data.authority_set.set_id() + 1,
+			);
+
+			let set_id = data.authority_set.set_id() + 1;
+			data.authority_set.update(set_id, change.next_authorities);
+			enacted = true;
+		} else {
+			data.scheduled_change = Some((signal_number, change));
+		}
+	}
+
+	if enacted {
+		require_insert_aux(&client, LIGHT_AUTHORITY_SET_KEY, &data.authority_set, "authority set")?;
+	}
+	require_insert_aux(&client, LIGHT_SCHEDULED_CHANGE_KEY, &data.scheduled_change, "scheduled change")?;
+
+	Ok(import_result)
+}
+
+/// Forget the scheduled change once the authority set has been updated by a proof.
+fn forget_scheduled_change<Block: BlockT<Hash=H256>, A: AuxStore>(
+	store: &A,
+	data: &mut LightImportData<Block>,
+) -> Result<(), ConsensusError> {
+	if data.scheduled_change.take().is_some() {
+		require_insert_aux(store, LIGHT_SCHEDULED_CHANGE_KEY, &data.scheduled_change, "scheduled change")?;
+	}
+
+	Ok(())
 }
 
 /// Finalize the block.
@@ -507,10 +824,19 @@ fn load_aux_import_data<B, Block: BlockT<Hash=H256>>(
 		},
 	};
 
+	let proved_set_changes = load_decode(aux_store, LIGHT_PROVED_SET_CHANGES_KEY)?
+		.unwrap_or_default();
+	let scheduled_change = load_decode(aux_store, LIGHT_SCHEDULED_CHANGE_KEY)?
+		.unwrap_or_default();
+
 	Ok(LightImportData {
 		last_finalized,
 		authority_set,
 		consensus_changes,
+		authority_set_changes_request: None,
+		authority_set_changes_synced: false,
+		proved_set_changes,
+		scheduled_change,
 	})
 }
 
@@ -545,8 +871,11 @@ pub mod tests {
 	use sp_core::{H256, crypto::Public};
 	use substrate_test_runtime_client::sc_client::in_mem::Blockchain as InMemoryAuxStore;
 	use substrate_test_runtime_client::runtime::{Block, Header};
+	use sp_blockchain::well_known_cache_keys::Id as CacheKeyId;
+	use sp_finality_grandpa::{ConsensusLog, ScheduledChange, GRANDPA_ENGINE_ID};
+	use sp_runtime::DigestItem;
 	use crate::tests::TestApi;
-	use crate::finality_proof::tests::TestJustification;
+	use crate::finality_proof::{AuthoritySetChangeFragment, tests::TestJustification};
 
 	pub struct NoJustificationsImport<B, E, Block: BlockT<Hash=H256>, RA>(
 		pub GrandpaLightBlockImport<B, E, Block, RA>
@@ -642,6 +971,10 @@ pub mod tests {
 			last_finalized: Default::default(),
 			authority_set: LightAuthoritySet::genesis(vec![(AuthorityId::from_slice(&[1; 32]), 1)]),
 			consensus_changes: ConsensusChanges::empty(),
+			authority_set_changes_request: None,
+			authority_set_changes_synced: false,
+			proved_set_changes: Vec::new(),
+			scheduled_change: None,
 		};
 		let block = BlockImportParams {
 			origin: BlockOrigin::Own,
@@ -727,6 +1060,303 @@ pub mod tests {
 	}
 
 
+	struct PassThroughVerifier;
+
+	impl Verifier<Block> for PassThroughVerifier {
+		fn verify(
+			&mut self,
+			origin: BlockOrigin,
+			header: Header,
+			justification: Option<Justification>,
+			body: Option<Vec<<Block as BlockT>::Extrinsic>>,
+		) -> Result<(BlockImportParams<Block>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String> {
+			Ok((BlockImportParams {
+				origin,
+				header,
+				justification,
+				post_digests: Vec::new(),
+				body,
+				finalized: false,
+				auxiliary: Vec::new(),
+				fork_choice: ForkChoiceStrategy::LongestChain,
+				allow_missing_state: true,
+				import_existing: false,
+			}, None))
+		}
+	}
+
+	fn import_data(authorities: AuthorityList) -> LightImportData<Block> {
+		LightImportData {
+			last_finalized: Default::default(),
+			authority_set: LightAuthoritySet::genesis(authorities),
+			consensus_changes: ConsensusChanges::empty(),
+			authority_set_changes_request: None,
+			authority_set_changes_synced: false,
+			proved_set_changes: Vec::new(),
+			scheduled_change: None,
+		}
+	}
+
+	#[test]
+	fn authority_set_changes_are_requested_for_first_block_only() {
+		// given
+		let data = Arc::new(RwLock::new(import_data(vec![(AuthorityId::from_slice(&[1; 32]), 1)])));
+		let mut builder = GrandpaFinalityProofRequestBuilder(data.clone());
+		let (first, second) = (H256::from_low_u64_be(1), H256::from_low_u64_be(2));
+
+		// when
+		let requests = vec![
+			builder.build_request_data(&first),
+			builder.build_request_data(&second),
+		];
+
+		// then
+		assert_eq!(requests, vec![
+			make_authority_set_changes_request::<H256>(0),
+			make_finality_proof_request(H256::default(), 0),
+		]);
+		assert_eq!(data.read().authority_set_changes_request, Some(first));
+	}
+
+	#[test]
+	fn finality_proof_is_requested_when_authority_set_changes_are_not_provided() {
+		// given
+		let data = Arc::new(RwLock::new(import_data(vec![(AuthorityId::from_slice(&[1; 32]), 1)])));
+		let mut builder = GrandpaFinalityProofRequestBuilder(data.clone());
+		let block = H256::from_low_u64_be(1);
+		builder.build_request_data(&block);
+
+		// when
+		let request = builder.build_request_data(&block);
+
+		// then
+		assert_eq!(request, make_finality_proof_request(H256::default(), 0));
+		assert!(data.read().authority_set_changes_synced);
+		assert_eq!(data.read().authority_set_changes_request, None);
+	}
+
+	#[test]
+	fn authority_set_change_proof_imports_carried_headers() {
+		// given
+		let (client, _backend) = substrate_test_runtime_client::new_light();
+		let genesis_authorities = vec![(AuthorityId::from_slice(&[1; 32]), 1)];
+		let next_authorities = vec![(AuthorityId::from_slice(&[2; 32]), 1)];
+		let mut data = import_data(genesis_authorities.clone());
+
+		let mut header = Header {
+			number: 1,
+			parent_hash: client.chain_info().best_hash,
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		header.digest_mut().push(DigestItem::Consensus(
+			GRANDPA_ENGINE_ID,
+			ConsensusLog::ScheduledChange(ScheduledChange {
+				next_authorities: next_authorities.clone(),
+				delay: 0,
+			}).encode(),
+		));
+		let proof = vec![AuthoritySetChangeFragment {
+			headers: vec![header.clone()],
+			justification: TestJustification((0, genesis_authorities), Vec::new()).encode(),
+		}].encode();
+
+		// when
+		let finalized = do_import_authority_set_change_proof::<_, _, _, TestJustification>(
+			&client,
+			&mut data,
+			proof,
+			&mut PassThroughVerifier,
+		).unwrap();
+
+		// then
+		assert_eq!(finalized, (header.hash(), 1));
+		assert_eq!(client.chain_info().finalized_hash, header.hash());
+		assert_eq!(data.authority_set.set_id(), 1);
+		assert_eq!(data.authority_set.authorities(), next_authorities);
+	}
+
+	fn enacting_header(number: u64, parent_hash: H256, next_authorities: AuthorityList) -> Header {
+		signaling_header(number, parent_hash, next_authorities, 0)
+	}
+
+	fn signaling_header(number: u64, parent_hash: H256, next_authorities: AuthorityList, delay: u64) -> Header {
+		let mut header = Header {
+			number,
+			parent_hash,
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		header.digest_mut().push(DigestItem::Consensus(
+			GRANDPA_ENGINE_ID,
+			ConsensusLog::ScheduledChange(ScheduledChange {
+				next_authorities,
+				delay,
+			}).encode(),
+		));
+		header
+	}
+
+	fn import_header(
+		client: &Client<
+			substrate_test_runtime_client::LightBackend,
+			substrate_test_runtime_client::LightExecutor,
+			Block,
+			substrate_test_runtime_client::runtime::RuntimeApi,
+		>,
+		data: &mut LightImportData<Block>,
+		header: Header,
+		justification: Option<Justification>,
+		new_authorities: Option<AuthorityList>,
+	) -> ImportResult {
+		let (block, _) = PassThroughVerifier
+			.verify(BlockOrigin::NetworkBroadcast, header, justification, None)
+			.unwrap();
+		let mut cache = HashMap::new();
+		if let Some(authorities) = new_authorities {
+			cache.insert(well_known_cache_keys::AUTHORITIES, authorities.encode());
+		}
+		do_import_block::<_, _, _, TestJustification>(client, data, block, cache).unwrap()
+	}
+
+	#[test]
+	fn authority_set_change_proof_keeps_handoffs_ahead_of_chain() {
+		// given
+		let (client, _backend) = substrate_test_runtime_client::new_light();
+		let genesis_authorities = vec![(AuthorityId::from_slice(&[1; 32]), 1)];
+		let next_authorities = vec![(AuthorityId::from_slice(&[2; 32]), 1)];
+		let mut data = import_data(genesis_authorities.clone());
+
+		let header = enacting_header(2, H256::from_low_u64_be(42), next_authorities.clone());
+		let justification = TestJustification((0, genesis_authorities), Vec::new()).encode();
+		let proof = vec![AuthoritySetChangeFragment {
+			headers: vec![header.clone()],
+			justification: justification.clone(),
+		}].encode();
+
+		// when
+		let result = do_import_authority_set_change_proof::<_, _, _, TestJustification>(
+			&client,
+			&mut data,
+			proof,
+			&mut PassThroughVerifier,
+		);
+
+		// then
+		assert!(result.is_err());
+		assert_eq!(data.authority_set.set_id(), 1);
+		assert_eq!(data.authority_set.authorities(), next_authorities);
+		assert_eq!(data.proved_set_changes, vec![(2, header.hash(), justification)]);
+	}
+
+	#[test]
+	fn authority_set_change_proof_crosses_several_handoffs() {
+		// given
+		let (client, _backend) = substrate_test_runtime_client::new_light();
+		let authorities = (1..4)
+			.map(|i| vec![(AuthorityId::from_slice(&[i; 32]), 1)])
+			.collect::<Vec<_>>();
+		let mut data = import_data(authorities[0].clone());
+
+		let first = enacting_header(1, client.chain_info().best_hash, authorities[1].clone());
+		let between = Header {
+			number: 2,
+			parent_hash: first.hash(),
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let second = enacting_header(3, between.hash(), authorities[2].clone());
+		let proof = vec![
+			AuthoritySetChangeFragment {
+				headers: vec![first.clone()],
+				justification: TestJustification((0, authorities[0].clone()), Vec::new()).encode(),
+			},
+			AuthoritySetChangeFragment {
+				headers: vec![second.clone()],
+				justification: TestJustification((1, authorities[1].clone()), Vec::new()).encode(),
+			},
+		].encode();
+
+		// when
+		let finalized = do_import_authority_set_change_proof::<_, _, _, TestJustification>(
+			&client,
+			&mut data,
+			proof,
+			&mut PassThroughVerifier,
+		).unwrap();
+
+		// then both handoffs are applied, although the chain only reaches the first one
+		assert_eq!(finalized, (first.hash(), 1));
+		assert_eq!(client.chain_info().finalized_hash, first.hash());
+		assert_eq!(data.authority_set.set_id(), 2);
+		assert_eq!(data.authority_set.authorities(), authorities[2]);
+		assert_eq!(data.proved_set_changes.len(), 1);
+
+		// the block preceding the second handoff, justified by the previous set, needs no finality proof
+		let justification = TestJustification((1, authorities[1].clone()), Vec::new()).encode();
+		let imported = import_header(&client, &mut data, between, Some(justification), None);
+		assert_eq!(imported, ImportResult::Imported(ImportedAux::default()));
+
+		// the block enacting the second handoff is finalized once imported, without finality proof
+		let imported = import_header(&client, &mut data, second.clone(), None, Some(authorities[2].clone()));
+		assert_eq!(imported, ImportResult::imported(true));
+		assert_eq!(client.chain_info().finalized_hash, second.hash());
+		assert!(data.proved_set_changes.is_empty());
+		assert!(data.consensus_changes.pending_changes().is_empty());
+	}
+
+	#[test]
+	fn finalization_with_justification_enacts_scheduled_changes() {
+		// given
+		let (client, _backend) = substrate_test_runtime_client::new_light();
+		let genesis_authorities = vec![(AuthorityId::from_slice(&[1; 32]), 1)];
+		let next_authorities = vec![(AuthorityId::from_slice(&[2; 32]), 1)];
+		let mut data = import_data(genesis_authorities.clone());
+
+		let signal = signaling_header(1, client.chain_info().best_hash, next_authorities.clone(), 1);
+		let enact = Header {
+			number: 2,
+			parent_hash: signal.hash(),
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		import_header(&client, &mut data, signal.clone(), None, None);
+		import_header(&client, &mut data, enact.clone(), None, None);
+		let justification = TestJustification((0, genesis_authorities), Vec::new());
+
+		// when the block scheduling the change is finalized
+		do_import_justification::<_, _, _, TestJustification>(
+			&client,
+			&mut data,
+			signal.hash(),
+			1,
+			justification.encode(),
+		).unwrap();
+
+		// then the change is kept until it is enacted
+		assert_eq!(data.authority_set.set_id(), 0);
+		assert_eq!(data.scheduled_change.as_ref().map(|(number, _)| *number), Some(1));
+
+		// when the block enacting the change is finalized
+		do_import_justification::<_, _, _, TestJustification>(
+			&client,
+			&mut data,
+			enact.hash(),
+			2,
+			justification.encode(),
+		).unwrap();
+
+		// then the change is enacted
+		assert_eq!(client.chain_info().finalized_hash, enact.hash());
+		assert_eq!(data.authority_set.set_id(), 1);
+		assert_eq!(data.authority_set.authorities(), next_authorities);
+		assert!(data.scheduled_change.is_none());
+	}
+
 	#[test]
 	fn aux_data_updated_on_start() {
 		let aux_store = InMemoryAuxStore::<Block>::new();
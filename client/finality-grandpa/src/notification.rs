@@ -0,0 +1,84 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Notifications about justifications of finalized blocks.
+
+use std::sync::Arc;
+
+use futures03::channel::mpsc;
+use parking_lot::Mutex;
+use sp_runtime::traits::Block as BlockT;
+
+use crate::justification::GrandpaJustification;
+use crate::Error;
+
+type JustificationSubscribers<Block> = Arc<Mutex<Vec<mpsc::UnboundedSender<GrandpaJustification<Block>>>>>;
+
+/// The sending half of the GRANDPA justification channel(s).
+///
+/// Used to send notifications about justifications generated
+/// at the end of a GRANDPA round.
+#[derive(Clone)]
+pub struct GrandpaJustificationSender<Block: BlockT> {
+	subscribers: JustificationSubscribers<Block>,
+}
+
+impl<Block: BlockT> GrandpaJustificationSender<Block> {
+	/// Create a new pair of sender and stream, sharing the same subscribers.
+	pub fn channel() -> (Self, GrandpaJustificationStream<Block>) {
+		let subscribers = Arc::new(Mutex::new(Vec::new()));
+		(
+			GrandpaJustificationSender { subscribers: subscribers.clone() },
+			GrandpaJustificationStream { subscribers },
+		)
+	}
+
+	/// Send the justification to all subscribers, forgetting the ones that
+	/// have been dropped. The justification is only built if there are
+	/// subscribers.
+	pub(crate) fn notify<F>(&self, justification: F) -> Result<(), Error> where
+		F: FnOnce() -> Result<GrandpaJustification<Block>, Error>,
+	{
+		let mut subscribers = self.subscribers.lock();
+		subscribers.retain(|subscriber| !subscriber.is_closed());
+
+		if !subscribers.is_empty() {
+			let justification = justification()?;
+			subscribers.retain(|subscriber| subscriber.unbounded_send(justification.clone()).is_ok());
+		}
+
+		Ok(())
+	}
+}
+
+/// The receiving half of the GRANDPA justification channel.
+///
+/// Used to receive notifications about justifications generated
+/// at the end of a GRANDPA round.
+#[derive(Clone)]
+pub struct GrandpaJustificationStream<Block: BlockT> {
+	subscribers: JustificationSubscribers<Block>,
+}
+
+impl<Block: BlockT> GrandpaJustificationStream<Block> {
+	/// Subscribe to a channel through which justifications are sent
+	/// at the end of each GRANDPA voting round.
+	pub fn subscribe(&self) -> mpsc::UnboundedReceiver<GrandpaJustification<Block>> {
+		let (sender, receiver) = mpsc::unbounded();
+		self.subscribers.lock().push(sender);
+		receiver
+	}
+}
@@ -25,7 +25,7 @@ use finality_grandpa::{
 use log::{debug, info, warn};
 
 use sp_consensus::SelectChain;
-use sc_client_api::{CallExecutor, backend::Backend};
+use sc_client_api::{BlockchainEvents, CallExecutor, backend::Backend};
 use sc_client::Client;
 use sp_runtime::traits::{NumberFor, Block as BlockT};
 use sp_core::{H256, Blake2Hasher};
@@ -34,9 +34,13 @@ use crate::{
 	global_communication, CommandOrError, CommunicationIn, Config, environment,
 	LinkHalf, Error, aux_schema::PersistentData, VoterCommand, VoterSetState,
 };
-use crate::authorities::SharedAuthoritySet;
+use crate::authorities::{AuthoritySet, SharedAuthoritySet};
 use crate::communication::{Network as NetworkT, NetworkBridge};
 use crate::consensus_changes::SharedConsensusChanges;
+use crate::justification::GrandpaJustification;
+use crate::light_import::GrandpaLightBlockImport;
+use crate::until_imported::UntilGlobalMessageBlocksImported;
+use crate::notification::GrandpaJustificationSender;
 use sp_finality_grandpa::AuthorityId;
 
 struct ObserverChain<'a, Block: BlockT, B, E, RA>(&'a Client<B, E, Block, RA>);
@@ -61,6 +65,7 @@ fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S, F>(
 	client: &Arc<Client<B, E, Block, RA>>,
 	authority_set: &SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
 	consensus_changes: &SharedConsensusChanges<Block::Hash, NumberFor<Block>>,
+	justification_sender: &GrandpaJustificationSender<Block>,
 	voters: &Arc<VoterSet<AuthorityId>>,
 	last_finalized_number: NumberFor<Block>,
 	commits: S,
@@ -78,6 +83,7 @@ fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S, F>(
 {
 	let authority_set = authority_set.clone();
 	let consensus_changes = consensus_changes.clone();
+	let justification_sender = justification_sender.clone();
 	let client = client.clone();
 	let voters = voters.clone();
 
@@ -118,6 +124,7 @@ fn grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S, F>(
 				&authority_set,
 				&consensus_changes,
 				None,
+				Some(&justification_sender),
 				finalized_hash,
 				finalized_number,
 				(round, commit).into(),
@@ -171,6 +178,8 @@ pub fn run_grandpa_observer<B, E, Block: BlockT<Hash=H256>, N, RA, SC, Sp>(
 		select_chain: _,
 		persistent_data,
 		voter_commands_rx,
+		justification_sender,
+		..
 	} = link;
 
 	let network = NetworkBridge::new(
@@ -186,7 +195,8 @@ pub fn run_grandpa_observer<B, E, Block: BlockT<Hash=H256>, N, RA, SC, Sp>(
 		network,
 		persistent_data,
 		config.keystore.clone(),
-		voter_commands_rx
+		voter_commands_rx,
+		justification_sender,
 	);
 
 	let observer_work = observer_work
@@ -209,6 +219,7 @@ struct ObserverWork<B: BlockT<Hash=H256>, N: NetworkT<B>, E, Backend, RA> {
 	persistent_data: PersistentData<B>,
 	keystore: Option<sc_keystore::KeyStorePtr>,
 	voter_commands_rx: mpsc::UnboundedReceiver<VoterCommand<B::Hash, NumberFor<B>>>,
+	justification_sender: GrandpaJustificationSender<B>,
 }
 
 impl<B, N, E, Bk, RA> ObserverWork<B, N, E, Bk, RA>
@@ -226,6 +237,7 @@ where
 		persistent_data: PersistentData<B>,
 		keystore: Option<sc_keystore::KeyStorePtr>,
 		voter_commands_rx: mpsc::UnboundedReceiver<VoterCommand<B::Hash, NumberFor<B>>>,
+		justification_sender: GrandpaJustificationSender<B>,
 	) -> Self {
 
 		let mut work = ObserverWork {
@@ -237,6 +249,7 @@ where
 			persistent_data,
 			keystore,
 			voter_commands_rx,
+			justification_sender,
 		};
 		work.rebuild_observer();
 		work
@@ -280,6 +293,7 @@ where
 			&self.client,
 			&self.persistent_data.authority_set,
 			&self.persistent_data.consensus_changes,
+			&self.justification_sender,
 			&voters,
 			last_finalized_number,
 			global_in,
@@ -376,3 +390,267 @@ where
 		Ok(Async::NotReady)
 	}
 }
+
+fn light_grandpa_observer<B, E, Block: BlockT<Hash=H256>, RA, S, F>(
+	light_import: &GrandpaLightBlockImport<B, E, Block, RA>,
+	voters: &Arc<VoterSet<AuthorityId>>,
+	last_finalized_number: NumberFor<Block>,
+	commits: S,
+	note_round: F,
+) -> impl Future<Item=(), Error=Error> where
+	NumberFor<Block>: BlockNumberOps,
+	B: Backend<Block, Blake2Hasher> + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + Clone + 'static,
+	RA: Send + Sync,
+	S: Stream<Item = CommunicationIn<Block>, Error = Error>,
+	F: Fn(u64),
+{
+	let light_import = light_import.clone();
+	let client = light_import.client();
+	let voters = voters.clone();
+
+	let observer = commits.fold(last_finalized_number, move |last_finalized_number, global| {
+		let (round, commit, callback) = match global {
+			voter::CommunicationIn::Commit(round, commit, callback) => {
+				let commit = finality_grandpa::Commit::from(commit);
+				(round, commit, callback)
+			},
+			voter::CommunicationIn::CatchUp(..) => {
+				// ignore catch up messages
+				return future::ok(last_finalized_number);
+			},
+		};
+
+		// blocks might have been finalized by the import queue in the meantime
+		let last_finalized_number = std::cmp::max(last_finalized_number, client.chain_info().finalized_number);
+		if commit.target_number <= last_finalized_number {
+			return future::ok(last_finalized_number);
+		}
+
+		let validation_result = match finality_grandpa::validate_commit(
+			&commit,
+			&voters,
+			&ObserverChain(&*client),
+		) {
+			Ok(r) => r,
+			Err(e) => return future::err(e.into()),
+		};
+
+		if let Some(_) = validation_result.ghost() {
+			let finalized_hash = commit.target_hash;
+			let finalized_number = commit.target_number;
+
+			// commit is valid, persist the justification built from it to the light db
+			let finalized = GrandpaJustification::from_commit(&client, round, commit)
+				.map_err(|e| e.to_string())
+				.and_then(|justification| light_import
+					.finalize_with_justification(finalized_hash, finalized_number, justification)
+					.map_err(|e| e.to_string())
+				);
+
+			note_round(round + 1);
+
+			finality_grandpa::process_commit_validation_result(validation_result, callback);
+
+			match finalized {
+				Ok(()) => future::ok(finalized_number),
+				Err(e) => {
+					warn!(target: "afg", "Failed to finalize block {:?} from observed commit: {}", finalized_hash, e);
+					future::ok(last_finalized_number)
+				},
+			}
+		} else {
+			debug!(target: "afg", "Received invalid commit: ({:?}, {:?})", round, commit);
+
+			finality_grandpa::process_commit_validation_result(validation_result, callback);
+
+			// commit is invalid, continue processing commits with the current state
+			future::ok(last_finalized_number)
+		}
+	});
+
+	observer.map(|_| ())
+}
+
+/// Run a GRANDPA observer on top of a light client. The observer follows
+/// finality by validating GRANDPA commits against the authority set tracked by
+/// the given light block import, and persists the finalized blocks (along with
+/// their justifications) to the light database.
+///
+/// Authority set changes are enacted when the observer finalizes the blocks
+/// scheduling them, or learnt through finality proofs fetched by the light
+/// import queue. The observer follows the new set once it has been applied.
+pub fn run_light_grandpa_observer<B, E, Block: BlockT<Hash=H256>, N, RA, Sp>(
+	config: Config,
+	light_import: GrandpaLightBlockImport<B, E, Block, RA>,
+	network: N,
+	on_exit: impl futures03::Future<Output=()> + Clone + Send + Unpin + 'static,
+	executor: Sp,
+) -> ::sp_blockchain::Result<impl Future<Item=(),Error=()> + Send + 'static> where
+	B: Backend<Block, Blake2Hasher> + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + Clone + 'static,
+	N: NetworkT<Block> + Send + Clone + 'static,
+	NumberFor<Block>: BlockNumberOps,
+	RA: Send + Sync + 'static,
+	Sp: futures03::task::Spawn + 'static,
+{
+	use futures03::{FutureExt, StreamExt, TryFutureExt, TryStreamExt};
+
+	let client = light_import.client();
+	let chain_info = client.chain_info();
+	let (set_id, authorities) = light_import.authority_set();
+	let set_state = VoterSetState::live(
+		set_id,
+		&AuthoritySet::genesis(authorities),
+		(chain_info.finalized_hash, chain_info.finalized_number),
+	);
+
+	let network = NetworkBridge::new(
+		network,
+		config.clone(),
+		set_state.into(),
+		&executor,
+		on_exit.clone(),
+	);
+
+	let finality_notifications = client.finality_notification_stream()
+		.map(Ok::<_, ()>)
+		.compat();
+
+	let observer_work = LightObserverWork {
+		// `observer` is set to a temporary value and replaced below when
+		// calling `rebuild_observer`.
+		observer: Box::new(futures::empty()) as Box<_>,
+		set_id,
+		light_import,
+		network,
+		finality_notifications: Box::new(finality_notifications),
+	};
+
+	let observer_work = observer_work.rebuilt()
+		.map_err(|e| {
+			warn!("GRANDPA light observer failed: {:?}", e);
+		});
+
+	Ok(observer_work.select(on_exit.map(Ok).compat()).map(|_| ()).map_err(|_| ()))
+}
+
+/// Future that powers the light observer.
+#[must_use]
+struct LightObserverWork<B: BlockT<Hash=H256>, N: NetworkT<B>, E, Backend, RA> {
+	observer: Box<dyn Future<Item = (), Error = Error> + Send>,
+	set_id: u64,
+	light_import: GrandpaLightBlockImport<Backend, E, B, RA>,
+	network: NetworkBridge<B, N>,
+	finality_notifications: Box<dyn Stream<Item = sc_client_api::FinalityNotification<B>, Error = ()> + Send>,
+}
+
+impl<B, N, E, Bk, RA> LightObserverWork<B, N, E, Bk, RA>
+where
+	B: BlockT<Hash=H256>,
+	N: NetworkT<B>,
+	NumberFor<B>: BlockNumberOps,
+	RA: 'static + Send + Sync,
+	E: CallExecutor<B, Blake2Hasher> + Send + Sync + Clone + 'static,
+	Bk: Backend<B, Blake2Hasher> + 'static,
+{
+	fn rebuilt(mut self) -> Self {
+		self.rebuild_observer();
+		self
+	}
+
+	/// Rebuilds the `self.observer` field using the current light authority set.
+	fn rebuild_observer(&mut self) {
+		let (set_id, authorities) = self.light_import.authority_set();
+		let voters = Arc::new(authorities.into_iter().collect::<VoterSet<AuthorityId>>());
+		let client = self.light_import.client();
+
+		// start global communication stream for the current set, the light client
+		// is never a voter. Commits are held back until their blocks are imported.
+		let (global_in, _) = self.network.global_communication(
+			crate::communication::SetId(set_id),
+			voters.clone(),
+			false,
+		);
+		let global_in = UntilGlobalMessageBlocksImported::new(
+			client.import_notification_stream(),
+			self.network.clone(),
+			client.clone(),
+			global_in,
+			"global",
+		);
+
+		let last_finalized_number = client.chain_info().finalized_number;
+
+		// NOTE: since we are not using `round_communication` we have to
+		// manually note the round with the gossip validator.
+		let note_round = {
+			let network = self.network.clone();
+			let voters = voters.clone();
+
+			move |round| network.note_round(
+				crate::communication::Round(round),
+				crate::communication::SetId(set_id),
+				&*voters,
+			)
+		};
+
+		let observer = light_grandpa_observer(
+			&self.light_import,
+			&voters,
+			last_finalized_number,
+			global_in,
+			note_round,
+		);
+
+		self.set_id = set_id;
+		self.observer = Box::new(observer);
+	}
+}
+
+impl<B, N, E, Bk, RA> Future for LightObserverWork<B, N, E, Bk, RA>
+where
+	B: BlockT<Hash=H256>,
+	N: NetworkT<B>,
+	NumberFor<B>: BlockNumberOps,
+	RA: 'static + Send + Sync,
+	E: CallExecutor<B, Blake2Hasher> + Send + Sync + Clone + 'static,
+	Bk: Backend<B, Blake2Hasher> + 'static,
+{
+	type Item = ();
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.observer.poll() {
+			Ok(Async::NotReady) => {}
+			Ok(Async::Ready(())) => {
+				// observer commit stream doesn't conclude naturally; this could reasonably be an error.
+				return Ok(Async::Ready(()))
+			}
+			Err(e) => {
+				// return inner observer error
+				return Err(e)
+			}
+		}
+
+		// the light authority set only changes when a block is finalized, either by
+		// an observed commit or by an imported finality proof
+		loop {
+			match self.finality_notifications.poll() {
+				Ok(Async::NotReady) => break,
+				Ok(Async::Ready(Some(_))) => {
+					if self.light_import.authority_set().0 != self.set_id {
+						self.rebuild_observer();
+						futures::task::current().notify();
+					}
+				},
+				Ok(Async::Ready(None)) | Err(_) => {
+					// the client is shutting down
+					return Ok(Async::Ready(()))
+				},
+			}
+		}
+
+		Ok(Async::NotReady)
+	}
+}
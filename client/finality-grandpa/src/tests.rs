@@ -483,6 +483,39 @@ fn finalize_3_voters_no_observers() {
 	);
 }
 
+#[test]
+fn justifications_and_voter_state_are_reported() {
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let threads_pool = futures03::executor::ThreadPool::new().unwrap();
+	let peers = &[Ed25519Keyring::Alice, Ed25519Keyring::Bob, Ed25519Keyring::Charlie];
+	let voters = make_ids(peers);
+
+	let mut net = GrandpaTestNet::new(TestApi::new(voters), 3);
+	net.peer(0).push_blocks(20, false);
+	net.block_until_sync(&mut runtime);
+
+	let (mut justifications, voter_state) = {
+		let link = net.peer(0).data.lock();
+		let link = link.as_ref().expect("link initialized at startup; qed");
+		(link.justification_stream().subscribe(), link.shared_voter_state())
+	};
+
+	let net = Arc::new(Mutex::new(net));
+	run_to_completion(&mut runtime, &threads_pool, 20, net.clone(), peers);
+
+	// every finalization is notified with a justification, even if it isn't stored
+	let justification = justifications.try_next()
+		.expect("at least one justification was sent")
+		.expect("stream is not closed while the voter runs");
+	assert!(justification.commit.target_number <= 20);
+
+	let state = voter_state.voter_state().expect("voter is live");
+	assert_eq!(state.set_id, 0);
+	assert!(state.round >= 1);
+	assert_eq!(state.total_weight, 3);
+	assert_eq!(state.threshold_weight, 3);
+}
+
 #[test]
 fn finalize_3_voters_1_full_observer() {
 	let mut runtime = current_thread::Runtime::new().unwrap();
@@ -834,6 +867,13 @@ fn sync_justifications_on_change_blocks() {
 		assert!(net.lock().peer(i).client().justification(&BlockId::Number(21)).unwrap().is_some());
 	}
 
+	// ... and have recorded the handoff, so that it could be proved to light clients
+	for i in 0..3 {
+		let full_client = net.lock().peer(i).client().as_full().expect("only full clients are used in test");
+		let set_changes = crate::aux_schema::load_authority_set_changes::<Block, _>(&*full_client).unwrap();
+		assert_eq!(set_changes, vec![crate::aux_schema::AuthoritySetChange { set_id: 1, block_number: 21 }]);
+	}
+
 	// the last peer should get the justification by syncing from other peers
 	runtime.block_on(futures::future::poll_fn(move || -> std::result::Result<_, ()> {
 		if net.lock().peer(3).client().justification(&BlockId::Number(21)).unwrap().is_none() {
@@ -1376,6 +1416,137 @@ fn finalize_3_voters_1_light_observer() {
 	});
 }
 
+#[test]
+fn finalize_3_voters_1_light_client_observer() {
+	use crate::light_import::tests::light_block_import_without_justifications;
+
+	let _ = env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let threads_pool = futures03::executor::ThreadPool::new().unwrap();
+	let authorities = &[Ed25519Keyring::Alice, Ed25519Keyring::Bob, Ed25519Keyring::Charlie];
+	let voters = make_ids(authorities);
+
+	let mut net = GrandpaTestNet::new(TestApi::new(voters), 3);
+	net.add_light_peer(&GrandpaTestNet::default_config());
+	net.peer(0).push_blocks(20, false);
+	net.block_until_sync(&mut runtime);
+
+	// the light client ignores justifications, so it only finalizes through the observer
+	assert_eq!(net.peer(3).client().info().best_number, 20);
+	assert_eq!(net.peer(3).client().info().finalized_number, 0);
+
+	let light_import = match net.peer(3).client() {
+		PeersClient::Light(client, backend) => light_block_import_without_justifications(
+			client.clone(),
+			backend.clone(),
+			&net.test_config,
+			Arc::new(net.test_config.clone()),
+		).expect("Could not create light block import").0,
+		PeersClient::Full(..) => panic!("peer #3 is a light client"),
+	};
+
+	let net = Arc::new(Mutex::new(net));
+	let finality_notifications = net.lock().peer(3).client().finality_notification_stream()
+		.map(|v| Ok::<_, ()>(v)).compat()
+		.take_while(|n| Ok(n.header.number() < &20))
+		.collect();
+
+	run_to_completion_with(&mut runtime, &threads_pool, 20, net.clone(), authorities, |executor| {
+		executor.spawn(
+			run_light_grandpa_observer(
+				Config {
+					gossip_duration: TEST_GOSSIP_DURATION,
+					justification_period: 32,
+					keystore: None,
+					name: Some("light observer".to_string()),
+					is_authority: false,
+					observer_enabled: true,
+				},
+				light_import,
+				net.lock().peers[3].network_service().clone(),
+				Exit,
+				threads_pool.clone(),
+			).unwrap()
+		).unwrap();
+
+		Some(Box::new(finality_notifications.map(|_| ())))
+	});
+
+	assert_eq!(net.lock().peer(3).client().info().finalized_number, 20);
+}
+
+#[test]
+fn finalize_3_voters_1_light_client_observer_across_set_change() {
+	use crate::light_import::tests::light_block_import_without_justifications;
+
+	let _ = env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let threads_pool = futures03::executor::ThreadPool::new().unwrap();
+	let authorities = &[Ed25519Keyring::Alice, Ed25519Keyring::Bob, Ed25519Keyring::Charlie];
+	let voters = make_ids(authorities);
+
+	let mut net = GrandpaTestNet::new(TestApi::new(voters.clone()), 3);
+	net.add_light_peer(&GrandpaTestNet::default_config());
+	net.peer(0).push_blocks(9, false);
+
+	// at block 10 we do add a transition which is instant
+	net.peer(0).generate_blocks(1, BlockOrigin::File, |builder| {
+		let mut block = builder.bake().unwrap();
+		add_scheduled_change(&mut block, ScheduledChange {
+			next_authorities: voters.clone(),
+			delay: 0,
+		});
+		block
+	});
+
+	net.peer(0).push_blocks(10, false);
+	net.block_until_sync(&mut runtime);
+
+	assert_eq!(net.peer(3).client().info().best_number, 20);
+
+	let light_import = match net.peer(3).client() {
+		PeersClient::Light(client, backend) => light_block_import_without_justifications(
+			client.clone(),
+			backend.clone(),
+			&net.test_config,
+			Arc::new(net.test_config.clone()),
+		).expect("Could not create light block import").0,
+		PeersClient::Full(..) => panic!("peer #3 is a light client"),
+	};
+	let observed_import = light_import.clone();
+
+	let net = Arc::new(Mutex::new(net));
+	let finality_notifications = net.lock().peer(3).client().finality_notification_stream()
+		.map(|v| Ok::<_, ()>(v)).compat()
+		.take_while(|n| Ok(n.header.number() < &20))
+		.collect();
+
+	run_to_completion_with(&mut runtime, &threads_pool, 20, net.clone(), authorities, |executor| {
+		executor.spawn(
+			run_light_grandpa_observer(
+				Config {
+					gossip_duration: TEST_GOSSIP_DURATION,
+					justification_period: 32,
+					keystore: None,
+					name: Some("light observer".to_string()),
+					is_authority: false,
+					observer_enabled: true,
+				},
+				light_import,
+				net.lock().peers[3].network_service().clone(),
+				Exit,
+				threads_pool.clone(),
+			).unwrap()
+		).unwrap();
+
+		Some(Box::new(finality_notifications.map(|_| ())))
+	});
+
+	// the observer has followed the voters into the next set to finalize the last block
+	assert_eq!(net.lock().peer(3).client().info().finalized_number, 20);
+	assert_eq!(observed_import.authority_set(), (1, voters));
+}
+
 #[test]
 fn finality_proof_is_fetched_by_light_client_when_consensus_data_changes() {
 	let _ = ::env_logger::try_init();
@@ -1639,6 +1810,8 @@ fn grandpa_environment_respects_voting_rules() {
 			voters: Arc::new(authority_set.current_authorities()),
 			network,
 			voting_rule,
+			round_votes: link.round_votes.clone(),
+			justification_sender: link.justification_sender.clone(),
 		}
 	};
 
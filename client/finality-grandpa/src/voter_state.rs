@@ -0,0 +1,152 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reporting on the progress of the GRANDPA voter.
+
+use std::collections::HashSet;
+
+use finality_grandpa::voter_set::VoterSet;
+use sp_finality_grandpa::{AuthorityId, RoundNumber, SetId};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+use crate::authorities::SharedAuthoritySet;
+use crate::environment::{SharedRoundVotes, SharedVoterSetState, VoterSetState};
+
+/// Votes of a single kind (prevotes or precommits) cast in a round.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundVotesState {
+	/// Total weight of the voters that have cast the vote.
+	pub current_weight: u64,
+	/// Voters that haven't cast the vote yet.
+	pub missing: Vec<AuthorityId>,
+}
+
+/// State of the best round of the voter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoterState {
+	/// The current authority set id.
+	pub set_id: SetId,
+	/// The best round number.
+	pub round: RoundNumber,
+	/// Total weight of the authority set.
+	pub total_weight: u64,
+	/// Weight required to reach supermajority.
+	pub threshold_weight: u64,
+	/// The prevotes cast in the round.
+	pub prevotes: RoundVotesState,
+	/// The precommits cast in the round.
+	pub precommits: RoundVotesState,
+}
+
+/// A handle to the state of the GRANDPA voter, meant to be shared with
+/// anyone reporting on its progress (e.g. RPC).
+#[derive(Clone)]
+pub struct SharedVoterState<Block: BlockT> {
+	authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+	voter_set_state: SharedVoterSetState<Block>,
+	round_votes: SharedRoundVotes,
+}
+
+impl<Block: BlockT> SharedVoterState<Block> {
+	pub(crate) fn new(
+		authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+		voter_set_state: SharedVoterSetState<Block>,
+		round_votes: SharedRoundVotes,
+	) -> Self {
+		SharedVoterState { authority_set, voter_set_state, round_votes }
+	}
+
+	/// Returns the state of the best round of the voter, or `None` if the
+	/// voter is paused.
+	pub fn voter_state(&self) -> Option<VoterState> {
+		let round = match &*self.voter_set_state.read() {
+			VoterSetState::Live { current_rounds, .. } => *current_rounds.keys().last()?,
+			VoterSetState::Paused { .. } => return None,
+		};
+
+		let (set_id, authorities) = {
+			let authority_set = self.authority_set.inner().read();
+			let (set_id, authorities) = authority_set.current();
+			(set_id, authorities.to_vec())
+		};
+		let voters = authorities.iter().cloned().collect::<VoterSet<AuthorityId>>();
+
+		let votes = self.round_votes.round(round);
+		let tally = |seen: &HashSet<AuthorityId>| RoundVotesState {
+			current_weight: authorities.iter()
+				.filter(|(id, _)| seen.contains(id))
+				.map(|(_, weight)| weight)
+				.sum(),
+			missing: authorities.iter()
+				.filter(|(id, _)| !seen.contains(id))
+				.map(|(id, _)| id.clone())
+				.collect(),
+		};
+
+		Some(VoterState {
+			set_id,
+			round,
+			total_weight: voters.total_weight(),
+			threshold_weight: voters.threshold(),
+			prevotes: tally(&votes.prevotes),
+			precommits: tally(&votes.precommits),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+	use sp_keyring::Ed25519Keyring;
+	use substrate_test_runtime_client::runtime::Block;
+	use crate::authorities::AuthoritySet;
+
+	fn prevote() -> finality_grandpa::Message<H256, u64> {
+		finality_grandpa::Message::Prevote(finality_grandpa::Prevote::new(Default::default(), 1))
+	}
+
+	#[test]
+	fn should_report_weights_and_missing_voters_of_best_round() {
+		// given
+		let voters = [Ed25519Keyring::Alice, Ed25519Keyring::Bob, Ed25519Keyring::Charlie]
+			.iter()
+			.map(|k| (AuthorityId::from(k.public()), 1))
+			.collect::<Vec<_>>();
+		let authority_set = AuthoritySet::<H256, u64>::genesis(voters.clone());
+		let voter_set_state = VoterSetState::<Block>::live(0, &authority_set, (Default::default(), 0));
+		let round_votes = SharedRoundVotes::default();
+		let state = SharedVoterState::<Block>::new(
+			authority_set.into(),
+			SharedVoterSetState::new(voter_set_state),
+			round_votes.clone(),
+		);
+
+		// when
+		round_votes.note_vote(1, &voters[0].0, &prevote());
+		round_votes.note_vote(1, &voters[1].0, &prevote());
+
+		// then
+		let state = state.voter_state().unwrap();
+		assert_eq!(state.set_id, 0);
+		assert_eq!(state.round, 1);
+		assert_eq!(state.total_weight, 3);
+		assert_eq!(state.threshold_weight, 3);
+		assert_eq!(state.prevotes, RoundVotesState { current_weight: 2, missing: vec![voters[2].0.clone()] });
+		assert_eq!(state.precommits.current_weight, 0);
+		assert_eq!(state.precommits.missing.len(), 3);
+	}
+}
@@ -19,12 +19,21 @@
 //! This exposes the `VotingRule` trait used to implement arbitrary voting
 //! restrictions that are taken into account by the GRANDPA environment when
 //! selecting a finality target to vote on.
+//!
+//! Besides the default rules, a set of built-in rules can be enabled without
+//! recompiling the node through `VotingRulesConfig`, e.g. from the chain spec
+//! or the command line.
 
+use std::collections::{HashSet, VecDeque};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use sc_client_api::blockchain::HeaderBackend;
+use sp_core::Bytes;
 use sp_runtime::generic::BlockId;
-use sp_runtime::traits::{Block as BlockT, Header, NumberFor, One, Zero};
+use sp_runtime::traits::{Block as BlockT, Header, NumberFor, One, Zero, SaturatedConversion};
 
 /// A trait for custom voting rules in GRANDPA.
 pub trait VotingRule<Block, B>: Send + Sync where
@@ -130,26 +139,211 @@ impl<Block, B> VotingRule<Block, B> for ThreeQuartersOfTheUnfinalizedChain where
 			return None;
 		}
 
-		let mut target_header = current_target.clone();
-		let mut target_hash = current_target.hash();
-
-		// walk backwards until we find the target block
-		loop {
-			if *target_header.number() < target_number {
-				unreachable!(
-					"we are traversing backwards from a known block; \
-					 blocks are stored contiguously; \
-					 qed"
-				);
+		find_target::<Block, B>(backend, target_number, current_target)
+	}
+}
+
+/// A voting rule that never votes past the block with the given number, i.e.
+/// it pauses finality at the given height.
+#[derive(Clone)]
+pub struct PauseAtBlock<N>(pub N);
+
+impl<Block, B> VotingRule<Block, B> for PauseAtBlock<NumberFor<Block>> where
+	Block: BlockT,
+	B: HeaderBackend<Block>,
+{
+	fn restrict_vote(
+		&self,
+		backend: &B,
+		base: &Block::Header,
+		_best_target: &Block::Header,
+		current_target: &Block::Header,
+	) -> Option<(Block::Hash, NumberFor<Block>)> {
+		if *current_target.number() <= self.0 {
+			return None;
+		}
+
+		// we can't vote below the base, even if it is past the pause height
+		let target_number = std::cmp::max(self.0, *base.number());
+		find_target::<Block, B>(backend, target_number, current_target)
+	}
+}
+
+/// A voting rule that only votes on blocks whose height has been known to us
+/// for at least the given duration, i.e. it lags our votes by a time duration.
+///
+/// The age of a height is measured from the moment it has first been seen as
+/// the best vote target, so all votes are restricted to the base block for
+/// the first `lag` after startup.
+pub struct LagByDuration<N> {
+	lag: Duration,
+	seen: Mutex<VecDeque<(Instant, N)>>,
+	clock: Box<dyn Fn() -> Instant + Send + Sync>,
+}
+
+impl<N> LagByDuration<N> {
+	/// Create a new rule lagging the votes by the given duration.
+	pub fn new(lag: Duration) -> Self {
+		Self::with_clock(lag, Instant::now)
+	}
+
+	/// Create a new rule lagging the votes by the given duration, as measured by `clock`.
+	pub fn with_clock(lag: Duration, clock: impl Fn() -> Instant + Send + Sync + 'static) -> Self {
+		LagByDuration {
+			lag,
+			seen: Mutex::new(VecDeque::new()),
+			clock: Box::new(clock),
+		}
+	}
+}
+
+impl<Block, B> VotingRule<Block, B> for LagByDuration<NumberFor<Block>> where
+	Block: BlockT,
+	B: HeaderBackend<Block>,
+{
+	fn restrict_vote(
+		&self,
+		backend: &B,
+		base: &Block::Header,
+		best_target: &Block::Header,
+		current_target: &Block::Header,
+	) -> Option<(Block::Hash, NumberFor<Block>)> {
+		let now = (self.clock)();
+		let mut seen = self.seen.lock();
+
+		if seen.back().map_or(true, |(_, number)| number < best_target.number()) {
+			seen.push_back((now, *best_target.number()));
+		}
+
+		// only keep the most recent observation that is older than the lag
+		while seen.len() > 1 && now.duration_since(seen[1].0) >= self.lag {
+			seen.pop_front();
+		}
+
+		let target_number = match seen.front() {
+			Some((seen_at, number)) if now.duration_since(*seen_at) >= self.lag =>
+				std::cmp::max(*number, *base.number()),
+			_ => *base.number(),
+		};
+
+		if target_number >= *current_target.number() {
+			return None;
+		}
+
+		find_target::<Block, B>(backend, target_number, current_target)
+	}
+}
+
+/// Extracts the author of a block, as used by the `TrustedConfirmations`
+/// voting rule.
+pub trait BlockAuthor<Block: BlockT>: Send + Sync {
+	/// Return the public key of the author of the given block, if known.
+	fn author(&self, header: &Block::Header) -> Option<Vec<u8>>;
+}
+
+impl<Block, F> BlockAuthor<Block> for F where
+	Block: BlockT,
+	F: Fn(&Block::Header) -> Option<Vec<u8>> + Send + Sync,
+{
+	fn author(&self, header: &Block::Header) -> Option<Vec<u8>> {
+		(self)(header)
+	}
+}
+
+/// A voting rule that never votes past a block which hasn't been built upon by
+/// the given number of distinct trusted authors, i.e. it requires confirmations
+/// from a trusted set before finalizing a block.
+pub struct TrustedConfirmations<Block: BlockT> {
+	trusted: HashSet<Vec<u8>>,
+	confirmations: usize,
+	block_author: Box<dyn BlockAuthor<Block>>,
+}
+
+impl<Block: BlockT> TrustedConfirmations<Block> {
+	/// Create a new rule requiring `confirmations` descendants authored by
+	/// distinct members of the `trusted` set, as reported by `block_author`.
+	pub fn new<A>(
+		trusted: impl IntoIterator<Item=Vec<u8>>,
+		confirmations: usize,
+		block_author: A,
+	) -> Self where
+		A: BlockAuthor<Block> + 'static,
+	{
+		TrustedConfirmations {
+			trusted: trusted.into_iter().collect(),
+			confirmations,
+			block_author: Box::new(block_author),
+		}
+	}
+}
+
+impl<Block, B> VotingRule<Block, B> for TrustedConfirmations<Block> where
+	Block: BlockT,
+	B: HeaderBackend<Block>,
+{
+	fn restrict_vote(
+		&self,
+		backend: &B,
+		base: &Block::Header,
+		best_target: &Block::Header,
+		current_target: &Block::Header,
+	) -> Option<(Block::Hash, NumberFor<Block>)> {
+		let mut confirmed_by = HashSet::new();
+		let mut header = best_target.clone();
+
+		// walk backwards from the best target until we find a block that has
+		// enough confirmations and isn't past the current target
+		while header.number() > base.number() {
+			if header.number() <= current_target.number() && confirmed_by.len() >= self.confirmations {
+				break;
 			}
-			if *target_header.number() == target_number {
-				return Some((target_hash, target_number));
+
+			if let Some(author) = self.block_author.author(&header) {
+				if self.trusted.contains(&author) {
+					confirmed_by.insert(author);
+				}
 			}
 
-			target_hash = *target_header.parent_hash();
-			target_header = backend.header(BlockId::Hash(target_hash)).ok()?
+			header = backend.header(BlockId::Hash(*header.parent_hash())).ok()?
 				.expect("Header known to exist due to the existence of one of its descendents; qed");
 		}
+
+		if header.hash() == current_target.hash() {
+			return None;
+		}
+
+		Some((header.hash(), *header.number()))
+	}
+}
+
+/// Walk backwards from `current_target` to its ancestor with the given number.
+fn find_target<Block, B>(
+	backend: &B,
+	target_number: NumberFor<Block>,
+	current_target: &Block::Header,
+) -> Option<(Block::Hash, NumberFor<Block>)> where
+	Block: BlockT,
+	B: HeaderBackend<Block>,
+{
+	let mut target_header = current_target.clone();
+	let mut target_hash = current_target.hash();
+
+	// walk backwards until we find the target block
+	loop {
+		if *target_header.number() < target_number {
+			unreachable!(
+				"we are traversing backwards from a known block; \
+				 blocks are stored contiguously; \
+				 qed"
+			);
+		}
+		if *target_header.number() == target_number {
+			return Some((target_hash, target_number));
+		}
+
+		target_hash = *target_header.parent_hash();
+		target_header = backend.header(BlockId::Hash(target_hash)).ok()?
+			.expect("Header known to exist due to the existence of one of its descendents; qed");
 	}
 }
 
@@ -245,6 +439,32 @@ impl<Block, B> VotingRulesBuilder<Block, B> where
 		self
 	}
 
+	/// Add the voting rules enabled in the given configuration to the builder.
+	///
+	/// `block_author` is only used if trusted confirmations are configured.
+	pub fn add_config<A>(mut self, config: &VotingRulesConfig, block_author: A) -> Self where
+		A: BlockAuthor<Block> + 'static,
+		NumberFor<Block>: Send + Sync + 'static,
+	{
+		if let Some(pause_at) = config.pause_at {
+			self = self.add(PauseAtBlock(pause_at.saturated_into::<NumberFor<Block>>()));
+		}
+
+		if let Some(lag) = config.lag_by_millis {
+			self = self.add(LagByDuration::<NumberFor<Block>>::new(Duration::from_millis(lag)));
+		}
+
+		if let Some(ref trusted) = config.trusted_confirmations {
+			self = self.add(TrustedConfirmations::new(
+				trusted.authors.iter().map(|author| author.0.clone()),
+				trusted.confirmations as usize,
+				block_author,
+			));
+		}
+
+		self
+	}
+
 	/// Return a new `VotingRule` that applies all of the previously added
 	/// voting rules in-order.
 	pub fn build(self) -> impl VotingRule<Block, B> + Clone {
@@ -268,3 +488,151 @@ impl<Block, B> VotingRule<Block, B> for Box<dyn VotingRule<Block, B>> where
 		(**self).restrict_vote(backend, base, best_target, current_target)
 	}
 }
+
+/// Built-in voting rules that can be enabled without recompiling the node,
+/// e.g. from the chain spec or the command line.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct VotingRulesConfig {
+	/// Never vote past the block with the given number.
+	#[serde(default)]
+	pub pause_at: Option<u64>,
+	/// Only vote on blocks whose height has been known for the given number of milliseconds.
+	#[serde(default)]
+	pub lag_by_millis: Option<u64>,
+	/// Never vote past a block lacking confirmations from a trusted set of authors.
+	#[serde(default)]
+	pub trusted_confirmations: Option<TrustedConfirmationsConfig>,
+}
+
+impl VotingRulesConfig {
+	/// Return the configuration where rules enabled in `overrides` replace
+	/// the ones of `self`.
+	pub fn merge(self, overrides: VotingRulesConfig) -> Self {
+		VotingRulesConfig {
+			pause_at: overrides.pause_at.or(self.pause_at),
+			lag_by_millis: overrides.lag_by_millis.or(self.lag_by_millis),
+			trusted_confirmations: overrides.trusted_confirmations.or(self.trusted_confirmations),
+		}
+	}
+}
+
+/// Configuration of the `TrustedConfirmations` voting rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TrustedConfirmationsConfig {
+	/// Public keys of the trusted block authors.
+	pub authors: Vec<Bytes>,
+	/// Number of distinct trusted authors that must build on top of a block.
+	pub confirmations: u32,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::traits::Header as _;
+	use substrate_test_runtime_client::{
+		runtime::{Block, Header},
+		sc_client::in_mem::Blockchain as InMemoryBlockchain,
+	};
+	use sc_client_api::NewBlockState;
+
+	/// Chain of headers #0..=#10, where each block's author is its number.
+	fn blockchain() -> (InMemoryBlockchain<Block>, Vec<Header>) {
+		let blockchain = InMemoryBlockchain::<Block>::new();
+		let mut headers: Vec<Header> = Vec::new();
+		for number in 0..=10u64 {
+			let parent_hash = headers.last().map(|h| h.hash()).unwrap_or_default();
+			let header = Header::new(number, Default::default(), Default::default(), parent_hash, Default::default());
+			blockchain.insert(header.hash(), header.clone(), None, None, NewBlockState::Best).unwrap();
+			headers.push(header);
+		}
+		(blockchain, headers)
+	}
+
+	fn author(header: &Header) -> Option<Vec<u8>> {
+		Some(vec![*header.number() as u8])
+	}
+
+	#[test]
+	fn pause_at_block_restricts_votes_past_the_height() {
+		let (blockchain, headers) = blockchain();
+		let rule = PauseAtBlock(5u64);
+
+		assert_eq!(
+			VotingRule::<Block, _>::restrict_vote(&rule, &blockchain, &headers[0], &headers[10], &headers[8]),
+			Some((headers[5].hash(), 5)),
+		);
+		assert_eq!(
+			VotingRule::<Block, _>::restrict_vote(&rule, &blockchain, &headers[0], &headers[10], &headers[4]),
+			None,
+		);
+		// never below the base
+		assert_eq!(
+			VotingRule::<Block, _>::restrict_vote(&rule, &blockchain, &headers[7], &headers[10], &headers[8]),
+			Some((headers[7].hash(), 7)),
+		);
+	}
+
+	#[test]
+	fn lag_by_duration_only_votes_on_heights_seen_long_enough() {
+		let (blockchain, headers) = blockchain();
+		let now = Arc::new(Mutex::new(Instant::now()));
+		let rule = LagByDuration::<u64>::with_clock(Duration::from_millis(50), {
+			let now = now.clone();
+			move || *now.lock()
+		});
+
+		// nothing has been seen for long enough
+		assert_eq!(
+			VotingRule::<Block, _>::restrict_vote(&rule, &blockchain, &headers[2], &headers[6], &headers[6]),
+			Some((headers[2].hash(), 2)),
+		);
+
+		*now.lock() += Duration::from_millis(50);
+
+		// #6 has been seen long enough, #10 hasn't
+		assert_eq!(
+			VotingRule::<Block, _>::restrict_vote(&rule, &blockchain, &headers[2], &headers[10], &headers[10]),
+			Some((headers[6].hash(), 6)),
+		);
+	}
+
+	#[test]
+	fn trusted_confirmations_require_descendants_by_trusted_authors() {
+		let (blockchain, headers) = blockchain();
+		let rule = TrustedConfirmations::<Block>::new(vec![vec![7], vec![9], vec![10]], 2, author);
+
+		// #8 is confirmed by #9 and #10 only, #7 is confirmed twice
+		assert_eq!(
+			VotingRule::<Block, _>::restrict_vote(&rule, &blockchain, &headers[0], &headers[10], &headers[10]),
+			Some((headers[8].hash(), 8)),
+		);
+		assert_eq!(
+			VotingRule::<Block, _>::restrict_vote(&rule, &blockchain, &headers[0], &headers[10], &headers[5]),
+			None,
+		);
+
+		// not enough trusted authors above the base
+		let rule = TrustedConfirmations::<Block>::new(vec![vec![42]], 1, author);
+		assert_eq!(
+			VotingRule::<Block, _>::restrict_vote(&rule, &blockchain, &headers[3], &headers[10], &headers[10]),
+			Some((headers[3].hash(), 3)),
+		);
+	}
+
+	#[test]
+	fn config_is_decoded_and_merged() {
+		let config: VotingRulesConfig = serde_json::from_str(
+			r#"{"pauseAt":100,"trustedConfirmations":{"authors":["0x0102"],"confirmations":1}}"#
+		).unwrap();
+		assert_eq!(config.pause_at, Some(100));
+		assert_eq!(config.trusted_confirmations.as_ref().unwrap().authors, vec![Bytes(vec![1, 2])]);
+
+		let overrides = VotingRulesConfig { pause_at: Some(50), lag_by_millis: Some(1000), ..Default::default() };
+		let merged = config.merge(overrides);
+		assert_eq!(merged.pause_at, Some(50));
+		assert_eq!(merged.lag_by_millis, Some(1000));
+		assert!(merged.trusted_confirmations.is_some());
+	}
+}
@@ -0,0 +1,667 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bandwidth accounting and shaping.
+//!
+//! This module contains four building blocks:
+//!
+//! - [`RateLimiter`], a token bucket that can be shared between multiple connections.
+//! - [`Throttled`], a wrapper around a connection that only reads and writes as fast as the
+//!   rate limiters it holds allow, and that reports the bytes that went through it.
+//! - [`CountedMuxer`], a wrapper around a stream muxer that reports the bytes exchanged over the
+//!   Kademlia substreams.
+//! - [`BandwidthCounters`], which accumulates the number of bytes and messages exchanged per
+//!   protocol. The Substrate protocol reports every message it sends or receives, the muxer
+//!   reports the Kademlia traffic and the transport reports the total; the difference is
+//!   accounted as [`OTHER`].
+
+use futures::{prelude::*, try_ready};
+use futures03::{compat::Compat, TryFutureExt as _};
+use futures_timer::Delay;
+use libp2p::core::muxing::StreamMuxer;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sp_runtime::ConsensusEngineId;
+use std::{cmp, collections::HashMap, io, sync::Arc, time::{Duration, Instant}};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Block requests and responses, block announces and status messages.
+pub const SYNC: &str = "sync";
+/// Requests and responses of the light client protocol, including finality proofs.
+pub const LIGHT: &str = "light";
+/// Transactions propagation.
+pub const TRANSACTIONS: &str = "transactions";
+/// Messages of the network specialization.
+pub const CHAIN_SPECIFIC: &str = "chain-specific";
+/// Kademlia requests and responses.
+pub const KADEMLIA: &str = "kademlia";
+/// Everything that isn't reported by the Substrate protocol or attributed to Kademlia: identify,
+/// ping, protocol negotiation and the framing overhead of the encryption and the multiplexers.
+pub const OTHER: &str = "other";
+
+/// Returns the name under which the gossip traffic of the given consensus engine is accounted.
+pub fn gossip(engine_id: &ConsensusEngineId) -> String {
+	format!("gossip/{}", String::from_utf8_lossy(&engine_id[..]))
+}
+
+/// Amount of traffic exchanged over a protocol since the network started.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolTraffic {
+	/// Number of bytes received.
+	pub bytes_in: u64,
+	/// Number of bytes sent.
+	pub bytes_out: u64,
+	/// Number of messages received. Always 0 for [`KADEMLIA`] and [`OTHER`].
+	pub messages_in: u64,
+	/// Number of messages sent. Always 0 for [`KADEMLIA`] and [`OTHER`].
+	pub messages_out: u64,
+}
+
+/// Per-protocol traffic counters, shared between the transport, the protocol and the service.
+#[derive(Debug, Default)]
+pub struct BandwidthCounters {
+	protocols: Mutex<HashMap<String, ProtocolTraffic>>,
+	total_in: AtomicU64,
+	total_out: AtomicU64,
+}
+
+impl BandwidthCounters {
+	/// Reports a message of `len` bytes received on `protocol`.
+	pub fn inbound(&self, protocol: &str, len: usize) {
+		let mut protocols = self.protocols.lock();
+		let entry = entry(&mut protocols, protocol);
+		entry.bytes_in += len as u64;
+		entry.messages_in += 1;
+	}
+
+	/// Reports a message of `len` bytes sent on `protocol`.
+	pub fn outbound(&self, protocol: &str, len: usize) {
+		let mut protocols = self.protocols.lock();
+		let entry = entry(&mut protocols, protocol);
+		entry.bytes_out += len as u64;
+		entry.messages_out += 1;
+	}
+
+	/// Returns the traffic accounted so far, per protocol.
+	///
+	/// The [`OTHER`] entry holds the part of the total transport traffic that hasn't been
+	/// attributed to any protocol.
+	pub fn snapshot(&self) -> HashMap<String, ProtocolTraffic> {
+		let mut protocols = self.protocols.lock().clone();
+		let (known_in, known_out) = protocols.values()
+			.fold((0u64, 0u64), |(i, o), t| (i.saturating_add(t.bytes_in), o.saturating_add(t.bytes_out)));
+		protocols.insert(OTHER.to_owned(), ProtocolTraffic {
+			bytes_in: self.total_in.load(Ordering::Relaxed).saturating_sub(known_in),
+			bytes_out: self.total_out.load(Ordering::Relaxed).saturating_sub(known_out),
+			messages_in: 0,
+			messages_out: 0,
+		});
+		protocols
+	}
+
+	/// Reports `len` bytes exchanged on `protocol` without delimiting messages.
+	fn substream_bytes(&self, protocol: &str, inbound: bool, len: u64) {
+		let mut protocols = self.protocols.lock();
+		let entry = entry(&mut protocols, protocol);
+		if inbound {
+			entry.bytes_in += len;
+		} else {
+			entry.bytes_out += len;
+		}
+	}
+
+	fn transport_inbound(&self, len: usize) {
+		self.total_in.fetch_add(len as u64, Ordering::Relaxed);
+	}
+
+	fn transport_outbound(&self, len: usize) {
+		self.total_out.fetch_add(len as u64, Ordering::Relaxed);
+	}
+}
+
+fn entry<'a>(protocols: &'a mut HashMap<String, ProtocolTraffic>, protocol: &str) -> &'a mut ProtocolTraffic {
+	if !protocols.contains_key(protocol) {
+		protocols.insert(protocol.to_owned(), ProtocolTraffic::default());
+	}
+	protocols.get_mut(protocol).expect("inserted above if missing; qed")
+}
+
+/// Source of the current time of the rate limiters.
+pub trait Clock: Send + Sync + std::fmt::Debug {
+	/// Returns the current instant.
+	fn now(&self) -> Instant;
+}
+
+/// The clock of the system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now(&self) -> Instant {
+		Instant::now()
+	}
+}
+
+/// Highest rate of a [`RateLimiter`], so that a bucket always fits an `i64`.
+const MAX_RATE: u64 = i64::max_value() as u64;
+
+/// Token bucket limiting the rate at which bytes go through.
+///
+/// The bucket holds at most one second worth of bytes. Consuming more than what is available is
+/// allowed and puts the bucket in debt, which has to be paid back before anything else goes
+/// through. Rates above `i64::max_value()` are clamped.
+#[derive(Debug)]
+pub struct RateLimiter {
+	bytes_per_sec: u64,
+	clock: Arc<dyn Clock>,
+	state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+	available: i64,
+	last_refill: Instant,
+}
+
+impl RateLimiter {
+	/// Creates a new limiter allowing `bytes_per_sec` bytes per second on average.
+	pub fn new(bytes_per_sec: u64) -> Self {
+		Self::with_clock(bytes_per_sec, Arc::new(SystemClock))
+	}
+
+	/// Creates a new limiter allowing `bytes_per_sec` bytes per second on average, as measured
+	/// by `clock`.
+	pub fn with_clock(bytes_per_sec: u64, clock: Arc<dyn Clock>) -> Self {
+		let bytes_per_sec = cmp::min(cmp::max(bytes_per_sec, 1), MAX_RATE);
+		let last_refill = clock.now();
+		RateLimiter {
+			bytes_per_sec,
+			clock,
+			state: Mutex::new(BucketState {
+				available: bytes_per_sec as i64,
+				last_refill,
+			}),
+		}
+	}
+
+	/// Returns the number of bytes that can go through right now.
+	pub fn available(&self) -> u64 {
+		let mut state = self.state.lock();
+		self.refill(&mut state);
+		cmp::max(state.available, 0) as u64
+	}
+
+	/// Removes `bytes` from the bucket.
+	pub fn consume(&self, bytes: u64) {
+		let mut state = self.state.lock();
+		self.refill(&mut state);
+		state.available = state.available.saturating_sub(cmp::min(bytes, MAX_RATE) as i64);
+	}
+
+	/// Returns how long to wait before at least one byte can go through.
+	pub fn wait_time(&self) -> Duration {
+		let mut state = self.state.lock();
+		self.refill(&mut state);
+		if state.available > 0 {
+			return Duration::from_secs(0)
+		}
+		let missing = (1i128 - i128::from(state.available)) as u64;
+		Duration::from_nanos(missing.saturating_mul(1_000_000_000) / self.bytes_per_sec)
+	}
+
+	fn refill(&self, state: &mut BucketState) {
+		let now = self.clock.now();
+		let elapsed = now.duration_since(state.last_refill);
+		let refill = elapsed.as_nanos().saturating_mul(u128::from(self.bytes_per_sec)) / 1_000_000_000;
+		let refill = cmp::min(refill, u128::from(MAX_RATE)) as i64;
+		if refill > 0 {
+			state.available = cmp::min(state.available.saturating_add(refill), self.bytes_per_sec as i64);
+			state.last_refill = now;
+		}
+	}
+}
+
+/// Limits applied to the connections created by the transport.
+#[derive(Debug, Clone)]
+pub struct ConnectionLimits {
+	/// Limiter shared by all inbound traffic, if any.
+	pub global_in: Option<Arc<RateLimiter>>,
+	/// Limiter shared by all outbound traffic, if any.
+	pub global_out: Option<Arc<RateLimiter>>,
+	/// Rate allowed for the inbound traffic of each individual peer.
+	pub peer_in: Option<u64>,
+	/// Rate allowed for the outbound traffic of each individual peer.
+	pub peer_out: Option<u64>,
+	/// Where the traffic going through the connections is reported.
+	pub counters: Arc<BandwidthCounters>,
+}
+
+/// Connection that only reads and writes as fast as its rate limiters allow.
+pub struct Throttled<T> {
+	inner: T,
+	inbound: Vec<Arc<RateLimiter>>,
+	outbound: Vec<Arc<RateLimiter>>,
+	read_delay: Option<Compat<Delay>>,
+	write_delay: Option<Compat<Delay>>,
+	counters: Arc<BandwidthCounters>,
+}
+
+impl<T> Throttled<T> {
+	/// Wraps around `inner`, applying the global limits and fresh per-peer limits.
+	pub fn new(inner: T, limits: &ConnectionLimits) -> Self {
+		let inbound = limits.global_in.iter().cloned()
+			.chain(limits.peer_in.map(|rate| Arc::new(RateLimiter::new(rate))))
+			.collect();
+		let outbound = limits.global_out.iter().cloned()
+			.chain(limits.peer_out.map(|rate| Arc::new(RateLimiter::new(rate))))
+			.collect();
+
+		Throttled {
+			inner,
+			inbound,
+			outbound,
+			read_delay: None,
+			write_delay: None,
+			counters: limits.counters.clone(),
+		}
+	}
+}
+
+/// Returns the number of bytes that the limiters allow to go through right now.
+///
+/// If nothing can go through, arms `delay` so that the current task is woken up once the
+/// limiters have refilled.
+fn poll_budget(
+	limiters: &[Arc<RateLimiter>],
+	delay: &mut Option<Compat<Delay>>,
+) -> Poll<usize, io::Error> {
+	loop {
+		if let Some(timer) = delay.as_mut() {
+			try_ready!(timer.poll());
+			*delay = None;
+		}
+
+		match limiters.iter().map(|l| l.available()).min() {
+			None => return Ok(Async::Ready(usize::max_value())),
+			Some(0) => {
+				let wait = limiters.iter().map(|l| l.wait_time()).max().unwrap_or_default();
+				*delay = Some(Delay::new(wait).compat());
+			}
+			Some(n) => return Ok(Async::Ready(cmp::min(n, usize::max_value() as u64) as usize)),
+		}
+	}
+}
+
+impl<T: io::Read> io::Read for Throttled<T> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let budget = match poll_budget(&self.inbound, &mut self.read_delay)? {
+			Async::Ready(budget) => budget,
+			Async::NotReady => return Err(io::ErrorKind::WouldBlock.into()),
+		};
+
+		let len = cmp::min(buf.len(), budget);
+		let num_read = self.inner.read(&mut buf[..len])?;
+		for limiter in &self.inbound {
+			limiter.consume(num_read as u64);
+		}
+		self.counters.transport_inbound(num_read);
+		Ok(num_read)
+	}
+}
+
+impl<T: AsyncRead> AsyncRead for Throttled<T> {
+	unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+		self.inner.prepare_uninitialized_buffer(buf)
+	}
+}
+
+impl<T: io::Write> io::Write for Throttled<T> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let budget = match poll_budget(&self.outbound, &mut self.write_delay)? {
+			Async::Ready(budget) => budget,
+			Async::NotReady => return Err(io::ErrorKind::WouldBlock.into()),
+		};
+
+		let len = cmp::min(buf.len(), budget);
+		let num_written = self.inner.write(&buf[..len])?;
+		for limiter in &self.outbound {
+			limiter.consume(num_written as u64);
+		}
+		self.counters.transport_outbound(num_written);
+		Ok(num_written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+impl<T: AsyncWrite> AsyncWrite for Throttled<T> {
+	fn shutdown(&mut self) -> Poll<(), io::Error> {
+		self.inner.shutdown()
+	}
+}
+
+/// Maximum number of bytes of a multistream-select proposal looked at to find the protocol of a
+/// substream.
+const MAX_PROPOSAL_LEN: usize = 256;
+
+/// The multistream-select header, sent by the dialer before proposing a protocol.
+const MULTISTREAM_HEADER: &[u8] = b"/multistream/1.0.0\n";
+
+/// Returns the category under which the traffic of a libp2p protocol is reported, if any.
+fn protocol_category(protocol: &[u8]) -> Option<&'static str> {
+	if protocol.ends_with(b"/kad/1.0.0") {
+		Some(KADEMLIA)
+	} else {
+		None
+	}
+}
+
+/// Reads an unsigned LEB128 length prefix from `data`.
+///
+/// Returns the length and the number of bytes of the prefix, or `None` if `data` is incomplete.
+fn read_length_prefix(data: &[u8]) -> Option<(usize, usize)> {
+	let mut len = 0usize;
+	for (i, byte) in data.iter().enumerate().take(4) {
+		len |= usize::from(byte & 0x7f) << (7 * i);
+		if byte & 0x80 == 0 {
+			return Some((len, i + 1))
+		}
+	}
+	None
+}
+
+/// Finds the protocol proposed by the dialer of a substream.
+///
+/// Returns `None` if more data is needed, `Some(None)` if the protocol is unknown and
+/// `Some(Some(category))` for the protocols whose traffic is reported.
+fn parse_proposal(proposal: &[u8]) -> Option<Option<&'static str>> {
+	let (header_len, prefix_len) = read_length_prefix(proposal)?;
+	let header = proposal.get(prefix_len..prefix_len + header_len)?;
+	if header != MULTISTREAM_HEADER {
+		return Some(None)
+	}
+
+	let rest = &proposal[prefix_len + header_len..];
+	let (protocol_len, prefix_len) = read_length_prefix(rest)?;
+	let protocol = rest.get(prefix_len..prefix_len + protocol_len)?;
+	let protocol = match protocol.split_last() {
+		Some((b'\n', protocol)) => protocol,
+		_ => protocol,
+	};
+	Some(protocol_category(protocol))
+}
+
+/// Attribution of the traffic of a substream.
+#[derive(Debug)]
+enum Attribution {
+	/// The protocol is not known yet. The bytes exchanged so far are kept to be reported once it
+	/// is known.
+	Sniffing { proposal: Vec<u8>, pending_in: u64, pending_out: u64 },
+	/// The traffic is reported under the given category, if any.
+	Known(Option<&'static str>),
+}
+
+/// Substream of a [`CountedMuxer`].
+pub struct CountedSubstream<S> {
+	inner: S,
+	/// Whether we opened the substream, i.e. whether we send the multistream-select proposal.
+	dialer: bool,
+	attribution: Attribution,
+}
+
+impl<S> CountedSubstream<S> {
+	fn new(inner: S, dialer: bool) -> Self {
+		CountedSubstream {
+			inner,
+			dialer,
+			attribution: Attribution::Sniffing { proposal: Vec::new(), pending_in: 0, pending_out: 0 },
+		}
+	}
+
+	/// Reports `data` going through the substream, in the given direction.
+	fn report(&mut self, counters: &BandwidthCounters, inbound: bool, data: &[u8]) {
+		let len = data.len() as u64;
+		let attribution = match self.attribution {
+			Attribution::Known(Some(category)) => {
+				counters.substream_bytes(category, inbound, len);
+				return
+			},
+			Attribution::Known(None) => return,
+			Attribution::Sniffing { ref mut proposal, ref mut pending_in, ref mut pending_out } => {
+				if inbound {
+					*pending_in += len;
+				} else {
+					*pending_out += len;
+				}
+
+				// The proposal is sent by the dialer: we write it on the substreams we open and
+				// read it on the ones opened by the remote.
+				if inbound == self.dialer {
+					return
+				}
+				let take = cmp::min(data.len(), MAX_PROPOSAL_LEN.saturating_sub(proposal.len()));
+				proposal.extend_from_slice(&data[..take]);
+
+				match parse_proposal(proposal) {
+					Some(category) => (category, *pending_in, *pending_out),
+					None if proposal.len() >= MAX_PROPOSAL_LEN => (None, 0, 0),
+					None => return,
+				}
+			},
+		};
+
+		let (category, pending_in, pending_out) = attribution;
+		if let Some(category) = category {
+			counters.substream_bytes(category, true, pending_in);
+			counters.substream_bytes(category, false, pending_out);
+		}
+		self.attribution = Attribution::Known(category);
+	}
+}
+
+/// Stream muxer reporting the traffic of the Kademlia substreams to [`BandwidthCounters`].
+///
+/// The protocol of a substream is found by looking at the multistream-select proposal of its
+/// dialer. The bytes exchanged before the protocol is known are reported once it is.
+pub struct CountedMuxer<M> {
+	inner: M,
+	counters: Arc<BandwidthCounters>,
+}
+
+impl<M> CountedMuxer<M> {
+	/// Wraps around `inner`, reporting to `counters`.
+	pub fn new(inner: M, counters: Arc<BandwidthCounters>) -> Self {
+		CountedMuxer { inner, counters }
+	}
+}
+
+impl<M: StreamMuxer> StreamMuxer for CountedMuxer<M> {
+	type Substream = CountedSubstream<M::Substream>;
+	type OutboundSubstream = M::OutboundSubstream;
+	type Error = M::Error;
+
+	fn poll_inbound(&self) -> Poll<Self::Substream, Self::Error> {
+		let substream = try_ready!(self.inner.poll_inbound());
+		Ok(Async::Ready(CountedSubstream::new(substream, false)))
+	}
+
+	fn open_outbound(&self) -> Self::OutboundSubstream {
+		self.inner.open_outbound()
+	}
+
+	fn poll_outbound(&self, s: &mut Self::OutboundSubstream) -> Poll<Self::Substream, Self::Error> {
+		let substream = try_ready!(self.inner.poll_outbound(s));
+		Ok(Async::Ready(CountedSubstream::new(substream, true)))
+	}
+
+	fn destroy_outbound(&self, s: Self::OutboundSubstream) {
+		self.inner.destroy_outbound(s)
+	}
+
+	unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+		self.inner.prepare_uninitialized_buffer(buf)
+	}
+
+	fn read_substream(&self, s: &mut Self::Substream, buf: &mut [u8]) -> Poll<usize, Self::Error> {
+		let num_read = try_ready!(self.inner.read_substream(&mut s.inner, buf));
+		s.report(&self.counters, true, &buf[..num_read]);
+		Ok(Async::Ready(num_read))
+	}
+
+	fn write_substream(&self, s: &mut Self::Substream, buf: &[u8]) -> Poll<usize, Self::Error> {
+		let num_written = try_ready!(self.inner.write_substream(&mut s.inner, buf));
+		s.report(&self.counters, false, &buf[..num_written]);
+		Ok(Async::Ready(num_written))
+	}
+
+	fn flush_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+		self.inner.flush_substream(&mut s.inner)
+	}
+
+	fn shutdown_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+		self.inner.shutdown_substream(&mut s.inner)
+	}
+
+	fn destroy_substream(&self, s: Self::Substream) {
+		self.inner.destroy_substream(s.inner)
+	}
+
+	fn is_remote_acknowledged(&self) -> bool {
+		self.inner.is_remote_acknowledged()
+	}
+
+	fn close(&self) -> Poll<(), Self::Error> {
+		self.inner.close()
+	}
+
+	fn flush_all(&self) -> Poll<(), Self::Error> {
+		self.inner.flush_all()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rate_limiter_goes_into_debt() {
+		let limiter = RateLimiter::new(1000);
+		assert_eq!(limiter.available(), 1000);
+		assert_eq!(limiter.wait_time(), Duration::from_secs(0));
+
+		limiter.consume(1500);
+		assert_eq!(limiter.available(), 0);
+		assert!(limiter.wait_time() > Duration::from_millis(400));
+	}
+
+	#[derive(Debug)]
+	struct ManualClock(Mutex<Instant>);
+
+	impl ManualClock {
+		fn advance(&self, by: Duration) {
+			*self.0.lock() += by;
+		}
+	}
+
+	impl Clock for ManualClock {
+		fn now(&self) -> Instant {
+			*self.0.lock()
+		}
+	}
+
+	#[test]
+	fn rate_limiter_refills_up_to_capacity() {
+		let clock = Arc::new(ManualClock(Mutex::new(Instant::now())));
+		let limiter = RateLimiter::with_clock(1_000_000, clock.clone());
+		limiter.consume(1_000_000);
+		assert_eq!(limiter.available(), 0);
+
+		clock.advance(Duration::from_millis(20));
+		assert_eq!(limiter.available(), 20_000);
+
+		clock.advance(Duration::from_secs(2));
+		assert_eq!(limiter.available(), 1_000_000);
+	}
+
+	#[test]
+	fn rate_limiter_clamps_huge_rates() {
+		let clock = Arc::new(ManualClock(Mutex::new(Instant::now())));
+		let limiter = RateLimiter::with_clock(u64::max_value(), clock.clone());
+		assert_eq!(limiter.available(), MAX_RATE);
+
+		limiter.consume(u64::max_value());
+		limiter.consume(u64::max_value());
+		assert_eq!(limiter.available(), 0);
+		assert!(limiter.wait_time() > Duration::from_secs(0));
+
+		clock.advance(Duration::from_secs(1_000_000_000));
+		assert_eq!(limiter.available(), MAX_RATE);
+	}
+
+	fn length_prefixed(message: &[u8]) -> Vec<u8> {
+		let mut out = vec![message.len() as u8];
+		out.extend_from_slice(message);
+		out
+	}
+
+	#[test]
+	fn kademlia_substreams_are_attributed() {
+		let counters = BandwidthCounters::default();
+		let mut proposal = length_prefixed(MULTISTREAM_HEADER);
+		proposal.extend(length_prefixed(b"/substrate/dot/kad/1.0.0\n"));
+
+		// We dial: the proposal is written, split over two writes.
+		let mut dialed = CountedSubstream::new((), true);
+		dialed.report(&counters, false, &proposal[..5]);
+		dialed.report(&counters, true, &[0; 3]);
+		dialed.report(&counters, false, &proposal[5..]);
+		dialed.report(&counters, true, &[0; 10]);
+
+		// The remote dials an unrelated protocol.
+		let mut other = CountedSubstream::new((), false);
+		let mut ping = length_prefixed(MULTISTREAM_HEADER);
+		ping.extend(length_prefixed(b"/ipfs/ping/1.0.0\n"));
+		other.report(&counters, true, &ping);
+		other.report(&counters, false, &[0; 32]);
+
+		let snapshot = counters.snapshot();
+		assert_eq!(snapshot[KADEMLIA], ProtocolTraffic {
+			bytes_in: 13, bytes_out: proposal.len() as u64, messages_in: 0, messages_out: 0,
+		});
+		assert_eq!(snapshot.len(), 2);
+	}
+
+	#[test]
+	fn unattributed_traffic_is_other() {
+		let counters = BandwidthCounters::default();
+		counters.transport_inbound(100);
+		counters.transport_outbound(50);
+		counters.inbound(SYNC, 60);
+		counters.outbound(&gossip(b"FRNK"), 20);
+
+		let snapshot = counters.snapshot();
+		assert_eq!(snapshot[SYNC], ProtocolTraffic {
+			bytes_in: 60, bytes_out: 0, messages_in: 1, messages_out: 0,
+		});
+		assert_eq!(snapshot["gossip/FRNK"].bytes_out, 20);
+		assert_eq!(snapshot[OTHER].bytes_in, 40);
+		assert_eq!(snapshot[OTHER].bytes_out, 30);
+	}
+}
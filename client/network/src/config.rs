@@ -265,6 +265,8 @@ pub struct NetworkConfiguration {
 	pub transport: TransportConfig,
 	/// Maximum number of peers to ask the same blocks in parallel.
 	pub max_parallel_downloads: u32,
+	/// Limits on the bandwidth used by the node.
+	pub bandwidth: BandwidthConfig,
 }
 
 impl Default for NetworkConfiguration {
@@ -289,6 +291,7 @@ impl Default for NetworkConfiguration {
 				wasm_external_transport: None,
 			},
 			max_parallel_downloads: 5,
+			bandwidth: BandwidthConfig::default(),
 		}
 	}
 }
@@ -350,6 +353,26 @@ pub enum TransportConfig {
 	MemoryOnly,
 }
 
+/// Limits on the bandwidth used by the node, in bytes per second.
+///
+/// `None` means that the corresponding traffic is not limited.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BandwidthConfig {
+	/// Maximum rate at which data is received, all peers combined.
+	pub max_download_rate: Option<u64>,
+	/// Maximum rate at which data is sent, all peers combined.
+	pub max_upload_rate: Option<u64>,
+	/// Maximum rate at which data is received from an individual peer.
+	pub max_download_rate_per_peer: Option<u64>,
+	/// Maximum rate at which data is sent to an individual peer.
+	pub max_upload_rate_per_peer: Option<u64>,
+	/// Maximum rate at which blocks are downloaded by the sync.
+	///
+	/// Once this budget is spent, no new block request is sent until it refills, which leaves
+	/// the rest of the download capacity to consensus gossip.
+	pub max_sync_download_rate: Option<u64>,
+}
+
 /// The policy for connections to non-reserved peers.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum NonReservedPeerMode {
@@ -168,6 +168,7 @@
 //! More precise usage details are still being worked on and will likely change in the future.
 //!
 
+mod bandwidth;
 mod behaviour;
 mod chain;
 mod debug_info;
@@ -186,6 +187,7 @@ pub use service::{
 	NetworkService, NetworkWorker, TransactionPool, ExHashT, ReportHandle,
 	NetworkStateInfo,
 };
+pub use bandwidth::ProtocolTraffic;
 pub use protocol::{PeerInfo, Context, ProtocolConfig, message, specialization};
 pub use protocol::event::{Event, DhtEvent};
 pub use protocol::sync::SyncState;
@@ -239,6 +241,9 @@ pub struct NetworkState {
 	pub average_download_per_sec: u64,
 	/// Uploaded bytes per second averaged over the past few seconds.
 	pub average_upload_per_sec: u64,
+	/// Traffic exchanged since the network started, per protocol.
+	#[serde(default)]
+	pub protocol_traffic: HashMap<String, ProtocolTraffic>,
 	/// State of the peerset manager.
 	pub peerset: serde_json::Value,
 }
@@ -15,6 +15,7 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{DiscoveryNetBehaviour, config::ProtocolId};
+use crate::bandwidth::{self, BandwidthCounters, RateLimiter};
 use legacy_proto::{LegacyProto, LegacyProtoOut};
 use crate::utils::interval;
 use bytes::{Bytes, BytesMut};
@@ -148,6 +149,9 @@ pub struct Protocol<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 	behaviour: LegacyProto<Substream<StreamMuxerBox>>,
 	/// List of notification protocols that have been registered.
 	registered_notif_protocols: HashSet<ConsensusEngineId>,
+	/// Budget for downloading blocks, if limited. New block requests are held back while it is
+	/// exhausted.
+	sync_limiter: Option<RateLimiter>,
 }
 
 #[derive(Default)]
@@ -196,6 +200,14 @@ pub struct PeerInfo<B: BlockT> {
 struct LightDispatchIn<'a> {
 	behaviour: &'a mut LegacyProto<Substream<StreamMuxerBox>>,
 	peerset: sc_peerset::PeersetHandle,
+	traffic: &'a BandwidthCounters,
+}
+
+impl<'a> LightDispatchIn<'a> {
+	fn send_packet(&mut self, who: &PeerId, message: Vec<u8>) {
+		self.traffic.outbound(bandwidth::LIGHT, message.len());
+		self.behaviour.send_packet(who, message)
+	}
 }
 
 impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
@@ -213,7 +225,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			block,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		self.send_packet(who, message.encode())
 	}
 
 	fn send_read_request(
@@ -229,7 +241,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			keys,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		self.send_packet(who, message.encode())
 	}
 
 	fn send_read_child_request(
@@ -251,7 +263,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			keys,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		self.send_packet(who, message.encode())
 	}
 
 	fn send_call_request(
@@ -269,7 +281,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			data,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		self.send_packet(who, message.encode())
 	}
 
 	fn send_changes_request(
@@ -293,7 +305,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			key,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		self.send_packet(who, message.encode())
 	}
 
 	fn send_body_request(
@@ -315,7 +327,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			max,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		self.send_packet(who, message.encode())
 	}
 }
 
@@ -372,6 +384,7 @@ impl<'a, B: BlockT + 'a, H: ExHashT + 'a> Context<B> for ProtocolContext<'a, B,
 					send_message::<B> (
 						self.behaviour,
 						&mut self.context_data.stats,
+						&self.context_data.traffic,
 						&who,
 						GenericMessage::ConsensusBatch(std::mem::replace(&mut batch, Vec::new())),
 					)
@@ -383,6 +396,7 @@ impl<'a, B: BlockT + 'a, H: ExHashT + 'a> Context<B> for ProtocolContext<'a, B,
 				send_message::<B> (
 					self.behaviour,
 					&mut self.context_data.stats,
+					&self.context_data.traffic,
 					&who,
 					GenericMessage::Consensus(message)
 				)
@@ -394,6 +408,7 @@ impl<'a, B: BlockT + 'a, H: ExHashT + 'a> Context<B> for ProtocolContext<'a, B,
 		send_message::<B> (
 			self.behaviour,
 			&mut self.context_data.stats,
+			&self.context_data.traffic,
 			&who,
 			GenericMessage::ChainSpecific(message)
 		)
@@ -405,6 +420,8 @@ struct ContextData<B: BlockT, H: ExHashT> {
 	// All connected peers
 	peers: HashMap<PeerId, Peer<B, H>>,
 	stats: HashMap<&'static str, PacketStats>,
+	/// Traffic per protocol, shared with the transport and the service.
+	traffic: Arc<BandwidthCounters>,
 	pub chain: Arc<dyn Client<B>>,
 }
 
@@ -415,6 +432,8 @@ pub struct ProtocolConfig {
 	pub roles: Roles,
 	/// Maximum number of peers to ask the same blocks in parallel.
 	pub max_parallel_downloads: u32,
+	/// Maximum rate, in bytes per second, at which blocks are downloaded. `None` if unlimited.
+	pub max_sync_download_rate: Option<u64>,
 }
 
 impl Default for ProtocolConfig {
@@ -422,6 +441,7 @@ impl Default for ProtocolConfig {
 		ProtocolConfig {
 			roles: Roles::FULL,
 			max_parallel_downloads: 5,
+			max_sync_download_rate: None,
 		}
 	}
 }
@@ -438,7 +458,8 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		finality_proof_request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
 		protocol_id: ProtocolId,
 		peerset_config: sc_peerset::PeersetConfig,
-		block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>
+		block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>,
+		traffic: Arc<BandwidthCounters>,
 	) -> error::Result<(Protocol<B, S, H>, sc_peerset::PeersetHandle)> {
 		let info = chain.info();
 		let sync = ChainSync::new(
@@ -463,6 +484,8 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		let versions = &((MIN_VERSION as u8)..=(CURRENT_VERSION as u8)).collect::<Vec<u8>>();
 		let behaviour = LegacyProto::new(protocol_id, versions, peerset);
 
+		let sync_limiter = config.max_sync_download_rate.map(RateLimiter::new);
+
 		let protocol = Protocol {
 			tick_timeout: Box::new(interval(TICK_TIMEOUT).map(|v| Ok::<_, ()>(v)).compat()),
 			propagate_timeout: Box::new(interval(PROPAGATE_TIMEOUT).map(|v| Ok::<_, ()>(v)).compat()),
@@ -470,8 +493,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			context_data: ContextData {
 				peers: HashMap::new(),
 				stats: HashMap::new(),
+				traffic,
 				chain,
 			},
+			sync_limiter,
 			light_dispatch: LightDispatch::new(checker),
 			genesis_hash: info.genesis_hash,
 			sync,
@@ -554,6 +579,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.light_dispatch.add_request(LightDispatchIn {
 			behaviour: &mut self.behaviour,
 			peerset: self.peerset_handle.clone(),
+			traffic: &self.context_data.traffic,
 		}, rq);
 	}
 
@@ -615,6 +641,11 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		let mut stats = self.context_data.stats.entry(message.id()).or_default();
 		stats.bytes_in += data.len() as u64;
 		stats.count_in += 1;
+		account_message(&self.context_data.traffic, &message, data.len(), TrafficDirection::Inbound);
+
+		if let (GenericMessage::BlockResponse(_), Some(limiter)) = (&message, &self.sync_limiter) {
+			limiter.consume(data.len() as u64);
+		}
 
 		match message {
 			GenericMessage::Status(s) => return self.on_status_message(who, s),
@@ -705,6 +736,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		send_request::<B, H>(
 			&mut self.behaviour,
 			&mut self.context_data.stats,
+			&self.context_data.traffic,
 			&mut self.context_data.peers,
 			who,
 			message,
@@ -715,6 +747,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		send_message::<B>(
 			&mut self.behaviour,
 			&mut self.context_data.stats,
+			&self.context_data.traffic,
 			who,
 			message,
 		);
@@ -916,6 +949,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.light_dispatch.maintain_peers(LightDispatchIn {
 			behaviour: &mut self.behaviour,
 			peerset: self.peerset_handle.clone(),
+			traffic: &self.context_data.traffic,
 		});
 	}
 
@@ -1058,6 +1092,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.light_dispatch.on_connect(LightDispatchIn {
 			behaviour: &mut self.behaviour,
 			peerset: self.peerset_handle.clone(),
+			traffic: &self.context_data.traffic,
 		}, who.clone(), status.roles, status.best_number);
 		if info.roles.is_full() {
 			match self.sync.new_peer(who.clone(), info.best_hash, info.best_number) {
@@ -1296,6 +1331,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.light_dispatch.update_best_number(LightDispatchIn {
 			behaviour: &mut self.behaviour,
 			peerset: self.peerset_handle.clone(),
+			traffic: &self.context_data.traffic,
 		}, who.clone(), *announce.header.number());
 
 		let is_their_best = match announce.state.unwrap_or(message::BlockState::Best) {
@@ -1501,6 +1537,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.light_dispatch.on_remote_call_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
 			peerset: self.peerset_handle.clone(),
+			traffic: &self.context_data.traffic,
 		}, who, response);
 	}
 
@@ -1608,6 +1645,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.light_dispatch.on_remote_read_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
 			peerset: self.peerset_handle.clone(),
+			traffic: &self.context_data.traffic,
 		}, who, response);
 	}
 
@@ -1649,6 +1687,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.light_dispatch.on_remote_header_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
 			peerset: self.peerset_handle.clone(),
+			traffic: &self.context_data.traffic,
 		}, who, response);
 	}
 
@@ -1725,6 +1764,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.light_dispatch.on_remote_changes_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
 			peerset: self.peerset_handle.clone(),
+			traffic: &self.context_data.traffic,
 		}, who, response);
 	}
 
@@ -1786,6 +1826,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.light_dispatch.on_remote_body_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
 			peerset: self.peerset_handle.clone(),
+			traffic: &self.context_data.traffic,
 		}, peer, response);
 	}
 
@@ -1824,6 +1865,7 @@ pub enum CustomMessageOutcome<B: BlockT> {
 fn send_request<B: BlockT, H: ExHashT>(
 	behaviour: &mut LegacyProto<Substream<StreamMuxerBox>>,
 	stats: &mut HashMap<&'static str, PacketStats>,
+	traffic: &BandwidthCounters,
 	peers: &mut HashMap<PeerId, Peer<B, H>>,
 	who: &PeerId,
 	mut message: Message<B>,
@@ -1839,12 +1881,13 @@ fn send_request<B: BlockT, H: ExHashT>(
 			peer.block_request = Some((time::Instant::now(), r.clone()));
 		}
 	}
-	send_message::<B>(behaviour, stats, who, message)
+	send_message::<B>(behaviour, stats, traffic, who, message)
 }
 
 fn send_message<B: BlockT>(
 	behaviour: &mut LegacyProto<Substream<StreamMuxerBox>>,
 	stats: &mut HashMap<&'static str, PacketStats>,
+	traffic: &BandwidthCounters,
 	who: &PeerId,
 	message: Message<B>,
 ) {
@@ -1852,9 +1895,57 @@ fn send_message<B: BlockT>(
 	let mut stats = stats.entry(message.id()).or_default();
 	stats.bytes_out += encoded.len() as u64;
 	stats.count_out += 1;
+	account_message(traffic, &message, encoded.len(), TrafficDirection::Outbound);
 	behaviour.send_packet(who, encoded);
 }
 
+/// Direction of a message, for traffic accounting purposes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TrafficDirection {
+	Inbound,
+	Outbound,
+}
+
+/// Reports a message of `len` bytes under the protocol it belongs to.
+///
+/// The messages of a consensus batch are reported individually, under the gossip protocol of
+/// their respective engine.
+fn account_message<B: BlockT>(
+	traffic: &BandwidthCounters,
+	message: &Message<B>,
+	len: usize,
+	direction: TrafficDirection,
+) {
+	let report = |protocol: &str, len: usize| match direction {
+		TrafficDirection::Inbound => traffic.inbound(protocol, len),
+		TrafficDirection::Outbound => traffic.outbound(protocol, len),
+	};
+
+	match message {
+		GenericMessage::Status(_) |
+		GenericMessage::BlockRequest(_) |
+		GenericMessage::BlockResponse(_) |
+		GenericMessage::BlockAnnounce(_) => report(bandwidth::SYNC, len),
+		GenericMessage::Transactions(_) => report(bandwidth::TRANSACTIONS, len),
+		GenericMessage::RemoteCallRequest(_) |
+		GenericMessage::RemoteCallResponse(_) |
+		GenericMessage::RemoteReadRequest(_) |
+		GenericMessage::RemoteReadResponse(_) |
+		GenericMessage::RemoteHeaderRequest(_) |
+		GenericMessage::RemoteHeaderResponse(_) |
+		GenericMessage::RemoteChangesRequest(_) |
+		GenericMessage::RemoteChangesResponse(_) |
+		GenericMessage::RemoteReadChildRequest(_) |
+		GenericMessage::FinalityProofRequest(_) |
+		GenericMessage::FinalityProofResponse(_) => report(bandwidth::LIGHT, len),
+		GenericMessage::Consensus(msg) => report(&bandwidth::gossip(&msg.engine_id), len),
+		GenericMessage::ConsensusBatch(messages) => for msg in messages {
+			report(&bandwidth::gossip(&msg.engine_id), msg.data.len());
+		},
+		GenericMessage::ChainSpecific(_) => report(bandwidth::CHAIN_SPECIFIC, len),
+	}
+}
+
 impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviour for
 Protocol<B, S, H> {
 	type ProtocolsHandler = <LegacyProto<Substream<StreamMuxerBox>> as NetworkBehaviour>::ProtocolsHandler;
@@ -1901,19 +1992,25 @@ Protocol<B, S, H> {
 			self.propagate_extrinsics();
 		}
 
-		for (id, r) in self.sync.block_requests() {
-			send_request(
-				&mut self.behaviour,
-				&mut self.context_data.stats,
-				&mut self.context_data.peers,
-				&id,
-				GenericMessage::BlockRequest(r)
-			)
+		// Hold back new block requests while the download budget of the sync is exhausted.
+		let sync_throttled = self.sync_limiter.as_ref().map_or(false, |l| l.available() == 0);
+		if !sync_throttled {
+			for (id, r) in self.sync.block_requests() {
+				send_request(
+					&mut self.behaviour,
+					&mut self.context_data.stats,
+					&self.context_data.traffic,
+					&mut self.context_data.peers,
+					&id,
+					GenericMessage::BlockRequest(r)
+				)
+			}
 		}
 		for (id, r) in self.sync.justification_requests() {
 			send_request(
 				&mut self.behaviour,
 				&mut self.context_data.stats,
+				&self.context_data.traffic,
 				&mut self.context_data.peers,
 				&id,
 				GenericMessage::BlockRequest(r)
@@ -1923,6 +2020,7 @@ Protocol<B, S, H> {
 			send_request(
 				&mut self.behaviour,
 				&mut self.context_data.stats,
+				&self.context_data.traffic,
 				&mut self.context_data.peers,
 				&id,
 				GenericMessage::FinalityProofRequest(r))
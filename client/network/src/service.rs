@@ -42,6 +42,7 @@ use sp_runtime::{traits::{Block as BlockT, NumberFor}, ConsensusEngineId};
 
 use crate::{behaviour::{Behaviour, BehaviourOut}, config::{parse_str_addr, parse_addr}};
 use crate::{NetworkState, NetworkStateNotConnectedPeer, NetworkStatePeer};
+use crate::bandwidth::{BandwidthCounters, ProtocolTraffic};
 use crate::{transport, config::NonReservedPeerMode, ReputationChange};
 use crate::config::{Params, TransportConfig};
 use crate::error::Error;
@@ -110,6 +111,8 @@ pub struct NetworkService<B: BlockT + 'static, S: NetworkSpecialization<B>, H: E
 	local_peer_id: PeerId,
 	/// Bandwidth logging system. Can be queried to know the average bandwidth consumed.
 	bandwidth: Arc<transport::BandwidthSinks>,
+	/// Traffic exchanged since the network started, per protocol.
+	protocol_traffic: Arc<BandwidthCounters>,
 	/// Peerset manager (PSM); manages the reputation of nodes and indicates the network which
 	/// nodes it should be connected to or not.
 	peerset: PeersetHandle,
@@ -192,10 +195,12 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 
 		let num_connected = Arc::new(AtomicUsize::new(0));
 		let is_major_syncing = Arc::new(AtomicBool::new(false));
+		let protocol_traffic = Arc::new(BandwidthCounters::default());
 		let (protocol, peerset_handle) = Protocol::new(
 			protocol::ProtocolConfig {
 				roles: params.roles,
 				max_parallel_downloads: params.network_config.max_parallel_downloads,
+				max_sync_download_rate: params.network_config.bandwidth.max_sync_download_rate,
 			},
 			params.chain,
 			params.on_demand.as_ref().map(|od| od.checker().clone())
@@ -206,7 +211,8 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			params.finality_proof_request_builder,
 			params.protocol_id,
 			peerset_config,
-			params.block_announce_validator
+			params.block_announce_validator,
+			protocol_traffic.clone(),
 		)?;
 
 		// Build the swarm.
@@ -236,7 +242,13 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 					TransportConfig::Normal { wasm_external_transport, .. } =>
 						(false, wasm_external_transport)
 				};
-				transport::build_transport(local_identity, config_mem, config_wasm)
+				transport::build_transport(
+					local_identity,
+					config_mem,
+					config_wasm,
+					&params.network_config.bandwidth,
+					protocol_traffic.clone(),
+				)
 			};
 			(Swarm::<B, S, H>::new(transport, behaviour, local_peer_id.clone()), bandwidth)
 		};
@@ -257,6 +269,7 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 
 		let service = Arc::new(NetworkService {
 			bandwidth,
+			protocol_traffic,
 			external_addresses: external_addresses.clone(),
 			num_connected: num_connected.clone(),
 			is_major_syncing: is_major_syncing.clone(),
@@ -289,6 +302,11 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 		self.service.bandwidth.average_upload_per_sec()
 	}
 
+	/// Returns the traffic exchanged since the network started, per protocol.
+	pub fn protocol_traffic(&self) -> HashMap<String, ProtocolTraffic> {
+		self.service.protocol_traffic()
+	}
+
 	/// Returns the number of peers we're connected to.
 	pub fn num_connected_peers(&self) -> usize {
 		self.network_service.user_protocol().num_connected_peers()
@@ -395,6 +413,7 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			external_addresses: Swarm::<B, S, H>::external_addresses(&swarm).cloned().collect(),
 			average_download_per_sec: self.service.bandwidth.average_download_per_sec(),
 			average_upload_per_sec: self.service.bandwidth.average_upload_per_sec(),
+			protocol_traffic: self.service.protocol_traffic(),
 			connected_peers,
 			not_connected_peers,
 			peerset: swarm.user_protocol_mut().peerset_debug_info(),
@@ -524,6 +543,13 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 		self.is_major_syncing.load(Ordering::Relaxed)
 	}
 
+	/// Returns the traffic exchanged since the network started, per protocol.
+	///
+	/// Gossip traffic is reported per consensus engine, under `gossip/<engine id>`.
+	pub fn protocol_traffic(&self) -> HashMap<String, ProtocolTraffic> {
+		self.protocol_traffic.snapshot()
+	}
+
 	/// Start getting a value from the DHT.
 	///
 	/// This will generate either a `ValueFound` or a `ValueNotFound` event and pass it as an
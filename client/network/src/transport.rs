@@ -25,6 +25,8 @@ use libp2p::{tcp, dns, websocket, noise};
 use libp2p::core::{either::EitherError, either::EitherOutput};
 use libp2p::core::{self, upgrade, transport::boxed::Boxed, transport::OptionalTransport, muxing::StreamMuxerBox};
 use std::{io, sync::Arc, time::Duration, usize};
+use crate::bandwidth::{BandwidthCounters, ConnectionLimits, CountedMuxer, RateLimiter, Throttled};
+use crate::config::BandwidthConfig;
 
 pub use self::bandwidth::BandwidthSinks;
 
@@ -33,12 +35,17 @@ pub use self::bandwidth::BandwidthSinks;
 /// If `memory_only` is true, then only communication within the same process are allowed. Only
 /// addresses with the format `/memory/...` are allowed.
 ///
+/// The connections are throttled according to `bandwidth_config`, and the traffic going through
+/// them is reported to `counters`.
+///
 /// Returns a `BandwidthSinks` object that allows querying the average bandwidth produced by all
 /// the connections spawned with this transport.
 pub fn build_transport(
 	keypair: identity::Keypair,
 	memory_only: bool,
-	wasm_external_transport: Option<wasm_ext::ExtTransport>
+	wasm_external_transport: Option<wasm_ext::ExtTransport>,
+	bandwidth_config: &BandwidthConfig,
+	counters: Arc<BandwidthCounters>,
 ) -> (Boxed<(PeerId, StreamMuxerBox), io::Error>, Arc<bandwidth::BandwidthSinks>) {
 	// Build configuration objects for encryption mechanisms.
 	#[cfg(not(target_os = "unknown"))]
@@ -60,6 +67,16 @@ pub fn build_transport(
 	mplex_config.max_buffer_len(usize::MAX);
 	let yamux_config = yamux::Config::default();
 
+	// Build the limits shared by all the connections. Per-peer limiters are created for each
+	// connection once the remote is known.
+	let limits = ConnectionLimits {
+		global_in: bandwidth_config.max_download_rate.map(|rate| Arc::new(RateLimiter::new(rate))),
+		global_out: bandwidth_config.max_upload_rate.map(|rate| Arc::new(RateLimiter::new(rate))),
+		peer_in: bandwidth_config.max_download_rate_per_peer,
+		peer_out: bandwidth_config.max_upload_rate_per_peer,
+		counters: counters.clone(),
+	};
+
 	// Build the base layer of the transport.
 	let transport = if let Some(t) = wasm_external_transport {
 		OptionalTransport::some(t)
@@ -113,6 +130,9 @@ pub fn build_transport(
 			.and_then(|(id, stream)| Ok((stream, id)))
 	});
 
+	// Throttling
+	let transport = transport.map(move |(stream, peer_id), _| (Throttled::new(stream, &limits), peer_id));
+
 	// Multiplexing
 	let transport = transport.and_then(move |(stream, peer_id), endpoint| {
 			let peer_id2 = peer_id.clone();
@@ -121,7 +141,7 @@ pub fn build_transport(
 				.map_outbound(move |muxer| (peer_id2, muxer));
 
 			core::upgrade::apply(stream, upgrade, endpoint, upgrade::Version::V1)
-				.map(|(id, muxer)| (id, core::muxing::StreamMuxerBox::new(muxer)))
+				.map(move |(id, muxer)| (id, StreamMuxerBox::new(CountedMuxer::new(muxer, counters))))
 		})
 
 		.timeout(Duration::from_secs(20))
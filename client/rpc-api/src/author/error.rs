@@ -90,6 +90,8 @@ const POOL_CYCLE_DETECTED: i64 = POOL_INVALID_TX + 5;
 const POOL_IMMEDIATELY_DROPPED: i64 = POOL_INVALID_TX + 6;
 /// The key type crypto is not known.
 const UNSUPPORTED_KEY_TYPE: i64 = POOL_INVALID_TX + 7;
+/// The sender already has too many transactions in the pool.
+const POOL_SENDER_LIMIT_REACHED: i64 = POOL_INVALID_TX + 8;
 
 impl From<Error> for rpc::Error {
 	fn from(e: Error) -> Self {
@@ -141,6 +143,15 @@ impl From<Error> for rpc::Error {
 				message: "Immediately Dropped".into(),
 				data: Some("The transaction couldn't enter the pool because of the limit".into()),
 			},
+			Error::Pool(PoolError::TooManyTransactionsFromSender { limit }) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(POOL_SENDER_LIMIT_REACHED),
+				message: "Too Many Transactions From Sender".into(),
+				data: Some(format!(
+					"The sender already has {} transactions in the pool. Wait for some of them to be \
+					included or replace one of them.",
+					limit,
+				).into()),
+			},
 			Error::UnsupportedKeyType => rpc::Error {
 				code: rpc::ErrorCode::ServerError(UNSUPPORTED_KEY_TYPE),
 				message: "Unknown key type crypto" .into(),
@@ -41,6 +41,21 @@ pub enum Error {
 		/// Details of the error message.
 		details: String,
 	},
+	/// Requested page size exceeds the maximal allowed one.
+	#[display(fmt = "Requested count ({}) is greater than allowed ({})", value, max)]
+	InvalidCount {
+		/// Provided value
+		value: u32,
+		/// Maximal allowed value
+		max: u32,
+	},
+	/// Storage read proof doesn't prove the requested entries.
+	#[display(fmt = "Invalid read proof: {}", _0)]
+	InvalidReadProof(String),
+	/// The runtime call exceeded its deadline or fuel budget.
+	#[display(fmt = "Call interrupted: {}", _0)]
+	#[from(ignore)]
+	CallLimitExceeded(String),
 }
 
 impl std::error::Error for Error {
@@ -63,6 +78,21 @@ impl From<Error> for rpc::Error {
 				message: format!("{}", e),
 				data: None,
 			},
+			Error::InvalidReadProof(_) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 2),
+				message: format!("{}", e),
+				data: None,
+			},
+			Error::InvalidCount { .. } => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 3),
+				message: format!("{}", e),
+				data: None,
+			},
+			Error::CallLimitExceeded(_) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 4),
+				message: format!("{}", e),
+				data: None,
+			},
 			e => errors::internal(e),
 		}
 	}
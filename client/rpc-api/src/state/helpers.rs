@@ -0,0 +1,297 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Substrate state API helpers.
+
+use codec::Codec;
+use serde::{Serialize, Deserialize};
+use sp_core::{Bytes, Hasher, storage::{StorageKey, StorageData}};
+use sp_state_machine::{StorageProof, read_proof_check, read_child_proof_check};
+
+use super::error::{Error, Result};
+
+/// Storage read proof, as returned by `state_getReadProof` and `state_getChildReadProof`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadProof<Hash> {
+	/// Block hash used to generate the proof.
+	pub at: Hash,
+	/// Trie nodes proving the values of the requested storage entries.
+	pub proof: Vec<Bytes>,
+}
+
+/// Result of re-executing a block with `state_traceBlock`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTrace<Hash> {
+	/// Hash of the traced block.
+	pub block_hash: Hash,
+	/// Hash of the parent block, whose state the block was executed on.
+	pub parent_hash: Hash,
+	/// Targets used to filter the spans and events.
+	pub targets: String,
+	/// Spans emitted by the runtime, in the order they were closed.
+	pub spans: Vec<TraceSpan>,
+	/// Events emitted by the runtime, in the order they were emitted.
+	pub events: Vec<TraceEvent>,
+	/// Storage reads and writes performed by the runtime, in execution order.
+	pub storage: Vec<TraceStorageAccess>,
+	/// Execution error, if the block failed to execute.
+	pub error: Option<String>,
+}
+
+/// A span emitted by the runtime while executing a traced block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceSpan {
+	/// Id of the span, unique within the trace.
+	pub id: u64,
+	/// Id of the parent span, if any.
+	pub parent_id: Option<u64>,
+	/// Name of the span.
+	pub name: String,
+	/// Target of the span, usually the module path of the pallet.
+	pub target: String,
+	/// Level of the span.
+	pub level: String,
+	/// Values recorded with the span.
+	pub values: Vec<(String, String)>,
+	/// Time spent inside the span, in nanoseconds.
+	pub exec_time: u64,
+}
+
+/// An event emitted by the runtime while executing a traced block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceEvent {
+	/// Id of the span the event was emitted in, if any.
+	pub parent_id: Option<u64>,
+	/// Name of the event.
+	pub name: String,
+	/// Target of the event.
+	pub target: String,
+	/// Level of the event.
+	pub level: String,
+	/// Values recorded with the event.
+	pub values: Vec<(String, String)>,
+}
+
+/// A storage access performed by the runtime while executing a traced block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum TraceStorageAccess {
+	/// A value was read.
+	#[serde(rename_all = "camelCase")]
+	Read {
+		/// Child storage key, if the value belongs to a child trie.
+		child: Option<Bytes>,
+		/// Key of the value.
+		key: Bytes,
+		/// The value that was read.
+		value: Option<Bytes>,
+	},
+	/// The hash of a value was read.
+	#[serde(rename_all = "camelCase")]
+	ReadHash {
+		/// Child storage key, if the value belongs to a child trie.
+		child: Option<Bytes>,
+		/// Key of the value.
+		key: Bytes,
+		/// The hash that was read.
+		hash: Option<Bytes>,
+	},
+	/// The key following a key was read.
+	#[serde(rename_all = "camelCase")]
+	NextKey {
+		/// Child storage key, if the keys belong to a child trie.
+		child: Option<Bytes>,
+		/// The key whose next key was read.
+		key: Bytes,
+		/// The next key that was read.
+		next: Option<Bytes>,
+	},
+	/// A value was written, `None` meaning it was removed.
+	#[serde(rename_all = "camelCase")]
+	Write {
+		/// Child storage key, if the value belongs to a child trie.
+		child: Option<Bytes>,
+		/// Key of the value.
+		key: Bytes,
+		/// The new value.
+		value: Option<Bytes>,
+	},
+	/// All values under a prefix were removed.
+	#[serde(rename_all = "camelCase")]
+	ClearPrefix {
+		/// Child storage key, if the values belong to a child trie.
+		child: Option<Bytes>,
+		/// The removed prefix.
+		prefix: Bytes,
+	},
+	/// A child trie was removed.
+	#[serde(rename_all = "camelCase")]
+	KillChild {
+		/// Child storage key.
+		child: Bytes,
+	},
+}
+
+impl<Hash> ReadProof<Hash> {
+	fn into_storage_proof(self) -> StorageProof {
+		StorageProof::new(self.proof.into_iter().map(|node| node.0).collect())
+	}
+}
+
+/// Verify a proof returned by `state_getReadProof` against the state root of
+/// the block it was generated at.
+///
+/// Returns the proven value of every requested key, in the order of `keys`.
+/// A key that is absent from storage is proven to be `None`.
+pub fn verify_read_proof<H, Hash>(
+	state_root: H::Out,
+	proof: ReadProof<Hash>,
+	keys: &[StorageKey],
+) -> Result<Vec<(StorageKey, Option<StorageData>)>> where
+	H: Hasher,
+	H::Out: Ord + Codec,
+{
+	let mut values = read_proof_check::<H, _>(
+		state_root,
+		proof.into_storage_proof(),
+		keys.iter().map(|key| &key.0),
+	).map_err(|e| Error::InvalidReadProof(e.to_string()))?;
+
+	Ok(collect_proven_values(&mut values, keys))
+}
+
+/// Verify a proof returned by `state_getChildReadProof` against the state root
+/// of the block it was generated at.
+///
+/// Returns the proven value of every requested child storage key, in the order of `keys`.
+pub fn verify_child_read_proof<H, Hash>(
+	state_root: H::Out,
+	proof: ReadProof<Hash>,
+	child_storage_key: &StorageKey,
+	keys: &[StorageKey],
+) -> Result<Vec<(StorageKey, Option<StorageData>)>> where
+	H: Hasher,
+	H::Out: Ord + Codec,
+{
+	let mut values = read_child_proof_check::<H, _>(
+		state_root,
+		proof.into_storage_proof(),
+		&child_storage_key.0,
+		keys.iter().map(|key| &key.0),
+	).map_err(|e| Error::InvalidReadProof(e.to_string()))?;
+
+	Ok(collect_proven_values(&mut values, keys))
+}
+
+fn collect_proven_values(
+	values: &mut std::collections::HashMap<Vec<u8>, Option<Vec<u8>>>,
+	keys: &[StorageKey],
+) -> Vec<(StorageKey, Option<StorageData>)> {
+	keys.iter()
+		.map(|key| {
+			let value = values.get(&key.0).cloned().and_then(|value| value.map(StorageData));
+			(key.clone(), value)
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_matches::assert_matches;
+	use sp_core::{Blake2Hasher, H256};
+	use sp_state_machine::{Backend, backend::InMemory, prove_read};
+
+	// values shorter than a hash would be inlined in their parent node, and so proved along
+	// with any other key
+	fn value(byte: u8) -> Vec<u8> {
+		vec![byte; 64]
+	}
+
+	fn backend() -> InMemory<Blake2Hasher> {
+		vec![
+			(None, vec![(b"key1".to_vec(), Some(value(1)))]),
+			(None, vec![(b"key2".to_vec(), Some(value(2)))]),
+		].into()
+	}
+
+	fn read_proof(backend: InMemory<Blake2Hasher>, keys: &[&[u8]]) -> ReadProof<H256> {
+		let proof = prove_read(backend, keys).unwrap();
+		ReadProof {
+			at: Default::default(),
+			proof: proof.iter_nodes().map(Into::into).collect(),
+		}
+	}
+
+	#[test]
+	fn verifies_read_proof() {
+		// given
+		let backend = backend();
+		let root = backend.storage_root(std::iter::empty()).0;
+		let proof = read_proof(backend, &[b"key1", b"key3"]);
+
+		// when
+		let values = verify_read_proof::<Blake2Hasher, _>(
+			root,
+			proof,
+			&[StorageKey(b"key1".to_vec()), StorageKey(b"key3".to_vec())],
+		).unwrap();
+
+		// then
+		assert_eq!(values, vec![
+			(StorageKey(b"key1".to_vec()), Some(StorageData(value(1)))),
+			(StorageKey(b"key3".to_vec()), None),
+		]);
+	}
+
+	#[test]
+	fn rejects_read_proof_for_unproven_key() {
+		// given
+		let backend = backend();
+		let root = backend.storage_root(std::iter::empty()).0;
+		let proof = read_proof(backend, &[b"key1"]);
+
+		// when
+		let result = verify_read_proof::<Blake2Hasher, _>(
+			root,
+			proof,
+			&[StorageKey(b"key2".to_vec())],
+		);
+
+		// then
+		assert_matches!(result, Err(Error::InvalidReadProof(_)));
+	}
+
+	#[test]
+	fn rejects_read_proof_against_wrong_root() {
+		// given
+		let proof = read_proof(backend(), &[b"key1"]);
+
+		// when
+		let result = verify_read_proof::<Blake2Hasher, _>(
+			H256::repeat_byte(1),
+			proof,
+			&[StorageKey(b"key1".to_vec())],
+		);
+
+		// then
+		assert_matches!(result, Err(Error::InvalidReadProof(_)));
+	}
+}
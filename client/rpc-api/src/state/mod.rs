@@ -17,6 +17,7 @@
 //! Substrate state API.
 
 pub mod error;
+pub mod helpers;
 
 use jsonrpc_core::Result as RpcResult;
 use jsonrpc_core::futures::Future;
@@ -26,6 +27,7 @@ use sp_core::Bytes;
 use sp_core::storage::{StorageKey, StorageData, StorageChangeSet};
 use sp_version::RuntimeVersion;
 use self::error::FutureResult;
+use self::helpers::{BlockTrace, ReadProof};
 
 pub use self::gen_client::Client as StateClient;
 
@@ -43,6 +45,26 @@ pub trait StateApi<Hash> {
 	#[rpc(name = "state_getKeys")]
 	fn storage_keys(&self, prefix: StorageKey, hash: Option<Hash>) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns up to `count` keys with prefix, starting after `start_key` if given.
+	#[rpc(name = "state_getKeysPaged", alias("state_getKeysPagedAt"))]
+	fn storage_keys_paged(
+		&self,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<StorageKey>>;
+
+	/// Returns up to `count` key-value pairs with prefix, starting after `start_key` if given.
+	#[rpc(name = "state_getPairsPaged", alias("state_getPairsPagedAt"))]
+	fn storage_pairs_paged(
+		&self,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>>;
+
 	/// Returns a storage entry at a specific block's state.
 	#[rpc(name = "state_getStorage", alias("state_getStorageAt"))]
 	fn storage(&self, key: StorageKey, hash: Option<Hash>) -> FutureResult<Option<StorageData>>;
@@ -66,6 +88,34 @@ pub trait StateApi<Hash> {
 		hash: Option<Hash>
 	) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns up to `count` keys with prefix from a child storage, starting after
+	/// `start_key` if given.
+	#[rpc(name = "state_getChildKeysPaged")]
+	fn child_storage_keys_paged(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<StorageKey>>;
+
+	/// Returns up to `count` key-value pairs with prefix from a child storage, starting
+	/// after `start_key` if given.
+	#[rpc(name = "state_getChildPairsPaged")]
+	fn child_storage_pairs_paged(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>>;
+
 	/// Returns a child storage entry at a specific block's state.
 	#[rpc(name = "state_getChildStorage")]
 	fn child_storage(
@@ -119,6 +169,34 @@ pub trait StateApi<Hash> {
 		hash: Option<Hash>
 	) -> FutureResult<Vec<StorageChangeSet<Hash>>>;
 
+	/// Returns proof of storage entries at a specific block's state.
+	#[rpc(name = "state_getReadProof")]
+	fn read_proof(&self, keys: Vec<StorageKey>, hash: Option<Hash>) -> FutureResult<ReadProof<Hash>>;
+
+	/// Returns proof of child storage entries at a specific block's state.
+	#[rpc(name = "state_getChildReadProof")]
+	fn read_child_proof(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		keys: Vec<StorageKey>,
+		hash: Option<Hash>
+	) -> FutureResult<ReadProof<Hash>>;
+
+	/// Re-executes the given block and returns the spans and events emitted by the
+	/// runtime along with its storage accesses.
+	///
+	/// `targets` is a comma separated list of span targets, optionally with a level
+	/// (e.g. `pallet_balances=debug`). All targets are traced when not given.
+	///
+	/// The block is executed in Wasm, spans are only emitted by runtimes built with the
+	/// `wasm-tracing` feature of `frame-support`.
+	///
+	/// This is an unsafe method, only allowed by RPC policies allowing unsafe methods.
+	#[rpc(name = "state_traceBlock")]
+	fn trace_block(&self, block: Hash, targets: Option<String>) -> FutureResult<BlockTrace<Hash>>;
+
 	/// New runtime version subscription
 	#[pubsub(
 		subscription = "state_runtimeVersion",
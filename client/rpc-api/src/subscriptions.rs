@@ -28,6 +28,9 @@ type Id = u64;
 /// Alias for a an implementation of `futures::future::Executor`.
 pub type TaskExecutor = Arc<dyn future::Executor<Box<dyn Future<Item = (), Error = ()> + Send>> + Send + Sync>;
 
+/// Callback notified of the subscriptions ending without being cancelled.
+pub type EndListener = Arc<dyn Fn(&SubscriptionId) + Send + Sync>;
+
 /// Generate unique ids for subscriptions.
 #[derive(Clone, Debug)]
 pub struct IdProvider {
@@ -57,6 +60,7 @@ pub struct Subscriptions {
 	next_id: IdProvider,
 	active_subscriptions: Arc<Mutex<HashMap<Id, oneshot::Sender<()>>>>,
 	executor: TaskExecutor,
+	on_end: Option<EndListener>,
 }
 
 impl Subscriptions {
@@ -66,9 +70,17 @@ impl Subscriptions {
 			next_id: Default::default(),
 			active_subscriptions: Default::default(),
 			executor,
+			on_end: None,
 		}
 	}
 
+	/// Notify `on_end` of the subscriptions ending without being cancelled, e.g. when their
+	/// stream is exhausted.
+	pub fn with_end_listener(mut self, on_end: impl Fn(&SubscriptionId) + Send + Sync + 'static) -> Self {
+		self.on_end = Some(Arc::new(on_end));
+		self
+	}
+
 	/// Borrows the internal task executor.
 	///
 	/// This can be used to spawn additional tasks on the underyling event loop.
@@ -90,10 +102,21 @@ impl Subscriptions {
 		let subscription_id: SubscriptionId = id.into();
 		if let Ok(sink) = subscriber.assign_id(subscription_id.clone()) {
 			let (tx, rx) = oneshot::channel();
+			let active_subscriptions = self.active_subscriptions.clone();
+			let on_end = self.on_end.clone();
+			let ended_id = subscription_id.clone();
 			let future = into_future(sink)
 				.into_future()
-				.select(rx.map_err(|e| warn!("Error timeing out: {:?}", e)))
-				.then(|_| Ok(()));
+				.select2(rx.map_err(|e| warn!("Error timeing out: {:?}", e)))
+				.then(move |result| {
+					if let Ok(future::Either::A(_)) | Err(future::Either::A(_)) = result {
+						active_subscriptions.lock().remove(&id);
+						if let Some(on_end) = on_end {
+							on_end(&ended_id);
+						}
+					}
+					Ok(())
+				});
 
 			self.active_subscriptions.lock().insert(id, tx);
 			if self.executor.execute(Box::new(future)).is_err() {
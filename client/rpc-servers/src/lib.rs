@@ -18,11 +18,17 @@
 
 #![warn(missing_docs)]
 
+mod middleware;
+mod policy;
+
 use std::io;
-use jsonrpc_core::IoHandlerExtension;
+use jsonrpc_core::{IoHandlerExtension, MetaIoHandler};
 use log::error;
 use pubsub::PubSubMetadata;
 
+pub use middleware::RpcMiddleware;
+pub use policy::{Rejection, RpcPolicies, RpcPolicy};
+
 /// Maximal payload accepted by RPC servers.
 const MAX_PAYLOAD: usize = 15 * 1024 * 1024;
 
@@ -30,19 +36,24 @@ const MAX_PAYLOAD: usize = 15 * 1024 * 1024;
 const WS_MAX_CONNECTIONS: usize = 100;
 
 /// The RPC IoHandler containing all requested APIs.
-pub type RpcHandler<T> = pubsub::PubSubHandler<T>;
+pub type RpcHandler<T> = pubsub::PubSubHandler<T, RpcMiddleware>;
 
 pub use self::inner::*;
 
-/// Construct rpc `IoHandler`
+/// Construct rpc `IoHandler` whose calls are subject to the policy of `middleware`.
 pub fn rpc_handler<M: PubSubMetadata>(
-	extension: impl IoHandlerExtension<M>
+	extension: impl IoHandlerExtension<M>,
+	middleware: RpcMiddleware,
 ) -> RpcHandler<M> {
-	let mut io = pubsub::PubSubHandler::default();
+	let policy = middleware.policy().clone();
+	let mut io = pubsub::PubSubHandler::new(MetaIoHandler::with_middleware(middleware));
 	extension.augment(&mut io);
 
 	// add an endpoint to list all available methods.
-	let mut methods = io.iter().map(|x| x.0.clone()).collect::<Vec<String>>();
+	let mut methods = io.iter()
+		.map(|x| x.0.clone())
+		.filter(|method| policy.is_method_allowed(method))
+		.collect::<Vec<String>>();
 	io.add_method("rpc_methods", {
 		methods.sort();
 		let methods = serde_json::to_value(&methods)
@@ -59,21 +70,34 @@ pub fn rpc_handler<M: PubSubMetadata>(
 #[cfg(not(target_os = "unknown"))]
 mod inner {
 	use super::*;
+	use std::net::IpAddr;
+	use crate::policy::{RateLimiter, client_ip};
 
 	/// Type alias for http server
 	pub type HttpServer = http::Server;
 	/// Type alias for ws server
 	pub type WsServer = ws::Server;
+	/// Type alias for ipc server
+	pub type IpcServer = ipc::Server;
 
 	/// Start HTTP server listening on given address.
 	///
+	/// Requests are rate limited per client IP address, see [`RpcPolicy::max_requests_per_minute`].
+	///
 	/// **Note**: Only available if `not(target_os = "unknown")`.
 	pub fn start_http<M: pubsub::PubSubMetadata + Default>(
 		addr: &std::net::SocketAddr,
 		cors: Option<&Vec<String>>,
+		policy: &RpcPolicy,
 		io: RpcHandler<M>,
 	) -> io::Result<http::Server> {
-		http::ServerBuilder::new(io)
+		let builder = http::ServerBuilder::new(io);
+		let builder = match ClientRateLimiter::new(addr, policy)? {
+			Some(limiter) => builder.request_middleware(limiter),
+			None => builder,
+		};
+
+		builder
 			.threads(4)
 			.health_api(("/health", "system_health"))
 			.allowed_hosts(hosts_filtering(cors.is_some()))
@@ -83,22 +107,33 @@ mod inner {
 				http::RestApi::Unsecure
 			})
 			.cors(map_cors::<http::AccessControlAllowOrigin>(cors))
-			.max_request_body_size(MAX_PAYLOAD)
+			.max_request_body_size(policy.max_payload(MAX_PAYLOAD))
 			.start_http(addr)
 	}
 
 	/// Start WS server listening on given address.
 	///
+	/// The maximum number of connections of `policy` takes precedence over `max_connections`.
+	/// Every new connection counts as a call of its client IP address, the calls made over the
+	/// connection being rate limited by the handler.
+	///
 	/// **Note**: Only available if `not(target_os = "unknown")`.
 	pub fn start_ws<M: pubsub::PubSubMetadata + From<jsonrpc_core::futures::sync::mpsc::Sender<String>>> (
 		addr: &std::net::SocketAddr,
 		max_connections: Option<usize>,
 		cors: Option<&Vec<String>>,
+		policy: &RpcPolicy,
 		io: RpcHandler<M>,
 	) -> io::Result<ws::Server> {
-		ws::ServerBuilder::with_meta_extractor(io, |context: &ws::RequestContext| context.sender().into())
-			.max_payload(MAX_PAYLOAD)
-			.max_connections(max_connections.unwrap_or(WS_MAX_CONNECTIONS))
+		let builder = ws::ServerBuilder::with_meta_extractor(io, |context: &ws::RequestContext| context.sender().into());
+		let builder = match ClientRateLimiter::new(addr, policy)? {
+			Some(limiter) => builder.request_middleware(limiter),
+			None => builder,
+		};
+
+		builder
+			.max_payload(policy.max_payload(MAX_PAYLOAD))
+			.max_connections(policy.max_connections.or(max_connections).unwrap_or(WS_MAX_CONNECTIONS))
 			.allowed_origins(map_cors(cors))
 			.allowed_hosts(hosts_filtering(cors.is_some()))
 			.start(addr)
@@ -112,6 +147,144 @@ mod inner {
 			})
 	}
 
+	/// Start IPC server listening on given path.
+	///
+	/// On Unix the path is a domain socket, created with the file permissions `permissions` if
+	/// given. On Windows it is a named pipe and `permissions` is ignored.
+	///
+	/// The policy is enforced by the handler, which should be created with
+	/// [`RpcMiddleware::per_server`]. There is no limit on the number of connections.
+	///
+	/// **Note**: Only available if `not(target_os = "unknown")`.
+	pub fn start_ipc<M: pubsub::PubSubMetadata + From<jsonrpc_core::futures::sync::mpsc::Sender<String>>> (
+		path: &str,
+		permissions: Option<u32>,
+		io: RpcHandler<M>,
+	) -> io::Result<ipc::Server> {
+		let builder = ipc::ServerBuilder::with_meta_extractor(
+			io,
+			|context: &ipc::RequestContext| context.sender.clone().into(),
+		);
+
+		#[cfg(unix)]
+		{
+			// The socket is bound with all the permissions left by the umask, so restricting
+			// the umask while binding leaves no window where the socket is more accessible
+			// than `permissions`. The server is bound by the time `start` returns.
+			match permissions {
+				Some(mode) => {
+					let previous = unsafe { libc::umask(!(mode as libc::mode_t) & 0o777) };
+					let server = builder.start(path);
+					unsafe { libc::umask(previous) };
+					server
+				},
+				None => builder.start(path),
+			}
+		}
+		#[cfg(not(unix))]
+		{
+			let _ = permissions;
+			builder.start(path)
+		}
+	}
+
+	/// Rejects the HTTP requests and WS connections of clients exceeding their rate limit.
+	///
+	/// The servers do not expose the address of their peers, so the server must listen on a
+	/// loopback address: its peers are then proxies on the local host and the client addresses
+	/// are read from the forwarding headers.
+	struct ClientRateLimiter {
+		limiter: RateLimiter<IpAddr>,
+		peer: IpAddr,
+		trusted_proxies: Vec<IpAddr>,
+	}
+
+	impl ClientRateLimiter {
+		fn new(addr: &std::net::SocketAddr, policy: &RpcPolicy) -> io::Result<Option<Self>> {
+			let max_per_minute = match policy.max_requests_per_minute {
+				Some(max_per_minute) => max_per_minute,
+				None => return Ok(None),
+			};
+			if !addr.ip().is_loopback() {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					format!(
+						"Rate limiting the RPC server listening on {} requires a loopback address, \
+						behind a reverse proxy forwarding the client addresses",
+						addr,
+					),
+				));
+			}
+
+			Ok(Some(ClientRateLimiter {
+				limiter: RateLimiter::new(max_per_minute),
+				peer: addr.ip(),
+				trusted_proxies: policy.trusted_proxies.clone().unwrap_or_default(),
+			}))
+		}
+
+		/// Record a request, reading the forwarding headers with `header`.
+		fn check(&self, header: impl Fn(&str) -> Option<String>) -> Result<(), Rejection> {
+			let forwarded_for = header("x-forwarded-for");
+			let real_ip = header("x-real-ip");
+			self.limiter.check(client_ip(
+				self.peer,
+				forwarded_for.as_ref().map(String::as_str),
+				real_ip.as_ref().map(String::as_str),
+				&self.trusted_proxies,
+			))
+		}
+	}
+
+	impl http::RequestMiddleware for ClientRateLimiter {
+		fn on_request(&self, request: http::hyper::Request<http::hyper::Body>) -> http::RequestMiddlewareAction {
+			let header = |name: &str| {
+				let values = request.headers().get_all(name).iter()
+					.filter_map(|value| value.to_str().ok())
+					.collect::<Vec<_>>();
+				Some(values.join(",")).filter(|values| !values.is_empty())
+			};
+
+			match self.check(header) {
+				Ok(()) => request.into(),
+				Err(rejection) => http::Response {
+					code: http::hyper::StatusCode::TOO_MANY_REQUESTS,
+					content_type: http::hyper::header::HeaderValue::from_static(
+						"application/json; charset=utf-8"
+					),
+					content: rejection_response(rejection),
+				}.into(),
+			}
+		}
+	}
+
+	impl ws::RequestMiddleware for ClientRateLimiter {
+		fn process(&self, request: &ws::ws::Request) -> ws::MiddlewareAction {
+			let header = |name: &str| {
+				let values = request.headers().iter()
+					.filter(|(header, _)| header.eq_ignore_ascii_case(name))
+					.filter_map(|(_, value)| std::str::from_utf8(value).ok())
+					.collect::<Vec<_>>();
+				Some(values.join(",")).filter(|values| !values.is_empty())
+			};
+
+			match self.check(header) {
+				Ok(()) => ws::MiddlewareAction::Proceed,
+				Err(rejection) => Some(ws::ws::Response::new(
+					429,
+					"Too Many Requests",
+					rejection_response(rejection).into_bytes(),
+				)).into(),
+			}
+		}
+	}
+
+	/// The JSON-RPC error response of a request rejected by the policy.
+	fn rejection_response(rejection: Rejection) -> String {
+		let response = jsonrpc_core::Response::from(rejection.into(), Some(jsonrpc_core::Version::V2));
+		serde_json::to_string(&response).expect("Serialization of a response is infallible; qed")
+	}
+
 	fn map_cors<T: for<'a> From<&'a str>>(
 		cors: Option<&Vec<String>>
 	) -> http::DomainsValidation<T> {
@@ -133,3 +306,84 @@ mod inner {
 #[cfg(target_os = "unknown")]
 mod inner {
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+	use super::*;
+	use std::{
+		io::{BufRead, BufReader, Write},
+		os::unix::{fs::PermissionsExt, net::UnixStream},
+		path::Path,
+		sync::Arc,
+	};
+	use jsonrpc_core::{RemoteProcedure, futures::sync::mpsc};
+	use pubsub::Session;
+
+	#[derive(Clone, Default)]
+	struct Metadata(Option<Arc<Session>>);
+
+	impl jsonrpc_core::Metadata for Metadata {}
+	impl PubSubMetadata for Metadata {
+		fn session(&self) -> Option<Arc<Session>> {
+			self.0.clone()
+		}
+	}
+
+	impl From<mpsc::Sender<String>> for Metadata {
+		fn from(sender: mpsc::Sender<String>) -> Self {
+			Metadata(Some(Arc::new(Session::new(sender))))
+		}
+	}
+
+	fn start(path: &Path, permissions: Option<u32>) -> IpcServer {
+		let io = rpc_handler::<Metadata>(
+			Vec::<(String, RemoteProcedure<Metadata>)>::new(),
+			RpcMiddleware::per_server(Default::default()),
+		);
+		start_ipc(path.to_str().unwrap(), permissions, io).unwrap()
+	}
+
+	fn umask() -> libc::mode_t {
+		unsafe {
+			let umask = libc::umask(0o022);
+			libc::umask(umask);
+			umask
+		}
+	}
+
+	#[test]
+	fn should_create_ipc_socket_with_permissions() {
+		// given
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("rpc.ipc");
+		let umask_before = umask();
+
+		// when
+		let _server = start(&path, Some(0o600));
+
+		// then
+		let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+		assert_eq!(mode & 0o777, 0o600);
+		assert_eq!(umask(), umask_before);
+	}
+
+	#[test]
+	fn should_answer_requests_over_ipc() {
+		// given
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("rpc.ipc");
+		let _server = start(&path, None);
+		let mut stream = UnixStream::connect(&path).unwrap();
+
+		// when
+		stream.write_all(br#"{"jsonrpc":"2.0","method":"rpc_methods","params":[],"id":1}"#).unwrap();
+		stream.write_all(b"\n").unwrap();
+		let mut response = String::new();
+		BufReader::new(stream).read_line(&mut response).unwrap();
+
+		// then
+		let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+		assert_eq!(response["id"], 1);
+		assert_eq!(response["result"]["version"], 1);
+	}
+}
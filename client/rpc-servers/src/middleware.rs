@@ -0,0 +1,518 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Middleware enforcing an [`RpcPolicy`] on the calls of a handler.
+
+use std::{collections::HashMap, iter, sync::Arc};
+use jsonrpc_core::{
+	Call, Middleware, Output, Params, Request, Response, Value, Version,
+	futures::{Future, future::{self, Either}},
+};
+use parking_lot::Mutex;
+use pubsub::{PubSubMetadata, Session, SubscriptionId};
+
+use crate::policy::{RateLimiter, Rejection, RpcPolicy};
+
+/// Kind of a subscription related method, as guessed from its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SubscriptionMethod {
+	Subscribe,
+	Unsubscribe,
+}
+
+impl SubscriptionMethod {
+	/// The kind of `method`, from the words of its name, e.g. `subscribe` and `NewHead` for
+	/// `chain_subscribeNewHead` or `submit`, `And`, `Watch` and `Extrinsic` for
+	/// `author_submitAndWatchExtrinsic`.
+	fn of(method: &str) -> Option<Self> {
+		let is_any = |candidates: &[&str]| method.split('_')
+			.flat_map(words)
+			.any(|word| candidates.iter().any(|candidate| word.eq_ignore_ascii_case(candidate)));
+
+		if is_any(&["unsubscribe", "unwatch"]) {
+			Some(SubscriptionMethod::Unsubscribe)
+		} else if is_any(&["subscribe", "watch"]) {
+			Some(SubscriptionMethod::Subscribe)
+		} else {
+			None
+		}
+	}
+}
+
+/// The words of a camel case `identifier`.
+fn words(identifier: &str) -> impl Iterator<Item = &str> {
+	let mut start = 0;
+	identifier.char_indices()
+		.skip(1)
+		.filter(|(_, c)| c.is_ascii_uppercase())
+		.map(|(index, _)| index)
+		.chain(iter::once(identifier.len()))
+		.map(move |end| {
+			let word = &identifier[start..end];
+			start = end;
+			word
+		})
+}
+
+/// Identifies a connection by the address of its session.
+type ConnectionId = usize;
+
+/// The active subscriptions of the connections.
+#[derive(Default)]
+struct Subscriptions {
+	/// Number of active subscriptions per connection.
+	counts: HashMap<ConnectionId, usize>,
+	/// Connections of the active subscriptions, per subscription id.
+	///
+	/// The ids are only unique per subscriptions manager, so several connections may have a
+	/// subscription with the same id.
+	connections: HashMap<SubscriptionId, Vec<ConnectionId>>,
+}
+
+impl Subscriptions {
+	/// Release a slot of `connection`, for the subscription `id` if any.
+	fn release(&mut self, connection: ConnectionId, id: Option<&SubscriptionId>) {
+		if let Some(id) = id {
+			if let Some(connections) = self.connections.get_mut(id) {
+				match connections.iter().position(|c| *c == connection) {
+					Some(position) => { connections.remove(position); },
+					None => return,
+				}
+				if connections.is_empty() {
+					self.connections.remove(id);
+				}
+			}
+		}
+		if let Some(count) = self.counts.get_mut(&connection) {
+			*count = count.saturating_sub(1);
+		}
+	}
+
+	/// Forget about `connection` and its subscriptions.
+	fn remove_connection(&mut self, connection: ConnectionId) {
+		self.counts.remove(&connection);
+		self.connections.retain(|_, connections| {
+			connections.retain(|c| *c != connection);
+			!connections.is_empty()
+		});
+	}
+}
+
+struct Inner {
+	policy: RpcPolicy,
+	rate_limiter: Option<RateLimiter<ConnectionId>>,
+	/// Whether the connections share the rate limit of the server.
+	per_server: bool,
+	subscriptions: Mutex<Subscriptions>,
+}
+
+impl Inner {
+	/// Start tracking the connection of `session`, returning its id.
+	fn connection(self: &Arc<Self>, session: &Arc<Session>) -> ConnectionId {
+		let id = &**session as *const Session as ConnectionId;
+		let mut subscriptions = self.subscriptions.lock();
+		if !subscriptions.counts.contains_key(&id) {
+			subscriptions.counts.insert(id, 0);
+			// The callback runs while the session is dropped, before its address may be reused.
+			let inner = Arc::downgrade(self);
+			session.on_drop(move || if let Some(inner) = inner.upgrade() {
+				inner.subscriptions.lock().remove_connection(id);
+				if let (Some(limiter), false) = (inner.rate_limiter.as_ref(), inner.per_server) {
+					limiter.remove(&id);
+				}
+			});
+		}
+		id
+	}
+
+	/// Record a call of `connection`, failing if it exceeds the rate limit.
+	fn check_rate(&self, connection: ConnectionId) -> Result<(), Rejection> {
+		match self.rate_limiter {
+			Some(ref limiter) => limiter.check(if self.per_server { 0 } else { connection }),
+			None => Ok(()),
+		}
+	}
+
+	/// Reserve a subscription slot of `connection`.
+	fn reserve_subscription(&self, connection: ConnectionId) -> Result<(), Rejection> {
+		let max = match self.policy.max_subscriptions_per_connection {
+			Some(max) => max,
+			None => return Ok(()),
+		};
+
+		let mut subscriptions = self.subscriptions.lock();
+		let count = subscriptions.counts.entry(connection).or_insert(0);
+		if *count >= max {
+			return Err(Rejection::TooManySubscriptions { max });
+		}
+		*count += 1;
+		Ok(())
+	}
+
+	/// Attribute the subscription `id` to `connection`, which reserved its slot.
+	fn subscribed(&self, connection: ConnectionId, id: SubscriptionId) {
+		self.subscriptions.lock().connections.entry(id).or_insert_with(Vec::new).push(connection);
+	}
+
+	/// Replace `output` with an error if it exceeds the maximum response size.
+	fn limit_response(&self, output: Output) -> Output {
+		let max = match self.policy.max_response_size {
+			Some(max) => max,
+			None => return output,
+		};
+
+		let size = serde_json::to_vec(&output).map(|encoded| encoded.len()).unwrap_or(0);
+		if size <= max {
+			return output;
+		}
+
+		let (id, version) = match output {
+			Output::Success(success) => (success.id, success.jsonrpc),
+			Output::Failure(failure) => (failure.id, failure.jsonrpc),
+		};
+		Output::from(Err(Rejection::ResponseTooLarge { size, max }.into()), id, version)
+	}
+}
+
+/// Enforces an [`RpcPolicy`] on every call of a handler.
+///
+/// Calls are rate limited and subscriptions are counted per connection, so both only apply to
+/// transports with sessions. Subscriptions are recognized by their method name and are counted
+/// until they are unsubscribed, end or their connection is closed.
+#[derive(Clone)]
+pub struct RpcMiddleware {
+	inner: Arc<Inner>,
+}
+
+impl RpcMiddleware {
+	/// Create a middleware enforcing `policy`, rate limiting each connection.
+	pub fn new(policy: RpcPolicy) -> Self {
+		Self::with_rate_limit(policy, false)
+	}
+
+	/// Create a middleware enforcing `policy`, the connections sharing a single rate limit.
+	///
+	/// Meant for IPC, whose clients are all on the local host.
+	pub fn per_server(policy: RpcPolicy) -> Self {
+		Self::with_rate_limit(policy, true)
+	}
+
+	fn with_rate_limit(policy: RpcPolicy, per_server: bool) -> Self {
+		RpcMiddleware {
+			inner: Arc::new(Inner {
+				rate_limiter: policy.max_requests_per_minute.map(RateLimiter::new),
+				per_server,
+				subscriptions: Mutex::new(Default::default()),
+				policy,
+			}),
+		}
+	}
+
+	/// The enforced policy.
+	pub fn policy(&self) -> &RpcPolicy {
+		&self.inner.policy
+	}
+
+	/// Release the slot of the subscription `id`, which ended without being unsubscribed.
+	///
+	/// The slot is only released when a single connection has a subscription with this id,
+	/// otherwise the subscription is counted until it is unsubscribed or its connection closed.
+	pub fn release_subscription(&self, id: &SubscriptionId) {
+		let mut subscriptions = self.inner.subscriptions.lock();
+		let connection = match subscriptions.connections.get(id).map(Vec::as_slice) {
+			Some(&[connection]) => connection,
+			_ => return,
+		};
+		subscriptions.release(connection, Some(id));
+	}
+}
+
+impl Default for RpcMiddleware {
+	fn default() -> Self {
+		RpcMiddleware::new(RpcPolicy::default())
+	}
+}
+
+impl<M: PubSubMetadata> Middleware<M> for RpcMiddleware {
+	type Future = Box<dyn Future<Item = Option<Response>, Error = ()> + Send>;
+	type CallFuture = Box<dyn Future<Item = Option<Output>, Error = ()> + Send>;
+
+	fn on_request<F, X>(&self, request: Request, meta: M, next: F) -> Either<Self::Future, X> where
+		F: Fn(Request, M) -> X + Send + Sync,
+		X: Future<Item = Option<Response>, Error = ()> + Send + 'static,
+	{
+		if let Some(max) = self.inner.policy.max_request_size {
+			let size = serde_json::to_vec(&request).map(|encoded| encoded.len()).unwrap_or(0);
+			if size > max {
+				let response = Response::from(
+					Rejection::RequestTooLarge { size, max }.into(),
+					Some(Version::V2),
+				);
+				return Either::A(Box::new(future::ok(Some(response))));
+			}
+		}
+
+		Either::B(next(request, meta))
+	}
+
+	fn on_call<F, X>(&self, call: Call, meta: M, next: F) -> Either<Self::CallFuture, X> where
+		F: Fn(Call, M) -> X + Send + Sync,
+		X: Future<Item = Option<Output>, Error = ()> + Send + 'static,
+	{
+		let (method, id, version) = match call {
+			Call::MethodCall(ref call) => (call.method.clone(), Some(call.id.clone()), call.jsonrpc),
+			Call::Notification(ref notification) => (notification.method.clone(), None, notification.jsonrpc),
+			Call::Invalid { .. } => return Either::B(next(call, meta)),
+		};
+		let reject = |rejection: Rejection| -> Either<Self::CallFuture, X> {
+			let output = id.clone().map(|id| Output::from(Err(rejection.into()), id, version));
+			Either::A(Box::new(future::ok(output)))
+		};
+
+		if !self.inner.policy.is_method_allowed(&method) {
+			return reject(Rejection::MethodNotAllowed { method });
+		}
+
+		let connection = meta.session().map(|session| self.inner.connection(&session));
+		let subscription = SubscriptionMethod::of(&method);
+		if let Some(connection) = connection {
+			if let Err(rejection) = self.inner.check_rate(connection) {
+				return reject(rejection);
+			}
+			if subscription == Some(SubscriptionMethod::Subscribe) {
+				if let Err(rejection) = self.inner.reserve_subscription(connection) {
+					return reject(rejection);
+				}
+			}
+		}
+
+		let tracked = connection.filter(|_| self.inner.policy.max_subscriptions_per_connection.is_some());
+		if self.inner.policy.max_response_size.is_none() && (tracked.is_none() || subscription.is_none()) {
+			return Either::B(next(call, meta));
+		}
+
+		let unsubscribed = match call {
+			Call::MethodCall(ref call) => match call.params {
+				Params::Array(ref params) => params.first().and_then(SubscriptionId::parse_value),
+				_ => None,
+			},
+			_ => None,
+		};
+		let inner = self.inner.clone();
+		Either::A(Box::new(next(call, meta).map(move |output| {
+			if let (Some(connection), Some(subscription)) = (tracked, subscription) {
+				let result = match output {
+					Some(Output::Success(ref success)) => Some(&success.result),
+					_ => None,
+				};
+				match (subscription, result) {
+					(SubscriptionMethod::Subscribe, Some(result)) => {
+						// Without an id the slot is only released when the connection is closed.
+						if let Some(id) = SubscriptionId::parse_value(result) {
+							inner.subscribed(connection, id);
+						}
+					},
+					(SubscriptionMethod::Subscribe, None) =>
+						inner.subscriptions.lock().release(connection, None),
+					(SubscriptionMethod::Unsubscribe, Some(&Value::Bool(true))) => {
+						if let Some(ref id) = unsubscribed {
+							inner.subscriptions.lock().release(connection, Some(id));
+						}
+					},
+					_ => {},
+				}
+			}
+
+			output.map(|output| inner.limit_response(output))
+		})))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jsonrpc_core::{MetaIoHandler, futures::sync::mpsc};
+	use pubsub::{PubSubHandler, Subscriber};
+
+	#[derive(Clone, Default)]
+	struct Metadata(Option<Arc<Session>>);
+
+	impl jsonrpc_core::Metadata for Metadata {}
+	impl PubSubMetadata for Metadata {
+		fn session(&self) -> Option<Arc<Session>> {
+			self.0.clone()
+		}
+	}
+
+	fn handler(policy: RpcPolicy) -> PubSubHandler<Metadata, RpcMiddleware> {
+		handler_with_middleware(RpcMiddleware::new(policy))
+	}
+
+	fn handler_with_middleware(middleware: RpcMiddleware) -> PubSubHandler<Metadata, RpcMiddleware> {
+		let mut io = PubSubHandler::new(MetaIoHandler::with_middleware(middleware));
+		io.add_method("chain_getBlock", |_| Ok(Value::String("x".repeat(64))));
+		io.add_method("author_rotateKeys", |_| Ok(Value::Null));
+		io.add_subscription(
+			"test",
+			("test_subscribe", |_: Params, _, subscriber: Subscriber| {
+				let _ = subscriber.assign_id(SubscriptionId::Number(1));
+			}),
+			("test_unsubscribe", |_, _| Ok(Value::Bool(true))),
+		);
+		io
+	}
+
+	fn session() -> Metadata {
+		let (tx, _rx) = mpsc::channel(1);
+		Metadata(Some(Arc::new(Session::new(tx))))
+	}
+
+	fn call(io: &PubSubHandler<Metadata, RpcMiddleware>, meta: &Metadata, method: &str) -> Value {
+		let params = if method.ends_with("_unsubscribe") { "[1]" } else { "[]" };
+		let request = format!(
+			r#"{{"jsonrpc":"2.0","method":"{}","params":{},"id":1}}"#,
+			method,
+			params,
+		);
+		let response = io.handle_request_sync(&request, meta.clone()).unwrap();
+		serde_json::from_str(&response).unwrap()
+	}
+
+	fn error_code(response: &Value) -> Option<i64> {
+		response["error"]["code"].as_i64()
+	}
+
+	#[test]
+	fn should_reject_methods_outside_allowed_namespaces() {
+		// given
+		let io = handler(RpcPolicy {
+			allowed_namespaces: Some(vec!["chain".into(), "test".into()]),
+			..Default::default()
+		});
+		let meta = Metadata::default();
+
+		// then
+		assert_eq!(error_code(&call(&io, &meta, "chain_getBlock")), None);
+		let response = call(&io, &meta, "author_rotateKeys");
+		assert_eq!(error_code(&response), Some(9001));
+		assert_eq!(response["error"]["data"]["reason"], "methodNotAllowed");
+	}
+
+	#[test]
+	fn should_rate_limit_connections() {
+		// given
+		let io = handler(RpcPolicy { max_requests_per_minute: Some(1), ..Default::default() });
+		let (first, second) = (session(), session());
+
+		// when
+		assert_eq!(error_code(&call(&io, &first, "chain_getBlock")), None);
+
+		// then
+		assert_eq!(error_code(&call(&io, &first, "chain_getBlock")), Some(9002));
+		assert_eq!(error_code(&call(&io, &second, "chain_getBlock")), None);
+	}
+
+	#[test]
+	fn should_limit_subscriptions_per_connection() {
+		// given
+		let io = handler(RpcPolicy { max_subscriptions_per_connection: Some(1), ..Default::default() });
+		let (first, second) = (session(), session());
+
+		// when
+		assert_eq!(error_code(&call(&io, &first, "test_subscribe")), None);
+
+		// then
+		assert_eq!(error_code(&call(&io, &first, "test_subscribe")), Some(9003));
+		assert_eq!(error_code(&call(&io, &second, "test_subscribe")), None);
+
+		// unsubscribing frees the slot
+		assert_eq!(error_code(&call(&io, &first, "test_unsubscribe")), None);
+		assert_eq!(error_code(&call(&io, &first, "test_subscribe")), None);
+	}
+
+	#[test]
+	fn should_release_subscriptions_that_ended_or_whose_connection_closed() {
+		// given
+		let middleware = RpcMiddleware::new(RpcPolicy {
+			max_subscriptions_per_connection: Some(1),
+			..Default::default()
+		});
+		let io = handler_with_middleware(middleware.clone());
+		let meta = session();
+		assert_eq!(error_code(&call(&io, &meta, "test_subscribe")), None);
+		assert_eq!(error_code(&call(&io, &meta, "test_subscribe")), Some(9003));
+
+		// when
+		middleware.release_subscription(&SubscriptionId::Number(1));
+
+		// then
+		assert_eq!(error_code(&call(&io, &meta, "test_subscribe")), None);
+
+		// when
+		drop(meta);
+
+		// then
+		assert!(middleware.inner.subscriptions.lock().counts.is_empty());
+		assert!(middleware.inner.subscriptions.lock().connections.is_empty());
+	}
+
+	#[test]
+	fn should_share_the_rate_limit_of_the_server_between_connections() {
+		// given
+		let io = handler_with_middleware(RpcMiddleware::per_server(RpcPolicy {
+			max_requests_per_minute: Some(1),
+			..Default::default()
+		}));
+		let (first, second) = (session(), session());
+
+		// when
+		assert_eq!(error_code(&call(&io, &first, "chain_getBlock")), None);
+
+		// then
+		assert_eq!(error_code(&call(&io, &second, "chain_getBlock")), Some(9002));
+	}
+
+	#[test]
+	fn should_recognize_subscription_methods_by_their_words() {
+		let of = SubscriptionMethod::of;
+
+		assert_eq!(of("chain_subscribeNewHead"), Some(SubscriptionMethod::Subscribe));
+		assert_eq!(of("subscribe_newHead"), Some(SubscriptionMethod::Subscribe));
+		assert_eq!(of("author_submitAndWatchExtrinsic"), Some(SubscriptionMethod::Subscribe));
+		assert_eq!(of("chain_unsubscribeNewHead"), Some(SubscriptionMethod::Unsubscribe));
+		assert_eq!(of("unsubscribe_newHead"), Some(SubscriptionMethod::Unsubscribe));
+		assert_eq!(of("author_unwatchExtrinsic"), Some(SubscriptionMethod::Unsubscribe));
+		assert_eq!(of("system_subscribers"), None);
+		assert_eq!(of("chain_getWatchdog"), None);
+		assert_eq!(of("offchain_getSubscriptionFee"), None);
+	}
+
+	#[test]
+	fn should_reject_too_large_requests_and_responses() {
+		// given
+		let io = handler(RpcPolicy {
+			max_request_size: Some(128),
+			max_response_size: Some(64),
+			..Default::default()
+		});
+		let meta = Metadata::default();
+
+		// then
+		assert_eq!(error_code(&call(&io, &meta, "chain_getBlock")), Some(9005));
+		assert_eq!(error_code(&call(&io, &meta, "author_rotateKeys")), None);
+		assert_eq!(error_code(&call(&io, &meta, &"x".repeat(128))), Some(9004));
+	}
+}
@@ -0,0 +1,342 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Access control policies of the RPC servers.
+
+use std::{collections::HashMap, hash::Hash, net::IpAddr, time::{Duration, Instant}};
+use jsonrpc_core::{Error, ErrorCode};
+use parking_lot::Mutex;
+use serde::{Serialize, Deserialize};
+
+/// Base code for all access control errors.
+const BASE_ERROR: i64 = 9000;
+
+/// Length of a rate limiting window.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Number of tracked clients above which expired rate limiting windows are pruned.
+const RATE_LIMIT_PRUNE_THRESHOLD: usize = 1024;
+
+/// Methods that may be called whatever the allowed namespaces.
+const ALWAYS_ALLOWED_METHODS: &[&str] = &["rpc_methods"];
+
+/// Methods that are too expensive to be exposed to untrusted clients, only allowed by policies
+/// allowing unsafe methods.
+const UNSAFE_METHODS: &[&str] = &["state_traceBlock"];
+
+/// Access control policy of a single RPC transport.
+///
+/// Every limit is disabled when left to `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct RpcPolicy {
+	/// Namespaces of the methods that may be called, e.g. `chain` for `chain_getBlock`.
+	pub allowed_namespaces: Option<Vec<String>>,
+	/// Maximum number of calls per minute.
+	///
+	/// Counted per client IP address for HTTP, per connection for WS, where every new connection
+	/// also counts as a call of its client IP address, and per server for IPC. The HTTP & WS
+	/// servers must then listen on a loopback address, behind a reverse proxy forwarding the
+	/// client addresses.
+	pub max_requests_per_minute: Option<u32>,
+	/// Addresses of the reverse proxies trusted to forward the client addresses, in addition to
+	/// the proxy on the local host.
+	pub trusted_proxies: Option<Vec<IpAddr>>,
+	/// Maximum number of concurrent connections. Only applies to WS.
+	pub max_connections: Option<usize>,
+	/// Maximum number of active subscriptions per WS or IPC connection.
+	pub max_subscriptions_per_connection: Option<usize>,
+	/// Maximum size in bytes of a request.
+	pub max_request_size: Option<usize>,
+	/// Maximum size in bytes of a call response. Subscription notifications are not limited.
+	pub max_response_size: Option<usize>,
+	/// Whether the unsafe methods, e.g. `state_traceBlock`, may be called.
+	pub allow_unsafe_methods: bool,
+}
+
+impl RpcPolicy {
+	/// Returns whether the given method may be called.
+	pub fn is_method_allowed(&self, method: &str) -> bool {
+		if ALWAYS_ALLOWED_METHODS.contains(&method) {
+			return true;
+		}
+		if UNSAFE_METHODS.contains(&method) && !self.allow_unsafe_methods {
+			return false;
+		}
+
+		let namespace = method.split('_').next().unwrap_or(method);
+		match self.allowed_namespaces {
+			Some(ref namespaces) => namespaces.iter().any(|allowed| allowed == namespace),
+			None => true,
+		}
+	}
+
+	/// Maximum request payload, capped at `max_payload`.
+	pub(crate) fn max_payload(&self, max_payload: usize) -> usize {
+		self.max_request_size.map_or(max_payload, |max| max.min(max_payload))
+	}
+}
+
+/// Access control policies of all RPC transports.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct RpcPolicies {
+	/// Policy of the HTTP server.
+	pub http: RpcPolicy,
+	/// Policy of the WS server.
+	pub ws: RpcPolicy,
+	/// Policy of the IPC server.
+	pub ipc: RpcPolicy,
+}
+
+/// The reason a request was rejected by an [`RpcPolicy`].
+///
+/// Returned to the client as the `data` of the error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "reason")]
+pub enum Rejection {
+	/// The namespace of the method is not allowed.
+	#[serde(rename_all = "camelCase")]
+	MethodNotAllowed {
+		/// The called method.
+		method: String,
+	},
+	/// Too many calls were made in the last minute.
+	#[serde(rename_all = "camelCase")]
+	RateLimited {
+		/// Maximum number of calls per minute.
+		max_per_minute: u32,
+	},
+	/// The connection has too many active subscriptions.
+	#[serde(rename_all = "camelCase")]
+	TooManySubscriptions {
+		/// Maximum number of subscriptions per connection.
+		max: usize,
+	},
+	/// The request is too large.
+	#[serde(rename_all = "camelCase")]
+	RequestTooLarge {
+		/// Size of the request.
+		size: usize,
+		/// Maximum size of a request.
+		max: usize,
+	},
+	/// The response is too large.
+	#[serde(rename_all = "camelCase")]
+	ResponseTooLarge {
+		/// Size of the response.
+		size: usize,
+		/// Maximum size of a response.
+		max: usize,
+	},
+}
+
+impl From<Rejection> for Error {
+	fn from(rejection: Rejection) -> Self {
+		let (code, message) = match rejection {
+			Rejection::MethodNotAllowed { ref method } =>
+				(1, format!("Method not allowed: {}", method)),
+			Rejection::RateLimited { max_per_minute } =>
+				(2, format!("Rate limit exceeded: at most {} calls per minute", max_per_minute)),
+			Rejection::TooManySubscriptions { max } =>
+				(3, format!("Too many subscriptions: at most {} per connection", max)),
+			Rejection::RequestTooLarge { size, max } =>
+				(4, format!("Request too large: {} bytes, at most {}", size, max)),
+			Rejection::ResponseTooLarge { size, max } =>
+				(5, format!("Response too large: {} bytes, at most {}", size, max)),
+		};
+
+		Error {
+			code: ErrorCode::ServerError(BASE_ERROR + code),
+			message,
+			data: serde_json::to_value(&rejection).ok(),
+		}
+	}
+}
+
+/// The IP address of the client of a request received from `peer`.
+///
+/// The forwarding headers are only read when `peer` is a trusted proxy, either on the local
+/// host or in `trusted_proxies`. The `X-Forwarded-For` addresses are then walked from the
+/// right, the first one that is not a trusted proxy being the client. Without the header, the
+/// `X-Real-IP` address is the client. Otherwise `peer` is the client.
+pub(crate) fn client_ip(
+	peer: IpAddr,
+	forwarded_for: Option<&str>,
+	real_ip: Option<&str>,
+	trusted_proxies: &[IpAddr],
+) -> IpAddr {
+	if !peer.is_loopback() && !trusted_proxies.contains(&peer) {
+		return peer;
+	}
+
+	match forwarded_for {
+		Some(forwarded_for) => {
+			let mut client = peer;
+			for address in forwarded_for.rsplit(',') {
+				match address.trim().parse::<IpAddr>() {
+					Ok(address) => {
+						client = address;
+						if !trusted_proxies.contains(&address) {
+							break;
+						}
+					},
+					Err(_) => break,
+				}
+			}
+			client
+		},
+		None => real_ip.and_then(|address| address.trim().parse().ok()).unwrap_or(peer),
+	}
+}
+
+/// Counts calls per client in fixed one minute windows.
+pub(crate) struct RateLimiter<K> {
+	max_per_minute: u32,
+	windows: Mutex<HashMap<K, (Instant, u32)>>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+	/// Create a rate limiter allowing `max_per_minute` calls per client.
+	pub fn new(max_per_minute: u32) -> Self {
+		RateLimiter {
+			max_per_minute,
+			windows: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Record a call of `client`, failing if it exceeds the limit.
+	pub fn check(&self, client: K) -> Result<(), Rejection> {
+		self.check_at(client, Instant::now())
+	}
+
+	/// Forget about `client`.
+	pub fn remove(&self, client: &K) {
+		self.windows.lock().remove(client);
+	}
+
+	fn check_at(&self, client: K, now: Instant) -> Result<(), Rejection> {
+		let mut windows = self.windows.lock();
+		if windows.len() > RATE_LIMIT_PRUNE_THRESHOLD {
+			windows.retain(|_, (start, _)| now.duration_since(*start) < RATE_LIMIT_WINDOW);
+		}
+
+		let (start, count) = windows.entry(client).or_insert((now, 0));
+		if now.duration_since(*start) >= RATE_LIMIT_WINDOW {
+			*start = now;
+			*count = 0;
+		}
+
+		if *count >= self.max_per_minute {
+			return Err(Rejection::RateLimited { max_per_minute: self.max_per_minute });
+		}
+		*count += 1;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn should_filter_methods_by_namespace() {
+		// given
+		let policy = RpcPolicy {
+			allowed_namespaces: Some(vec!["chain".into(), "system".into()]),
+			..Default::default()
+		};
+
+		// then
+		assert!(policy.is_method_allowed("chain_getBlock"));
+		assert!(policy.is_method_allowed("system_health"));
+		assert!(policy.is_method_allowed("rpc_methods"));
+		assert!(!policy.is_method_allowed("state_subscribeStorage"));
+		assert!(!policy.is_method_allowed("chainx_getBlock"));
+		assert!(RpcPolicy::default().is_method_allowed("author_rotateKeys"));
+	}
+
+	#[test]
+	fn should_only_allow_unsafe_methods_when_configured() {
+		// given
+		let policy = RpcPolicy {
+			allowed_namespaces: Some(vec!["chain".into()]),
+			allow_unsafe_methods: true,
+			..Default::default()
+		};
+
+		// then
+		assert!(!RpcPolicy::default().is_method_allowed("state_traceBlock"));
+		assert!(RpcPolicy::default().is_method_allowed("state_getStorage"));
+		assert!(!policy.is_method_allowed("state_traceBlock"));
+		assert!(RpcPolicy { allow_unsafe_methods: true, ..Default::default() }.is_method_allowed("state_traceBlock"));
+	}
+
+	#[test]
+	fn should_limit_calls_per_window() {
+		// given
+		let limiter = RateLimiter::new(2);
+		let now = Instant::now();
+
+		// when
+		assert_eq!(limiter.check_at("a", now), Ok(()));
+		assert_eq!(limiter.check_at("a", now), Ok(()));
+
+		// then
+		assert_eq!(limiter.check_at("a", now), Err(Rejection::RateLimited { max_per_minute: 2 }));
+		assert_eq!(limiter.check_at("b", now), Ok(()));
+		assert_eq!(limiter.check_at("a", now + RATE_LIMIT_WINDOW), Ok(()));
+	}
+
+	#[test]
+	fn should_only_trust_forwarded_addresses_of_trusted_proxies() {
+		// given
+		let local = IpAddr::from([127, 0, 0, 1]);
+		let proxy = IpAddr::from([10, 0, 0, 1]);
+		let client = IpAddr::from([192, 0, 2, 1]);
+		let spoofed = IpAddr::from([192, 0, 2, 2]);
+
+		// then
+		assert_eq!(client_ip(local, Some("192.0.2.2, 192.0.2.1"), None, &[]), client);
+		assert_eq!(client_ip(local, Some("192.0.2.2, 192.0.2.1, 10.0.0.1"), None, &[proxy]), client);
+		assert_eq!(client_ip(local, Some("192.0.2.2, 192.0.2.1, 10.0.0.1"), None, &[]), proxy);
+		assert_eq!(client_ip(local, Some("not an address"), Some("192.0.2.1"), &[]), local);
+		assert_eq!(client_ip(local, None, Some("192.0.2.1"), &[]), client);
+		assert_eq!(client_ip(local, None, None, &[]), local);
+		assert_eq!(client_ip(proxy, Some("192.0.2.1"), None, &[proxy]), client);
+		assert_eq!(client_ip(client, Some("192.0.2.2"), Some("192.0.2.2"), &[proxy]), client);
+		assert_ne!(client_ip(client, Some("192.0.2.2"), None, &[]), spoofed);
+	}
+
+	#[test]
+	fn should_deserialize_policies_and_serialize_rejections() {
+		// given
+		let policies: RpcPolicies = serde_json::from_str(r#"{
+			"ws": { "allowedNamespaces": ["chain"], "maxSubscriptionsPerConnection": 16 },
+			"http": { "trustedProxies": ["10.0.0.1"] }
+		}"#).unwrap();
+
+		// then
+		assert_eq!(policies.http.trusted_proxies, Some(vec![IpAddr::from([10, 0, 0, 1])]));
+		assert_eq!(policies.ipc, RpcPolicy::default());
+		assert_eq!(policies.ws.allowed_namespaces, Some(vec!["chain".to_string()]));
+		assert_eq!(policies.ws.max_subscriptions_per_connection, Some(16));
+
+		let error: Error = Rejection::TooManySubscriptions { max: 16 }.into();
+		assert_eq!(error.code, ErrorCode::ServerError(9003));
+		assert_eq!(error.data, Some(serde_json::json!({ "reason": "tooManySubscriptions", "max": 16 })));
+	}
+}
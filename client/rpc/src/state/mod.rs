@@ -40,9 +40,13 @@ use sp_runtime::{
 use sp_api::Metadata;
 
 use self::error::{Error, FutureResult};
+use self::helpers::{BlockTrace, ReadProof};
 
 pub use sc_rpc_api::state::*;
 
+/// Maximal number of entries returned by a single paged storage request.
+const STORAGE_PAGE_MAX_COUNT: u32 = 1000;
+
 /// State backend API.
 pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 	where
@@ -66,6 +70,27 @@ pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 		prefix: StorageKey,
 	) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns up to `count` key-value pairs with prefix, starting after `start_key` if given.
+	fn storage_pairs_paged(
+		&self,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>>;
+
+	/// Returns up to `count` keys with prefix, starting after `start_key` if given.
+	fn storage_keys_paged(
+		&self,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageKey>> {
+		Box::new(self.storage_pairs_paged(block, prefix, count, start_key)
+			.map(|pairs| pairs.into_iter().map(|(key, _)| key).collect()))
+	}
+
 	/// Returns a storage entry at a specific block's state.
 	fn storage(
 		&self,
@@ -100,6 +125,42 @@ pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 		prefix: StorageKey,
 	) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns up to `count` key-value pairs with prefix from a child storage, starting
+	/// after `start_key` if given.
+	fn child_storage_pairs_paged(
+		&self,
+		block: Option<Block::Hash>,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>>;
+
+	/// Returns up to `count` keys with prefix from a child storage, starting after
+	/// `start_key` if given.
+	fn child_storage_keys_paged(
+		&self,
+		block: Option<Block::Hash>,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageKey>> {
+		Box::new(self.child_storage_pairs_paged(
+			block,
+			child_storage_key,
+			child_info,
+			child_type,
+			prefix,
+			count,
+			start_key,
+		).map(|pairs| pairs.into_iter().map(|(key, _)| key).collect()))
+	}
+
 	/// Returns a child storage entry at a specific block's state.
 	fn child_storage(
 		&self,
@@ -150,6 +211,30 @@ pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 		keys: Vec<StorageKey>,
 	) -> FutureResult<Vec<StorageChangeSet<Block::Hash>>>;
 
+	/// Returns proof of storage entries at a specific block's state.
+	fn read_proof(
+		&self,
+		block: Option<Block::Hash>,
+		keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProof<Block::Hash>>;
+
+	/// Returns proof of child storage entries at a specific block's state.
+	fn read_child_proof(
+		&self,
+		block: Option<Block::Hash>,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProof<Block::Hash>>;
+
+	/// Re-execute the given block and return what the runtime did.
+	fn trace_block(
+		&self,
+		block: Block::Hash,
+		targets: Option<String>,
+	) -> FutureResult<BlockTrace<Block::Hash>>;
+
 	/// New runtime version subscription
 	fn subscribe_runtime_version(
 		&self,
@@ -249,6 +334,32 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
 		self.backend.storage_keys(block, key_prefix)
 	}
 
+	fn storage_keys_paged(
+		&self,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> FutureResult<Vec<StorageKey>> {
+		if let Err(e) = check_page_count(count) {
+			return Box::new(rpc::futures::future::err(e));
+		}
+		self.backend.storage_keys_paged(block, prefix, count, start_key)
+	}
+
+	fn storage_pairs_paged(
+		&self,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
+		if let Err(e) = check_page_count(count) {
+			return Box::new(rpc::futures::future::err(e));
+		}
+		self.backend.storage_pairs_paged(block, prefix, count, start_key)
+	}
+
 	fn storage(&self, key: StorageKey, block: Option<Block::Hash>) -> FutureResult<Option<StorageData>> {
 		self.backend.storage(block, key)
 	}
@@ -283,6 +394,54 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
 		self.backend.child_storage_keys(block, child_storage_key, child_info, child_type, key_prefix)
 	}
 
+	fn child_storage_keys_paged(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> FutureResult<Vec<StorageKey>> {
+		if let Err(e) = check_page_count(count) {
+			return Box::new(rpc::futures::future::err(e));
+		}
+		self.backend.child_storage_keys_paged(
+			block,
+			child_storage_key,
+			child_info,
+			child_type,
+			prefix,
+			count,
+			start_key,
+		)
+	}
+
+	fn child_storage_pairs_paged(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
+		if let Err(e) = check_page_count(count) {
+			return Box::new(rpc::futures::future::err(e));
+		}
+		self.backend.child_storage_pairs_paged(
+			block,
+			child_storage_key,
+			child_info,
+			child_type,
+			prefix,
+			count,
+			start_key,
+		)
+	}
+
 	fn child_storage_hash(
 		&self,
 		child_storage_key: StorageKey,
@@ -318,6 +477,25 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
 		self.backend.query_storage(from, to, keys)
 	}
 
+	fn read_proof(&self, keys: Vec<StorageKey>, block: Option<Block::Hash>) -> FutureResult<ReadProof<Block::Hash>> {
+		self.backend.read_proof(block, keys)
+	}
+
+	fn read_child_proof(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		keys: Vec<StorageKey>,
+		block: Option<Block::Hash>
+	) -> FutureResult<ReadProof<Block::Hash>> {
+		self.backend.read_child_proof(block, child_storage_key, child_info, child_type, keys)
+	}
+
+	fn trace_block(&self, block: Block::Hash, targets: Option<String>) -> FutureResult<BlockTrace<Block::Hash>> {
+		self.backend.trace_block(block, targets)
+	}
+
 	fn subscribe_storage(
 		&self,
 		meta: Self::Metadata,
@@ -348,8 +526,20 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
 	}
 }
 
+fn check_page_count(count: u32) -> Result<(), Error> {
+	if count > STORAGE_PAGE_MAX_COUNT {
+		Err(Error::InvalidCount { value: count, max: STORAGE_PAGE_MAX_COUNT })
+	} else {
+		Ok(())
+	}
+}
+
 fn client_err(err: sp_blockchain::Error) -> Error {
-	Error::Client(Box::new(err))
+	match err {
+		sp_blockchain::Error::Timeout | sp_blockchain::Error::OutOfFuel =>
+			Error::CallLimitExceeded(err.to_string()),
+		err => Error::Client(Box::new(err)),
+	}
 }
 
 const CHILD_RESOLUTION_ERROR: &str = "Unexpected child info and type";
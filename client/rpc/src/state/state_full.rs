@@ -40,15 +40,21 @@ use sp_core::{
 	storage::{well_known_keys, StorageKey, StorageData, StorageChangeSet, ChildInfo},
 };
 use sp_version::RuntimeVersion;
-use sp_state_machine::ExecutionStrategy;
+use sp_state_machine::{ExecutionStrategy, StorageAccess, StorageAccessRecorder};
+use sp_externalities::Extensions;
 use sp_runtime::{
 	generic::BlockId,
-	traits::{Block as BlockT, NumberFor, ProvideRuntimeApi, SaturatedConversion},
+	traits::{Block as BlockT, Header as HeaderT, NumberFor, ProvideRuntimeApi, SaturatedConversion, Zero},
 };
+use codec::Encode;
 
 use sp_api::Metadata;
 
-use super::{StateBackend, error::{FutureResult, Error, Result}, client_err, child_resolution_error};
+use super::{
+	StateBackend, error::{FutureResult, Error, Result},
+	helpers::{BlockTrace, ReadProof, TraceEvent, TraceSpan, TraceStorageAccess},
+	client_err, child_resolution_error,
+};
 
 /// Ranges to query in state_queryStorage.
 struct QueryStorageRange<Block: BlockT> {
@@ -86,6 +92,98 @@ impl<B, E, Block: BlockT, RA> FullState<B, E, Block, RA>
 		Ok(hash.unwrap_or_else(|| self.client.chain_info().best_hash))
 	}
 
+	/// Re-executes the block in Wasm on top of its parent state with spans and storage accesses
+	/// being collected.
+	///
+	/// Spans are only entered by runtimes built with the `wasm-tracing` feature of
+	/// `frame-support`.
+	fn trace_block_inner(&self, block: Block::Hash, targets: String) -> Result<BlockTrace<Block::Hash>> {
+		let id = BlockId::Hash(block);
+		let mut header = self.client.header(&id)
+			.and_then(|header| header.ok_or_else(|| ClientError::UnknownBlock(format!("{}", block))))
+			.map_err(client_err)?;
+		if header.number().is_zero() {
+			return Err(client_err(ClientError::Msg("Genesis block can't be traced".into())));
+		}
+		let extrinsics = self.client.body(&id)
+			.and_then(|body| body.ok_or_else(|| ClientError::UnknownBlock(format!("{}", block))))
+			.map_err(client_err)?;
+
+		// seals are removed by the consensus engine before the block is executed on import
+		header.digest_mut().logs.retain(|item| item.as_seal().is_none());
+		let parent_hash = *header.parent_hash();
+		let encoded_block = Block::new(header, extrinsics).encode();
+
+		let recorder = StorageAccessRecorder::default();
+		let mut extensions = Extensions::new();
+		extensions.register(recorder.clone());
+
+		let collector = sc_tracing::SpanCollector::new(&targets);
+		// the block is executed in Wasm, to trace the on-chain runtime whatever the native one,
+		// by the executor of the node so that the configured execution method, heap pages and
+		// host functions are used
+		let execution = collector.collect(|| self.client.executor().call(
+			&BlockId::Hash(parent_hash),
+			"Core_execute_block",
+			&encoded_block,
+			ExecutionStrategy::AlwaysWasm,
+			Some(extensions),
+		));
+		let (spans, events) = collector.finish();
+
+		Ok(BlockTrace {
+			block_hash: block,
+			parent_hash,
+			targets,
+			spans: spans.into_iter().map(|span| TraceSpan {
+				id: span.id,
+				parent_id: span.parent_id,
+				name: span.name,
+				target: span.target,
+				level: span.level.to_string(),
+				values: span.values,
+				exec_time: span.overall_time.as_nanos() as u64,
+			}).collect(),
+			events: events.into_iter().map(|event| TraceEvent {
+				parent_id: event.parent_id,
+				name: event.name,
+				target: event.target,
+				level: event.level.to_string(),
+				values: event.values,
+			}).collect(),
+			storage: recorder.take().into_iter().map(|access| match access {
+				StorageAccess::Read { child, key, value } => TraceStorageAccess::Read {
+					child: child.map(Into::into),
+					key: key.into(),
+					value: value.map(Into::into),
+				},
+				StorageAccess::ReadHash { child, key, hash } => TraceStorageAccess::ReadHash {
+					child: child.map(Into::into),
+					key: key.into(),
+					hash: hash.map(Into::into),
+				},
+				StorageAccess::NextKey { child, key, next } => TraceStorageAccess::NextKey {
+					child: child.map(Into::into),
+					key: key.into(),
+					next: next.map(Into::into),
+				},
+				StorageAccess::Write { child, key, value } => TraceStorageAccess::Write {
+					child: child.map(Into::into),
+					key: key.into(),
+					value: value.map(Into::into),
+				},
+				StorageAccess::ClearPrefix { child, prefix } => TraceStorageAccess::ClearPrefix {
+					child: child.map(Into::into),
+					prefix: prefix.into(),
+				},
+				StorageAccess::KillChild { child } => TraceStorageAccess::KillChild {
+					child: child.into(),
+				},
+			}).collect(),
+			error: execution.err().map(|e| e.to_string()),
+		})
+	}
+
 	/// Splits the `query_storage` block range into 'filtered' and 'unfiltered' subranges.
 	/// Blocks that contain changes within filtered subrange could be filtered using changes tries.
 	/// Blocks that contain changes within unfiltered subrange must be filtered manually.
@@ -234,6 +332,18 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 		method: String,
 		call_data: Bytes,
 	) -> FutureResult<Bytes> {
+		let extensions = self.client.execution_extensions().call_limits().extension().map(|limits| {
+			let mut extensions = Extensions::new();
+			extensions.register(limits);
+			extensions
+		});
+		// The limits are only enforced on the Wasm execution.
+		let strategy = if extensions.is_some() {
+			ExecutionStrategy::AlwaysWasm
+		} else {
+			ExecutionStrategy::NativeElseWasm
+		};
+
 		Box::new(result(
 			self.block_or_best(block)
 				.and_then(|block|
@@ -244,8 +354,8 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 						&BlockId::Hash(block),
 						&method,
 						&*call_data,
-						ExecutionStrategy::NativeElseWasm,
-						None,
+						strategy,
+						extensions,
 					)
 					.map(Into::into))
 				.map_err(client_err)))
@@ -262,6 +372,24 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 				.map_err(client_err)))
 	}
 
+	fn storage_pairs_paged(
+		&self,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
+		Box::new(result(
+			self.block_or_best(block)
+				.and_then(|block| self.client.storage_pairs_paged(
+					&BlockId::Hash(block),
+					&prefix,
+					start_key.as_ref(),
+					count as usize,
+				))
+				.map_err(client_err)))
+	}
+
 	fn storage(
 		&self,
 		block: Option<Block::Hash>,
@@ -304,6 +432,30 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 				.map_err(client_err)))
 	}
 
+	fn child_storage_pairs_paged(
+		&self,
+		block: Option<Block::Hash>,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
+		Box::new(result(
+			self.block_or_best(block)
+				.and_then(|block| self.client.child_storage_pairs_paged(
+					&BlockId::Hash(block),
+					&child_storage_key,
+					ChildInfo::resolve_child_info(child_type, &child_info.0[..])
+						.ok_or_else(child_resolution_error)?,
+					&prefix,
+					start_key.as_ref(),
+					count as usize,
+				))
+				.map_err(client_err)))
+	}
+
 	fn child_storage(
 		&self,
 		block: Option<Block::Hash>,
@@ -377,6 +529,54 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 		Box::new(result(call_fn()))
 	}
 
+	fn read_proof(
+		&self,
+		block: Option<Block::Hash>,
+		keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProof<Block::Hash>> {
+		Box::new(result(
+			self.block_or_best(block)
+				.and_then(|block| self.client.read_proof(
+					&BlockId::Hash(block),
+					keys.iter().map(|key| &key.0),
+				).map(|proof| ReadProof {
+					at: block,
+					proof: proof.iter_nodes().map(Into::into).collect(),
+				}))
+				.map_err(client_err)))
+	}
+
+	fn read_child_proof(
+		&self,
+		block: Option<Block::Hash>,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProof<Block::Hash>> {
+		Box::new(result(
+			self.block_or_best(block)
+				.and_then(|block| self.client.read_child_proof(
+					&BlockId::Hash(block),
+					&child_storage_key.0,
+					ChildInfo::resolve_child_info(child_type, &child_info.0[..])
+						.ok_or_else(child_resolution_error)?,
+					keys.iter().map(|key| &key.0),
+				).map(|proof| ReadProof {
+					at: block,
+					proof: proof.iter_nodes().map(Into::into).collect(),
+				}))
+				.map_err(client_err)))
+	}
+
+	fn trace_block(
+		&self,
+		block: Block::Hash,
+		targets: Option<String>,
+	) -> FutureResult<BlockTrace<Block::Hash>> {
+		Box::new(result(self.trace_block_inner(block, targets.unwrap_or_default())))
+	}
+
 	fn subscribe_runtime_version(
 		&self,
 		_meta: crate::metadata::Metadata,
@@ -58,7 +58,7 @@ use sp_runtime::{
 	traits::Block as BlockT,
 };
 
-use super::{StateBackend, error::{FutureResult, Error}, client_err};
+use super::{StateBackend, error::{FutureResult, Error}, helpers::{BlockTrace, ReadProof}, client_err};
 
 /// Storage data map of storage keys => (optional) storage value.
 type StorageMap = HashMap<StorageKey, Option<StorageData>>;
@@ -232,6 +232,29 @@ impl<Block, F, B, E, RA> StateBackend<B, E, Block, RA> for LightState<Block, F,
 		)
 	}
 
+	fn storage_pairs_paged(
+		&self,
+		_block: Option<Block::Hash>,
+		_prefix: StorageKey,
+		_count: u32,
+		_start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
+	fn child_storage_pairs_paged(
+		&self,
+		_block: Option<Block::Hash>,
+		_child_storage_key: StorageKey,
+		_child_info: StorageKey,
+		_child_type: u32,
+		_prefix: StorageKey,
+		_count: u32,
+		_start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
 	fn child_storage_keys(
 		&self,
 		_block: Option<Block::Hash>,
@@ -322,6 +345,33 @@ impl<Block, F, B, E, RA> StateBackend<B, E, Block, RA> for LightState<Block, F,
 		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
 	}
 
+	fn read_proof(
+		&self,
+		_block: Option<Block::Hash>,
+		_keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProof<Block::Hash>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
+	fn trace_block(
+		&self,
+		_block: Block::Hash,
+		_targets: Option<String>,
+	) -> FutureResult<BlockTrace<Block::Hash>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
+	fn read_child_proof(
+		&self,
+		_block: Option<Block::Hash>,
+		_child_storage_key: StorageKey,
+		_child_info: StorageKey,
+		_child_type: u32,
+		_keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProof<Block::Hash>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
 	fn subscribe_storage(
 		&self,
 		_meta: crate::metadata::Metadata,
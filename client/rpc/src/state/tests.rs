@@ -23,6 +23,7 @@ use assert_matches::assert_matches;
 use futures01::stream::Stream;
 use sp_core::storage::{well_known_keys, ChildInfo};
 use sp_core::hash::H256;
+use sp_runtime::{generic::BlockId, traits::Header as _};
 use sp_io::hashing::blake2_256;
 use substrate_test_runtime_client::{
 	prelude::*,
@@ -123,6 +124,134 @@ fn should_return_child_storage() {
 	);
 }
 
+#[test]
+fn should_return_storage_pages() {
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let client = TestClientBuilder::new()
+		.add_extra_storage(b":page:a".to_vec(), b"1".to_vec())
+		.add_extra_storage(b":page:b".to_vec(), b"2".to_vec())
+		.add_extra_storage(b":page:c".to_vec(), b"3".to_vec())
+		.add_extra_storage(b":pages".to_vec(), b"4".to_vec())
+		.add_extra_child_storage(b":child_storage:default:child".to_vec(), CHILD_INFO, b"a".to_vec(), b"5".to_vec())
+		.add_extra_child_storage(b":child_storage:default:child".to_vec(), CHILD_INFO, b"b".to_vec(), b"6".to_vec())
+		.build();
+	let client = new_full(Arc::new(client), Subscriptions::new(Arc::new(core.executor())));
+	let prefix = StorageKey(b":page:".to_vec());
+
+	assert_eq!(
+		client.storage_keys_paged(prefix.clone(), 2, None, None).wait().unwrap(),
+		vec![StorageKey(b":page:a".to_vec()), StorageKey(b":page:b".to_vec())],
+	);
+	assert_eq!(
+		client.storage_pairs_paged(prefix.clone(), 2, Some(StorageKey(b":page:b".to_vec())), None).wait().unwrap(),
+		vec![(StorageKey(b":page:c".to_vec()), StorageData(b"3".to_vec()))],
+	);
+	assert_eq!(
+		client.storage_keys_paged(prefix.clone(), 2, Some(StorageKey(b":page:c".to_vec())), None).wait().unwrap(),
+		Vec::<StorageKey>::new(),
+	);
+	assert_matches!(
+		client.storage_keys_paged(prefix, STORAGE_PAGE_MAX_COUNT + 1, None, None).wait(),
+		Err(Error::InvalidCount { .. })
+	);
+
+	let (child_info, child_type) = CHILD_INFO.info();
+	assert_eq!(
+		client.child_storage_pairs_paged(
+			StorageKey(b":child_storage:default:child".to_vec()),
+			StorageKey(child_info.to_vec()),
+			child_type,
+			StorageKey(vec![]),
+			10,
+			Some(StorageKey(b"a".to_vec())),
+			None,
+		).wait().unwrap(),
+		vec![(StorageKey(b"b".to_vec()), StorageData(b"6".to_vec()))],
+	);
+}
+
+#[test]
+fn should_return_verifiable_read_proof() {
+	const KEY: &[u8] = b":mock";
+	const VALUE: &[u8] = b"hello world";
+	const STORAGE_KEY: &[u8] = b":child_storage:default:child";
+	const CHILD_VALUE: &[u8] = b"hello world !";
+
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(TestClientBuilder::new()
+		.add_extra_storage(KEY.to_vec(), VALUE.to_vec())
+		.add_extra_child_storage(STORAGE_KEY.to_vec(), CHILD_INFO, KEY.to_vec(), CHILD_VALUE.to_vec())
+		.build());
+	let genesis_hash = client.genesis_hash();
+	let state_root = *client.header(&BlockId::Hash(genesis_hash)).unwrap().unwrap().state_root();
+	let api = new_full(client, Subscriptions::new(Arc::new(core.executor())));
+	let key = StorageKey(KEY.to_vec());
+	let missing_key = StorageKey(b":missing".to_vec());
+	let storage_key = StorageKey(STORAGE_KEY.to_vec());
+	let (child_info, child_type) = CHILD_INFO.info();
+	let child_info = StorageKey(child_info.to_vec());
+
+	let proof = api.read_proof(vec![key.clone(), missing_key.clone()], None).wait().unwrap();
+	assert_eq!(proof.at, genesis_hash);
+	assert_eq!(
+		helpers::verify_read_proof::<Blake2Hasher, _>(
+			state_root,
+			proof,
+			&[key.clone(), missing_key.clone()],
+		).unwrap(),
+		vec![(key.clone(), Some(StorageData(VALUE.to_vec()))), (missing_key, None)],
+	);
+
+	let proof = api.read_child_proof(
+		storage_key.clone(),
+		child_info,
+		child_type,
+		vec![key.clone()],
+		Some(genesis_hash).into(),
+	).wait().unwrap();
+	assert_eq!(
+		helpers::verify_child_read_proof::<Blake2Hasher, _>(
+			state_root,
+			proof,
+			&storage_key,
+			&[key.clone()],
+		).unwrap(),
+		vec![(key, Some(StorageData(CHILD_VALUE.to_vec())))],
+	);
+}
+
+#[test]
+fn should_trace_block() {
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(substrate_test_runtime_client::new());
+	let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
+
+	let mut builder = client.new_block(Default::default()).unwrap();
+	builder.push_storage_change(vec![5], Some(vec![7])).unwrap();
+	let block = builder.bake().unwrap();
+	let hash = block.header.hash();
+	client.import(BlockOrigin::Own, block).unwrap();
+
+	let trace = api.trace_block(hash, None).wait().unwrap();
+	assert_eq!(trace.block_hash, hash);
+	assert_eq!(trace.parent_hash, client.genesis_hash());
+	assert_eq!(trace.error, None);
+	assert!(trace.storage.contains(&helpers::TraceStorageAccess::Write {
+		child: None,
+		key: vec![5].into(),
+		value: Some(vec![7].into()),
+	}));
+	// the span entered in Wasm by the test runtime
+	let execute_block = trace.spans.iter()
+		.find(|span| span.name == "execute_block")
+		.expect("The block execution is traced");
+	assert_eq!(execute_block.target, "substrate_test_runtime::system");
+	assert_eq!(execute_block.parent_id, None);
+	assert!(execute_block.values.is_empty());
+
+	assert_matches!(api.trace_block(client.genesis_hash(), None).wait(), Err(Error::Client(_)));
+}
+
 #[test]
 fn should_call_contract() {
 	let core = tokio::runtime::Runtime::new().unwrap();
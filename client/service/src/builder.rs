@@ -38,7 +38,7 @@ use log::{info, warn, error};
 use sc_network::{FinalityProofProvider, OnDemand, NetworkService, NetworkStateInfo};
 use sc_network::{config::BoxFinalityProofRequestBuilder, specialization::NetworkSpecialization};
 use parking_lot::{Mutex, RwLock};
-use sp_core::{Blake2Hasher, H256, Hasher};
+use sp_core::{Blake2Hasher, H256, Hasher, storage::StorageMap};
 use sc_rpc;
 use sp_api::ConstructRuntimeApi;
 use sp_runtime::generic::BlockId;
@@ -90,9 +90,30 @@ pub struct ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImp
 	transaction_pool: Arc<TExPool>,
 	rpc_extensions: TRpc,
 	remote_backend: Option<Arc<dyn RemoteBlockchain<TBl>>>,
+	tasks: ServiceTasks,
 	marker: PhantomData<(TBl, TRtApi)>,
 }
 
+/// The exit signal and the queue of the tasks of the service.
+///
+/// They are created along with the builder, so that the components can be given a
+/// `SpawnTaskHandle` before the service is built.
+struct ServiceTasks {
+	signal: exit_future::Signal,
+	exit: exit_future::Exit,
+	to_spawn_tx: mpsc::UnboundedSender<Box<dyn Future<Item = (), Error = ()> + Send>>,
+	to_spawn_rx: mpsc::UnboundedReceiver<Box<dyn Future<Item = (), Error = ()> + Send>>,
+}
+
+impl ServiceTasks {
+	fn new() -> Self {
+		let (signal, exit) = exit_future::signal();
+		// List of asynchronous tasks to spawn. We collect them, then spawn them all at once.
+		let (to_spawn_tx, to_spawn_rx) = mpsc::unbounded();
+		ServiceTasks { signal, exit, to_spawn_tx, to_spawn_rx }
+	}
+}
+
 /// Full client type.
 pub type TFullClient<TBl, TRtApi, TExecDisp> = Client<
 	TFullBackend<TBl>,
@@ -157,6 +178,20 @@ pub fn new_full_client<TBl, TRtApi, TExecDisp, TCfg, TGen, TCSExt>(
 	new_full_parts(config).map(|parts| parts.0)
 }
 
+/// Enable the on-disk cache of the Wasm runtimes compiled by `executor` if configured.
+///
+/// Failing to do so only makes the runtimes be compiled from scratch, hence is not fatal.
+fn enable_wasm_compilation_cache<TCfg, TGen, TCSExt, D: NativeExecutionDispatch>(
+	config: &Configuration<TCfg, TGen, TCSExt>,
+	executor: &NativeExecutor<D>,
+) {
+	if let Some(ref directory) = config.wasm_compilation_cache {
+		if let Err(e) = sc_executor::enable_wasm_compilation_cache(directory, executor.host_functions()) {
+			warn!("Wasm compilation cache disabled: {}", e);
+		}
+	}
+}
+
 fn new_full_parts<TBl, TRtApi, TExecDisp, TCfg, TGen, TCSExt>(
 	config: &Configuration<TCfg, TGen, TCSExt>,
 ) -> Result<TFullParts<TBl, TRtApi, TExecDisp>,	Error> where
@@ -176,7 +211,11 @@ fn new_full_parts<TBl, TRtApi, TExecDisp, TCfg, TGen, TCSExt>(
 	let executor = NativeExecutor::<TExecDisp>::new(
 		config.wasm_method,
 		config.default_heap_pages,
-	);
+		config.max_runtime_instances,
+	)
+		.with_wasm_metering(config.wasm_metering)
+		.with_sandbox_backend(config.sandbox_backend);
+	enable_wasm_compilation_cache(config, &executor);
 
 	let fork_blocks = config.chain_spec
 		.extensions()
@@ -210,7 +249,9 @@ fn new_full_parts<TBl, TRtApi, TExecDisp, TCfg, TGen, TCSExt>(
 		let extensions = sc_client_api::execution_extensions::ExecutionExtensions::new(
 			config.execution_strategies.clone(),
 			Some(keystore.clone()),
-		);
+		)
+			.with_call_limits(config.call_limits.clone())
+			.with_mismatch_dump(config.execution_mismatch_dump.clone());
 
 		sc_client_db::new_client(
 			db_config,
@@ -265,6 +306,7 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 			transaction_pool: Arc::new(()),
 			rpc_extensions: Default::default(),
 			remote_backend: None,
+			tasks: ServiceTasks::new(),
 			marker: PhantomData,
 		})
 	}
@@ -300,7 +342,11 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 		let executor = NativeExecutor::<TExecDisp>::new(
 			config.wasm_method,
 			config.default_heap_pages,
-		);
+			config.max_runtime_instances,
+		)
+		.with_wasm_metering(config.wasm_metering)
+		.with_sandbox_backend(config.sandbox_backend);
+		enable_wasm_compilation_cache(&config, &executor);
 
 		let db_storage = {
 			let db_settings = sc_client_db::DatabaseSettings {
@@ -345,6 +391,7 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 			transaction_pool: Arc::new(()),
 			rpc_extensions: Default::default(),
 			remote_backend: Some(remote_blockchain),
+			tasks: ServiceTasks::new(),
 			marker: PhantomData,
 		})
 	}
@@ -369,6 +416,16 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 		self.select_chain.as_ref()
 	}
 
+	/// Returns a handle for spawning tasks in the service that is being built.
+	///
+	/// The tasks only start running once the service is built and polled.
+	pub fn spawn_task_handle(&self) -> SpawnTaskHandle {
+		SpawnTaskHandle {
+			sender: self.tasks.to_spawn_tx.clone(),
+			on_exit: self.tasks.exit.clone(),
+		}
+	}
+
 	/// Defines which head-of-chain strategy to use.
 	pub fn with_opt_select_chain<USc>(
 		self,
@@ -393,6 +450,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
+			tasks: self.tasks,
 			marker: self.marker,
 		})
 	}
@@ -435,6 +493,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
+			tasks: self.tasks,
 			marker: self.marker,
 		})
 	}
@@ -461,6 +520,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
+			tasks: self.tasks,
 			marker: self.marker,
 		})
 	}
@@ -502,6 +562,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
+			tasks: self.tasks,
 			marker: self.marker,
 		})
 	}
@@ -567,6 +628,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
+			tasks: self.tasks,
 			marker: self.marker,
 		})
 	}
@@ -622,6 +684,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			transaction_pool: Arc::new(transaction_pool),
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
+			tasks: self.tasks,
 			marker: self.marker,
 		})
 	}
@@ -661,6 +724,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			transaction_pool: self.transaction_pool,
 			rpc_extensions,
 			remote_backend: self.remote_backend,
+			tasks: self.tasks,
 			marker: self.marker,
 		})
 	}
@@ -698,6 +762,19 @@ pub trait ServiceBuilderCommand {
 		self,
 		block: BlockId<Self::Block>
 	) -> Box<dyn Future<Item = (), Error = Error> + Send>;
+
+	/// Check that the chain can be upgraded to the runtime `code` on top of `block`, the best
+	/// block by default.
+	///
+	/// The state of `block` is used unless a `snapshot` of the state is given, in which case
+	/// `block` is required and must be the block of the snapshot. Fails if the new runtime can't
+	/// be executed, if its metadata can't be decoded or if its migrations fail.
+	fn try_runtime(
+		&self,
+		block: Option<BlockId<Self::Block>>,
+		code: Vec<u8>,
+		snapshot: Option<StorageMap>,
+	) -> Result<(), Error>;
 }
 
 impl<TBl, TRtApi, TCfg, TGen, TCSExt, TBackend, TExec, TSc, TImpQu, TNetP, TExPool, TRpc>
@@ -769,6 +846,7 @@ ServiceBuilder<
 			transaction_pool,
 			rpc_extensions,
 			remote_backend,
+			tasks: ServiceTasks { signal, exit, to_spawn_tx, to_spawn_rx },
 		} = self;
 
 		sp_session::generate_initial_session_keys(
@@ -777,12 +855,6 @@ ServiceBuilder<
 			config.dev_key_seed.clone().map(|s| vec![s]).unwrap_or_default(),
 		)?;
 
-		let (signal, exit) = exit_future::signal();
-
-		// List of asynchronous tasks to spawn. We collect them, then spawn them all at once.
-		let (to_spawn_tx, to_spawn_rx) =
-			mpsc::unbounded::<Box<dyn Future<Item = (), Error = ()> + Send>>();
-
 		// A side-channel for essential tasks to communicate shutdown.
 		let (essential_failed_tx, essential_failed_rx) = mpsc::unbounded();
 
@@ -948,6 +1020,9 @@ ServiceBuilder<
 				"height" => best_number,
 				"best" => ?best_hash,
 				"txcount" => txpool_status.ready,
+				"txpool_replaced" => txpool_status.evictions.replaced,
+				"txpool_evicted" => txpool_status.evictions.evicted,
+				"txpool_rejected_sender_limit" => txpool_status.evictions.rejected_sender_limit,
 				"cpu" => cpu_usage,
 				"memory" => memory,
 				"finalized_height" => finalized_number,
@@ -963,6 +1038,9 @@ ServiceBuilder<
 				"peers" => num_peers,
 				"height" => best_number,
 				"txcount" => txpool_status.ready,
+				"txpool_replaced" => txpool_status.evictions.replaced,
+				"txpool_evicted" => txpool_status.evictions.evicted,
+				"txpool_rejected_sender_limit" => txpool_status.evictions.rejected_sender_limit,
 				"cpu" => cpu_usage,
 				"memory" => memory,
 				"finalized_height" => finalized_number,
@@ -993,7 +1071,7 @@ ServiceBuilder<
 
 		// RPC
 		let (system_rpc_tx, system_rpc_rx) = futures03::channel::mpsc::unbounded();
-		let gen_handler = || {
+		let gen_handler = |middleware: sc_rpc_server::RpcMiddleware| {
 			use sc_rpc::{chain, state, author, system};
 
 			let system_info = sc_rpc::system::SystemInfo {
@@ -1006,7 +1084,10 @@ ServiceBuilder<
 			let subscriptions = sc_rpc::Subscriptions::new(Arc::new(SpawnTaskHandle {
 				sender: to_spawn_tx.clone(),
 				on_exit: exit.clone()
-			}));
+			})).with_end_listener({
+				let middleware = middleware.clone();
+				move |id| middleware.release_subscription(id)
+			});
 
 			let (chain, state) = if let (Some(remote_backend), Some(on_demand)) =
 				(remote_backend.as_ref(), on_demand.as_ref()) {
@@ -1046,9 +1127,9 @@ ServiceBuilder<
 				author::AuthorApi::to_delegate(author),
 				system::SystemApi::to_delegate(system),
 				rpc_extensions.clone(),
-			))
+			), middleware)
 		};
-		let rpc_handlers = gen_handler();
+		let rpc_handlers = gen_handler(Default::default());
 		let rpc = start_rpc_servers(&config, gen_handler)?;
 
 
@@ -25,18 +25,25 @@ use futures::{future, prelude::*};
 use futures03::{
 	TryFutureExt as _,
 };
-use sp_core::{Blake2Hasher, Hasher};
+use sp_core::{
+	Blake2Hasher, Hasher, NativeOrEncoded, NeverNativeValue, OpaqueMetadata, twox_128,
+	storage::{ChildInfo, StorageMap, well_known_keys},
+};
 use sp_runtime::traits::{
 	Block as BlockT, NumberFor, One, Zero, Header, SaturatedConversion
 };
 use sp_runtime::generic::{BlockId, SignedBlock};
+use sp_state_machine::{Backend as StateBackend, OverlayedChanges, backend::InMemory};
 use codec::{Decode, Encode, IoReader};
-use sc_client::Client;
+use sc_client::{Client, CallExecutor, ExecutionStrategy};
+use sc_executor::RuntimeVersion;
 use sp_consensus::import_queue::{IncomingBlock, Link, BlockImportError, BlockImportResult, ImportQueue};
 use sp_consensus::BlockOrigin;
+use frame_metadata::RuntimeMetadataPrefixed;
 
 use std::{
 	io::{Read, Write, Seek},
+	time::Instant,
 };
 
 use sc_network::message;
@@ -309,5 +316,409 @@ impl<
 			Err(e) => Box::new(future::err(format!("Error reading block: {:?}", e).into())),
 		}
 	}
+
+	fn try_runtime(
+		&self,
+		block_id: Option<BlockId<TBl>>,
+		code: Vec<u8>,
+		snapshot: Option<StorageMap>,
+	) -> Result<(), Error> {
+		// the header of the next block is built on top of the block of the snapshot
+		let block_id = match (block_id, &snapshot) {
+			(Some(block_id), _) => block_id,
+			(None, None) => BlockId::Hash(self.client.chain_info().best_hash),
+			(None, Some(_)) => return Err("The block of the snapshot must be given".into()),
+		};
+		let parent = self.client.header(&block_id)?.ok_or("Unknown block")?;
+		let executor = self.client.executor();
+
+		match snapshot {
+			Some(snapshot) => {
+				info!(
+					"Checking the runtime upgrade on top of a snapshot of {} keys, as block #{}",
+					snapshot.len(),
+					parent.number(),
+				);
+				let state = InMemory::<Blake2Hasher>::from(snapshot);
+				try_runtime_upgrade::<TBl, _, _>(executor, &state, &parent, code)
+			},
+			None => {
+				info!(
+					"Checking the runtime upgrade on top of block #{} ({})",
+					parent.number(),
+					parent.hash(),
+				);
+				let state = self.client.state_at(&block_id)?;
+				try_runtime_upgrade::<TBl, _, _>(executor, &state, &parent, code)
+			},
+		}
+	}
+}
+
+/// Key of the weight registered for the current block by `frame_system`.
+fn block_weight_key() -> Vec<u8> {
+	[twox_128(b"System"), twox_128(b"AllExtrinsicsWeight")].concat()
+}
+
+/// Check the upgrade to the runtime `code` on top of `state`, which is the state after the block
+/// `parent`.
+///
+/// The new runtime is executed in Wasm, against the next block. FRAME has no dedicated hook run
+/// on runtime upgrades, so the migrations are the `on_initialize` hooks run by
+/// `Core_initialize_block`.
+fn try_runtime_upgrade<TBl, TExec, S>(
+	executor: &TExec,
+	state: &S,
+	parent: &TBl::Header,
+	code: Vec<u8>,
+) -> Result<(), Error> where
+	TBl: BlockT<Hash = <Blake2Hasher as Hasher>::Out>,
+	TExec: CallExecutor<TBl, Blake2Hasher>,
+	S: StateBackend<Blake2Hasher>,
+{
+	let new_state = StateWithCode { state, code };
+	let current_version = runtime_version::<TBl, _, _>(executor, state)?;
+	let new_version = runtime_version::<TBl, _, _>(executor, &new_state)?;
+	info!("Upgrading the runtime from {} to {}", current_version, new_version);
+
+	if new_version.spec_name != current_version.spec_name {
+		return Err(Error::Other(format!(
+			"The new runtime is for `{}` instead of `{}`",
+			new_version.spec_name,
+			current_version.spec_name,
+		)));
+	}
+	if new_version.spec_version <= current_version.spec_version {
+		warn!("The `spec_version` is not increased, nodes will keep using their native runtime.");
+	}
+
+	let metadata = call_in_wasm::<TBl, _, _>(
+		executor,
+		&new_state,
+		&mut Default::default(),
+		"Metadata_metadata",
+		&[],
+	)?;
+	OpaqueMetadata::decode(&mut &metadata[..])
+		.and_then(|metadata| RuntimeMetadataPrefixed::decode(&mut &metadata[..]))
+		.map_err(|e| Error::Other(format!("Invalid metadata: {:?}", e)))?;
+	info!("Metadata decoded.");
+
+	let header = <TBl::Header as Header>::new(
+		*parent.number() + One::one(),
+		Default::default(),
+		Default::default(),
+		parent.hash(),
+		Default::default(),
+	);
+	let mut overlay = OverlayedChanges::default();
+	let start = Instant::now();
+	call_in_wasm::<TBl, _, _>(executor, &new_state, &mut overlay, "Core_initialize_block", &header.encode())
+		.map_err(|e| Error::Other(format!("Migrations failed: {}", e)))?;
+	let elapsed = start.elapsed();
+
+	overlay.commit_prospective();
+	let weight = overlay.storage(&block_weight_key())
+		.and_then(|weight| weight.and_then(|weight| u32::decode(&mut &weight[..]).ok()))
+		.unwrap_or_default();
+
+	let (top, children) = overlay.into_committed();
+	let (mut written, mut written_bytes, mut deleted) = (0, 0, 0);
+	for (_, value) in top.chain(children.flat_map(|(_, (changes, _))| changes)) {
+		match value {
+			Some(value) => {
+				written += 1;
+				written_bytes += value.len();
+			},
+			None => deleted += 1,
+		}
+	}
+
+	info!(
+		"Migrations executed in {} ms: {} keys written ({} bytes), {} keys deleted, weight {}.",
+		elapsed.as_millis(),
+		written,
+		written_bytes,
+		deleted,
+		weight,
+	);
+
+	Ok(())
+}
+
+/// The version of the runtime of `state`, executed in Wasm.
+fn runtime_version<TBl, TExec, S>(executor: &TExec, state: &S) -> Result<RuntimeVersion, Error> where
+	TBl: BlockT<Hash = <Blake2Hasher as Hasher>::Out>,
+	TExec: CallExecutor<TBl, Blake2Hasher>,
+	S: StateBackend<Blake2Hasher>,
+{
+	let encoded = call_in_wasm::<TBl, _, _>(executor, state, &mut Default::default(), "Core_version", &[])?;
+	RuntimeVersion::decode(&mut &encoded[..])
+		.map_err(|e| Error::Other(format!("Invalid runtime version: {:?}", e)))
+}
+
+/// Call `method` of the runtime in Wasm, on top of `state` and `overlay`.
+fn call_in_wasm<TBl, TExec, S>(
+	executor: &TExec,
+	state: &S,
+	overlay: &mut OverlayedChanges,
+	method: &str,
+	call_data: &[u8],
+) -> Result<Vec<u8>, Error> where
+	TBl: BlockT<Hash = <Blake2Hasher as Hasher>::Out>,
+	TExec: CallExecutor<TBl, Blake2Hasher>,
+	S: StateBackend<Blake2Hasher>,
+{
+	let (result, _, _) = executor.call_at_state::<_, _, NeverNativeValue, fn() -> _>(
+		state,
+		overlay,
+		method,
+		call_data,
+		ExecutionStrategy::AlwaysWasm.get_manager(),
+		None,
+		None,
+	)?;
+
+	match result {
+		NativeOrEncoded::Encoded(encoded) => Ok(encoded),
+		NativeOrEncoded::Native(never) => match never {},
+	}
+}
+
+/// State replacing the runtime code of `state` with `code`.
+///
+/// The runtime executing a call is the one of the code in the state backend, the code changed in
+/// the overlay only taking effect at the next block. Executing the new runtime thus requires to
+/// replace the code in the backend itself.
+struct StateWithCode<'a, S> {
+	state: &'a S,
+	code: Vec<u8>,
+}
+
+impl<'a, S> StateWithCode<'a, S> {
+	/// The value of `key` in this state, given its `value` in the wrapped state.
+	fn value<'b>(&'b self, key: &[u8], value: &'b [u8]) -> &'b [u8] {
+		if key == well_known_keys::CODE {
+			&self.code
+		} else {
+			value
+		}
+	}
+}
+
+impl<'a, S: std::fmt::Debug> std::fmt::Debug for StateWithCode<'a, S> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "StateWithCode({:?}, {} bytes of code)", self.state, self.code.len())
+	}
+}
+
+impl<'a, S: StateBackend<Blake2Hasher>> StateBackend<Blake2Hasher> for StateWithCode<'a, S> {
+	type Error = S::Error;
+	type Transaction = S::Transaction;
+	type TrieBackendStorage = S::TrieBackendStorage;
+
+	fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		if key == well_known_keys::CODE {
+			Ok(Some(self.code.clone()))
+		} else {
+			self.state.storage(key)
+		}
+	}
+
+	fn child_storage(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.state.child_storage(storage_key, child_info, key)
+	}
+
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.state.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.state.next_child_storage_key(storage_key, child_info, key)
+	}
+
+	fn for_keys_in_child_storage<F: FnMut(&[u8])>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		f: F,
+	) {
+		self.state.for_keys_in_child_storage(storage_key, child_info, f)
+	}
+
+	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
+		self.state.for_keys_with_prefix(prefix, f)
+	}
+
+	fn for_key_values_with_prefix<F: FnMut(&[u8], &[u8])>(&self, prefix: &[u8], mut f: F) {
+		self.state.for_key_values_with_prefix(prefix, |key, value| f(key, self.value(key, value)))
+	}
+
+	fn for_child_keys_with_prefix<F: FnMut(&[u8])>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		prefix: &[u8],
+		f: F,
+	) {
+		self.state.for_child_keys_with_prefix(storage_key, child_info, prefix, f)
+	}
+
+	fn apply_to_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		mut f: F,
+	) {
+		self.state.apply_to_key_values_while(prefix, start_at, |key, value| f(key, self.value(key, value)))
+	}
+
+	fn apply_to_child_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		self.state.apply_to_child_key_values_while(storage_key, child_info, prefix, start_at, f)
+	}
+
+	fn storage_root<I>(&self, delta: I) -> (<Blake2Hasher as Hasher>::Out, Self::Transaction)
+	where
+		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
+		<Blake2Hasher as Hasher>::Out: Ord,
+	{
+		let code = (well_known_keys::CODE.to_vec(), Some(self.code.clone()));
+		self.state.storage_root(std::iter::once(code).chain(delta))
+	}
+
+	fn child_storage_root<I>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		delta: I,
+	) -> (<Blake2Hasher as Hasher>::Out, bool, Self::Transaction)
+	where
+		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
+		<Blake2Hasher as Hasher>::Out: Ord,
+	{
+		self.state.child_storage_root(storage_key, child_info, delta)
+	}
+
+	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+		self.state.pairs().into_iter()
+			.map(|(key, value)| {
+				let value = self.value(&key, &value).to_vec();
+				(key, value)
+			})
+			.collect()
+	}
 }
 
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_test_runtime_client::runtime::{Block, VERSION};
+
+	/// A runtime only able to report the version of the test runtime, with the given
+	/// `spec_name` and `spec_version`.
+	fn runtime_with_version(spec_name: &'static str, spec_version: u32) -> Vec<u8> {
+		let version = RuntimeVersion { spec_name: spec_name.into(), spec_version, ..VERSION }.encode();
+		let data = version.iter().map(|byte| format!("\\{:02x}", byte)).collect::<String>();
+		wabt::wat2wasm(format!(r#"
+			(module
+				(memory (export "memory") 1)
+				(global (export "__heap_base") i32 (i32.const {heap_base}))
+				(data (i32.const 0) "{data}")
+
+				;; the version is returned as a pointer (0) and a length packed in an i64
+				(func (export "Core_version") (param i32 i32) (result i64)
+					i64.const {pointer_and_len}
+				)
+			)
+			"#,
+			heap_base = version.len(),
+			data = data,
+			pointer_and_len = (version.len() as i64) << 32,
+		)).unwrap()
+	}
+
+	#[test]
+	fn should_execute_the_new_runtime() {
+		// given
+		let client = substrate_test_runtime_client::new();
+		let genesis = BlockId::Number(0);
+		let state = client.state_at(&genesis).unwrap();
+
+		// when
+		let current = runtime_version::<Block, _, _>(client.executor(), &state).unwrap();
+		let new = runtime_version::<Block, _, _>(
+			client.executor(),
+			&StateWithCode { state: &state, code: runtime_with_version("test", 2) },
+		).unwrap();
+
+		// then
+		assert_eq!(current.spec_version, 1);
+		assert_eq!(new.spec_version, 2);
+		assert_eq!(&*new.spec_name, "test");
+	}
+
+	#[test]
+	fn should_reject_the_runtime_of_another_chain() {
+		// given
+		let client = substrate_test_runtime_client::new();
+		let genesis = BlockId::Number(0);
+		let state = client.state_at(&genesis).unwrap();
+		let parent = client.header(&genesis).unwrap().unwrap();
+
+		// when
+		let result = try_runtime_upgrade::<Block, _, _>(
+			client.executor(),
+			&state,
+			&parent,
+			runtime_with_version("other", 2),
+		);
+
+		// then
+		match result {
+			Err(Error::Other(message)) => assert_eq!(
+				message,
+				"The new runtime is for `other` instead of `test`",
+			),
+			result => panic!("Unexpected result: {:?}", result),
+		}
+	}
+
+	#[test]
+	fn should_replace_only_the_code() {
+		// given
+		let state = InMemory::<Blake2Hasher>::from(vec![
+			(well_known_keys::CODE.to_vec(), b"old".to_vec()),
+			(b"key".to_vec(), b"value".to_vec()),
+		].into_iter().collect::<StorageMap>());
+		let new_state = StateWithCode { state: &state, code: b"new".to_vec() };
+
+		// when
+		let code = new_state.storage(well_known_keys::CODE).unwrap();
+		let value = new_state.storage(b"key").unwrap();
+		let pairs = new_state.pairs();
+		let (root, _) = new_state.storage_root(std::iter::empty());
+
+		// then
+		assert_eq!(code, Some(b"new".to_vec()));
+		assert_eq!(value, Some(b"value".to_vec()));
+		assert!(pairs.contains(&(well_known_keys::CODE.to_vec(), b"new".to_vec())));
+		assert_eq!(root, state.storage_root(vec![(well_known_keys::CODE.to_vec(), Some(b"new".to_vec()))]).0);
+	}
+}
@@ -17,9 +17,11 @@
 //! Service configuration.
 
 pub use sc_client::ExecutionStrategies;
+pub use sc_client_api::execution_extensions::CallLimits;
 pub use sc_client_db::{kvdb::KeyValueDB, PruningMode};
 pub use sc_network::config::{ExtTransport, NetworkConfiguration, Roles};
-pub use sc_executor::WasmExecutionMethod;
+pub use sc_executor::{SandboxBackend, WasmExecutionMethod};
+pub use sc_rpc_server::{RpcPolicies, RpcPolicy};
 
 use std::{path::PathBuf, net::SocketAddr, sync::Arc};
 pub use sc_transaction_pool::txpool::Options as TransactionPoolOptions;
@@ -63,16 +65,30 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub name: String,
 	/// Wasm execution method.
 	pub wasm_method: WasmExecutionMethod,
+	/// Engine executing the modules sandboxed by the runtime in Wasm, e.g. the smart contracts.
+	pub sandbox_backend: SandboxBackend,
 	/// Execution strategies.
 	pub execution_strategies: ExecutionStrategies,
+	/// Directory the mismatches between the native and wasm executions are dumped to, if any.
+	pub execution_mismatch_dump: Option<PathBuf>,
+	/// Whether the Wasm execution is metered, which is required to enforce `call_limits`.
+	pub wasm_metering: bool,
+	/// Limits on the resources consumed by the runtime calls made outside of consensus.
+	pub call_limits: CallLimits,
 	/// RPC over HTTP binding address. `None` if disabled.
 	pub rpc_http: Option<SocketAddr>,
 	/// RPC over Websockets binding address. `None` if disabled.
 	pub rpc_ws: Option<SocketAddr>,
 	/// Maximum number of connections for WebSockets RPC server. `None` if default.
 	pub rpc_ws_max_connections: Option<usize>,
+	/// RPC over IPC binding path. `None` if disabled.
+	pub rpc_ipc: Option<String>,
+	/// File permissions of the IPC socket. `None` to keep the default ones.
+	pub rpc_ipc_permissions: Option<u32>,
 	/// CORS settings for HTTP & WS servers. `None` if all origins are allowed.
 	pub rpc_cors: Option<Vec<String>>,
+	/// Access control policies of the RPC servers.
+	pub rpc_policies: RpcPolicies,
 	/// Grafana data source http port. `None` if disabled.
 	pub grafana_port: Option<SocketAddr>,
 	/// Telemetry service URL. `None` if disabled.
@@ -82,6 +98,10 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub telemetry_external_transport: Option<ExtTransport>,
 	/// The default number of 64KB pages to allocate for Wasm execution
 	pub default_heap_pages: Option<u64>,
+	/// Maximum number of idle instances kept per Wasm runtime by every thread.
+	pub max_runtime_instances: usize,
+	/// Directory caching the code compiled by `WasmExecutionMethod::Compiled`. `None` if disabled.
+	pub wasm_compilation_cache: Option<PathBuf>,
 	/// Should offchain workers be executed.
 	pub offchain_worker: bool,
 	/// Sentry mode is enabled, the node's role is AUTHORITY but it should not
@@ -92,6 +112,10 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub force_authoring: bool,
 	/// Disable GRANDPA when running in validator mode
 	pub disable_grandpa: bool,
+	/// Follow GRANDPA finality by observing commits when running as a light client.
+	///
+	/// Full nodes that aren't authorities always observe GRANDPA.
+	pub grandpa_observer: bool,
 	/// Development key seed.
 	///
 	/// When running in development mode, the seed will be used to generate authority keys by the keystore.
@@ -163,19 +187,29 @@ impl<C, G, E> Configuration<C, G, E> where
 			custom: Default::default(),
 			pruning: PruningMode::default(),
 			wasm_method: WasmExecutionMethod::Interpreted,
+			sandbox_backend: SandboxBackend::Wasmi,
 			execution_strategies: Default::default(),
+			execution_mismatch_dump: None,
+			wasm_metering: false,
+			call_limits: Default::default(),
 			rpc_http: None,
 			rpc_ws: None,
 			rpc_ws_max_connections: None,
+			rpc_ipc: None,
+			rpc_ipc_permissions: None,
 			rpc_cors: Some(vec![]),
+			rpc_policies: Default::default(),
 			grafana_port: None,
 			telemetry_endpoints: None,
 			telemetry_external_transport: None,
 			default_heap_pages: None,
+			max_runtime_instances: 8,
+			wasm_compilation_cache: None,
 			offchain_worker: Default::default(),
 			sentry_mode: false,
 			force_authoring: false,
 			disable_grandpa: false,
+			grandpa_observer: false,
 			dev_key_seed: None,
 			tracing_targets: Default::default(),
 			tracing_receiver: Default::default(),
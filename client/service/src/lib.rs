@@ -61,7 +61,11 @@ pub use self::builder::{
 pub use config::{Configuration, Roles, PruningMode};
 pub use sc_chain_spec::{ChainSpec, Properties, RuntimeGenesis, Extension as ChainSpecExtension};
 pub use sp_transaction_pool::{TransactionPool, TransactionPoolMaintainer, InPoolTransaction, error::IntoPoolError};
-pub use sc_transaction_pool::txpool::Options as TransactionPoolOptions;
+pub use sc_transaction_pool::txpool::{
+	Options as TransactionPoolOptions,
+	SenderLimit as TransactionPoolSenderLimit,
+	JournalOptions as TransactionPoolJournalOptions,
+};
 pub use sc_client::FinalityNotifications;
 pub use sc_rpc::Metadata as RpcMetadata;
 #[doc(hidden)]
@@ -473,6 +477,7 @@ fn build_network_future<
 				num_active_peers: network.num_active_peers(),
 				average_download_per_sec: network.average_download_per_sec(),
 				average_upload_per_sec: network.average_upload_per_sec(),
+				protocol_traffic: network.protocol_traffic(),
 			};
 			let state = network.network_state();
 			(status, state)
@@ -515,6 +520,8 @@ pub struct NetworkStatus<B: BlockT> {
 	pub average_download_per_sec: u64,
 	/// Uploaded bytes per second averaged over the past few seconds.
 	pub average_upload_per_sec: u64,
+	/// Traffic exchanged since the network started, per protocol.
+	pub protocol_traffic: HashMap<String, sc_network::ProtocolTraffic>,
 }
 
 impl<TBl, TCl, TSc, TNetStatus, TNet, TTxPool, TOc> Drop for
@@ -530,7 +537,7 @@ impl<TBl, TCl, TSc, TNetStatus, TNet, TTxPool, TOc> Drop for
 
 /// Starts RPC servers that run in their own thread, and returns an opaque object that keeps them alive.
 #[cfg(not(target_os = "unknown"))]
-fn start_rpc_servers<C, G, E, H: FnMut() -> sc_rpc_server::RpcHandler<sc_rpc::Metadata>>(
+fn start_rpc_servers<C, G, E, H: FnMut(sc_rpc_server::RpcMiddleware) -> sc_rpc_server::RpcHandler<sc_rpc::Metadata>>(
 	config: &Configuration<C, G, E>,
 	mut gen_handler: H
 ) -> Result<Box<dyn std::any::Any + Send + Sync>, error::Error> {
@@ -555,7 +562,12 @@ fn start_rpc_servers<C, G, E, H: FnMut() -> sc_rpc_server::RpcHandler<sc_rpc::Me
 	Ok(Box::new((
 		maybe_start_server(
 			config.rpc_http,
-			|address| sc_rpc_server::start_http(address, config.rpc_cors.as_ref(), gen_handler()),
+			|address| sc_rpc_server::start_http(
+				address,
+				config.rpc_cors.as_ref(),
+				&config.rpc_policies.http,
+				gen_handler(sc_rpc_server::RpcMiddleware::new(config.rpc_policies.http.clone())),
+			),
 		)?,
 		maybe_start_server(
 			config.rpc_ws,
@@ -563,15 +575,24 @@ fn start_rpc_servers<C, G, E, H: FnMut() -> sc_rpc_server::RpcHandler<sc_rpc::Me
 				address,
 				config.rpc_ws_max_connections,
 				config.rpc_cors.as_ref(),
-				gen_handler(),
+				&config.rpc_policies.ws,
+				gen_handler(sc_rpc_server::RpcMiddleware::new(config.rpc_policies.ws.clone())),
 			),
 		)?.map(Mutex::new),
+		config.rpc_ipc.as_ref()
+			.map(|path| sc_rpc_server::start_ipc(
+				path,
+				config.rpc_ipc_permissions,
+				gen_handler(sc_rpc_server::RpcMiddleware::per_server(config.rpc_policies.ipc.clone())),
+			))
+			.transpose()?
+			.map(Mutex::new),
 	)))
 }
 
 /// Starts RPC servers that run in their own thread, and returns an opaque object that keeps them alive.
 #[cfg(target_os = "unknown")]
-fn start_rpc_servers<C, G, E, H: FnMut() -> sc_rpc_server::RpcHandler<sc_rpc::Metadata>>(
+fn start_rpc_servers<C, G, E, H: FnMut(sc_rpc_server::RpcMiddleware) -> sc_rpc_server::RpcHandler<sc_rpc::Metadata>>(
 	_: &Configuration<C, G, E>,
 	_: H
 ) -> Result<Box<dyn std::any::Any + Send + Sync>, error::Error> {
@@ -126,7 +126,8 @@ where F: Send + 'static, L: Send +'static, U: Clone + Send + 'static
 	}
 }
 
-fn node_config<G, E: Clone> (
+/// Configuration of the test node with the given `index`, stored under `root`.
+pub fn node_config<G, E: Clone> (
 	index: usize,
 	spec: &ChainSpec<G, E>,
 	role: Roles,
@@ -164,6 +165,7 @@ fn node_config<G, E: Clone> (
 			wasm_external_transport: None,
 		},
 		max_parallel_downloads: NetworkConfiguration::default().max_parallel_downloads,
+		bandwidth: Default::default(),
 	};
 
 	Configuration {
@@ -189,19 +191,29 @@ fn node_config<G, E: Clone> (
 		custom: Default::default(),
 		name: format!("Node {}", index),
 		wasm_method: sc_service::config::WasmExecutionMethod::Interpreted,
+		sandbox_backend: sc_service::config::SandboxBackend::Wasmi,
 		execution_strategies: Default::default(),
+		execution_mismatch_dump: None,
+		wasm_metering: false,
+		call_limits: Default::default(),
 		rpc_http: None,
 		rpc_ws: None,
 		rpc_ws_max_connections: None,
+		rpc_ipc: None,
+		rpc_ipc_permissions: None,
 		rpc_cors: None,
+		rpc_policies: Default::default(),
 		grafana_port: None,
 		telemetry_endpoints: None,
 		telemetry_external_transport: None,
 		default_heap_pages: None,
+		max_runtime_instances: 8,
+		wasm_compilation_cache: None,
 		offchain_worker: false,
 		sentry_mode: false,
 		force_authoring: false,
 		disable_grandpa: false,
+		grandpa_observer: false,
 		dev_key_seed: key_seed,
 		tracing_targets: None,
 		tracing_receiver: Default::default(),
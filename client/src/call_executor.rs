@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{sync::Arc, panic::UnwindSafe, result, cell::RefCell};
+use std::{sync::Arc, panic::UnwindSafe, result, cell::RefCell, any::TypeId};
 use codec::{Encode, Decode};
 use sp_runtime::{
 	generic::BlockId, traits::Block as BlockT, traits::NumberFor,
@@ -28,7 +28,7 @@ use sp_externalities::Extensions;
 use hash_db::Hasher;
 use sp_core::{
 	H256, Blake2Hasher, NativeOrEncoded, NeverNativeValue,
-	traits::CodeExecutor,
+	traits::{CodeExecutor, ExecutionLimits, ExecutionLimitsExt, LimitExceeded},
 };
 use sp_api::{ProofRecorder, InitializeBlock};
 use sc_client_api::{backend, call_executor::CallExecutor};
@@ -53,6 +53,23 @@ impl<B, E> LocalCallExecutor<B, E> {
 	}
 }
 
+/// Returns the execution limits registered in `extensions`, if any.
+fn execution_limits(extensions: &mut Option<Extensions>) -> Option<ExecutionLimits> {
+	extensions.as_mut()?
+		.get_mut(TypeId::of::<ExecutionLimitsExt>())?
+		.downcast_ref::<ExecutionLimitsExt>()
+		.map(|limits| limits.0.clone())
+}
+
+/// Replace `error` by the limit the call exceeded, if any.
+fn limit_error(limits: &Option<ExecutionLimits>, error: sp_blockchain::Error) -> sp_blockchain::Error {
+	match limits.as_ref().and_then(ExecutionLimits::exceeded) {
+		Some(LimitExceeded::Deadline) => sp_blockchain::Error::Timeout,
+		Some(LimitExceeded::Fuel) => sp_blockchain::Error::OutOfFuel,
+		None => error,
+	}
+}
+
 impl<B, E> Clone for LocalCallExecutor<B, E> where E: Clone {
 	fn clone(&self) -> Self {
 		LocalCallExecutor {
@@ -76,8 +93,9 @@ impl<B, E, Block> CallExecutor<Block, Blake2Hasher> for LocalCallExecutor<B, E>
 		method: &str,
 		call_data: &[u8],
 		strategy: ExecutionStrategy,
-		extensions: Option<Extensions>,
+		mut extensions: Option<Extensions>,
 	) -> sp_blockchain::Result<Vec<u8>> {
+		let limits = execution_limits(&mut extensions);
 		let mut changes = OverlayedChanges::default();
 		let state = self.backend.state_at(*id)?;
 		let return_data = StateMachine::new(
@@ -93,7 +111,8 @@ impl<B, E, Block> CallExecutor<Block, Blake2Hasher> for LocalCallExecutor<B, E>
 			false,
 			None,
 		)
-		.map(|(result, _, _)| result)?;
+		.map(|(result, _, _)| result)
+		.map_err(|e| limit_error(&limits, e.into()))?;
 		{
 			let _lock = self.backend.get_import_lock().read();
 			self.backend.destroy_state(state)?;
@@ -121,8 +140,10 @@ impl<B, E, Block> CallExecutor<Block, Blake2Hasher> for LocalCallExecutor<B, E>
 		execution_manager: ExecutionManager<EM>,
 		native_call: Option<NC>,
 		recorder: &Option<ProofRecorder<Block>>,
-		extensions: Option<Extensions>,
+		mut extensions: Option<Extensions>,
 	) -> Result<NativeOrEncoded<R>, sp_blockchain::Error> where ExecutionManager<EM>: Clone {
+		let limits = execution_limits(&mut extensions);
+
 		match initialize_block {
 			InitializeBlock::Do(ref init_block)
 				if init_block.borrow().as_ref().map(|id| id != at).unwrap_or(true) => {
@@ -179,7 +200,7 @@ impl<B, E, Block> CallExecutor<Block, Blake2Hasher> for LocalCallExecutor<B, E>
 				native_call,
 			)
 			.map(|(result, _, _)| result)
-		}?;
+		}.map_err(|e| limit_error(&limits, e.into()))?;
 		{
 			let _lock = self.backend.get_import_lock().read();
 			self.backend.destroy_state(state)?;
@@ -32,6 +32,7 @@ use sp_core::{
 	traits::CodeExecutor,
 };
 use sc_telemetry::{telemetry, SUBSTRATE_INFO};
+use sp_externalities::Extensions;
 use sp_runtime::{
 	Justification, BuildStorage,
 	generic::{BlockId, SignedBlock, DigestItem},
@@ -256,6 +257,45 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Ok(keys)
 	}
 
+	/// Given a `BlockId` and a key prefix, return up to `count` matching keys in that block,
+	/// starting after `start_key` if given.
+	pub fn storage_keys_paged(
+		&self,
+		id: &BlockId<Block>,
+		key_prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		count: usize,
+	) -> sp_blockchain::Result<Vec<StorageKey>> {
+		Ok(self.storage_pairs_paged(id, key_prefix, start_key, count)?
+			.into_iter()
+			.map(|(key, _)| key)
+			.collect())
+	}
+
+	/// Given a `BlockId` and a key prefix, return up to `count` matching key-value pairs in
+	/// that block, starting after `start_key` if given.
+	pub fn storage_pairs_paged(
+		&self,
+		id: &BlockId<Block>,
+		key_prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		count: usize,
+	) -> sp_blockchain::Result<Vec<(StorageKey, StorageData)>> {
+		let mut pairs = Vec::new();
+		if count == 0 {
+			return Ok(pairs);
+		}
+		self.state_at(id)?.apply_to_key_values_while(
+			&key_prefix.0,
+			start_key.map(|key| &key.0[..]),
+			|key, value| {
+				pairs.push((StorageKey(key.to_vec()), StorageData(value.to_vec())));
+				pairs.len() < count
+			},
+		);
+		Ok(pairs)
+	}
+
 	/// Given a `BlockId` and a key, return the value under the key in that block.
 	pub fn storage(&self, id: &BlockId<Block>, key: &StorageKey) -> sp_blockchain::Result<Option<StorageData>> {
 		Ok(self.state_at(id)?
@@ -288,6 +328,51 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Ok(keys)
 	}
 
+	/// Given a `BlockId`, a key prefix, and a child storage key, return up to `count` matching
+	/// child storage keys, starting after `start_key` if given.
+	pub fn child_storage_keys_paged(
+		&self,
+		id: &BlockId<Block>,
+		child_storage_key: &StorageKey,
+		child_info: ChildInfo,
+		key_prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		count: usize,
+	) -> sp_blockchain::Result<Vec<StorageKey>> {
+		Ok(self.child_storage_pairs_paged(id, child_storage_key, child_info, key_prefix, start_key, count)?
+			.into_iter()
+			.map(|(key, _)| key)
+			.collect())
+	}
+
+	/// Given a `BlockId`, a key prefix, and a child storage key, return up to `count` matching
+	/// child storage key-value pairs, starting after `start_key` if given.
+	pub fn child_storage_pairs_paged(
+		&self,
+		id: &BlockId<Block>,
+		child_storage_key: &StorageKey,
+		child_info: ChildInfo,
+		key_prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		count: usize,
+	) -> sp_blockchain::Result<Vec<(StorageKey, StorageData)>> {
+		let mut pairs = Vec::new();
+		if count == 0 {
+			return Ok(pairs);
+		}
+		self.state_at(id)?.apply_to_child_key_values_while(
+			&child_storage_key.0,
+			child_info,
+			&key_prefix.0,
+			start_key.map(|key| &key.0[..]),
+			|key, value| {
+				pairs.push((StorageKey(key.to_vec()), StorageData(value.to_vec())));
+				pairs.len() < count
+			},
+		);
+		Ok(pairs)
+	}
+
 	/// Given a `BlockId`, a key and a child storage key, return the value under the key in that block.
 	pub fn child_storage(
 		&self,
@@ -333,6 +418,11 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		&self.executor
 	}
 
+	/// Get backend reference.
+	pub fn backend(&self) -> &Arc<B> {
+		&self.backend
+	}
+
 	/// Reads storage value at a given block + key, returning read proof.
 	pub fn read_proof<I>(&self, id: &BlockId<Block>, keys: I) -> sp_blockchain::Result<StorageProof> where
 		I: IntoIterator,
@@ -1009,6 +1099,14 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 					body,
 				);
 
+				let extensions = self.execution_extensions()
+					.mismatch_dump(format!("block #{} ({})", import_headers.post().number(), hash))
+					.map(|dump| {
+						let mut extensions = Extensions::new();
+						extensions.register(dump);
+						extensions
+					});
+
 				let (_, storage_update, changes_update) = self.executor
 					.call_at_state::<_, _, NeverNativeValue, fn() -> _>(
 						transaction_state,
@@ -1022,7 +1120,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 							_ => get_execution_manager(self.execution_extensions().strategies().importing),
 						},
 						None,
-						None,
+						extensions,
 					)?;
 
 				overlay.commit_prospective();
@@ -35,9 +35,13 @@
 //! let _guard = span.enter();
 //! ```
 //! Currently we provide `Log` (default), `Telemetry` and `Grafana` variants for `Receiver`
+//!
+//! Spans and events can also be collected in memory for the duration of a single call with
+//! a [`SpanCollector`], e.g. to return them to an RPC caller.
 
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
@@ -45,17 +49,25 @@ use parking_lot::Mutex;
 use serde::ser::{Serialize, Serializer, SerializeMap};
 use slog::{SerdeValue, Value};
 use tracing_core::{
+	dispatcher::{self, Dispatch},
 	event::Event,
 	field::{Visit, Field},
 	Level,
 	metadata::Metadata,
 	span::{Attributes, Id, Record},
-	subscriber::Subscriber
+	subscriber::{Interest, Subscriber},
 };
 
 use grafana_data_source::{self, record_metrics};
 use sc_telemetry::{telemetry, SUBSTRATE_INFO};
 
+/// Target of the spans entered by the runtime in Wasm, as given by `sp_io::WASM_TRACE_TARGET`.
+const WASM_TRACE_TARGET: &str = "wasm_tracing";
+/// Key of the value holding the target given by the runtime to a span entered in Wasm.
+const WASM_TARGET_KEY: &str = "wasm_target";
+/// Key of the value holding the name given by the runtime to a span entered in Wasm.
+const WASM_NAME_KEY: &str = "wasm_name";
+
 /// Used to configure how to receive the metrics
 #[derive(Debug, Clone)]
 pub enum TracingReceiver {
@@ -101,6 +113,10 @@ impl Visit for Visitor {
 		self.record_debug(field, &value)
 	}
 
+	fn record_str(&mut self, field: &Field, value: &str) {
+		self.0.push((field.name().to_string(), value.to_string()))
+	}
+
 	fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
 		self.0.push((field.name().to_string(), format!("{:?}",value)));
 	}
@@ -297,3 +313,267 @@ fn send_grafana(span_datum: SpanDatum) {
 		log::warn!("Unable to send metrics to grafana: {:?}", e);
 	}
 }
+
+/// A span recorded by a [`SpanCollector`].
+#[derive(Debug, Clone)]
+pub struct CollectedSpan {
+	/// Id of the span, unique within the collector.
+	pub id: u64,
+	/// Id of the parent span, if any.
+	pub parent_id: Option<u64>,
+	/// Name of the span.
+	pub name: String,
+	/// Target of the span.
+	pub target: String,
+	/// Level of the span.
+	pub level: Level,
+	/// Line of the span in its source file.
+	pub line: u32,
+	/// Values recorded with the span.
+	pub values: Vec<(String, String)>,
+	/// Time spent inside the span.
+	pub overall_time: Duration,
+}
+
+/// An event recorded by a [`SpanCollector`].
+#[derive(Debug, Clone)]
+pub struct CollectedEvent {
+	/// Id of the span the event happened in, if any.
+	pub parent_id: Option<u64>,
+	/// Name of the event.
+	pub name: String,
+	/// Target of the event.
+	pub target: String,
+	/// Level of the event.
+	pub level: Level,
+	/// Values recorded with the event.
+	pub values: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+struct CollectorState {
+	open: HashMap<u64, CollectedSpan>,
+	entered: Vec<(u64, Instant)>,
+	spans: Vec<CollectedSpan>,
+	events: Vec<CollectedEvent>,
+}
+
+/// Collects spans and events in memory instead of sending them to a receiver.
+///
+/// Unlike the `ProfilingSubscriber` it is not meant to be installed globally,
+/// but only for the duration of [`SpanCollector::collect`].
+#[derive(Clone)]
+pub struct SpanCollector {
+	next_id: Arc<AtomicU64>,
+	targets: Arc<Vec<(String, Level)>>,
+	state: Arc<Mutex<CollectorState>>,
+}
+
+impl SpanCollector {
+	/// Takes a comma separated list of targets, in the same format as
+	/// `ProfilingSubscriber::new`.
+	pub fn new(targets: &str) -> Self {
+		SpanCollector {
+			next_id: Arc::new(AtomicU64::new(1)),
+			targets: Arc::new(targets.split(',').map(|s| parse_target(s)).collect()),
+			state: Default::default(),
+		}
+	}
+
+	/// Run `f` with this collector as the default subscriber of the current thread.
+	pub fn collect<R>(&self, f: impl FnOnce() -> R) -> R {
+		dispatcher::with_default(&Dispatch::new(self.clone()), f)
+	}
+
+	/// Take the spans and events collected so far, in the order they were closed and
+	/// emitted respectively. Spans which have not been closed yet are returned last.
+	pub fn finish(&self) -> (Vec<CollectedSpan>, Vec<CollectedEvent>) {
+		let mut state = self.state.lock();
+		let mut spans = std::mem::replace(&mut state.spans, Vec::new());
+		let mut open = state.open.drain().map(|(_, span)| span).collect::<Vec<_>>();
+		open.sort_by_key(|span| span.id);
+		spans.extend(open);
+		(spans, std::mem::replace(&mut state.events, Vec::new()))
+	}
+
+	fn current_span(state: &CollectorState) -> Option<u64> {
+		state.entered.last().map(|(id, _)| *id)
+	}
+
+	fn is_target_enabled(&self, target: &str, level: &Level) -> bool {
+		self.targets.iter().any(|(enabled, max_level)| target.starts_with(enabled.as_str()) && level <= max_level)
+	}
+}
+
+impl Subscriber for SpanCollector {
+	fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+		// the collector is only installed temporarily, so the interest must not be cached
+		Interest::sometimes()
+	}
+
+	fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+		// the targets of the spans entered in Wasm are only known once they are created
+		metadata.target() == WASM_TRACE_TARGET || self.is_target_enabled(metadata.target(), metadata.level())
+	}
+
+	fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+		let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+		let mut values = Visitor(Vec::new());
+		attrs.record(&mut values);
+
+		let mut name = attrs.metadata().name().to_string();
+		let mut target = attrs.metadata().target().to_string();
+		if target == WASM_TRACE_TARGET {
+			for (key, value) in std::mem::replace(&mut values.0, Vec::new()) {
+				match key.as_str() {
+					WASM_TARGET_KEY => target = value,
+					WASM_NAME_KEY => name = value,
+					_ => values.0.push((key, value)),
+				}
+			}
+			if !self.is_target_enabled(&target, attrs.metadata().level()) {
+				// never entered nor collected
+				return Id::from_u64(id);
+			}
+		}
+
+		let mut state = self.state.lock();
+		let parent_id = match attrs.parent() {
+			Some(parent) => Some(parent.into_u64()),
+			None if attrs.is_contextual() => Self::current_span(&state),
+			None => None,
+		};
+		state.open.insert(id, CollectedSpan {
+			id,
+			parent_id,
+			name,
+			target,
+			level: *attrs.metadata().level(),
+			line: attrs.metadata().line().unwrap_or(0),
+			values: values.0,
+			overall_time: Duration::from_nanos(0),
+		});
+		Id::from_u64(id)
+	}
+
+	fn record(&self, span: &Id, values: &Record<'_>) {
+		if let Some(span) = self.state.lock().open.get_mut(&span.into_u64()) {
+			let mut visitor = Visitor(Vec::new());
+			values.record(&mut visitor);
+			span.values.extend(visitor.0);
+		}
+	}
+
+	fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+	fn event(&self, event: &Event<'_>) {
+		let mut values = Visitor(Vec::new());
+		event.record(&mut values);
+
+		let mut state = self.state.lock();
+		let parent_id = match event.parent() {
+			Some(parent) => Some(parent.into_u64()),
+			None if event.is_contextual() => Self::current_span(&state),
+			None => None,
+		};
+		state.events.push(CollectedEvent {
+			parent_id,
+			name: event.metadata().name().to_string(),
+			target: event.metadata().target().to_string(),
+			level: *event.metadata().level(),
+			values: values.0,
+		});
+	}
+
+	fn enter(&self, span: &Id) {
+		let mut state = self.state.lock();
+		if state.open.contains_key(&span.into_u64()) {
+			state.entered.push((span.into_u64(), Instant::now()));
+		}
+	}
+
+	fn exit(&self, span: &Id) {
+		let mut state = self.state.lock();
+		let id = span.into_u64();
+		if let Some(position) = state.entered.iter().rposition(|(entered, _)| *entered == id) {
+			let (_, start_time) = state.entered.remove(position);
+			if let Some(span) = state.open.get_mut(&id) {
+				span.overall_time += start_time.elapsed();
+			}
+		}
+	}
+
+	fn try_close(&self, span: Id) -> bool {
+		let mut state = self.state.lock();
+		if let Some(span) = state.open.remove(&span.into_u64()) {
+			state.spans.push(span);
+		}
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn span_collector_collects_nested_spans_and_events() {
+		// given
+		let collector = SpanCollector::new("sc_tracing,other=info");
+
+		// when
+		collector.collect(|| {
+			let outer = tracing::span!(tracing::Level::INFO, "outer", number = 10);
+			let _guard = outer.enter();
+			tracing::event!(tracing::Level::INFO, value = 1);
+			tracing::span!(tracing::Level::DEBUG, "inner").in_scope(|| {
+				tracing::event!(target: "other", tracing::Level::INFO, value = 2);
+				tracing::event!(target: "other", tracing::Level::DEBUG, value = 3);
+			});
+		});
+
+		// then
+		let (spans, events) = collector.finish();
+		assert_eq!(
+			spans.iter().map(|span| (span.name.as_str(), span.parent_id)).collect::<Vec<_>>(),
+			vec![("inner", Some(spans[1].id)), ("outer", None)],
+		);
+		assert_eq!(spans[1].values, vec![("number".to_string(), "10".to_string())]);
+		assert_eq!(
+			events.iter().map(|event| (event.values.clone(), event.parent_id)).collect::<Vec<_>>(),
+			vec![
+				(vec![("value".to_string(), "1".to_string())], Some(spans[1].id)),
+				(vec![("value".to_string(), "2".to_string())], Some(spans[0].id)),
+			],
+		);
+	}
+
+	#[test]
+	fn span_collector_collects_wasm_spans_of_enabled_targets() {
+		// given
+		let collector = SpanCollector::new("pallet_enabled");
+		let wasm_span = |target: &str, name: &str| tracing::span!(
+			target: WASM_TRACE_TARGET,
+			tracing::Level::DEBUG,
+			"wasm_tracing",
+			wasm_target = target,
+			wasm_name = name,
+		);
+
+		// when
+		collector.collect(|| {
+			wasm_span("pallet_disabled", "on_initialize").in_scope(|| {
+				wasm_span("pallet_enabled", "transfer").in_scope(|| {})
+			})
+		});
+
+		// then
+		let (spans, _) = collector.finish();
+		assert_eq!(
+			spans.iter()
+				.map(|span| (span.target.as_str(), span.name.as_str(), span.parent_id, span.values.len()))
+				.collect::<Vec<_>>(),
+			vec![("pallet_enabled", "transfer", None, 0)],
+		);
+	}
+}
@@ -19,7 +19,7 @@
 //! For a more full-featured pool, have a look at the `pool` module.
 
 use std::{
-	collections::HashSet,
+	collections::{HashMap, HashSet},
 	fmt,
 	hash,
 	sync::Arc,
@@ -212,6 +212,12 @@ const RECENTLY_PRUNED_TAGS: usize = 2;
 #[derive(Debug)]
 pub struct BasePool<Hash: hash::Hash + Eq, Ex> {
 	reject_future_transactions: bool,
+	sender_limit: Option<SenderLimit>,
+	/// Hashes of the transactions in the pool per sender, only kept when a sender limit is set.
+	///
+	/// Transactions discarded while being promoted from the future queue are only removed the
+	/// next time their sender is looked up.
+	by_sender: HashMap<Vec<u8>, HashSet<Hash>>,
 	future: FutureTransactions<Hash, Ex>,
 	ready: ReadyTransactions<Hash, Ex>,
 	/// Store recently pruned tags (for last two invocations).
@@ -233,6 +239,8 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 	pub fn new(reject_future_transactions: bool) -> Self {
 		BasePool {
 			reject_future_transactions,
+			sender_limit: None,
+			by_sender: Default::default(),
 			future: Default::default(),
 			ready: Default::default(),
 			recently_pruned: Default::default(),
@@ -240,6 +248,20 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 		}
 	}
 
+	/// Sets the limit on the number of transactions a single sender can have in the pool.
+	///
+	/// Should be set before any transaction is imported, only the transactions imported while a
+	/// limit is set are attributed to their senders.
+	pub fn set_sender_limit(&mut self, limit: Option<SenderLimit>) {
+		self.sender_limit = limit;
+	}
+
+	/// Sets the priority increase required for a transaction to replace the ones providing the
+	/// same tags.
+	pub fn set_min_priority_bump(&mut self, bump: Priority) {
+		self.ready.set_min_priority_bump(bump);
+	}
+
 	/// Temporary enables future transactions, runs closure and then restores
 	/// `reject_future_transactions` flag back to previous value.
 	///
@@ -268,6 +290,20 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 			return Err(error::Error::AlreadyImported(Box::new(tx.hash.clone())))
 		}
 
+		let senders = self.sender_limit.as_ref().map(|limit| limit.senders(&tx)).unwrap_or_default();
+		if let Some(limit) = self.sender_limit.as_ref().map(|limit| limit.count) {
+			let in_pool = self.count_from_same_sender(&senders, &tx);
+			if in_pool >= limit {
+				debug!(
+					target: "txpool",
+					"[{:?}] Sender already has {} transactions in the pool",
+					tx.hash,
+					in_pool,
+				);
+				return Err(error::Error::TooManyTransactionsFromSender { limit })
+			}
+		}
+
 		let tx = WaitingTransaction::new(
 			tx,
 			self.ready.provided_tags(),
@@ -289,10 +325,64 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 
 			let hash = tx.transaction.hash.clone();
 			self.future.import(tx);
+			self.track_senders(senders, &hash);
 			return Ok(Imported::Future { hash });
 		}
 
-		self.import_to_ready(tx)
+		let imported = self.import_to_ready(tx)?;
+		if let Imported::Ready { ref hash, ref removed, .. } = imported {
+			self.forget_senders(removed);
+			self.track_senders(senders, hash);
+		}
+		Ok(imported)
+	}
+
+	/// Counts the transactions in the pool from the given `senders` of `tx`.
+	///
+	/// Transactions that `tx` would replace (i.e. that provide one of its tags) are not counted.
+	fn count_from_same_sender(&mut self, senders: &[Vec<u8>], tx: &Transaction<Hash, Ex>) -> usize {
+		let (ready, future) = (&self.ready, &self.future);
+		let mut in_pool = HashSet::new();
+		for sender in senders {
+			if let Some(hashes) = self.by_sender.get_mut(sender) {
+				hashes.retain(|hash| ready.contains(hash) || future.contains(hash));
+				in_pool.extend(hashes.iter().cloned());
+			}
+		}
+
+		let in_pool = in_pool.into_iter().collect::<Vec<_>>();
+		self.by_hash(&in_pool)
+			.into_iter()
+			.filter_map(|other| other)
+			.filter(|other| !other.provides.iter().any(|tag| tx.provides.contains(tag)))
+			.count()
+	}
+
+	/// Attributes the transaction with the given `hash` to its `senders`.
+	fn track_senders(&mut self, senders: Vec<Vec<u8>>, hash: &Hash) {
+		for sender in senders {
+			self.by_sender.entry(sender).or_default().insert(hash.clone());
+		}
+	}
+
+	/// Removes the attribution of the `removed` transactions to their senders.
+	fn forget_senders(&mut self, removed: &[Arc<Transaction<Hash, Ex>>]) {
+		let limit = match self.sender_limit {
+			Some(ref limit) => limit,
+			None => return,
+		};
+
+		for tx in removed {
+			for sender in limit.senders(tx) {
+				let now_empty = self.by_sender.get_mut(&sender).map_or(false, |hashes| {
+					hashes.remove(&tx.hash);
+					hashes.is_empty()
+				});
+				if now_empty {
+					self.by_sender.remove(&sender);
+				}
+			}
+		}
 	}
 
 	/// Imports transaction to ready queue.
@@ -389,7 +479,8 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 	///
 	/// Removes and returns worst transactions from the queues and all transactions that depend on them.
 	/// Technically the worst transaction should be evaluated by computing the entire pending set.
-	/// We use a simplified approach to remove the transaction that occupies the pool for the longest time.
+	/// We use a simplified approach to remove the transaction with the lowest priority, and
+	/// among those the one that occupies the pool for the longest time.
 	pub fn enforce_limits(&mut self, ready: &Limit, future: &Limit) -> Vec<Arc<Transaction<Hash, Ex>>> {
 		let mut removed = vec![];
 
@@ -400,7 +491,9 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 					let transaction = &current.transaction;
 					match minimal {
 						None => Some(transaction.clone()),
-						Some(ref tx) if tx.insertion_id > transaction.insertion_id => {
+						Some(ref tx) if (tx.transaction.priority, tx.insertion_id)
+							> (transaction.transaction.priority, transaction.insertion_id) =>
+						{
 							Some(transaction.clone())
 						},
 						other => other,
@@ -420,7 +513,9 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 				.fold(|minimal, current| {
 					match minimal {
 						None => Some(current.clone()),
-						Some(ref tx) if tx.imported_at > current.imported_at => {
+						Some(ref tx) if (tx.transaction.priority, tx.imported_at)
+							> (current.transaction.priority, current.imported_at) =>
+						{
 							Some(current.clone())
 						},
 						other => other,
@@ -448,12 +543,15 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 	pub fn remove_subtree(&mut self, hashes: &[Hash]) -> Vec<Arc<Transaction<Hash, Ex>>> {
 		let mut removed = self.ready.remove_subtree(hashes);
 		removed.extend(self.future.remove(hashes));
+		self.forget_senders(&removed);
 		removed
 	}
 
 	/// Removes and returns all transactions from the future queue.
 	pub fn clear_future(&mut self) -> Vec<Arc<Transaction<Hash, Ex>>> {
-		self.future.clear()
+		let removed = self.future.clear();
+		self.forget_senders(&removed);
+		removed
 	}
 
 	/// Prunes transactions that provide given list of tags.
@@ -478,12 +576,19 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 			recently_pruned.insert(tag);
 		}
 
+		self.forget_senders(&pruned);
+
 		let mut promoted = vec![];
 		let mut failed = vec![];
 		for tx in to_import {
 			let hash = tx.transaction.hash.clone();
 			match self.import_to_ready(tx) {
-				Ok(res) => promoted.push(res),
+				Ok(res) => {
+					if let Imported::Ready { ref removed, .. } = res {
+						self.forget_senders(removed);
+					}
+					promoted.push(res)
+				},
 				Err(e) => {
 					warn!(target: "txpool", "[{:?}] Failed to promote during pruning: {:?}", hash, e);
 					failed.push(hash)
@@ -505,10 +610,37 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 			ready_bytes: self.ready.bytes(),
 			future: self.future.len(),
 			future_bytes: self.future.bytes(),
+			evictions: Default::default(),
 		}
 	}
 }
 
+/// Limit on the number of transactions a single sender can have in the pool.
+///
+/// The sender of a transaction is identified by the first `prefix_len` bytes of the tags it
+/// provides. FRAME-based runtimes provide the encoded `(AccountId, Index)` pair, in which case
+/// `prefix_len` should be the encoded length of the account id. Tags shorter than `prefix_len`
+/// are not attributed to any sender.
+#[derive(Debug, Clone)]
+pub struct SenderLimit {
+	/// Number of leading bytes of a tag that identify the sender.
+	pub prefix_len: usize,
+	/// Maximal number of transactions per sender, in the ready and future queues combined.
+	pub count: usize,
+}
+
+impl SenderLimit {
+	/// Returns the distinct senders of the given transaction.
+	fn senders<Hash, Ex>(&self, tx: &Transaction<Hash, Ex>) -> Vec<Vec<u8>> {
+		tx.provides.iter()
+			.filter_map(|tag| tag.get(..self.prefix_len))
+			.collect::<HashSet<_>>()
+			.into_iter()
+			.map(|sender| sender.to_vec())
+			.collect()
+	}
+}
+
 /// Queue limits
 #[derive(Debug, Clone)]
 pub struct Limit {
@@ -528,6 +660,7 @@ impl Limit {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use assert_matches::assert_matches;
 
 	type Hash = u64;
 
@@ -1119,4 +1252,94 @@ requires: [03,02], provides: [04], data: [4]}".to_owned()
 		assert_eq!(pool.reject_future_transactions, true);
 		assert_eq!(pool.future.len(), 1);
 	}
+
+	#[test]
+	fn should_limit_transactions_per_sender() {
+		// given
+		let mut pool = pool();
+		pool.set_sender_limit(Some(SenderLimit { prefix_len: 1, count: 2 }));
+		let tx = |hash: u64, sender: u8, nonce: u8| Transaction {
+			data: vec![sender, nonce],
+			bytes: 1,
+			hash,
+			priority: 5u64,
+			valid_till: 64u64,
+			requires: if nonce > 0 { vec![vec![sender, nonce - 1]] } else { vec![] },
+			provides: vec![vec![sender, nonce]],
+			propagate: true,
+		};
+		pool.import(tx(1, 1, 0)).unwrap();
+		pool.import(tx(2, 1, 1)).unwrap();
+
+		// when
+		let err = pool.import(tx(3, 1, 2)).unwrap_err();
+		pool.import(tx(4, 2, 0)).unwrap();
+		let mut replacement = tx(5, 1, 1);
+		replacement.priority = 10;
+		pool.import(replacement).unwrap();
+
+		// then
+		assert_matches!(err, error::Error::TooManyTransactionsFromSender { limit: 2 });
+		assert_eq!(pool.ready().map(|tx| tx.hash).collect::<HashSet<_>>(), vec![1, 4, 5].into_iter().collect());
+	}
+
+	#[test]
+	fn should_free_sender_slots_when_transactions_leave_the_pool() {
+		// given
+		let mut pool = pool();
+		pool.set_sender_limit(Some(SenderLimit { prefix_len: 1, count: 2 }));
+		let tx = |hash: u64, sender: u8, nonce: u8| Transaction {
+			data: vec![sender, nonce],
+			bytes: 1,
+			hash,
+			priority: 5u64,
+			valid_till: 64u64,
+			requires: if nonce > 0 { vec![vec![sender, nonce - 1]] } else { vec![] },
+			provides: vec![vec![sender, nonce]],
+			propagate: true,
+		};
+		pool.import(tx(1, 1, 0)).unwrap();
+		pool.import(tx(2, 1, 1)).unwrap();
+
+		// when
+		pool.prune_tags(vec![vec![1, 0]]);
+		pool.import(tx(3, 1, 2)).unwrap();
+		let err = pool.import(tx(4, 1, 3)).unwrap_err();
+		pool.remove_subtree(&[3]);
+		pool.import(tx(5, 1, 2)).unwrap();
+
+		// then
+		assert_matches!(err, error::Error::TooManyTransactionsFromSender { limit: 2 });
+		assert_eq!(pool.ready().map(|tx| tx.hash).collect::<HashSet<_>>(), vec![2, 5].into_iter().collect());
+		assert_eq!(pool.by_sender.len(), 1);
+		assert_eq!(pool.by_sender[&vec![1]], vec![2, 5].into_iter().collect());
+	}
+
+	#[test]
+	fn should_evict_lowest_priority_transactions_first() {
+		// given
+		let mut pool = pool();
+		let tx = |hash: u64, priority: u64| Transaction {
+			data: vec![hash as u8],
+			bytes: 1,
+			hash,
+			priority,
+			valid_till: 64u64,
+			requires: vec![],
+			provides: vec![vec![hash as u8]],
+			propagate: true,
+		};
+		pool.import(tx(1, 5)).unwrap();
+		pool.import(tx(2, 1)).unwrap();
+		pool.import(tx(3, 7)).unwrap();
+		pool.import(tx(4, 1)).unwrap();
+		let limit = Limit { count: 2, total_bytes: 1000 };
+
+		// when
+		let removed = pool.enforce_limits(&limit, &limit);
+
+		// then
+		assert_eq!(removed.iter().map(|tx| tx.hash).collect::<Vec<_>>(), vec![2, 4]);
+		assert_eq!(pool.ready().map(|tx| tx.hash).collect::<HashSet<_>>(), vec![1, 3].into_iter().collect());
+	}
 }
@@ -34,9 +34,9 @@ mod validated_pool;
 pub mod base_pool;
 pub mod watcher;
 
-pub use self::base_pool::Transaction;
+pub use self::base_pool::{Transaction, SenderLimit};
 pub use self::pool::{
 	Pool,
-	Options, ChainApi, EventStream, ExtrinsicFor,
+	Options, JournalOptions, ChainApi, EventStream, ExtrinsicFor,
 	BlockHash, ExHash, NumberFor, TransactionFor,
 };
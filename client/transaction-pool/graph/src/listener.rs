@@ -20,25 +20,47 @@ use std::{
 	fmt,
 	hash,
 };
+use linked_hash_map::LinkedHashMap;
 use serde::Serialize;
 use crate::watcher;
 use sp_runtime::traits;
 use log::{debug, trace, warn};
 
 /// Extrinsic pool default listener.
-pub struct Listener<H: hash::Hash + Eq, H2> {
-	watchers: HashMap<H, watcher::Sender<H, H2>>
+///
+/// By default watchers end once their transaction is included in a block. When
+/// `retracted_watch_blocks` is set, watchers of transactions included in that many
+/// recent blocks are kept, so that they can be notified if the block is retracted.
+pub struct Listener<H: hash::Hash + Eq, H2: hash::Hash + Eq> {
+	watchers: HashMap<H, watcher::Sender<H, H2>>,
+	retracted_watch_blocks: usize,
+	/// Watched transactions per recent block, oldest block first.
+	in_block_watchers: LinkedHashMap<H2, Vec<H>>,
+	/// The most recent block each watched transaction was included in.
+	in_block: HashMap<H, H2>,
 }
 
-impl<H: hash::Hash + Eq, H2> Default for Listener<H, H2> {
+impl<H: hash::Hash + Eq, H2: hash::Hash + Eq> Default for Listener<H, H2> {
 	fn default() -> Self {
+		Self::new(0)
+	}
+}
+
+impl<
+	H: hash::Hash + traits::Member + Serialize,
+	H2: hash::Hash + Eq + Clone + fmt::Debug,
+> Listener<H, H2> {
+	/// Creates a new listener keeping the watchers of transactions included in the
+	/// `retracted_watch_blocks` most recent blocks.
+	pub fn new(retracted_watch_blocks: usize) -> Self {
 		Listener {
 			watchers: Default::default(),
+			retracted_watch_blocks,
+			in_block_watchers: LinkedHashMap::new(),
+			in_block: Default::default(),
 		}
 	}
-}
 
-impl<H: hash::Hash + traits::Member + Serialize, H2: Clone + fmt::Debug> Listener<H, H2> {
 	fn fire<F>(&mut self, hash: &H, fun: F) where F: FnOnce(&mut watcher::Sender<H, H2>) {
 		let clean = if let Some(h) = self.watchers.get_mut(hash) {
 			fun(h);
@@ -49,6 +71,7 @@ impl<H: hash::Hash + traits::Member + Serialize, H2: Clone + fmt::Debug> Listene
 
 		if clean {
 			self.watchers.remove(hash);
+			self.in_block.remove(hash);
 		}
 	}
 
@@ -101,8 +124,42 @@ impl<H: hash::Hash + traits::Member + Serialize, H2: Clone + fmt::Debug> Listene
 	}
 
 	/// Transaction was pruned from the pool.
+	///
+	/// If watchers are kept for recent blocks, the watcher stays alive so that it
+	/// can be notified if the block is retracted.
 	pub fn pruned(&mut self, header_hash: H2, tx: &H) {
 		debug!(target: "txpool", "[{:?}] Pruned at {:?}", tx, header_hash);
-		self.fire(tx, |watcher| watcher.in_block(header_hash))
+		let keep_alive = self.retracted_watch_blocks > 0;
+		self.fire(tx, |watcher| watcher.in_block(header_hash.clone(), keep_alive));
+
+		if keep_alive && self.watchers.contains_key(tx) {
+			self.in_block.insert(tx.clone(), header_hash.clone());
+			self.in_block_watchers.entry(header_hash).or_insert_with(Vec::new).push(tx.clone());
+			while self.in_block_watchers.len() > self.retracted_watch_blocks {
+				if let Some((block, txs)) = self.in_block_watchers.pop_front() {
+					for tx in txs {
+						// the transaction may have been re-included in a more recent block
+						if self.in_block.get(&tx) == Some(&block) {
+							self.in_block.remove(&tx);
+							self.fire(&tx, |watcher| watcher.buried());
+						}
+					}
+				}
+			}
+		}
+	}
+
+	/// Block with given hash was retracted from the best chain.
+	pub fn retracted(&mut self, header_hash: H2) {
+		if let Some(txs) = self.in_block_watchers.remove(&header_hash) {
+			for tx in txs {
+				if self.in_block.get(&tx) != Some(&header_hash) {
+					continue;
+				}
+				self.in_block.remove(&tx);
+				trace!(target: "txpool", "[{:?}] Retracted from {:?}", tx, header_hash);
+				self.fire(&tx, |watcher| watcher.retracted(header_hash.clone()));
+			}
+		}
 	}
 }
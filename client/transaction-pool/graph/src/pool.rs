@@ -17,6 +17,7 @@
 use std::{
 	hash,
 	collections::HashMap,
+	path::PathBuf,
 	sync::Arc,
 };
 
@@ -32,7 +33,10 @@ use futures::{
 use sp_runtime::{
 	generic::BlockId,
 	traits::{self, SaturatedConversion},
-	transaction_validity::{TransactionValidity, TransactionTag as Tag, TransactionValidityError},
+	transaction_validity::{
+		TransactionValidity, TransactionTag as Tag, TransactionValidityError,
+		TransactionPriority as Priority,
+	},
 };
 use sp_transaction_pool::{error, PoolStatus};
 
@@ -95,6 +99,28 @@ pub struct Options {
 	pub future: base::Limit,
 	/// Reject future transactions.
 	pub reject_future_transactions: bool,
+	/// Limit on the number of transactions a single sender can have in the pool.
+	pub sender_limit: Option<base::SenderLimit>,
+	/// Priority increase required for a transaction to replace the ones providing the same tags.
+	pub min_priority_bump: Priority,
+	/// Journal of locally submitted transactions, persisted across restarts.
+	pub journal: Option<JournalOptions>,
+	/// Number of recent blocks for which watchers of included transactions are kept
+	/// open, so that they are notified with `Retracted` if the block is retracted.
+	///
+	/// With `0`, watchers end once the transaction is included in a block.
+	pub retracted_watch_blocks: usize,
+}
+
+/// Options of the on-disk journal of locally submitted transactions.
+///
+/// The journal itself is maintained by the `sc-transaction-pool` crate.
+#[derive(Debug, Clone)]
+pub struct JournalOptions {
+	/// Path to the journal file.
+	pub path: PathBuf,
+	/// Maximal number of transactions kept in the journal.
+	pub max_transactions: usize,
 }
 
 impl Default for Options {
@@ -109,6 +135,10 @@ impl Default for Options {
 				total_bytes: 1 * 1024 * 1024,
 			},
 			reject_future_transactions: false,
+			sender_limit: None,
+			min_priority_bump: 0,
+			journal: None,
+			retracted_watch_blocks: 0,
 		}
 	}
 }
@@ -180,18 +210,32 @@ impl<B: ChainApi> Pool<B> {
 		at: &BlockId<B::Block>,
 		max: Option<usize>,
 	) -> impl Future<Output=Result<(), B::Error>> {
-		use std::time::Instant;
 		log::debug!(target: "txpool",
 			"Fetching ready transactions (up to: {})",
 			max.map(|x| format!("{}", x)).unwrap_or_else(|| "all".into())
 		);
-		let validated_pool = self.validated_pool.clone();
 		let ready = self.validated_pool.ready()
 			.map(|tx| tx.data.clone())
-			.take(max.unwrap_or_else(usize::max_value));
+			.take(max.unwrap_or_else(usize::max_value))
+			.collect::<Vec<_>>();
+
+		self.revalidate(at, ready)
+	}
+
+	/// Revalidate given transactions at given block and resubmit them to the pool.
+	///
+	/// Transactions may be either in the ready or in the future queue. The ones
+	/// that are no longer part of the pool are ignored during resubmission.
+	pub fn revalidate(
+		&self,
+		at: &BlockId<B::Block>,
+		xts: impl IntoIterator<Item=ExtrinsicFor<B>>,
+	) -> impl Future<Output=Result<(), B::Error>> {
+		use std::time::Instant;
+		let validated_pool = self.validated_pool.clone();
 
 		let now = Instant::now();
-		self.verify(at, ready, false)
+		self.verify(at, xts, false)
 			.map(move |revalidated_transactions| {
 				log::debug!(target: "txpool",
 					"Re-verified transactions, took {} ms. Resubmitting.",
@@ -355,6 +399,13 @@ impl<B: ChainApi> Pool<B> {
 		self.validated_pool.on_broadcasted(propagated)
 	}
 
+	/// Invoked when a block has been retracted from the best chain.
+	///
+	/// Notifies watchers of transactions that were included in that block.
+	pub fn on_block_retracted(&self, block_hash: BlockHash<B>) {
+		self.validated_pool.on_block_retracted(block_hash)
+	}
+
 	/// Remove invalid transactions from the pool.
 	pub fn remove_invalid(&self, hashes: &[ExHash<B>]) -> Vec<TransactionFor<B>> {
 		self.validated_pool.remove_invalid(hashes)
@@ -365,6 +416,18 @@ impl<B: ChainApi> Pool<B> {
 		self.validated_pool.ready()
 	}
 
+	/// Returns pool transactions given list of hashes.
+	///
+	/// Returns `None` for transactions that are not in the pool.
+	pub fn by_hash(&self, hashes: &[ExHash<B>]) -> Vec<Option<TransactionFor<B>>> {
+		self.validated_pool.by_hash(hashes)
+	}
+
+	/// Returns hashes and extrinsics of all transactions in the future queue.
+	pub fn futures(&self) -> Vec<(ExHash<B>, ExtrinsicFor<B>)> {
+		self.validated_pool.futures()
+	}
+
 	/// Returns pool status.
 	pub fn status(&self) -> PoolStatus {
 		self.validated_pool.status()
@@ -466,7 +529,7 @@ mod tests {
 	use parking_lot::Mutex;
 	use futures::executor::block_on;
 	use super::*;
-	use sp_transaction_pool::TransactionStatus;
+	use sp_transaction_pool::{EvictionStats, TransactionStatus};
 	use sp_runtime::transaction_validity::{ValidTransaction, InvalidTransaction};
 	use codec::Encode;
 	use substrate_test_runtime::{Block, Extrinsic, Transfer, H256, AccountId};
@@ -737,6 +800,42 @@ mod tests {
 		assert!(!pool.validated_pool.rotator().is_banned(&hash2));
 	}
 
+	#[test]
+	fn should_count_evicted_transactions() {
+		// given
+		let limit = Limit {
+			count: 1,
+			total_bytes: 1000,
+		};
+		let pool = Pool::new(Options {
+			ready: limit.clone(),
+			future: limit.clone(),
+			..Default::default()
+		}, TestApi::default());
+
+		// when
+		block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			from: AccountId::from_h256(H256::from_low_u64_be(1)),
+			to: AccountId::from_h256(H256::from_low_u64_be(2)),
+			amount: 5,
+			nonce: 1,
+		}))).unwrap();
+		block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			from: AccountId::from_h256(H256::from_low_u64_be(2)),
+			to: AccountId::from_h256(H256::from_low_u64_be(1)),
+			amount: 5,
+			nonce: 10,
+		}))).unwrap();
+
+		// then
+		assert_eq!(pool.status().future, 1);
+		assert_eq!(pool.status().evictions, EvictionStats {
+			replaced: 0,
+			evicted: 1,
+			rejected_sender_limit: 0,
+		});
+	}
+
 	#[test]
 	fn should_error_if_reject_immediately() {
 		// given
@@ -835,6 +934,102 @@ mod tests {
 			assert_eq!(stream.next(), None);
 		}
 
+		fn pool_watching_retracted(blocks: usize) -> Pool<TestApi> {
+			Pool::new(Options {
+				retracted_watch_blocks: blocks,
+				..Default::default()
+			}, TestApi::default())
+		}
+
+		#[test]
+		fn should_trigger_retracted_when_block_is_retracted() {
+			// given
+			let pool = pool_watching_retracted(8);
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+				from: AccountId::from_h256(H256::from_low_u64_be(1)),
+				to: AccountId::from_h256(H256::from_low_u64_be(2)),
+				amount: 5,
+				nonce: 0,
+			}))).unwrap();
+			block_on(pool.prune_tags(&BlockId::Number(2), vec![vec![0u8]], vec![])).unwrap();
+			assert_eq!(pool.status().ready, 0);
+
+			// when
+			pool.on_block_retracted(H256::from_low_u64_be(2).into());
+			drop(pool);
+
+			// then
+			let mut stream = futures::executor::block_on_stream(watcher.into_stream());
+			assert_eq!(stream.next(), Some(TransactionStatus::Ready));
+			assert_eq!(stream.next(), Some(TransactionStatus::InBlock(H256::from_low_u64_be(2).into())));
+			assert_eq!(stream.next(), Some(TransactionStatus::Retracted(H256::from_low_u64_be(2).into())));
+			assert_eq!(stream.next(), None);
+		}
+
+		#[test]
+		fn should_end_watchers_of_transactions_in_old_blocks() {
+			// given
+			let pool = pool_watching_retracted(1);
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+				from: AccountId::from_h256(H256::from_low_u64_be(1)),
+				to: AccountId::from_h256(H256::from_low_u64_be(2)),
+				amount: 5,
+				nonce: 0,
+			}))).unwrap();
+			let _other = block_on(pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+				from: AccountId::from_h256(H256::from_low_u64_be(2)),
+				to: AccountId::from_h256(H256::from_low_u64_be(1)),
+				amount: 5,
+				nonce: 1,
+			}))).unwrap();
+			block_on(pool.prune_tags(&BlockId::Number(1), vec![vec![0u8]], vec![])).unwrap();
+
+			// when
+			block_on(pool.prune_tags(&BlockId::Number(2), vec![vec![1u8]], vec![])).unwrap();
+
+			// then
+			let mut stream = futures::executor::block_on_stream(watcher.into_stream());
+			assert_eq!(stream.next(), Some(TransactionStatus::Ready));
+			assert_eq!(stream.next(), Some(TransactionStatus::InBlock(H256::from_low_u64_be(1).into())));
+			assert_eq!(stream.next(), None);
+		}
+
+		#[test]
+		fn should_keep_watchers_of_reincluded_transactions() {
+			// given
+			let pool = pool_watching_retracted(2);
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+				from: AccountId::from_h256(H256::from_low_u64_be(1)),
+				to: AccountId::from_h256(H256::from_low_u64_be(2)),
+				amount: 5,
+				nonce: 0,
+			}))).unwrap();
+			let hash = *watcher.hash();
+			block_on(pool.prune_tags(&BlockId::Number(2), vec![vec![0u8]], vec![])).unwrap();
+			let _other = block_on(pool.submit_and_watch(&BlockId::Number(2), uxt(Transfer {
+				from: AccountId::from_h256(H256::from_low_u64_be(2)),
+				to: AccountId::from_h256(H256::from_low_u64_be(1)),
+				amount: 5,
+				nonce: 2,
+			}))).unwrap();
+
+			// when
+			// the transaction is included again in a block of another fork
+			block_on(pool.prune_tags(&BlockId::Number(3), vec![], vec![hash])).unwrap();
+			// and block 2 is no longer watched once block 4 is imported
+			block_on(pool.prune_tags(&BlockId::Number(4), vec![vec![2u8]], vec![])).unwrap();
+			pool.on_block_retracted(H256::from_low_u64_be(3).into());
+			drop(pool);
+
+			// then
+			let mut stream = futures::executor::block_on_stream(watcher.into_stream());
+			assert_eq!(stream.next(), Some(TransactionStatus::Ready));
+			assert_eq!(stream.next(), Some(TransactionStatus::InBlock(H256::from_low_u64_be(2).into())));
+			assert_eq!(stream.next(), Some(TransactionStatus::InBlock(H256::from_low_u64_be(3).into())));
+			assert_eq!(stream.next(), Some(TransactionStatus::Retracted(H256::from_low_u64_be(3).into())));
+			assert_eq!(stream.next(), None);
+		}
+
 		#[test]
 		fn should_trigger_future_and_ready_after_promoted() {
 			// given
@@ -27,6 +27,7 @@ use parking_lot::RwLock;
 use sp_runtime::traits::Member;
 use sp_runtime::transaction_validity::{
 	TransactionTag as Tag,
+	TransactionPriority as Priority,
 };
 use sp_transaction_pool::error;
 
@@ -114,6 +115,8 @@ pub struct ReadyTransactions<Hash: hash::Hash + Eq, Ex> {
 	ready: Arc<RwLock<HashMap<Hash, ReadyTx<Hash, Ex>>>>,
 	/// Best transactions that are ready to be included to the block without any other previous transaction.
 	best: BTreeSet<TransactionRef<Hash, Ex>>,
+	/// Priority increase required for a transaction to replace the ones providing the same tags.
+	min_priority_bump: Priority,
 }
 
 impl<Hash: hash::Hash + Eq, Ex> Default for ReadyTransactions<Hash, Ex> {
@@ -123,11 +126,20 @@ impl<Hash: hash::Hash + Eq, Ex> Default for ReadyTransactions<Hash, Ex> {
 			provided_tags: Default::default(),
 			ready: Default::default(),
 			best: Default::default(),
+			min_priority_bump: 0,
 		}
 	}
 }
 
 impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
+	/// Sets the priority increase required for a transaction to replace the ones providing the
+	/// same tags.
+	///
+	/// With the default of `0`, any strictly higher priority is enough.
+	pub fn set_min_priority_bump(&mut self, bump: Priority) {
+		self.min_priority_bump = bump;
+	}
+
 	/// Borrows a map of tags that are provided by transactions in this queue.
 	pub fn provided_tags(&self) -> &HashMap<Tag, Hash> {
 		&self.provided_tags
@@ -428,7 +440,7 @@ impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
 			};
 
 			// bail - the transaction has too low priority to replace the old ones
-			if old_priority >= tx.priority {
+			if old_priority.saturating_add(self.min_priority_bump) >= tx.priority {
 				return Err(error::Error::TooLowPriority { old: old_priority, new: tx.priority })
 			}
 
@@ -540,6 +552,7 @@ fn remove_item<T: PartialEq>(vec: &mut Vec<T>, item: &T) {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use assert_matches::assert_matches;
 
 	fn tx(id: u8) -> Transaction<u64, Vec<u8>> {
 		Transaction {
@@ -590,6 +603,32 @@ mod tests {
 		assert_eq!(ready.get().count(), 1);
 	}
 
+	#[test]
+	fn should_require_priority_bump_to_replace() {
+		// given
+		let mut ready = ReadyTransactions::default();
+		ready.set_min_priority_bump(5);
+		let mut tx1 = tx(1);
+		tx1.requires.clear();
+		let mut tx2 = tx(2);
+		tx2.requires.clear();
+		tx2.priority = 6;
+		let mut tx3 = tx(3);
+		tx3.requires.clear();
+		tx3.priority = 7;
+		import(&mut ready, tx1).unwrap();
+
+		// when
+		let err = import(&mut ready, tx2).unwrap_err();
+		let replaced = import(&mut ready, tx3).unwrap();
+
+		// then
+		assert_matches!(err, error::Error::TooLowPriority { old: 1, new: 6 });
+		assert_eq!(replaced.len(), 1);
+		assert_eq!(replaced[0].hash, 1);
+		assert_eq!(ready.get().count(), 1);
+	}
+
 	#[test]
 	fn should_replace_multiple_transactions_correctly() {
 		// given
@@ -18,7 +18,7 @@ use std::{
 	collections::{HashSet, HashMap},
 	fmt,
 	hash,
-	sync::Arc,
+	sync::{Arc, atomic::{AtomicU64, Ordering}},
 	time,
 };
 
@@ -36,7 +36,7 @@ use sp_runtime::{
 	traits::{self, SaturatedConversion},
 	transaction_validity::TransactionTag as Tag,
 };
-use sp_transaction_pool::{error, PoolStatus};
+use sp_transaction_pool::{error, EvictionStats, PoolStatus};
 
 use crate::base_pool::PruneStatus;
 use crate::pool::{EventStream, Options, ChainApi, BlockHash, ExHash, ExtrinsicFor, TransactionFor};
@@ -61,6 +61,13 @@ pub type ValidatedTransactionFor<B> = ValidatedTransaction<
 	<B as ChainApi>::Error,
 >;
 
+#[derive(Debug, Default)]
+struct EvictionCounters {
+	replaced: AtomicU64,
+	evicted: AtomicU64,
+	rejected_sender_limit: AtomicU64,
+}
+
 /// Pool that deals with validated transactions.
 pub(crate) struct ValidatedPool<B: ChainApi> {
 	api: B,
@@ -72,19 +79,23 @@ pub(crate) struct ValidatedPool<B: ChainApi> {
 	>>,
 	import_notification_sinks: Mutex<Vec<mpsc::UnboundedSender<()>>>,
 	rotator: PoolRotator<ExHash<B>>,
+	evictions: EvictionCounters,
 }
 
 impl<B: ChainApi> ValidatedPool<B> {
 	/// Create a new transaction pool.
 	pub fn new(options: Options, api: B) -> Self {
-		let base_pool = base::BasePool::new(options.reject_future_transactions);
+		let mut base_pool = base::BasePool::new(options.reject_future_transactions);
+		base_pool.set_sender_limit(options.sender_limit.clone());
+		base_pool.set_min_priority_bump(options.min_priority_bump);
 		ValidatedPool {
 			api,
+			listener: RwLock::new(Listener::new(options.retracted_watch_blocks)),
 			options,
-			listener: Default::default(),
 			pool: RwLock::new(base_pool),
 			import_notification_sinks: Default::default(),
 			rotator: Default::default(),
+			evictions: Default::default(),
 		}
 	}
 
@@ -123,9 +134,15 @@ impl<B: ChainApi> ValidatedPool<B> {
 	fn submit_one(&self, tx: ValidatedTransactionFor<B>) -> Result<ExHash<B>, B::Error> {
 		match tx {
 			ValidatedTransaction::Valid(tx) => {
-				let imported = self.pool.write().import(tx)?;
+				let imported = self.pool.write().import(tx).map_err(|e| {
+					if let error::Error::TooManyTransactionsFromSender { .. } = e {
+						self.evictions.rejected_sender_limit.fetch_add(1, Ordering::Relaxed);
+					}
+					e
+				})?;
 
-				if let base::Imported::Ready { .. } = imported {
+				if let base::Imported::Ready { ref removed, .. } = imported {
+					self.evictions.replaced.fetch_add(removed.len() as u64, Ordering::Relaxed);
 					self.import_notification_sinks.lock().retain(|sink| sink.unbounded_send(()).is_ok());
 				}
 
@@ -162,6 +179,8 @@ impl<B: ChainApi> ValidatedPool<B> {
 				self.rotator.ban(&std::time::Instant::now(), removed.iter().map(|x| x.clone()));
 				removed
 			};
+			self.evictions.evicted.fetch_add(removed.len() as u64, Ordering::Relaxed);
+			debug!(target: "txpool", "Evicted {} transactions to stay within the limits", removed.len());
 			// run notifications
 			let mut listener = self.listener.write();
 			for h in &removed {
@@ -397,6 +416,11 @@ impl<B: ChainApi> ValidatedPool<B> {
 		Ok(())
 	}
 
+	/// Fire notifications for transactions included in retracted block.
+	pub fn on_block_retracted(&self, block_hash: BlockHash<B>) {
+		self.listener.write().retracted(block_hash)
+	}
+
 	/// Removes stale transactions from the pool.
 	///
 	/// Stale transactions are transaction beyond their longevity period.
@@ -489,9 +513,27 @@ impl<B: ChainApi> ValidatedPool<B> {
 		self.pool.read().ready()
 	}
 
+	/// Returns pool transactions given list of hashes.
+	pub fn by_hash(&self, hashes: &[ExHash<B>]) -> Vec<Option<TransactionFor<B>>> {
+		self.pool.read().by_hash(hashes)
+	}
+
+	/// Returns hashes and extrinsics of all transactions in the future queue.
+	pub fn futures(&self) -> Vec<(ExHash<B>, ExtrinsicFor<B>)> {
+		self.pool.read().futures()
+			.map(|tx| (tx.hash.clone(), tx.data.clone()))
+			.collect()
+	}
+
 	/// Returns pool status.
 	pub fn status(&self) -> PoolStatus {
-		self.pool.read().status()
+		let mut status = self.pool.read().status();
+		status.evictions = EvictionStats {
+			replaced: self.evictions.replaced.load(Ordering::Relaxed),
+			evicted: self.evictions.evicted.load(Ordering::Relaxed),
+			rejected_sender_limit: self.evictions.rejected_sender_limit.load(Ordering::Relaxed),
+		};
+		status
 	}
 }
 
@@ -89,8 +89,21 @@ impl<H: Clone, H2: Clone> Sender<H, H2> {
 	}
 
 	/// Extrinsic has been included in block with given hash.
-	pub fn in_block(&mut self, hash: H2) {
+	///
+	/// With `keep_alive`, the watcher is not finalized, so that it can still be
+	/// notified if the block is retracted.
+	pub fn in_block(&mut self, hash: H2, keep_alive: bool) {
 		self.send(TransactionStatus::InBlock(hash));
+		self.finalized = !keep_alive;
+	}
+
+	/// The block this extrinsic was included in has been retracted.
+	pub fn retracted(&mut self, hash: H2) {
+		self.send(TransactionStatus::Retracted(hash));
+	}
+
+	/// The block this extrinsic was included in is too old to be watched for retraction.
+	pub fn buried(&mut self) {
 		self.finalized = true;
 	}
 
@@ -0,0 +1,266 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Journal of locally submitted transactions.
+//!
+//! Transactions submitted over RPC or by offchain workers are recorded in a file,
+//! so that they can be resubmitted (and revalidated) after the node restarts.
+//! Recording a transaction only updates the journal in memory: the file is
+//! rewritten by a dedicated thread, which coalesces the writes requested while
+//! it was busy. Transactions that have left the pool in the meantime are
+//! forgotten and only the most recent `max_transactions` are kept.
+
+use std::{collections::VecDeque, fs, io::{self, Write}, path::{Path, PathBuf}, sync::Arc, thread};
+
+use codec::{Decode, Encode};
+use futures::{StreamExt, channel::mpsc, executor::block_on};
+use log::{debug, warn};
+use parking_lot::Mutex;
+
+use sc_transaction_graph::JournalOptions;
+
+/// Journal of locally submitted transactions.
+///
+/// Pending writes are completed when the journal is dropped.
+pub struct Journal<Hash, Ex> {
+	max_transactions: usize,
+	transactions: Arc<Mutex<VecDeque<(Hash, Ex)>>>,
+	writer: Option<(mpsc::UnboundedSender<()>, thread::JoinHandle<()>)>,
+}
+
+impl<Hash, Ex> Journal<Hash, Ex> where
+	Hash: Clone + PartialEq + Send + 'static,
+	Ex: Clone + Encode + Decode + Send + 'static,
+{
+	/// Open the journal, reading previously recorded transactions from disk.
+	///
+	/// Missing or corrupted journal is reported and treated as empty.
+	pub fn open(options: JournalOptions, hash_of: impl Fn(&Ex) -> Hash) -> Self {
+		let transactions = match read_journal::<Ex>(&options.path) {
+			Ok(transactions) => transactions,
+			Err(e) => {
+				if e.kind() != io::ErrorKind::NotFound {
+					warn!(target: "txpool", "Failed to read transaction journal {:?}: {}", options.path, e);
+				}
+				Vec::new()
+			},
+		};
+		debug!(target: "txpool", "Read {} transactions from journal {:?}", transactions.len(), options.path);
+
+		let transactions = Arc::new(Mutex::new(transactions.into_iter()
+			.rev()
+			.take(options.max_transactions)
+			.rev()
+			.map(|xt| (hash_of(&xt), xt))
+			.collect()));
+
+		let (requests, receiver) = mpsc::unbounded();
+		let worker_transactions = transactions.clone();
+		let path = options.path.clone();
+		let writer = thread::Builder::new()
+			.name("txpool-journal".into())
+			.spawn(move || run_writer(path, worker_transactions, receiver));
+		let writer = match writer {
+			Ok(handle) => Some((requests, handle)),
+			Err(e) => {
+				warn!(target: "txpool", "Failed to spawn journal thread, journal disabled: {:?}", e);
+				None
+			},
+		};
+
+		Journal {
+			max_transactions: options.max_transactions,
+			transactions,
+			writer,
+		}
+	}
+
+	/// Returns all transactions in the journal, oldest first.
+	pub fn transactions(&self) -> Vec<Ex> {
+		self.transactions.lock().iter().map(|(_, xt)| xt.clone()).collect()
+	}
+
+	/// Record a new local transaction and request the journal to be written to disk.
+	///
+	/// `retain` is used to forget recorded transactions that are not in the pool any more.
+	pub fn record(&self, hash: Hash, xt: Ex, retain: impl FnOnce(&[Hash]) -> Vec<bool>) {
+		{
+			let mut transactions = self.transactions.lock();
+			let hashes = transactions.iter().map(|(hash, _)| hash.clone()).collect::<Vec<_>>();
+			let mut keep = retain(&hashes).into_iter();
+			transactions.retain(|_| keep.next().unwrap_or(false));
+
+			if transactions.iter().all(|(existing, _)| *existing != hash) {
+				transactions.push_back((hash, xt));
+			}
+			while transactions.len() > self.max_transactions {
+				transactions.pop_front();
+			}
+		}
+
+		if let Some((ref requests, _)) = self.writer {
+			let _ = requests.unbounded_send(());
+		}
+	}
+}
+
+impl<Hash, Ex> Drop for Journal<Hash, Ex> {
+	fn drop(&mut self) {
+		if let Some((requests, handle)) = self.writer.take() {
+			drop(requests);
+			if handle.join().is_err() {
+				warn!(target: "txpool", "Transaction journal thread panicked");
+			}
+		}
+	}
+}
+
+/// Write the journal every time it is requested, until the requesting side is dropped.
+fn run_writer<Hash, Ex: Encode>(
+	path: PathBuf,
+	transactions: Arc<Mutex<VecDeque<(Hash, Ex)>>>,
+	mut requests: mpsc::UnboundedReceiver<()>,
+) {
+	while let Some(()) = block_on(requests.next()) {
+		// the journal is written once for all the requests received in the meantime
+		while let Ok(Some(())) = requests.try_next() {}
+
+		let encoded = transactions.lock().iter().map(|(_, xt)| xt.encode()).collect::<Vec<_>>();
+		if let Err(e) = write_journal(&path, &encoded) {
+			warn!(target: "txpool", "Failed to write transaction journal {:?}: {}", path, e);
+		}
+	}
+}
+
+fn read_journal<Ex: Decode>(path: &Path) -> io::Result<Vec<Ex>> {
+	let data = fs::read(path)?;
+	let encoded = Vec::<Vec<u8>>::decode(&mut &data[..])
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.what()))?;
+
+	// transactions that can't be decoded any more (e.g. after a runtime upgrade) are skipped
+	Ok(encoded.into_iter().filter_map(|xt| Ex::decode(&mut &xt[..]).ok()).collect())
+}
+
+fn write_journal(path: &Path, encoded: &[Vec<u8>]) -> io::Result<()> {
+	let dir = match path.parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => dir,
+		_ => Path::new("."),
+	};
+	fs::create_dir_all(dir)?;
+
+	// Write to a temporary file first and sync it before renaming it over the journal,
+	// then sync the directory so that the rename itself is durable. This way a crash
+	// leaves either the previous or the new journal, never a truncated one.
+	let tmp_path = path.with_extension("tmp");
+	let mut file = fs::File::create(&tmp_path)?;
+	file.write_all(&encoded.encode())?;
+	file.sync_all()?;
+	fs::rename(&tmp_path, path)?;
+	sync_dir(dir)
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+	fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+	// directories can't be opened (and synced) as files on other platforms
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn options(dir: &tempfile::TempDir, max_transactions: usize) -> JournalOptions {
+		JournalOptions {
+			path: dir.path().join("txpool").join("journal"),
+			max_transactions,
+		}
+	}
+
+	#[test]
+	fn should_persist_recorded_transactions() {
+		// given
+		let dir = tempfile::tempdir().unwrap();
+		let journal = Journal::open(options(&dir, 10), |xt: &u64| *xt);
+
+		// when
+		journal.record(1, 1u64, |hashes| hashes.iter().map(|_| true).collect());
+		journal.record(2, 2u64, |hashes| hashes.iter().map(|_| true).collect());
+		drop(journal);
+
+		// then
+		let journal = Journal::open(options(&dir, 10), |xt: &u64| *xt);
+		assert_eq!(journal.transactions(), vec![1, 2]);
+	}
+
+	#[test]
+	fn should_forget_transactions_that_left_the_pool() {
+		// given
+		let dir = tempfile::tempdir().unwrap();
+		let journal = Journal::open(options(&dir, 10), |xt: &u64| *xt);
+		journal.record(1, 1u64, |_| Vec::new());
+		journal.record(2, 2u64, |hashes| hashes.iter().map(|_| true).collect());
+
+		// when
+		journal.record(3, 3u64, |hashes| hashes.iter().map(|hash| *hash != 1).collect());
+
+		// then
+		assert_eq!(journal.transactions(), vec![2, 3]);
+	}
+
+	#[test]
+	fn should_keep_most_recent_transactions() {
+		// given
+		let dir = tempfile::tempdir().unwrap();
+		let journal = Journal::open(options(&dir, 2), |xt: &u64| *xt);
+
+		// when
+		for xt in 1..=3u64 {
+			journal.record(xt, xt, |hashes| hashes.iter().map(|_| true).collect());
+		}
+
+		// then
+		assert_eq!(journal.transactions(), vec![2, 3]);
+		drop(journal);
+		let journal = Journal::open(options(&dir, 1), |xt: &u64| *xt);
+		assert_eq!(journal.transactions(), vec![3]);
+	}
+
+	#[test]
+	fn should_not_leave_temporary_file() {
+		// given
+		let dir = tempfile::tempdir().unwrap();
+		let journal = Journal::open(options(&dir, 10), |xt: &u64| *xt);
+
+		// when
+		for xt in 1..=100u64 {
+			journal.record(xt, xt, |hashes| hashes.iter().map(|_| true).collect());
+		}
+		drop(journal);
+
+		// then
+		let files = fs::read_dir(dir.path().join("txpool")).unwrap()
+			.map(|entry| entry.unwrap().file_name())
+			.collect::<Vec<_>>();
+		assert_eq!(files, vec![std::ffi::OsString::from("journal")]);
+		let journal = Journal::open(options(&dir, 10), |xt: &u64| *xt);
+		assert_eq!(journal.transactions(), (91..=100).collect::<Vec<_>>());
+	}
+}
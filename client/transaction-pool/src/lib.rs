@@ -20,7 +20,9 @@
 #![warn(unused_extern_crates)]
 
 mod api;
+mod journal;
 mod maintainer;
+mod revalidation;
 
 pub mod error;
 #[cfg(test)]
@@ -32,6 +34,7 @@ pub use crate::maintainer::{FullBasicPoolMaintainer, LightBasicPoolMaintainer};
 
 use std::{collections::HashMap, sync::Arc};
 use futures::{Future, FutureExt};
+use log::{debug, warn};
 
 use sp_runtime::{
 	generic::BlockId,
@@ -42,13 +45,20 @@ use sp_transaction_pool::{
 	TxHash, TransactionFor, TransactionStatusStreamFor,
 };
 
+use crate::journal::Journal;
+
 /// Basic implementation of transaction pool that can be customized by providing PoolApi.
+///
+/// If journal is enabled in the pool options, locally submitted transactions
+/// (see `TransactionPool::submit_local`) and watched transactions are recorded
+/// on disk and can be resubmitted after restart with `reload_journal`.
 pub struct BasicPool<PoolApi, Block>
 	where
 		Block: BlockT,
 		PoolApi: sc_transaction_graph::ChainApi<Block=Block, Hash=Block::Hash>,
 {
 	pool: Arc<sc_transaction_graph::Pool<PoolApi>>,
+	journal: Option<Arc<Journal<Block::Hash, Block::Extrinsic>>>,
 }
 
 impl<PoolApi, Block> BasicPool<PoolApi, Block>
@@ -58,8 +68,13 @@ impl<PoolApi, Block> BasicPool<PoolApi, Block>
 {
 	/// Create new basic transaction pool with provided api.
 	pub fn new(options: sc_transaction_graph::Options, pool_api: PoolApi) -> Self {
+		let journal_options = options.journal.clone();
+		let pool = Arc::new(sc_transaction_graph::Pool::new(options, pool_api));
+		let journal = journal_options
+			.map(|options| Arc::new(Journal::open(options, |xt| pool.hash_of(xt))));
 		BasicPool {
-			pool: Arc::new(sc_transaction_graph::Pool::new(options, pool_api)),
+			pool,
+			journal,
 		}
 	}
 
@@ -67,6 +82,44 @@ impl<PoolApi, Block> BasicPool<PoolApi, Block>
 	pub fn pool(&self) -> &Arc<sc_transaction_graph::Pool<PoolApi>> {
 		&self.pool
 	}
+
+	/// Resubmit transactions recorded in the journal to the pool.
+	///
+	/// The transactions are revalidated at given block; invalid ones are dropped from
+	/// the journal the next time it is written.
+	pub fn reload_journal(&self, at: &BlockId<Block>) -> impl Future<Output=()> {
+		let transactions = self.journal.as_ref()
+			.map(|journal| journal.transactions())
+			.unwrap_or_default();
+		let count = transactions.len();
+		self.pool.submit_at(at, transactions, false)
+			.map(move |result| match result {
+				Ok(imported) => debug!(target: "txpool",
+					"Reloaded {} of {} journaled transactions",
+					imported.iter().filter(|r| r.is_ok()).count(),
+					count,
+				),
+				Err(e) => warn!(target: "txpool", "Failed to reload journaled transactions: {:?}", e),
+			})
+	}
+
+	/// Record given transaction in the journal, if it has been imported.
+	fn journal_transaction<T>(
+		&self,
+		xt: &Block::Extrinsic,
+	) -> Box<dyn FnOnce(&Result<T, PoolApi::Error>) + Send> where PoolApi: 'static {
+		let journal = self.journal.clone();
+		let pool = self.pool.clone();
+		let xt = journal.as_ref().map(|_| xt.clone());
+		Box::new(move |result| if let (Ok(_), Some(journal), Some(xt)) = (result, journal, xt) {
+			let hash = pool.hash_of(&xt);
+			journal.record(hash, xt, |hashes| pool.by_hash(hashes)
+				.into_iter()
+				.map(|tx| tx.is_some())
+				.collect()
+			);
+		})
+	}
 }
 
 impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
@@ -95,14 +148,33 @@ impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
 		Box::new(self.pool.submit_one(at, xt))
 	}
 
+	fn submit_local(
+		&self,
+		at: &BlockId<Self::Block>,
+		xt: TransactionFor<Self>,
+	) -> Box<dyn Future<Output=Result<TxHash<Self>, Self::Error>> + Send + Unpin> {
+		let journal = self.journal_transaction(&xt);
+		Box::new(
+			self.pool.submit_one(at, xt)
+				.map(move |result| {
+					journal(&result);
+					result
+				})
+		)
+	}
+
 	fn submit_and_watch(
 		&self,
 		at: &BlockId<Self::Block>,
 		xt: TransactionFor<Self>,
 	) -> Box<dyn Future<Output=Result<Box<TransactionStatusStreamFor<Self>>, Self::Error>> + Send + Unpin> {
+		let journal = self.journal_transaction(&xt);
 		Box::new(
 			self.pool.submit_and_watch(at, xt)
-				.map(|result| result.map(|watcher| Box::new(watcher.into_stream()) as _))
+				.map(move |result| {
+					journal(&result);
+					result.map(|watcher| Box::new(watcher.into_stream()) as _)
+				})
 		)
 	}
 
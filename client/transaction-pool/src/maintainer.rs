@@ -16,7 +16,8 @@
 
 use std::{
 	marker::{PhantomData, Unpin},
-	sync::Arc,
+	pin::Pin,
+	sync::{Arc, atomic::{AtomicUsize, Ordering}},
 	time::Instant,
 };
 use futures::{
@@ -30,30 +31,145 @@ use sc_client_api::{
 	client::BlockBody,
 	light::{Fetcher, RemoteBodyRequest},
 };
+use sp_api::{ApiExt, RuntimeVersion};
 use sp_core::{Blake2Hasher, H256};
 use sp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, Extrinsic, Header, NumberFor, ProvideRuntimeApi, SimpleArithmetic},
 };
-use sp_blockchain::HeaderBackend;
+use sp_blockchain::{HeaderBackend, HeaderMetadata, tree_route};
 use sp_transaction_pool::TransactionPoolMaintainer;
 use sp_transaction_pool::runtime_api::TaggedTransactionQueue;
 
 use sc_transaction_graph::{self, ChainApi};
 
+use crate::revalidation::RevalidationQueue;
+
 /// Basic transaction pool maintainer for full clients.
+///
+/// On every new best block the maintainer computes the route from the previous
+/// best block: transactions from retracted blocks are put back into the pool and
+/// transactions included in enacted blocks are pruned.
+///
+/// Revalidation of the pool transactions happens in the background: a batch
+/// of transactions is revalidated every `revalidate_block_period` blocks and
+/// the whole pool is revalidated once runtime version changes.
 pub struct FullBasicPoolMaintainer<Client, PoolApi: ChainApi> {
 	pool: Arc<sc_transaction_graph::Pool<PoolApi>>,
 	client: Arc<Client>,
+	revalidation_queue: RevalidationQueue<PoolApi>,
+	revalidate_block_period: usize,
+	blocks_since_revalidation: AtomicUsize,
+	runtime_version: Mutex<Option<RuntimeVersion>>,
+	best_block: Mutex<Option<sc_transaction_graph::BlockHash<PoolApi>>>,
 }
 
-impl<Client, PoolApi: ChainApi> FullBasicPoolMaintainer<Client, PoolApi> {
-	/// Create new basic full pool maintainer.
+impl<Client, PoolApi: ChainApi + 'static> FullBasicPoolMaintainer<Client, PoolApi> {
+	/// Create new basic full pool maintainer with default constants.
+	///
+	/// Default constants are: revalidate up to 64 transactions every 4 blocks.
 	pub fn new(
 		pool: Arc<sc_transaction_graph::Pool<PoolApi>>,
 		client: Arc<Client>,
 	) -> Self {
-		FullBasicPoolMaintainer { pool, client }
+		Self::with_revalidation_params(pool, client, 4, 64)
+	}
+
+	/// Create new basic full pool maintainer with passed revalidation constants.
+	pub fn with_revalidation_params(
+		pool: Arc<sc_transaction_graph::Pool<PoolApi>>,
+		client: Arc<Client>,
+		revalidate_block_period: usize,
+		revalidation_batch_size: usize,
+	) -> Self {
+		let revalidation_queue = RevalidationQueue::new(pool.clone(), revalidation_batch_size);
+		Self::with_revalidation_queue(pool, client, revalidate_block_period, revalidation_queue)
+	}
+
+	fn with_revalidation_queue(
+		pool: Arc<sc_transaction_graph::Pool<PoolApi>>,
+		client: Arc<Client>,
+		revalidate_block_period: usize,
+		revalidation_queue: RevalidationQueue<PoolApi>,
+	) -> Self {
+		FullBasicPoolMaintainer {
+			revalidation_queue,
+			pool,
+			client,
+			revalidate_block_period: std::cmp::max(revalidate_block_period, 1),
+			blocks_since_revalidation: AtomicUsize::new(0),
+			runtime_version: Mutex::new(None),
+			best_block: Mutex::new(None),
+		}
+	}
+
+	/// Returns true if runtime version at given block differs from the one
+	/// seen at the previously maintained block.
+	fn is_runtime_upgraded<Block>(&self, id: &BlockId<Block>) -> bool where
+		Block: BlockT,
+		Client: ProvideRuntimeApi,
+		Client::Api: ApiExt<Block>,
+	{
+		let version = match self.client.runtime_api().runtime_version_at(id) {
+			Ok(version) => version,
+			Err(e) => {
+				debug!(target: "txpool", "[{:?}] Failed to read runtime version: {:?}", id, e);
+				return false;
+			},
+		};
+
+		match self.runtime_version.lock().replace(version.clone()) {
+			Some(previous) => previous != version,
+			None => false,
+		}
+	}
+
+	/// Returns hashes of blocks that are retracted and enacted when switching to
+	/// given block, or `None` if it is not the new best block.
+	///
+	/// `retracted` are the blocks reported as retracted by the import notification;
+	/// they are merged with the route computed from the previous best block.
+	fn route_to_best<Block>(
+		&self,
+		id: &BlockId<Block>,
+		retracted: &[Block::Hash],
+	) -> Option<(Vec<Block::Hash>, Vec<Block::Hash>)> where
+		Block: BlockT,
+		Client: HeaderBackend<Block> + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+		PoolApi: ChainApi<Block = Block>,
+	{
+		let hash = match self.client.block_hash_from_id(id) {
+			Ok(Some(hash)) => hash,
+			err => {
+				warn!(target: "txpool", "[{:?}] Error reading block hash: {:?}", id, err);
+				return None;
+			},
+		};
+		if retracted.is_empty() && self.client.info().best_hash != hash {
+			return None;
+		}
+
+		let previous = self.best_block.lock().replace(hash);
+		let (mut retracted_blocks, enacted_blocks) = match previous {
+			Some(previous) if previous != hash => match tree_route(&*self.client, previous, hash) {
+				Ok(route) => (
+					route.retracted().iter().map(|block| block.hash).collect::<Vec<_>>(),
+					route.enacted().iter().map(|block| block.hash).collect(),
+				),
+				Err(e) => {
+					debug!(target: "txpool", "[{:?}] Error computing tree route: {:?}", id, e);
+					(Vec::new(), vec![hash])
+				},
+			},
+			_ => (Vec::new(), vec![hash]),
+		};
+		for hash in retracted {
+			if !retracted_blocks.contains(hash) {
+				retracted_blocks.push(*hash);
+			}
+		}
+
+		Some((retracted_blocks, enacted_blocks))
 	}
 }
 
@@ -63,6 +179,7 @@ for
 where
 	Block: BlockT<Hash = <Blake2Hasher as sp_core::Hasher>::Out>,
 	Client: ProvideRuntimeApi + HeaderBackend<Block> + BlockBody<Block> + 'static,
+	Client: HeaderMetadata<Block, Error = sp_blockchain::Error>,
 	Client::Api: TaggedTransactionQueue<Block>,
 	PoolApi: ChainApi<Block = Block, Hash = H256> + 'static,
 {
@@ -78,10 +195,23 @@ where
 		let took = move || format!("Took {} ms", now.elapsed().as_millis());
 
 		let id = *id;
+		let (retracted_blocks, enacted_blocks) = match self.route_to_best(&id, retracted) {
+			Some(route) => route,
+			None => {
+				trace!(target: "txpool", "[{:?}] Not a new best block, skipping maintainance", id);
+				return Box::new(ready(()));
+			},
+		};
+
 		trace!(target: "txpool", "[{:?}] Starting pool maintainance", id);
+		// Notify watchers of transactions that were included in retracted blocks.
+		for hash in &retracted_blocks {
+			self.pool.on_block_retracted(*hash);
+		}
+
 		// Put transactions from retracted blocks back into the pool.
 		let client_copy = self.client.clone();
-		let retracted_transactions = retracted.to_vec().into_iter()
+		let retracted_transactions = retracted_blocks.into_iter()
 			.filter_map(move |hash| client_copy.block_body(&BlockId::hash(hash)).ok().unwrap_or(None))
 			.flat_map(|block| block.into_iter())
 			// if signed information is not present, attempt to resubmit anyway.
@@ -97,47 +227,58 @@ where
 				),
 			}));
 
+		// Keep track of the runtime version even if the pool is empty.
+		let revalidate_all = self.is_runtime_upgraded(&id);
+
 		// Avoid calling into runtime if there is nothing to prune from the pool anyway.
 		if self.pool.status().is_empty() {
 			return Box::new(resubmit_future)
 		}
 
-		let block = (self.client.header(id), self.client.block_body(&id));
-		let prune_future = match block {
-			(Ok(Some(header)), Ok(Some(extrinsics))) => {
-				let parent_id = BlockId::hash(*header.parent_hash());
-				let prune_future = self.pool
-					.prune(&id, &parent_id, &extrinsics)
-					.then(move |prune_result| ready(match prune_result {
-						Ok(_) => trace!(target: "txpool",
-							"[{:?}] Pruning done. {}", id, took()
-						),
-						Err(e) => warn!(target: "txpool",
-							"[{:?}] Error pruning transactions: {:?}", id, e
-						),
-					}));
-
-				Either::Left(resubmit_future.then(|_| prune_future))
-			},
-			(Ok(_), Ok(_)) => Either::Right(resubmit_future),
-			err => {
-				warn!(target: "txpool", "[{:?}] Error reading block: {:?}", id, err);
-				Either::Right(resubmit_future)
-			},
-		};
+		// Prune transactions included in enacted blocks, one block after another.
+		let mut prune_future: Pin<Box<dyn Future<Output=()> + Send>> = Box::pin(resubmit_future);
+		for hash in enacted_blocks {
+			let block_id = BlockId::hash(hash);
+			let block = (self.client.header(block_id), self.client.block_body(&block_id));
+			match block {
+				(Ok(Some(header)), Ok(Some(extrinsics))) => {
+					let parent_id = BlockId::hash(*header.parent_hash());
+					let pool = self.pool.clone();
+					prune_future = Box::pin(prune_future.then(move |_| pool
+						.prune(&block_id, &parent_id, &extrinsics)
+						.then(move |prune_result| ready(match prune_result {
+							Ok(_) => trace!(target: "txpool",
+								"[{:?}] Pruning done. {}", block_id, took()
+							),
+							Err(e) => warn!(target: "txpool",
+								"[{:?}] Error pruning transactions: {:?}", block_id, e
+							),
+						}))
+					));
+				},
+				(Ok(_), Ok(_)) => {},
+				err => warn!(target: "txpool", "[{:?}] Error reading block: {:?}", block_id, err),
+			}
+		}
 
-		let revalidate_future = self.pool
-			.revalidate_ready(&id, Some(16))
-			.then(move |result| ready(match result {
-				Ok(_) => debug!(target: "txpool",
-					"[{:?}] Revalidation done: {}", id, took()
-				),
-				Err(e) => warn!(target: "txpool",
-					"[{:?}] Encountered errors while revalidating transactions: {:?}", id, e
-				),
-			}));
+		// Revalidation is queued once pruning is done, so that the transactions
+		// included in the block are not needlessly revalidated.
+		let revalidate_batch = self.blocks_since_revalidation.fetch_add(1, Ordering::Relaxed) + 1
+			>= self.revalidate_block_period;
+		if revalidate_all || revalidate_batch {
+			self.blocks_since_revalidation.store(0, Ordering::Relaxed);
+		}
+		let revalidation_queue = self.revalidation_queue.clone();
+		let revalidate_future = prune_future.map(move |_| {
+			if revalidate_all {
+				debug!(target: "txpool", "[{:?}] Runtime upgraded, revalidating all transactions", id);
+				revalidation_queue.revalidate_all(id);
+			} else if revalidate_batch {
+				revalidation_queue.revalidate_batch(id);
+			}
+		});
 
-		Box::new(prune_future.then(|_| revalidate_future))
+		Box::new(revalidate_future)
 	}
 }
 
@@ -353,11 +494,12 @@ impl<N: Clone + Copy + SimpleArithmetic> TxPoolRevalidationStatus<N> {
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use futures::executor::block_on;
+	use futures::{channel::mpsc, executor::block_on};
 	use codec::Encode;
 	use substrate_test_runtime_client::{prelude::*, runtime::{Block, Transfer}, sp_consensus::{BlockOrigin, SelectChain}};
-	use sp_transaction_pool::PoolStatus;
+	use sp_transaction_pool::{PoolStatus, TransactionStatus};
 	use crate::api::{FullChainApi, LightChainApi};
+	use crate::revalidation::Revalidate;
 
 	#[test]
 	fn should_remove_transactions_from_the_full_pool() {
@@ -392,6 +534,81 @@ mod tests {
 		assert_eq!(pool.status().future, 0);
 	}
 
+	type TestMaintainer = FullBasicPoolMaintainer<TestClient, FullChainApi<TestClient, Block>>;
+	type RevalidationRequests = mpsc::UnboundedReceiver<Revalidate<FullChainApi<TestClient, Block>>>;
+
+	fn maintainer_with_queue(
+		revalidate_block_period: usize,
+	) -> (TestMaintainer, RevalidationRequests, Arc<TestClient>) {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let pool = Arc::new(sc_transaction_graph::Pool::new(Default::default(), FullChainApi::new(client.clone())));
+		let transaction = Transfer {
+			amount: 5,
+			nonce: 0,
+			from: AccountKeyring::Alice.into(),
+			to: Default::default(),
+		}.into_signed_tx();
+		block_on(pool.submit_one(&BlockId::number(0), transaction)).unwrap();
+
+		let (queue, requests) = RevalidationQueue::without_worker();
+		let maintainer = FullBasicPoolMaintainer::with_revalidation_queue(
+			pool,
+			client.clone(),
+			revalidate_block_period,
+			queue,
+		);
+		(maintainer, requests, client)
+	}
+
+	fn import_empty_block(client: &TestClient) -> BlockId<Block> {
+		let block = client.new_block(Default::default()).unwrap().bake().unwrap();
+		let id = BlockId::hash(block.header().hash());
+		client.import(BlockOrigin::Own, block).unwrap();
+		id
+	}
+
+	#[test]
+	fn should_queue_batch_revalidation_every_block_period() {
+		// given
+		let (maintainer, mut requests, client) = maintainer_with_queue(2);
+
+		// when
+		let first = import_empty_block(&client);
+		block_on(maintainer.maintain(&first, &[]));
+		let after_first = requests.try_next().is_ok();
+		let second = import_empty_block(&client);
+		block_on(maintainer.maintain(&second, &[]));
+		let after_second = requests.try_next();
+
+		// then
+		assert!(!after_first);
+		match after_second {
+			Ok(Some(Revalidate::Batch(at))) => assert_eq!(at, second),
+			_ => panic!("Expected batch revalidation at the second block"),
+		}
+		assert_eq!(maintainer.pool.status().ready, 1);
+	}
+
+	#[test]
+	fn should_queue_full_revalidation_on_runtime_upgrade() {
+		// given
+		let (maintainer, mut requests, client) = maintainer_with_queue(10);
+		let mut upgraded_from = client.runtime_api().runtime_version_at(&BlockId::number(0)).unwrap();
+		upgraded_from.spec_version += 1;
+		*maintainer.runtime_version.lock() = Some(upgraded_from);
+
+		// when
+		let id = import_empty_block(&client);
+		block_on(maintainer.maintain(&id, &[]));
+
+		// then
+		match requests.try_next() {
+			Ok(Some(Revalidate::All(at))) => assert_eq!(at, id),
+			_ => panic!("Expected full revalidation after the runtime upgrade"),
+		}
+		assert_eq!(maintainer.blocks_since_revalidation.load(Ordering::Relaxed), 0);
+	}
+
 	#[test]
 	fn should_remove_transactions_from_the_light_pool() {
 		let transaction = Transfer {
@@ -605,4 +822,58 @@ mod tests {
 		assert_eq!(pool.status().ready, 1);
 		assert_eq!(pool.status().future, 0);
 	}
+
+	#[test]
+	fn should_resubmit_transactions_from_blocks_retracted_by_reorg() {
+		let (client, longest_chain) = TestClientBuilder::new().build_with_longest_chain();
+		let client = Arc::new(client);
+		let options = sc_transaction_graph::Options {
+			retracted_watch_blocks: 8,
+			..Default::default()
+		};
+		let pool = sc_transaction_graph::Pool::new(options, FullChainApi::new(client.clone()));
+		let pool = Arc::new(pool);
+		let maintainer = FullBasicPoolMaintainer::new(pool.clone(), client.clone());
+		let transaction = Transfer {
+			amount: 5,
+			nonce: 0,
+			from: AccountKeyring::Alice.into(),
+			to: Default::default(),
+		}.into_signed_tx();
+		let genesis = longest_chain.best_chain().unwrap().hash();
+		let watcher = block_on(pool.submit_and_watch(&BlockId::hash(genesis), transaction.clone())).unwrap();
+
+		// import block including the transaction
+		let mut builder = client.new_block(Default::default()).unwrap();
+		builder.push(transaction.clone()).unwrap();
+		let block = builder.bake().unwrap();
+		let block1_hash = block.header().hash();
+		client.import(BlockOrigin::Own, block).unwrap();
+		block_on(maintainer.maintain(&BlockId::hash(block1_hash), &[]));
+		assert_eq!(pool.status().ready, 0);
+
+		// import a longer fork without the transaction
+		let block = client.new_block_at(&BlockId::hash(genesis), Default::default()).unwrap().bake().unwrap();
+		let fork1_hash = block.header().hash();
+		client.import(BlockOrigin::Own, block).unwrap();
+		let block = client.new_block_at(&BlockId::hash(fork1_hash), Default::default()).unwrap().bake().unwrap();
+		let fork2_hash = block.header().hash();
+		client.import(BlockOrigin::Own, block).unwrap();
+		assert_eq!(longest_chain.best_chain().unwrap().hash(), fork2_hash);
+
+		// when
+		block_on(maintainer.maintain(&BlockId::hash(fork2_hash), &[]));
+
+		// then
+		assert_eq!(pool.status().ready, 1);
+		drop(maintainer);
+		drop(pool);
+		let events = futures::executor::block_on_stream(watcher.into_stream()).collect::<Vec<_>>();
+		assert_eq!(events, vec![
+			TransactionStatus::Ready,
+			TransactionStatus::InBlock(block1_hash),
+			TransactionStatus::Retracted(block1_hash),
+			TransactionStatus::Ready,
+		]);
+	}
 }
@@ -0,0 +1,290 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Background revalidation of the pool transactions.
+//!
+//! Revalidation is performed on a dedicated thread, so that block import
+//! notifications are never blocked by validating a large pool. Every request
+//! revalidates a bounded batch of ready and future transactions; consecutive
+//! requests continue where the previous one stopped, so that every transaction
+//! is eventually revalidated. After a runtime upgrade the whole pool is
+//! revalidated at once.
+
+use std::{collections::HashSet, sync::Arc, thread};
+
+use futures::{
+	Future, FutureExt, StreamExt,
+	channel::mpsc,
+	executor::block_on,
+	future::{Either, ready},
+};
+use log::{debug, warn};
+use sp_runtime::generic::BlockId;
+
+use sc_transaction_graph::{self, ChainApi, ExHash, Pool};
+
+/// Request sent to the revalidation worker.
+pub(crate) enum Revalidate<B: ChainApi> {
+	/// Revalidate the next batch of transactions at given block.
+	Batch(BlockId<B::Block>),
+	/// Revalidate all transactions at given block.
+	All(BlockId<B::Block>),
+}
+
+/// Revalidation worker.
+///
+/// Remembers which transactions were already revalidated in the current round.
+struct RevalidationWorker<B: ChainApi> {
+	pool: Arc<Pool<B>>,
+	batch_size: usize,
+	revalidated: HashSet<ExHash<B>>,
+}
+
+impl<B: ChainApi> RevalidationWorker<B> {
+	fn new(pool: Arc<Pool<B>>, batch_size: usize) -> Self {
+		RevalidationWorker {
+			pool,
+			batch_size,
+			revalidated: HashSet::new(),
+		}
+	}
+
+	/// Returns the next batch of transactions to revalidate.
+	///
+	/// Ready transactions are picked (in priority order) before future ones.
+	/// Once every transaction in the pool has been revalidated, a new round is started.
+	fn next_batch(&mut self) -> Vec<sc_transaction_graph::ExtrinsicFor<B>> {
+		let pool = &self.pool;
+		let candidates = || pool.ready()
+			.map(|tx| (tx.hash.clone(), tx.data.clone()))
+			.chain(pool.futures());
+
+		let mut batch = Vec::with_capacity(self.batch_size);
+		for (hash, xt) in candidates() {
+			if batch.len() == self.batch_size {
+				break;
+			}
+			if self.revalidated.insert(hash) {
+				batch.push(xt);
+			}
+		}
+
+		if batch.is_empty() {
+			self.revalidated.clear();
+			for (hash, xt) in candidates().take(self.batch_size) {
+				self.revalidated.insert(hash);
+				batch.push(xt);
+			}
+		}
+
+		batch
+	}
+
+	/// Process single revalidation request.
+	fn revalidate(&mut self, request: Revalidate<B>) -> impl Future<Output=()> {
+		let (at, batch) = match request {
+			Revalidate::Batch(at) => (at, self.next_batch()),
+			Revalidate::All(at) => {
+				self.revalidated.clear();
+				let all = self.pool.ready()
+					.map(|tx| tx.data.clone())
+					.chain(self.pool.futures().into_iter().map(|(_, xt)| xt))
+					.collect::<Vec<_>>();
+				(at, all)
+			},
+		};
+
+		if batch.is_empty() {
+			return Either::Left(ready(()));
+		}
+
+		debug!(target: "txpool", "[{:?}] Revalidating {} transactions", at, batch.len());
+		Either::Right(self.pool
+			.revalidate(&at, batch)
+			.map(move |result| if let Err(e) = result {
+				warn!(target: "txpool",
+					"[{:?}] Encountered errors while revalidating transactions: {:?}", at, e
+				);
+			}))
+	}
+
+	/// Process requests until the sending side of the queue is dropped.
+	fn run(mut self, mut requests: mpsc::UnboundedReceiver<Revalidate<B>>) {
+		while let Some(mut request) = block_on(requests.next()) {
+			// only the most recent block matters; full revalidation takes precedence
+			while let Ok(Some(next)) = requests.try_next() {
+				request = match (request, next) {
+					(Revalidate::All(_), next) => Revalidate::All(next.at()),
+					(_, next) => next,
+				};
+			}
+
+			block_on(self.revalidate(request));
+		}
+	}
+}
+
+impl<B: ChainApi> Revalidate<B> {
+	fn at(&self) -> BlockId<B::Block> {
+		match *self {
+			Revalidate::Batch(at) | Revalidate::All(at) => at,
+		}
+	}
+}
+
+/// Handle to the background revalidation worker.
+///
+/// The worker thread exits once the queue is dropped.
+pub struct RevalidationQueue<B: ChainApi> {
+	requests: mpsc::UnboundedSender<Revalidate<B>>,
+}
+
+impl<B: ChainApi> Clone for RevalidationQueue<B> {
+	fn clone(&self) -> Self {
+		RevalidationQueue { requests: self.requests.clone() }
+	}
+}
+
+impl<B: ChainApi + 'static> RevalidationQueue<B> {
+	/// Spawn a new revalidation worker thread, revalidating up to
+	/// `batch_size` transactions per request.
+	pub fn new(pool: Arc<Pool<B>>, batch_size: usize) -> Self {
+		let (requests, receiver) = mpsc::unbounded();
+		let worker = RevalidationWorker::new(pool, batch_size);
+		let spawned = thread::Builder::new()
+			.name("txpool-revalidation".into())
+			.spawn(move || worker.run(receiver));
+		if let Err(e) = spawned {
+			warn!(target: "txpool", "Failed to spawn revalidation thread: {:?}", e);
+		}
+
+		RevalidationQueue { requests }
+	}
+
+	/// Create a queue without a worker, returning the receiving side of the requests.
+	#[cfg(test)]
+	pub(crate) fn without_worker() -> (Self, mpsc::UnboundedReceiver<Revalidate<B>>) {
+		let (requests, receiver) = mpsc::unbounded();
+		(RevalidationQueue { requests }, receiver)
+	}
+
+	/// Queue revalidation of the next batch of transactions at given block.
+	pub fn revalidate_batch(&self, at: BlockId<B::Block>) {
+		self.send(Revalidate::Batch(at));
+	}
+
+	/// Queue revalidation of all transactions at given block.
+	pub fn revalidate_all(&self, at: BlockId<B::Block>) {
+		self.send(Revalidate::All(at));
+	}
+
+	fn send(&self, request: Revalidate<B>) {
+		if self.requests.unbounded_send(request).is_err() {
+			warn!(target: "txpool", "Revalidation worker has stopped; request ignored.");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_test_runtime_client::{
+		prelude::*, runtime::{Block, Transfer}, sp_consensus::BlockOrigin,
+	};
+	use crate::api::FullChainApi;
+
+	fn pool_with_transactions(count: u64) -> (Arc<Pool<FullChainApi<TestClient, Block>>>, Arc<TestClient>) {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let pool = Arc::new(Pool::new(Default::default(), FullChainApi::new(client.clone())));
+		for nonce in 0..count {
+			let transaction = Transfer {
+				amount: 5,
+				nonce,
+				from: AccountKeyring::Alice.into(),
+				to: Default::default(),
+			}.into_signed_tx();
+			block_on(pool.submit_one(&BlockId::number(0), transaction)).unwrap();
+		}
+		(pool, client)
+	}
+
+	#[test]
+	fn should_revalidate_transactions_in_batches() {
+		// given
+		let (pool, _client) = pool_with_transactions(3);
+		let mut worker = RevalidationWorker::new(pool.clone(), 2);
+
+		// when
+		let first = worker.next_batch();
+		let second = worker.next_batch();
+		let third = worker.next_batch();
+
+		// then
+		assert_eq!(first.len(), 2);
+		assert_eq!(second.len(), 1);
+		assert!(first.iter().all(|xt| !second.contains(xt)));
+		// new round is started once every transaction has been revalidated
+		assert_eq!(third, first);
+	}
+
+	#[test]
+	fn should_keep_valid_transactions_after_revalidation() {
+		// given
+		let (pool, _client) = pool_with_transactions(3);
+		let mut worker = RevalidationWorker::new(pool.clone(), 1);
+
+		// when
+		block_on(worker.revalidate(Revalidate::Batch(BlockId::number(0))));
+		block_on(worker.revalidate(Revalidate::All(BlockId::number(0))));
+
+		// then
+		assert_eq!(pool.status().ready, 3);
+		assert_eq!(pool.status().future, 0);
+		assert!(worker.revalidated.is_empty());
+	}
+
+	#[test]
+	fn worker_should_remove_stale_transactions_at_most_recent_block() {
+		// given
+		let (pool, client) = pool_with_transactions(3);
+		let included = Transfer {
+			amount: 5,
+			nonce: 0,
+			from: AccountKeyring::Alice.into(),
+			to: Default::default(),
+		}.into_signed_tx();
+		let mut builder = client.new_block(Default::default()).unwrap();
+		builder.push(included).unwrap();
+		let block = builder.bake().unwrap();
+		let id = BlockId::hash(block.header().hash());
+		client.import(BlockOrigin::Own, block).unwrap();
+
+		let (requests, receiver) = mpsc::unbounded();
+		let worker = RevalidationWorker::new(pool.clone(), 1);
+
+		// when
+		// the worker only handles the most recent block, revalidating everything
+		// since a full revalidation was requested in the meantime
+		requests.unbounded_send(Revalidate::All(BlockId::number(0))).unwrap();
+		requests.unbounded_send(Revalidate::Batch(id)).unwrap();
+		drop(requests);
+		worker.run(receiver);
+
+		// then
+		assert_eq!(pool.status().ready, 2);
+		assert_eq!(pool.status().future, 0);
+	}
+}
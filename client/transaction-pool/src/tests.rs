@@ -220,3 +220,30 @@ fn should_correctly_prune_transactions_providing_more_than_one_tag() {
 	assert_eq!(pool.status().ready, 0);
 	assert_eq!(pool.status().future, 2);
 }
+
+#[test]
+fn should_reload_local_transactions_from_journal() {
+	let dir = tempfile::tempdir().unwrap();
+	let options = || sc_transaction_graph::Options {
+		journal: Some(sc_transaction_graph::JournalOptions {
+			path: dir.path().join("journal"),
+			max_transactions: 16,
+		}),
+		..Default::default()
+	};
+
+	// given
+	let pool = BasicPool::new(options(), TestApi::default());
+	block_on(pool.submit_local(&BlockId::number(0), uxt(Alice, 209))).unwrap();
+	block_on(pool.submit_one(&BlockId::number(0), uxt(Alice, 210))).unwrap();
+	assert_eq!(pool.status().ready, 2);
+	drop(pool);
+
+	// when
+	let pool = BasicPool::new(options(), TestApi::default());
+	block_on(pool.reload_journal(&BlockId::number(0)));
+
+	// then only the local transaction is restored
+	let pending: Vec<_> = pool.pool().ready().map(|a| a.data.transfer().nonce).collect();
+	assert_eq!(pending, vec![209]);
+}
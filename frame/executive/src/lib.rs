@@ -254,6 +254,8 @@ where
 		encoded_len: usize,
 		to_note: Option<Vec<u8>>,
 	) -> ApplyExtrinsicResult {
+		frame_support::enter_span!("apply_extrinsic");
+
 		// Verify that the signature is good.
 		let xt = uxt.check(&Default::default())?;
 
@@ -142,6 +142,54 @@ macro_rules! ensure {
 	}}
 }
 
+/// Enter a span named `$name` until the end of the current scope.
+///
+/// Natively, this is a `tracing` span. In Wasm, the span is entered on the host through
+/// `sp_io::wasm_tracing`, but only when `frame-support` is built with the `wasm-tracing` feature.
+///
+/// Used as `enter_span!("on_initialize")`.
+#[macro_export]
+macro_rules! enter_span {
+	( $name:expr ) => {
+		$crate::sp_std::if_std! {
+			use $crate::tracing;
+			let span = tracing::span!(tracing::Level::DEBUG, $name);
+			let _enter = span.enter();
+		}
+		let _wasm_span = $crate::WasmSpan::enter(module_path!(), $name);
+	}
+}
+
+/// A span entered in Wasm by [`enter_span`], exited when dropped.
+#[doc(hidden)]
+pub struct WasmSpan(u64);
+
+impl WasmSpan {
+	/// Enter a span, doing nothing unless built for Wasm with the `wasm-tracing` feature.
+	pub fn enter(target: &str, name: &str) -> Self {
+		#[cfg(all(not(feature = "std"), feature = "wasm-tracing"))]
+		{
+			WasmSpan(sp_io::wasm_tracing::enter_span(target, name))
+		}
+		#[cfg(not(all(not(feature = "std"), feature = "wasm-tracing")))]
+		{
+			let _ = (target, name);
+			WasmSpan(0)
+		}
+	}
+}
+
+impl Drop for WasmSpan {
+	fn drop(&mut self) {
+		#[cfg(all(not(feature = "std"), feature = "wasm-tracing"))]
+		{
+			if self.0 != 0 {
+				sp_io::wasm_tracing::exit_span(self.0);
+			}
+		}
+	}
+}
+
 /// Evaluate an expression, assert it returns an expected `Err` value and that
 /// runtime storage has not been mutated (i.e. expression is a no-operation).
 ///
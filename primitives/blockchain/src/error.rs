@@ -123,6 +123,12 @@ pub enum Error {
 	/// Invalid calculated state root on block import.
 	#[display(fmt = "Calculated state root does not match.")]
 	InvalidStateRoot,
+	/// The runtime call was interrupted because it did not complete before its deadline.
+	#[display(fmt = "Runtime call timed out")]
+	Timeout,
+	/// The runtime call was interrupted because it consumed all of its fuel.
+	#[display(fmt = "Runtime call ran out of fuel")]
+	OutOfFuel,
 	/// A convenience variant for String
 	#[display(fmt = "{}", _0)]
 	Msg(String),
@@ -21,7 +21,8 @@ use crate::{crypto::KeyTypeId, ed25519, sr25519};
 use std::{
 	fmt::{Debug, Display},
 	panic::UnwindSafe,
-	sync::Arc,
+	sync::{Arc, atomic::{AtomicU8, Ordering}},
+	time::Instant,
 };
 
 pub use sp_externalities::{Externalities, ExternalitiesExt};
@@ -79,6 +80,56 @@ sp_externalities::decl_extension! {
 	pub struct KeystoreExt(BareCryptoStorePtr);
 }
 
+/// A limit on the resources consumed by a runtime call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+	/// The call did not complete before its deadline.
+	Deadline = 1,
+	/// The call consumed all of its fuel.
+	Fuel = 2,
+}
+
+/// Limits on the resources consumed by a runtime call.
+///
+/// Only enforced when the call is executed by a metered Wasm runtime, which interrupts the call
+/// as soon as one of the limits is exceeded.
+#[derive(Debug, Clone)]
+pub struct ExecutionLimits {
+	/// Instant after which the call is interrupted.
+	pub deadline: Option<Instant>,
+	/// Amount of fuel, roughly the number of executed Wasm instructions, after which the call is
+	/// interrupted.
+	pub fuel: Option<u64>,
+	/// The exceeded limit, shared by all clones.
+	exceeded: Arc<AtomicU8>,
+}
+
+impl ExecutionLimits {
+	/// Create new limits.
+	pub fn new(deadline: Option<Instant>, fuel: Option<u64>) -> Self {
+		ExecutionLimits { deadline, fuel, exceeded: Arc::new(AtomicU8::new(0)) }
+	}
+
+	/// Returns the limit exceeded by the call, if any.
+	pub fn exceeded(&self) -> Option<LimitExceeded> {
+		match self.exceeded.load(Ordering::Relaxed) {
+			1 => Some(LimitExceeded::Deadline),
+			2 => Some(LimitExceeded::Fuel),
+			_ => None,
+		}
+	}
+
+	/// Record that the call exceeded `limit`.
+	pub fn set_exceeded(&self, limit: LimitExceeded) {
+		self.exceeded.store(limit as u8, Ordering::Relaxed);
+	}
+}
+
+sp_externalities::decl_extension! {
+	/// The execution limits extension to register/retrieve from the externalities.
+	pub struct ExecutionLimitsExt(ExecutionLimits);
+}
+
 /// Code execution engine.
 pub trait CodeExecutor: Sized + Send + Sync {
 	/// Externalities error type.
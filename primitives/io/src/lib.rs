@@ -753,6 +753,69 @@ pub trait Logging {
 	}
 }
 
+/// Interface that provides functions for tracing the execution of the runtime.
+///
+/// The spans entered by the runtime are entered on the host as spans of the
+/// [`WASM_TRACE_TARGET`] target, with the target and name given by the runtime recorded as the
+/// `wasm_target` and `wasm_name` values.
+#[runtime_interface]
+pub trait WasmTracing {
+	/// Enter a span and return its id, `0` if no subscriber is interested in it.
+	fn enter_span(target: &str, name: &str) -> u64 {
+		runtime_spans::enter(target, name)
+	}
+
+	/// Exit and close the span with the given `id`, as returned by `enter_span`.
+	fn exit_span(id: u64) {
+		runtime_spans::exit(id)
+	}
+}
+
+/// Target of the spans entered by the runtime through [`wasm_tracing`].
+#[cfg(feature = "std")]
+pub const WASM_TRACE_TARGET: &str = "wasm_tracing";
+
+/// The spans entered by the runtime, kept open until the runtime exits them.
+#[cfg(feature = "std")]
+mod runtime_spans {
+	use std::{cell::RefCell, collections::HashMap};
+	use tracing::{dispatcher, Span};
+
+	thread_local! {
+		/// The last id given to a span and the spans entered on this thread, by id.
+		static SPANS: RefCell<(u64, HashMap<u64, Span>)> = RefCell::new((0, HashMap::new()));
+	}
+
+	pub fn enter(target: &str, name: &str) -> u64 {
+		let span = tracing::span!(
+			target: super::WASM_TRACE_TARGET,
+			tracing::Level::DEBUG,
+			"wasm_tracing",
+			wasm_target = target,
+			wasm_name = name,
+		);
+		let span_id = match span.id() {
+			Some(span_id) => span_id,
+			None => return 0,
+		};
+		dispatcher::get_default(|dispatch| dispatch.enter(&span_id));
+
+		SPANS.with(|spans| {
+			let (ref mut last_id, ref mut spans) = *spans.borrow_mut();
+			*last_id += 1;
+			spans.insert(*last_id, span);
+			*last_id
+		})
+	}
+
+	pub fn exit(id: u64) {
+		let span = SPANS.with(|spans| spans.borrow_mut().1.remove(&id));
+		if let Some(span_id) = span.as_ref().and_then(Span::id) {
+			dispatcher::get_default(|dispatch| dispatch.exit(&span_id));
+		}
+	}
+}
+
 /// Wasm-only interface that provides functions for interacting with the sandbox.
 #[runtime_interface(wasm_only)]
 pub trait Sandbox {
@@ -895,6 +958,7 @@ pub type SubstrateHostFunctions = (
 	hashing::HostFunctions,
 	allocator::HostFunctions,
 	logging::HostFunctions,
+	wasm_tracing::HostFunctions,
 	sandbox::HostFunctions,
 	crate::trie::HostFunctions,
 );
@@ -22,7 +22,7 @@
 //! exported into wasm and convert back the result.
 //!
 //! [`generate`](bare_function_interface::generate) is the entry point for generating for each
-//! trait method one bare function.
+//! trait method one bare function, calling the latest version of the method.
 //!
 //! [`function_for_method`](bare_function_interface::function_for_method) generates the bare
 //! function per trait method. Each bare function contains both implementations. The implementations
@@ -30,7 +30,7 @@
 
 use crate::utils::{
 	generate_crate_access, create_exchangeable_host_function_ident, get_function_arguments,
-	get_function_argument_names, get_trait_methods,
+	get_function_argument_names, create_function_ident_with_version, RuntimeInterface,
 };
 
 use syn::{
@@ -45,10 +45,14 @@ use std::iter;
 
 /// Generate one bare function per trait method. The name of the bare function is equal to the name
 /// of the trait method.
-pub fn generate(trait_def: &ItemTrait, is_wasm_only: bool) -> Result<TokenStream> {
+pub fn generate(
+	trait_def: &ItemTrait,
+	runtime_interface: &RuntimeInterface,
+	is_wasm_only: bool,
+) -> Result<TokenStream> {
 	let trait_name = &trait_def.ident;
-	get_trait_methods(trait_def).try_fold(TokenStream::new(), |mut t, m| {
-		t.extend(function_for_method(trait_name, m, is_wasm_only)?);
+	runtime_interface.latest_versions().try_fold(TokenStream::new(), |mut t, (version, m)| {
+		t.extend(function_for_method(trait_name, m, version, is_wasm_only)?);
 		Ok(t)
 	})
 }
@@ -57,9 +61,10 @@ pub fn generate(trait_def: &ItemTrait, is_wasm_only: bool) -> Result<TokenStream
 fn function_for_method(
 	trait_name: &Ident,
 	method: &TraitItemMethod,
+	version: u32,
 	is_wasm_only: bool,
 ) -> Result<TokenStream> {
-	let std_impl = function_std_impl(trait_name, method, is_wasm_only)?;
+	let std_impl = function_std_impl(trait_name, method, version, is_wasm_only)?;
 	let no_std_impl = function_no_std_impl(method)?;
 
 	Ok(
@@ -96,6 +101,7 @@ fn function_no_std_impl(method: &TraitItemMethod) -> Result<TokenStream> {
 fn function_std_impl(
 	trait_name: &Ident,
 	method: &TraitItemMethod,
+	version: u32,
 	is_wasm_only: bool,
 ) -> Result<TokenStream> {
 	let function_name = &method.sig.ident;
@@ -118,7 +124,8 @@ fn function_std_impl(
 	let attrs = &method.attrs;
 	// Don't make the function public accessible when this is a wasm only interface.
 	let vis = if is_wasm_only { quote!() } else { quote!(pub) };
-	let call_to_trait = generate_call_to_trait(trait_name, method, is_wasm_only);
+	let arg_names = get_function_argument_names(&method.sig).map(|name| quote!( #name ));
+	let call_to_trait = generate_call_to_trait(trait_name, method, version, arg_names, is_wasm_only);
 
 	Ok(
 		quote_spanned! { method.span() =>
@@ -131,19 +138,21 @@ fn function_std_impl(
 	)
 }
 
-/// Generate the call to the interface trait.
-fn generate_call_to_trait(
+/// Generate the call to the given version of the method of the interface trait, passing it the
+/// `arg_values`.
+pub(super) fn generate_call_to_trait(
 	trait_name: &Ident,
 	method: &TraitItemMethod,
+	version: u32,
+	arg_values: impl Iterator<Item = TokenStream>,
 	is_wasm_only: bool,
 ) -> TokenStream {
 	let crate_ = generate_crate_access();
-	let method_name = &method.sig.ident;
+	let method_name = create_function_ident_with_version(&method.sig.ident, version);
 	let expect_msg = format!(
 		"`{}` called outside of an Externalities-provided environment.",
-		method_name,
+		method.sig.ident,
 	);
-	let arg_names = get_function_argument_names(&method.sig);
 
 	if takes_self_argument(&method.sig) {
 		let instance = if is_wasm_only {
@@ -152,7 +161,7 @@ fn generate_call_to_trait(
 			Ident::new("__externalities__", Span::call_site())
 		};
 
-		let impl_ = quote!( #trait_name::#method_name(&mut #instance, #( #arg_names, )*) );
+		let impl_ = quote!( #trait_name::#method_name(&mut #instance, #( #arg_values, )*) );
 
 		if is_wasm_only {
 			quote_spanned! { method.span() => #impl_ }
@@ -171,7 +180,7 @@ fn generate_call_to_trait(
 
 		quote_spanned! { method.span() =>
 			<&mut dyn #impl_trait_name as #trait_name>::#method_name(
-				#( #arg_names, )*
+				#( #arg_values, )*
 			)
 		}
 	}
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::utils::generate_runtime_interface_include;
+use crate::utils::{generate_runtime_interface_include, get_runtime_interface};
 
 use proc_macro2::{Span, TokenStream};
 
@@ -39,11 +39,20 @@ pub mod keywords {
 /// It expects the trait definition the attribute was put above and if this should be an wasm only
 /// interface.
 pub fn runtime_interface_impl(trait_def: ItemTrait, is_wasm_only: bool) -> Result<TokenStream> {
-	let bare_functions = bare_function_interface::generate(&trait_def, is_wasm_only)?;
+	let runtime_interface = get_runtime_interface(&trait_def)?;
+	let bare_functions = bare_function_interface::generate(
+		&trait_def,
+		&runtime_interface,
+		is_wasm_only,
+	)?;
 	let crate_include = generate_runtime_interface_include();
 	let mod_name = Ident::new(&trait_def.ident.to_string().to_snake_case(), Span::call_site());
-	let trait_decl_impl = trait_decl_impl::process(&trait_def, is_wasm_only)?;
-	let host_functions = host_function_interface::generate(&trait_def, is_wasm_only)?;
+	let trait_decl_impl = trait_decl_impl::process(&trait_def, &runtime_interface, is_wasm_only)?;
+	let host_functions = host_function_interface::generate(
+		&trait_def,
+		&runtime_interface,
+		is_wasm_only,
+	)?;
 	let vis = trait_def.vis;
 	let attrs = &trait_def.attrs;
 
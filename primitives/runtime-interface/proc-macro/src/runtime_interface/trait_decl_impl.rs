@@ -15,9 +15,13 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Checks the trait declaration, makes the trait declaration module local, removes all method
-//! default implementations and implements the trait for `&mut dyn Externalities`.
+//! default implementations, renames every method after its version and implements the trait for
+//! `&mut dyn Externalities`.
 
-use crate::utils::{generate_crate_access, get_function_argument_types_without_ref};
+use crate::utils::{
+	generate_crate_access, get_function_argument_types_without_ref, get_function_version,
+	strip_version_attribute, create_function_ident_with_version, RuntimeInterface,
+};
 
 use syn::{
 	ItemTrait, TraitItemMethod, Result, TraitItem, Error, fold::{self, Fold}, spanned::Spanned,
@@ -30,8 +34,12 @@ use quote::quote;
 
 /// Process the given trait definition, by checking that the definition is valid, fold it to the
 /// essential definition and implement this essential definition for `dyn Externalities`.
-pub fn process(trait_def: &ItemTrait, is_wasm_only: bool) -> Result<TokenStream> {
-	let impl_trait = impl_trait_for_externalities(trait_def, is_wasm_only)?;
+pub fn process(
+	trait_def: &ItemTrait,
+	runtime_interface: &RuntimeInterface,
+	is_wasm_only: bool,
+) -> Result<TokenStream> {
+	let impl_trait = impl_trait_for_externalities(trait_def, runtime_interface, is_wasm_only)?;
 	let essential_trait_def = ToEssentialTraitDef::convert(trait_def.clone())?;
 
 	Ok(
@@ -44,7 +52,8 @@ pub fn process(trait_def: &ItemTrait, is_wasm_only: bool) -> Result<TokenStream>
 }
 
 /// Converts the given trait definition into the essential trait definition without method
-/// default implementations and visibility set to inherited.
+/// default implementations, with every method renamed after its version and visibility set to
+/// inherited.
 struct ToEssentialTraitDef {
 	/// All errors found while doing the conversion.
 	errors: Vec<Error>,
@@ -98,6 +107,14 @@ impl Fold for ToEssentialTraitDef {
 
 		self.error_on_generic_parameters(&method.sig.generics);
 
+		match get_function_version(&method) {
+			Ok(version) => {
+				method = strip_version_attribute(method);
+				method.sig.ident = create_function_ident_with_version(&method.sig.ident, version);
+			},
+			Err(e) => self.errors.push(e),
+		}
+
 		fold::fold_trait_item_method(self, method)
 	}
 
@@ -118,15 +135,19 @@ impl Fold for ToEssentialTraitDef {
 }
 
 /// Implements the given trait definition for `dyn Externalities`.
-fn impl_trait_for_externalities(trait_def: &ItemTrait, is_wasm_only: bool) -> Result<TokenStream> {
+fn impl_trait_for_externalities(
+	trait_def: &ItemTrait,
+	runtime_interface: &RuntimeInterface,
+	is_wasm_only: bool,
+) -> Result<TokenStream> {
 	let trait_ = &trait_def.ident;
 	let crate_ = generate_crate_access();
-	let methods = trait_def
-		.items
-		.iter()
-		.filter_map(|i| match i {
-			TraitItem::Method(ref method) => Some(method),
-			_ => None,
+	let methods = runtime_interface
+		.all_versions()
+		.map(|(version, method)| {
+			let mut method = method.clone();
+			method.sig.ident = create_function_ident_with_version(&method.sig.ident, version);
+			method
 		});
 
 	let impl_type = if is_wasm_only {
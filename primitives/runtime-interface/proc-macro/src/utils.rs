@@ -20,12 +20,12 @@ use proc_macro2::{TokenStream, Span};
 
 use syn::{
 	Ident, Error, Signature, Pat, PatType, FnArg, Type, token, TraitItemMethod, ItemTrait,
-	TraitItem, parse_quote, spanned::Spanned,
+	TraitItem, LitInt, Result, parse_quote, spanned::Spanned,
 };
 
 use proc_macro_crate::crate_name;
 
-use std::env;
+use std::{env, collections::BTreeMap};
 
 use quote::quote;
 
@@ -66,18 +66,122 @@ pub fn create_exchangeable_host_function_ident(name: &Ident) -> Ident {
 	Ident::new(&format!("host_{}", name), Span::call_site())
 }
 
-/// Create the host function identifier for the given function name.
-pub fn create_host_function_ident(name: &Ident, trait_name: &Ident) -> Ident {
+/// Name of the attribute declaring the version of a runtime interface function.
+const VERSION_ATTRIBUTE: &str = "version";
+
+/// Create the host function identifier for the given function name and version.
+pub fn create_host_function_ident(name: &Ident, version: u32, trait_name: &Ident) -> Ident {
 	Ident::new(
 		&format!(
-			"ext_{}_{}_version_1",
+			"ext_{}_{}_version_{}",
 			trait_name.to_string().to_snake_case(),
 			name,
+			version,
 		),
 		Span::call_site(),
 	)
 }
 
+/// Create the identifier of the given version of a function in the trait declaration.
+pub fn create_function_ident_with_version(name: &Ident, version: u32) -> Ident {
+	Ident::new(&format!("{}_version_{}", name, version), Span::call_site())
+}
+
+/// A function of a runtime interface, in all of its versions.
+pub struct RuntimeInterfaceFunction {
+	/// The versions of the function, without their `#[version]` attribute.
+	versions: BTreeMap<u32, TraitItemMethod>,
+}
+
+impl RuntimeInterfaceFunction {
+	/// Returns the latest version of the function.
+	pub fn latest_version(&self) -> (u32, &TraitItemMethod) {
+		self.versions
+			.iter()
+			.next_back()
+			.map(|(version, method)| (*version, method))
+			.expect("a function is only created with at least one version; qed")
+	}
+
+	/// Returns all versions of the function, in ascending order.
+	pub fn versions(&self) -> impl Iterator<Item = (u32, &TraitItemMethod)> {
+		self.versions.iter().map(|(version, method)| (*version, method))
+	}
+}
+
+/// The functions of a runtime interface, in declaration order.
+pub struct RuntimeInterface {
+	items: Vec<RuntimeInterfaceFunction>,
+}
+
+impl RuntimeInterface {
+	/// Returns the latest version of every function.
+	pub fn latest_versions(&self) -> impl Iterator<Item = (u32, &TraitItemMethod)> {
+		self.items.iter().map(RuntimeInterfaceFunction::latest_version)
+	}
+
+	/// Returns all versions of every function.
+	pub fn all_versions(&self) -> impl Iterator<Item = (u32, &TraitItemMethod)> {
+		self.items.iter().flat_map(RuntimeInterfaceFunction::versions)
+	}
+}
+
+/// Returns the version declared by the `#[version(N)]` attribute of `method`, `1` by default.
+pub fn get_function_version(method: &TraitItemMethod) -> Result<u32> {
+	let mut attrs = method.attrs.iter().filter(|attr| attr.path.is_ident(VERSION_ATTRIBUTE));
+
+	let version = match attrs.next() {
+		Some(attr) => {
+			let version = attr.parse_args::<LitInt>()?;
+			match version.base10_parse::<u32>()? {
+				0 => return Err(Error::new(version.span(), "Versions start at 1.")),
+				version => version,
+			}
+		},
+		None => 1,
+	};
+
+	if let Some(duplicate) = attrs.next() {
+		return Err(Error::new(duplicate.span(), "Only one `#[version]` attribute is allowed."));
+	}
+
+	Ok(version)
+}
+
+/// Remove the `#[version]` attribute of `method`.
+pub fn strip_version_attribute(mut method: TraitItemMethod) -> TraitItemMethod {
+	method.attrs.retain(|attr| !attr.path.is_ident(VERSION_ATTRIBUTE));
+	method
+}
+
+/// Group the methods of the given trait definition by name and version.
+pub fn get_runtime_interface(trait_def: &ItemTrait) -> Result<RuntimeInterface> {
+	let mut items: Vec<RuntimeInterfaceFunction> = Vec::new();
+
+	for method in get_trait_methods(trait_def) {
+		let version = get_function_version(method)?;
+		let name = &method.sig.ident;
+
+		let function = match items.iter().position(|f| f.latest_version().1.sig.ident == *name) {
+			Some(index) => &mut items[index],
+			None => {
+				items.push(RuntimeInterfaceFunction { versions: BTreeMap::new() });
+				items.last_mut().expect("an item was just pushed; qed")
+			},
+		};
+
+		if function.versions.contains_key(&version) {
+			return Err(Error::new(
+				name.span(),
+				format!("Duplicated version `{}` of `{}`.", version, name),
+			));
+		}
+		function.versions.insert(version, strip_version_attribute(method.clone()));
+	}
+
+	Ok(RuntimeInterface { items })
+}
+
 /// Returns the function arguments of the given `Signature`, minus any `self` arguments.
 pub fn get_function_arguments<'a>(sig: &'a Signature) -> impl Iterator<Item = PatType> + 'a {
 	sig.inputs
@@ -125,14 +125,15 @@ pub use sp_std;
 /// // Be aware that this module is not `public`, the visibility of the module is determined based
 /// // on the visibility of the trait declaration.
 /// mod interface {
+///     // Every method is renamed to `FUNCTION_NAME_version_VERSION`.
 ///     trait Interface {
-///         fn call_some_complex_code(data: &[u8]) -> Vec<u8>;
-///         fn set_or_clear(&mut self, optional: Option<Vec<u8>>);
+///         fn call_some_complex_code_version_1(data: &[u8]) -> Vec<u8>;
+///         fn set_or_clear_version_1(&mut self, optional: Option<Vec<u8>>);
 ///     }
 ///
 ///     impl Interface for &mut dyn sp_externalities::Externalities {
-///         fn call_some_complex_code(data: &[u8]) -> Vec<u8> { Vec::new() }
-///         fn set_or_clear(&mut self, optional: Option<Vec<u8>>) {
+///         fn call_some_complex_code_version_1(data: &[u8]) -> Vec<u8> { Vec::new() }
+///         fn set_or_clear_version_1(&mut self, optional: Option<Vec<u8>>) {
 ///             match optional {
 ///                 Some(value) => self.set_storage([1, 2, 3, 4].to_vec(), value),
 ///                 None => self.clear_storage(&[1, 2, 3, 4]),
@@ -141,11 +142,11 @@ pub use sp_std;
 ///     }
 ///
 ///     pub fn call_some_complex_code(data: &[u8]) -> Vec<u8> {
-///         <&mut dyn sp_externalities::Externalities as Interface>::call_some_complex_code(data)
+///         <&mut dyn sp_externalities::Externalities as Interface>::call_some_complex_code_version_1(data)
 ///     }
 ///
 ///     pub fn set_or_clear(optional: Option<Vec<u8>>) {
-///         sp_externalities::with_externalities(|mut ext| Interface::set_or_clear(&mut ext, optional))
+///         sp_externalities::with_externalities(|mut ext| Interface::set_or_clear_version_1(&mut ext, optional))
 ///             .expect("`set_or_clear` called outside of an Externalities-provided environment.")
 ///     }
 ///
@@ -214,6 +215,38 @@ pub use sp_std;
 /// representation. On the host each argument is converted back to the native representation and
 /// the native implementation is called. Any return value is handled in the same way.
 ///
+/// # Versions
+///
+/// Changing the behaviour or the signature of a host function would break the runtimes that were
+/// built against it, so a function is changed by declaring a new version of it with the
+/// `#[version(N)]` attribute. A function without this attribute has the version `1`.
+///
+/// ```
+/// # use sp_runtime_interface::runtime_interface;
+///
+/// #[runtime_interface]
+/// trait Interface {
+///     fn double(value: u32) -> u32 {
+///         value * 2
+///     }
+///
+///     /// Doubles the value without overflowing.
+///     #[version(2)]
+///     fn double(value: u32) -> u32 {
+///         value.saturating_mul(2)
+///     }
+/// }
+/// ```
+///
+/// All versions are provided by the `HostFunctions` of the interface, each exported as
+/// `ext_TRAIT_NAME_FUNCTION_NAME_version_VERSION`, so the node keeps executing the runtimes built
+/// against an older version. The bare functions, and thus the runtimes built against the
+/// interface, always call the latest version. An older version may only be removed once no
+/// runtime that needs to be executed imports it anymore.
+///
+/// When a runtime imports a version of a function the node does not provide, the executor reports
+/// the missing import with its exact name and the versions the node does provide.
+///
 /// # Wasm only interfaces
 ///
 /// Some interfaces are only required from within the wasm runtime e.g. the allocator interface.
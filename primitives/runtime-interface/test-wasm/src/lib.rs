@@ -82,6 +82,17 @@ pub trait TestApi {
 	fn overwrite_native_function_implementation() -> bool {
 		false
 	}
+
+	/// Returns `true` for `42` and `50`.
+	fn test_versioning(data: u32) -> bool {
+		data == 42 || data == 50
+	}
+
+	/// Returns `true` for `42` only.
+	#[version(2)]
+	fn test_versioning(data: u32) -> bool {
+		data == 42
+	}
 }
 
 /// Two random external functions from the old runtime interface.
@@ -191,4 +202,10 @@ wasm_export_functions! {
 
 		assert!(test_api::overwrite_native_function_implementation());
 	}
+
+	fn test_versioning_works() {
+		// The runtime is built against the latest version.
+		assert!(test_api::test_versioning(42));
+		assert!(!test_api::test_versioning(50));
+	}
 }
@@ -106,3 +106,22 @@ fn test_invalid_utf8_data_should_return_an_error() {
 fn test_overwrite_native_function_implementation() {
 	call_wasm_method::<HostFunctions>("test_overwrite_native_function_implementation");
 }
+
+#[test]
+fn test_versioning_works() {
+	call_wasm_method::<HostFunctions>("test_versioning_works");
+}
+
+#[test]
+fn host_functions_provide_all_versions() {
+	let names = HostFunctions::host_functions().iter().map(|f| f.name()).collect::<Vec<_>>();
+
+	assert!(names.contains(&"ext_test_api_test_versioning_version_1"));
+	assert!(names.contains(&"ext_test_api_test_versioning_version_2"));
+}
+
+#[test]
+fn native_calls_use_latest_version() {
+	assert!(sp_runtime_interface_test_wasm::test_api::test_versioning(42));
+	assert!(!sp_runtime_interface_test_wasm::test_api::test_versioning(50));
+}
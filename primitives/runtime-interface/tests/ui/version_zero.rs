@@ -0,0 +1,9 @@
+use sp_runtime_interface::runtime_interface;
+
+#[runtime_interface]
+trait Test {
+	#[version(0)]
+	fn test() {}
+}
+
+fn main() {}
@@ -27,7 +27,8 @@
 //! structure. The user of this library is supposed to read the wasm module.
 //!
 //! When this crate is used in the `std` environment all these functions are implemented by directly
-//! calling the wasm VM.
+//! calling the wasm VM, which is always wasmi. The engine a node selects for the sandbox only
+//! applies to the runtimes it executes in Wasm.
 //!
 //! Examples of possible use-cases for this library are not limited to the following:
 //!
@@ -123,6 +123,48 @@ pub trait Backend<H: Hasher>: std::fmt::Debug {
 		f: F,
 	);
 
+	/// Retrieve entries keys and values which start with the given prefix, in
+	/// lexicographic order, and call `f` for each of those until it returns `false`.
+	///
+	/// If `start_at` is given, iteration starts with the first key strictly greater
+	/// than `start_at`.
+	fn apply_to_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		apply_to_key_values_by_next_key(
+			prefix,
+			start_at,
+			|key| self.next_storage_key(key),
+			|key| self.storage(key),
+			f,
+		)
+	}
+
+	/// Retrieve child entries keys and values which start with the given prefix, in
+	/// lexicographic order, and call `f` for each of those until it returns `false`.
+	///
+	/// If `start_at` is given, iteration starts with the first key strictly greater
+	/// than `start_at`.
+	fn apply_to_child_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		apply_to_key_values_by_next_key(
+			prefix,
+			start_at,
+			|key| self.next_child_storage_key(storage_key, child_info, key),
+			|key| self.child_storage(storage_key, child_info, key),
+			f,
+		)
+	}
+
 	/// Calculate the storage root, with given delta over what is already stored in
 	/// the backend, and produce a "transaction" that can be used to commit.
 	/// Does not include child storage updates.
@@ -288,6 +330,76 @@ impl<'a, T: Backend<H>, H: Hasher> Backend<H> for &'a T {
 	fn for_key_values_with_prefix<F: FnMut(&[u8], &[u8])>(&self, prefix: &[u8], f: F) {
 		(*self).for_key_values_with_prefix(prefix, f);
 	}
+
+	fn apply_to_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		(*self).apply_to_key_values_while(prefix, start_at, f)
+	}
+
+	fn apply_to_child_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		(*self).apply_to_child_key_values_while(storage_key, child_info, prefix, start_at, f)
+	}
+}
+
+/// Iterate over the keys starting with `prefix` by repeatedly looking up the next key,
+/// for backends that can't seek into their storage directly.
+fn apply_to_key_values_by_next_key<E: fmt::Display, F: FnMut(&[u8], &[u8]) -> bool>(
+	prefix: &[u8],
+	start_at: Option<&[u8]>,
+	next_key: impl Fn(&[u8]) -> Result<Option<Vec<u8>>, E>,
+	value: impl Fn(&[u8]) -> Result<Option<Vec<u8>>, E>,
+	mut f: F,
+) {
+	let mut key = match start_at {
+		Some(start_at) if start_at >= prefix => start_at.to_vec(),
+		_ => {
+			// `next_key` is strictly greater, so the prefix itself has to be checked first.
+			match value(prefix) {
+				Ok(Some(value)) => if !f(prefix, &value) {
+					return;
+				},
+				Ok(None) => (),
+				Err(e) => {
+					warn!(target: "trie", "Error while iterating by prefix: {}", e);
+					return;
+				},
+			}
+			prefix.to_vec()
+		},
+	};
+
+	loop {
+		key = match next_key(&key) {
+			Ok(Some(next)) if next.starts_with(prefix) => next,
+			Ok(_) => return,
+			Err(e) => {
+				warn!(target: "trie", "Error while iterating by prefix: {}", e);
+				return;
+			},
+		};
+
+		match value(&key) {
+			Ok(Some(value)) => if !f(&key, &value) {
+				return;
+			},
+			Ok(None) => (),
+			Err(e) => {
+				warn!(target: "trie", "Error while iterating by prefix: {}", e);
+				return;
+			},
+		}
+	}
 }
 
 /// Trait that allows consolidate two transactions together.
@@ -466,6 +466,7 @@ mod test {
 				].into_iter().collect(),
 			},
 			changes_trie_config: Some(config.clone()),
+			collect_extrinsics: false,
 		};
 
 		(backend, storage, changes, config)
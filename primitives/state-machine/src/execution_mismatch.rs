@@ -0,0 +1,287 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Diagnostics of the mismatches between the native and wasm executions of a call.
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::Write as _,
+	fs,
+	io::{self, Write as _},
+	path::PathBuf,
+	sync::atomic::{AtomicUsize, Ordering},
+	time::{SystemTime, UNIX_EPOCH},
+};
+use codec::Decode;
+use sp_core::{hexdisplay::HexDisplay, storage::well_known_keys};
+use sp_externalities::decl_extension;
+use crate::{
+	changes_trie::NO_EXTRINSIC_INDEX,
+	overlayed_changes::{OverlayedChangeSet, OverlayedChanges, OverlayedValue},
+};
+
+/// Sequence number of the dumps written by this process, keeping their file names unique.
+static DUMP_SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+/// Where to dump the mismatches and how to identify the call in the dumps.
+#[derive(Debug, Clone)]
+pub struct MismatchDumpConfig {
+	/// Directory the dumps are written to.
+	pub directory: PathBuf,
+	/// Label identifying the call in the dumps, e.g. the block being executed.
+	pub label: String,
+}
+
+decl_extension! {
+	/// Dumps the mismatches between the native and wasm executions of calls executed with
+	/// [`ExecutionManager::Both`](crate::ExecutionManager::Both) while registered.
+	///
+	/// With this extension registered, the storage changes of both executions are compared as
+	/// well, and changes that diverge are handled as a consensus failure even when both
+	/// executions return the same value.
+	#[derive(Clone)]
+	pub struct ExecutionMismatchDump(MismatchDumpConfig);
+}
+
+impl ExecutionMismatchDump {
+	/// Dump the mismatches to `directory`, identifying the call with `label`.
+	pub fn new(directory: impl Into<PathBuf>, label: impl Into<String>) -> Self {
+		ExecutionMismatchDump(MismatchDumpConfig {
+			directory: directory.into(),
+			label: label.into(),
+		})
+	}
+
+	/// Write `mismatch` to a new file of the dump directory and return its path.
+	pub(crate) fn write(&self, mismatch: &Mismatch) -> io::Result<PathBuf> {
+		fs::create_dir_all(&self.directory)?;
+
+		let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+			.map(|since_epoch| since_epoch.as_millis())
+			.unwrap_or_default();
+		let path = self.directory.join(format!(
+			"mismatch-{}-{}-{}.txt",
+			timestamp,
+			DUMP_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+			mismatch.method,
+		));
+
+		let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+		file.write_all(mismatch.render(&self.label).as_bytes())?;
+		Ok(path)
+	}
+}
+
+/// The outcome of one of the executions of a call.
+pub(crate) struct ExecutionOutcome {
+	/// The result returned by the call, formatted for display.
+	pub result: String,
+	/// Index of the last extrinsic applied by the execution, if any.
+	pub extrinsic_index: Option<u32>,
+}
+
+/// A mismatch between the native and wasm executions of a call.
+pub(crate) struct Mismatch<'a> {
+	/// The method that was called.
+	pub method: &'a str,
+	/// Outcome of the native execution.
+	pub native: ExecutionOutcome,
+	/// Outcome of the wasm execution.
+	pub wasm: ExecutionOutcome,
+	/// The storage values changed differently by the two executions.
+	pub storage: Vec<StorageMismatch>,
+}
+
+impl<'a> Mismatch<'a> {
+	fn render(&self, label: &str) -> String {
+		let mut out = String::new();
+		let _ = writeln!(out, "Mismatch between the native and wasm executions of `{}`", self.method);
+		let _ = writeln!(out, "Label: {}", label);
+		let _ = writeln!(out, "Native extrinsic index: {:?}", self.native.extrinsic_index);
+		let _ = writeln!(out, "Wasm extrinsic index: {:?}", self.wasm.extrinsic_index);
+		let _ = writeln!(out, "First diverging extrinsic: {:?}", self.first_diverging_extrinsic());
+		let _ = writeln!(out, "Native result: {}", self.native.result);
+		let _ = writeln!(out, "Wasm result: {}", self.wasm.result);
+		let _ = writeln!(out, "Storage changes ({} differing keys):", self.storage.len());
+		for mismatch in &self.storage {
+			match mismatch.child {
+				Some(ref child) => {
+					let _ = writeln!(out, "  child 0x{} key 0x{}", HexDisplay::from(child), HexDisplay::from(&mismatch.key));
+				},
+				None => {
+					let _ = writeln!(out, "  key 0x{}", HexDisplay::from(&mismatch.key));
+				},
+			}
+			let _ = writeln!(out, "    native: {}", render_change(&mismatch.native));
+			let _ = writeln!(out, "    wasm:   {}", render_change(&mismatch.wasm));
+		}
+		out
+	}
+
+	/// The index of the first extrinsic which changed a value differently in the two executions.
+	///
+	/// `None` if the differing values were only changed outside of the extrinsics.
+	fn first_diverging_extrinsic(&self) -> Option<u32> {
+		self.storage.iter()
+			.flat_map(|mismatch| mismatch.native.iter().chain(mismatch.wasm.iter()))
+			.filter_map(|change| change.extrinsics.as_ref())
+			.flat_map(|extrinsics| extrinsics.iter().cloned())
+			.filter(|extrinsic| *extrinsic != NO_EXTRINSIC_INDEX)
+			.min()
+	}
+}
+
+/// A storage value changed differently by the native and wasm executions of a call.
+#[derive(Debug, Clone)]
+#[cfg_attr(test, derive(PartialEq))]
+pub(crate) struct StorageMismatch {
+	/// Child storage key, if the value belongs to a child trie.
+	pub child: Option<Vec<u8>>,
+	/// Key of the value.
+	pub key: Vec<u8>,
+	/// The change of the native execution, `None` when the value was left untouched.
+	pub native: Option<OverlayedValue>,
+	/// The change of the wasm execution, `None` when the value was left untouched.
+	pub wasm: Option<OverlayedValue>,
+}
+
+/// Compare the storage changes of the native and wasm executions, key by key.
+pub(crate) fn diff_changes(native: &OverlayedChangeSet, wasm: &OverlayedChangeSet) -> Vec<StorageMismatch> {
+	let mut mismatches = diff_values(None, &native.top, &wasm.top);
+
+	let children = native.children.keys().chain(wasm.children.keys()).collect::<BTreeSet<_>>();
+	let empty = BTreeMap::new();
+	for child in children {
+		let native_child = native.children.get(child).map_or(&empty, |(values, _)| values);
+		let wasm_child = wasm.children.get(child).map_or(&empty, |(values, _)| values);
+		mismatches.extend(diff_values(Some(child), native_child, wasm_child));
+	}
+
+	mismatches
+}
+
+fn diff_values(
+	child: Option<&Vec<u8>>,
+	native: &BTreeMap<Vec<u8>, OverlayedValue>,
+	wasm: &BTreeMap<Vec<u8>, OverlayedValue>,
+) -> Vec<StorageMismatch> {
+	native.keys().chain(wasm.keys())
+		.collect::<BTreeSet<_>>()
+		.into_iter()
+		.filter_map(|key| {
+			let native = native.get(key);
+			let wasm = wasm.get(key);
+			if native.map(|v| &v.value) == wasm.map(|v| &v.value) {
+				return None;
+			}
+
+			Some(StorageMismatch {
+				child: child.cloned(),
+				key: key.clone(),
+				native: native.cloned(),
+				wasm: wasm.cloned(),
+			})
+		})
+		.collect()
+}
+
+/// The index of the last extrinsic applied according to `overlay`.
+///
+/// That is the extrinsic being applied if the execution stopped in one, otherwise the last one
+/// recorded as changing a value, since the index is removed once all extrinsics are applied.
+pub(crate) fn extrinsic_index(overlay: &OverlayedChanges) -> Option<u32> {
+	overlay.storage(well_known_keys::EXTRINSIC_INDEX)
+		.and_then(|value| value)
+		.and_then(|value| u32::decode(&mut &value[..]).ok())
+		.or_else(|| last_recorded_extrinsic(&overlay.prospective))
+}
+
+fn last_recorded_extrinsic(changes: &OverlayedChangeSet) -> Option<u32> {
+	changes.top.values()
+		.chain(changes.children.values().flat_map(|(values, _)| values.values()))
+		.filter_map(|change| change.extrinsics.as_ref())
+		.flat_map(|extrinsics| extrinsics.iter().cloned())
+		.filter(|extrinsic| *extrinsic != NO_EXTRINSIC_INDEX)
+		.max()
+}
+
+fn render_change(change: &Option<OverlayedValue>) -> String {
+	let change = match change {
+		Some(change) => change,
+		None => return "untouched".into(),
+	};
+
+	let value = match change.value {
+		Some(ref value) => format!("0x{}", HexDisplay::from(value)),
+		None => "removed".into(),
+	};
+	match change.extrinsics {
+		Some(ref extrinsics) => format!("{} (extrinsics {:?})", value, extrinsics),
+		None => value,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::storage::OwnedChildInfo;
+
+	fn value(value: Option<&[u8]>) -> OverlayedValue {
+		OverlayedValue::from(value.map(|v| v.to_vec()))
+	}
+
+	#[test]
+	fn should_diff_top_and_child_changes() {
+		// given
+		let child_info = OwnedChildInfo::new_default(b"unique_id".to_vec());
+		let mut native = OverlayedChangeSet::default();
+		native.top.insert(b"same".to_vec(), value(Some(b"1")));
+		native.top.insert(b"differs".to_vec(), value(Some(b"1")));
+		native.top.insert(b"native_only".to_vec(), value(None));
+		native.children.insert(
+			b"child".to_vec(),
+			(vec![(b"key".to_vec(), value(Some(b"1")))].into_iter().collect(), child_info),
+		);
+		let mut wasm = OverlayedChangeSet::default();
+		wasm.top.insert(b"same".to_vec(), value(Some(b"1")));
+		wasm.top.insert(b"differs".to_vec(), value(Some(b"2")));
+
+		// when
+		let mismatches = diff_changes(&native, &wasm);
+
+		// then
+		assert_eq!(mismatches, vec![
+			StorageMismatch {
+				child: None,
+				key: b"differs".to_vec(),
+				native: Some(value(Some(b"1"))),
+				wasm: Some(value(Some(b"2"))),
+			},
+			StorageMismatch {
+				child: None,
+				key: b"native_only".to_vec(),
+				native: Some(value(None)),
+				wasm: None,
+			},
+			StorageMismatch {
+				child: Some(b"child".to_vec()),
+				key: b"key".to_vec(),
+				native: Some(value(Some(b"1"))),
+				wasm: None,
+			},
+		]);
+	}
+}
@@ -685,6 +685,7 @@ mod tests {
 				digest_interval: 0,
 				digest_levels: 0,
 			}),
+			collect_extrinsics: false,
 		}
 	}
 
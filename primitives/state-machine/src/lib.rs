@@ -33,10 +33,12 @@ pub mod backend;
 mod changes_trie;
 mod error;
 mod ext;
+mod execution_mismatch;
 mod testing;
 mod basic;
 mod overlayed_changes;
 mod proving_backend;
+mod recording_ext;
 mod trie_backend;
 mod trie_backend_essence;
 
@@ -64,7 +66,9 @@ pub use proving_backend::{
 };
 pub use trie_backend_essence::{TrieBackendStorage, Storage};
 pub use trie_backend::TrieBackend;
+pub use recording_ext::{RecordingExt, StorageAccess, StorageAccessRecorder};
 pub use error::{Error, ExecutionError};
+pub use execution_mismatch::{ExecutionMismatchDump, MismatchDumpConfig};
 
 type CallResult<R, E> = Result<NativeOrEncoded<R>, E>;
 
@@ -244,6 +248,10 @@ impl<'a, B, H, N, T, Exec> StateMachine<'a, B, H, N, T, Exec> where
 		R: Decode + Encode + PartialEq,
 		NC: FnOnce() -> result::Result<R, String> + UnwindSafe,
 	{
+		let recorder = self.extensions.get_mut(std::any::TypeId::of::<StorageAccessRecorder>())
+			.and_then(|ext| ext.downcast_mut::<StorageAccessRecorder>())
+			.map(|recorder| recorder.clone());
+
 		let mut ext = Ext::new(
 			self.overlay,
 			self.backend,
@@ -260,13 +268,22 @@ impl<'a, B, H, N, T, Exec> StateMachine<'a, B, H, N, T, Exec> where
 			HexDisplay::from(&self.call_data),
 		);
 
-		let (result, was_native) = self.exec.call(
-			&mut ext,
-			self.method,
-			self.call_data,
-			use_native,
-			native_call,
-		);
+		let (result, was_native) = match recorder {
+			Some(recorder) => self.exec.call(
+				&mut RecordingExt::new(&mut ext, recorder),
+				self.method,
+				self.call_data,
+				use_native,
+				native_call,
+			),
+			None => self.exec.call(
+				&mut ext,
+				self.method,
+				self.call_data,
+				use_native,
+				native_call,
+			),
+		};
 
 		let (storage_delta, changes_delta) = if compute_tx {
 			let (storage_delta, changes_delta) = ext.transaction();
@@ -303,6 +320,14 @@ impl<'a, B, H, N, T, Exec> StateMachine<'a, B, H, N, T, Exec> where
 			CallResult<R, Exec::Error>,
 		) -> CallResult<R, Exec::Error>
 	{
+		let mismatch_dump = self.extensions.get_mut(std::any::TypeId::of::<ExecutionMismatchDump>())
+			.and_then(|ext| ext.downcast_mut::<ExecutionMismatchDump>())
+			.map(|dump| dump.clone());
+
+		// Record the extrinsics changing every value to tell where the executions diverged.
+		let collect_extrinsics = self.overlay.collect_extrinsics;
+		self.overlay.collect_extrinsics |= mismatch_dump.is_some();
+
 		let (result, was_native, storage_delta, changes_delta) = self.execute_aux(
 			compute_tx,
 			true,
@@ -310,22 +335,68 @@ impl<'a, B, H, N, T, Exec> StateMachine<'a, B, H, N, T, Exec> where
 		);
 
 		if was_native {
+			let native_changes = mismatch_dump.as_ref().map(|_| (
+				self.overlay.prospective.clone(),
+				execution_mismatch::extrinsic_index(self.overlay),
+			));
+
 			self.overlay.prospective = orig_prospective.clone();
 			let (wasm_result, _, wasm_storage_delta, wasm_changes_delta) = self.execute_aux(
 				compute_tx,
 				false,
 				native_call,
 			);
+			self.overlay.collect_extrinsics = collect_extrinsics;
 
-			if (result.is_ok() && wasm_result.is_ok()
+			let results_match = (result.is_ok() && wasm_result.is_ok()
 				&& result.as_ref().ok() == wasm_result.as_ref().ok())
-				|| result.is_err() && wasm_result.is_err()
-			{
+				|| result.is_err() && wasm_result.is_err();
+
+			let changes_match = match (mismatch_dump, native_changes) {
+				(Some(dump), Some((native_changes, native_extrinsic_index))) => {
+					let storage = execution_mismatch::diff_changes(
+						&native_changes,
+						&self.overlay.prospective,
+					);
+					let changes_match = storage.is_empty();
+					if !results_match || !changes_match {
+						let mismatch = execution_mismatch::Mismatch {
+							method: self.method,
+							native: execution_mismatch::ExecutionOutcome {
+								result: format!("{:?}", result),
+								extrinsic_index: native_extrinsic_index,
+							},
+							wasm: execution_mismatch::ExecutionOutcome {
+								result: format!("{:?}", wasm_result),
+								extrinsic_index: execution_mismatch::extrinsic_index(self.overlay),
+							},
+							storage,
+						};
+						match dump.write(&mismatch) {
+							Ok(path) => warn!(
+								"Mismatch between the native and wasm executions of {} dumped to {}",
+								self.method,
+								path.display(),
+							),
+							Err(e) => warn!(
+								"Failed to dump the mismatch between the native and wasm executions of {}: {}",
+								self.method,
+								e,
+							),
+						}
+					}
+					changes_match
+				},
+				_ => true,
+			};
+
+			if results_match && changes_match {
 				(result, storage_delta, changes_delta)
 			} else {
 				(on_consensus_failure(wasm_result, result), wasm_storage_delta, wasm_changes_delta)
 			}
 		} else {
+			self.overlay.collect_extrinsics = collect_extrinsics;
 			(result, storage_delta, changes_delta)
 		}
 	}
@@ -890,6 +961,127 @@ mod tests {
 		assert!(consensus_failed);
 	}
 
+	#[test]
+	fn dual_execution_strategy_dumps_consensus_failure() {
+		// given
+		let dump_dir = tempfile::tempdir().unwrap();
+		let mut consensus_failed = false;
+		let backend = trie_backend::tests::test_trie();
+		let mut overlayed_changes = Default::default();
+		let changes_trie_storage = InMemoryChangesTrieStorage::<Blake2Hasher, u64>::new();
+		let mut extensions = Extensions::default();
+		extensions.register(ExecutionMismatchDump::new(dump_dir.path(), "block #1"));
+
+		let mut state_machine = StateMachine::new(
+			&backend,
+			Some(&changes_trie_storage),
+			&mut overlayed_changes,
+			&DummyCodeExecutor {
+				change_changes_trie_config: false,
+				native_available: true,
+				native_succeeds: true,
+				fallback_succeeds: false,
+			},
+			"test",
+			&[],
+			extensions,
+		);
+
+		// when
+		let result = state_machine.execute_using_consensus_failure_handler::<_, NeverNativeValue, fn() -> _>(
+			ExecutionManager::Both(|we, _ne| {
+				consensus_failed = true;
+				we
+			}),
+			true,
+			None,
+		);
+
+		// then
+		assert!(result.is_err());
+		assert!(consensus_failed);
+		let dumps = std::fs::read_dir(dump_dir.path()).unwrap().collect::<Vec<_>>();
+		assert_eq!(dumps.len(), 1);
+		let dump = std::fs::read_to_string(dumps[0].as_ref().unwrap().path()).unwrap();
+		assert!(dump.contains("Label: block #1"));
+		assert!(dump.contains("Native result: Ok(42)"));
+		assert!(dump.contains("Wasm result: Err(0)"));
+	}
+
+	#[test]
+	fn dual_execution_strategy_dumps_diverging_storage_changes() {
+		struct DivergingCodeExecutor;
+
+		impl CodeExecutor for DivergingCodeExecutor {
+			type Error = u8;
+
+			fn call<
+				E: Externalities,
+				R: Encode + Decode + PartialEq,
+				NC: FnOnce() -> result::Result<R, String>,
+			>(
+				&self,
+				ext: &mut E,
+				_method: &str,
+				_data: &[u8],
+				use_native: bool,
+				_native_call: Option<NC>,
+			) -> (CallResult<R, Self::Error>, bool) {
+				for extrinsic in 0u32..3 {
+					ext.set_storage(well_known_keys::EXTRINSIC_INDEX.to_vec(), extrinsic.encode());
+					let value = if extrinsic == 1 && use_native { 1u8 } else { 0 };
+					ext.set_storage(extrinsic.encode(), vec![value]);
+				}
+				ext.clear_storage(well_known_keys::EXTRINSIC_INDEX);
+
+				(Ok(NativeOrEncoded::Encoded(vec![42])), use_native)
+			}
+		}
+
+		// given
+		let dump_dir = tempfile::tempdir().unwrap();
+		let mut consensus_failed = false;
+		let backend = trie_backend::tests::test_trie();
+		let mut overlayed_changes = Default::default();
+		let changes_trie_storage = InMemoryChangesTrieStorage::<Blake2Hasher, u64>::new();
+		let mut extensions = Extensions::default();
+		extensions.register(ExecutionMismatchDump::new(dump_dir.path(), "block #1"));
+
+		let mut state_machine = StateMachine::new(
+			&backend,
+			Some(&changes_trie_storage),
+			&mut overlayed_changes,
+			&DivergingCodeExecutor,
+			"test",
+			&[],
+			extensions,
+		);
+
+		// when
+		let result = state_machine.execute_using_consensus_failure_handler::<_, NeverNativeValue, fn() -> _>(
+			ExecutionManager::Both(|we, _ne| {
+				consensus_failed = true;
+				we
+			}),
+			true,
+			None,
+		);
+
+		// then
+		assert!(result.is_ok());
+		assert!(consensus_failed);
+		assert!(!overlayed_changes.collect_extrinsics);
+		let dumps = std::fs::read_dir(dump_dir.path()).unwrap().collect::<Vec<_>>();
+		assert_eq!(dumps.len(), 1);
+		let dump = std::fs::read_to_string(dumps[0].as_ref().unwrap().path()).unwrap();
+		assert!(dump.contains("Native extrinsic index: Some(2)"));
+		assert!(dump.contains("Wasm extrinsic index: Some(2)"));
+		assert!(dump.contains("First diverging extrinsic: Some(1)"));
+		assert!(dump.contains("Storage changes (1 differing keys):"));
+		assert!(dump.contains("native: 0x01 (extrinsics {1})"));
+		assert!(dump.contains("wasm:   0x00 (extrinsics {1})"));
+	}
+
 	#[test]
 	fn prove_execution_and_proof_check_works() {
 		let executor = DummyCodeExecutor {
@@ -37,6 +37,9 @@ pub struct OverlayedChanges {
 	/// Changes trie configuration. None by default, but could be installed by the
 	/// runtime if it supports change tries.
 	pub(crate) changes_trie_config: Option<ChangesTrieConfig>,
+	/// Whether to record the extrinsics changing the values even without changes trie
+	/// configuration, to tell which extrinsic the native and wasm executions diverged at.
+	pub(crate) collect_extrinsics: bool,
 }
 
 /// The storage value, used inside OverlayedChanges.
@@ -46,7 +49,8 @@ pub struct OverlayedValue {
 	/// Current value. None if value has been deleted.
 	pub value: Option<Vec<u8>>,
 	/// The set of extinsic indices where the values has been changed.
-	/// Is filled only if runtime has announced changes trie support.
+	/// Is filled only if runtime has announced changes trie support, or while the extrinsics are
+	/// collected to diagnose an execution mismatch.
 	pub extrinsics: Option<BTreeSet<u32>>,
 }
 
@@ -360,7 +364,7 @@ impl OverlayedChanges {
 	/// Changes that are made outside of extrinsics, are marked with
 	/// `NO_EXTRINSIC_INDEX` index.
 	fn extrinsic_index(&self) -> Option<u32> {
-		match self.changes_trie_config.is_some() {
+		match self.changes_trie_config.is_some() || self.collect_extrinsics {
 			true => Some(
 				self.storage(EXTRINSIC_INDEX)
 					.and_then(|idx| idx.and_then(|idx| Decode::decode(&mut &*idx).ok()))
@@ -320,6 +320,26 @@ impl<'a, S, H> Backend<H> for ProvingBackend<'a, S, H>
 		self.0.for_child_keys_with_prefix(storage_key, child_info, prefix, f)
 	}
 
+	fn apply_to_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		self.0.apply_to_key_values_while(prefix, start_at, f)
+	}
+
+	fn apply_to_child_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		self.0.apply_to_child_key_values_while(storage_key, child_info, prefix, start_at, f)
+	}
+
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
 		self.0.pairs()
 	}
@@ -0,0 +1,318 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Externalities wrapper that records the storage accesses of a call.
+
+use std::{any::{Any, TypeId}, sync::Arc};
+use parking_lot::Mutex;
+use sp_core::storage::{ChildStorageKey, ChildInfo};
+use sp_externalities::{Externalities, ExtensionStore, decl_extension};
+
+/// A storage access performed by the runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageAccess {
+	/// A value was read from storage.
+	Read {
+		/// Child storage key, if the value belongs to a child trie.
+		child: Option<Vec<u8>>,
+		/// Key of the value.
+		key: Vec<u8>,
+		/// The value that was read.
+		value: Option<Vec<u8>>,
+	},
+	/// The hash of a value was read from storage.
+	ReadHash {
+		/// Child storage key, if the value belongs to a child trie.
+		child: Option<Vec<u8>>,
+		/// Key of the value.
+		key: Vec<u8>,
+		/// The hash that was read.
+		hash: Option<Vec<u8>>,
+	},
+	/// The key following a key was read from storage.
+	NextKey {
+		/// Child storage key, if the keys belong to a child trie.
+		child: Option<Vec<u8>>,
+		/// The key whose next key was read.
+		key: Vec<u8>,
+		/// The next key that was read.
+		next: Option<Vec<u8>>,
+	},
+	/// A value was written to or removed from storage.
+	Write {
+		/// Child storage key, if the value belongs to a child trie.
+		child: Option<Vec<u8>>,
+		/// Key of the value.
+		key: Vec<u8>,
+		/// The new value, `None` when the value was removed.
+		value: Option<Vec<u8>>,
+	},
+	/// All values starting with a prefix were removed.
+	ClearPrefix {
+		/// Child storage key, if the values belong to a child trie.
+		child: Option<Vec<u8>>,
+		/// The prefix of the removed keys.
+		prefix: Vec<u8>,
+	},
+	/// A child trie was removed.
+	KillChild {
+		/// Child storage key.
+		child: Vec<u8>,
+	},
+}
+
+decl_extension! {
+	/// Records the storage accesses of calls executed with this extension registered.
+	///
+	/// Register it with the extensions of a call and read the accesses back through
+	/// [`StorageAccessRecorder::take`] once the call has finished.
+	#[derive(Clone, Default)]
+	pub struct StorageAccessRecorder(Arc<Mutex<Vec<StorageAccess>>>);
+}
+
+impl StorageAccessRecorder {
+	/// Take the storage accesses recorded so far.
+	pub fn take(&self) -> Vec<StorageAccess> {
+		std::mem::replace(&mut *self.0.lock(), Vec::new())
+	}
+
+	fn record(&self, access: StorageAccess) {
+		self.0.lock().push(access);
+	}
+}
+
+/// Wraps externalities and records every storage read and write to a
+/// [`StorageAccessRecorder`].
+pub struct RecordingExt<'a, E> {
+	inner: &'a mut E,
+	recorder: StorageAccessRecorder,
+}
+
+impl<'a, E: Externalities> RecordingExt<'a, E> {
+	/// Wrap the given externalities.
+	pub fn new(inner: &'a mut E, recorder: StorageAccessRecorder) -> Self {
+		RecordingExt { inner, recorder }
+	}
+}
+
+impl<'a, E: Externalities> ExtensionStore for RecordingExt<'a, E> {
+	fn extension_by_type_id(&mut self, type_id: TypeId) -> Option<&mut dyn Any> {
+		self.inner.extension_by_type_id(type_id)
+	}
+}
+
+impl<'a, E: Externalities> Externalities for RecordingExt<'a, E> {
+	fn storage(&self, key: &[u8]) -> Option<Vec<u8>> {
+		let value = self.inner.storage(key);
+		self.recorder.record(StorageAccess::Read {
+			child: None,
+			key: key.to_vec(),
+			value: value.clone(),
+		});
+		value
+	}
+
+	fn storage_hash(&self, key: &[u8]) -> Option<Vec<u8>> {
+		let hash = self.inner.storage_hash(key);
+		self.recorder.record(StorageAccess::ReadHash {
+			child: None,
+			key: key.to_vec(),
+			hash: hash.clone(),
+		});
+		hash
+	}
+
+	fn child_storage_hash(
+		&self,
+		storage_key: ChildStorageKey,
+		child_info: ChildInfo,
+		key: &[u8],
+	) -> Option<Vec<u8>> {
+		let child = storage_key.as_ref().to_vec();
+		let hash = self.inner.child_storage_hash(storage_key, child_info, key);
+		self.recorder.record(StorageAccess::ReadHash {
+			child: Some(child),
+			key: key.to_vec(),
+			hash: hash.clone(),
+		});
+		hash
+	}
+
+	fn original_storage(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.inner.original_storage(key)
+	}
+
+	fn original_child_storage(
+		&self,
+		storage_key: ChildStorageKey,
+		child_info: ChildInfo,
+		key: &[u8],
+	) -> Option<Vec<u8>> {
+		self.inner.original_child_storage(storage_key, child_info, key)
+	}
+
+	fn original_storage_hash(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.inner.original_storage_hash(key)
+	}
+
+	fn original_child_storage_hash(
+		&self,
+		storage_key: ChildStorageKey,
+		child_info: ChildInfo,
+		key: &[u8],
+	) -> Option<Vec<u8>> {
+		self.inner.original_child_storage_hash(storage_key, child_info, key)
+	}
+
+	fn child_storage(
+		&self,
+		storage_key: ChildStorageKey,
+		child_info: ChildInfo,
+		key: &[u8],
+	) -> Option<Vec<u8>> {
+		let child = storage_key.as_ref().to_vec();
+		let value = self.inner.child_storage(storage_key, child_info, key);
+		self.recorder.record(StorageAccess::Read {
+			child: Some(child),
+			key: key.to_vec(),
+			value: value.clone(),
+		});
+		value
+	}
+
+	fn next_storage_key(&self, key: &[u8]) -> Option<Vec<u8>> {
+		let next = self.inner.next_storage_key(key);
+		self.recorder.record(StorageAccess::NextKey {
+			child: None,
+			key: key.to_vec(),
+			next: next.clone(),
+		});
+		next
+	}
+
+	fn next_child_storage_key(
+		&self,
+		storage_key: ChildStorageKey,
+		child_info: ChildInfo,
+		key: &[u8],
+	) -> Option<Vec<u8>> {
+		let child = storage_key.as_ref().to_vec();
+		let next = self.inner.next_child_storage_key(storage_key, child_info, key);
+		self.recorder.record(StorageAccess::NextKey {
+			child: Some(child),
+			key: key.to_vec(),
+			next: next.clone(),
+		});
+		next
+	}
+
+	fn kill_child_storage(&mut self, storage_key: ChildStorageKey, child_info: ChildInfo) {
+		self.recorder.record(StorageAccess::KillChild { child: storage_key.as_ref().to_vec() });
+		self.inner.kill_child_storage(storage_key, child_info)
+	}
+
+	fn clear_prefix(&mut self, prefix: &[u8]) {
+		self.recorder.record(StorageAccess::ClearPrefix { child: None, prefix: prefix.to_vec() });
+		self.inner.clear_prefix(prefix)
+	}
+
+	fn clear_child_prefix(
+		&mut self,
+		storage_key: ChildStorageKey,
+		child_info: ChildInfo,
+		prefix: &[u8],
+	) {
+		self.recorder.record(StorageAccess::ClearPrefix {
+			child: Some(storage_key.as_ref().to_vec()),
+			prefix: prefix.to_vec(),
+		});
+		self.inner.clear_child_prefix(storage_key, child_info, prefix)
+	}
+
+	fn place_storage(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+		self.recorder.record(StorageAccess::Write {
+			child: None,
+			key: key.clone(),
+			value: value.clone(),
+		});
+		self.inner.place_storage(key, value)
+	}
+
+	fn place_child_storage(
+		&mut self,
+		storage_key: ChildStorageKey,
+		child_info: ChildInfo,
+		key: Vec<u8>,
+		value: Option<Vec<u8>>,
+	) {
+		self.recorder.record(StorageAccess::Write {
+			child: Some(storage_key.as_ref().to_vec()),
+			key: key.clone(),
+			value: value.clone(),
+		});
+		self.inner.place_child_storage(storage_key, child_info, key, value)
+	}
+
+	fn chain_id(&self) -> u64 {
+		self.inner.chain_id()
+	}
+
+	fn storage_root(&mut self) -> Vec<u8> {
+		self.inner.storage_root()
+	}
+
+	fn child_storage_root(&mut self, storage_key: ChildStorageKey) -> Vec<u8> {
+		self.inner.child_storage_root(storage_key)
+	}
+
+	fn storage_changes_root(&mut self, parent: &[u8]) -> Result<Option<Vec<u8>>, ()> {
+		self.inner.storage_changes_root(parent)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::BasicExternalities;
+
+	#[test]
+	fn records_reads_and_writes() {
+		// given
+		let recorder = StorageAccessRecorder::default();
+		let mut basic = BasicExternalities::default();
+		basic.insert(b"foo".to_vec(), b"bar".to_vec());
+		let mut ext = RecordingExt::new(&mut basic, recorder.clone());
+
+		// when
+		assert_eq!(ext.storage(b"foo"), Some(b"bar".to_vec()));
+		let hash = ext.storage_hash(b"foo");
+		assert_eq!(ext.next_storage_key(b"f"), Some(b"foo".to_vec()));
+		ext.set_storage(b"foo".to_vec(), b"baz".to_vec());
+		ext.clear_storage(b"qux");
+		ext.clear_prefix(b"f");
+
+		// then
+		assert_eq!(recorder.take(), vec![
+			StorageAccess::Read { child: None, key: b"foo".to_vec(), value: Some(b"bar".to_vec()) },
+			StorageAccess::ReadHash { child: None, key: b"foo".to_vec(), hash },
+			StorageAccess::NextKey { child: None, key: b"f".to_vec(), next: Some(b"foo".to_vec()) },
+			StorageAccess::Write { child: None, key: b"foo".to_vec(), value: Some(b"baz".to_vec()) },
+			StorageAccess::Write { child: None, key: b"qux".to_vec(), value: None },
+			StorageAccess::ClearPrefix { child: None, prefix: b"f".to_vec() },
+		]);
+		assert!(recorder.take().is_empty());
+	}
+}
@@ -106,6 +106,26 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 		self.essence.for_key_values_with_prefix(prefix, f)
 	}
 
+	fn apply_to_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		self.essence.apply_to_key_values_while(prefix, start_at, f)
+	}
+
+	fn apply_to_child_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		self.essence.apply_to_child_key_values_while(storage_key, child_info, prefix, start_at, f)
+	}
+
 	fn for_keys_in_child_storage<F: FnMut(&[u8])>(
 		&self,
 		storage_key: &[u8],
@@ -348,4 +368,67 @@ pub mod tests {
 		expected.insert(b"value2".to_vec());
 		assert_eq!(seen, expected);
 	}
+
+	fn keys_while<B: Backend<Blake2Hasher>>(
+		backend: &B,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		count: usize,
+	) -> Vec<Vec<u8>> {
+		let mut keys = Vec::new();
+		backend.apply_to_key_values_while(prefix, start_at, |key, _| {
+			keys.push(key.to_vec());
+			keys.len() < count
+		});
+		keys
+	}
+
+	#[test]
+	fn paged_prefix_walking_works() {
+		let trie = test_trie();
+
+		assert_eq!(keys_while(&trie, b"value", None, 1), vec![b"value1".to_vec()]);
+		assert_eq!(keys_while(&trie, b"value", Some(b"value1"), 10), vec![b"value2".to_vec()]);
+		assert_eq!(keys_while(&trie, b"value", Some(b"value2"), 10), Vec::<Vec<u8>>::new());
+		assert_eq!(
+			keys_while(&trie, b"value", Some(b"key"), 10),
+			vec![b"value1".to_vec(), b"value2".to_vec()],
+		);
+		assert_eq!(keys_while(&trie, b"", Some(&[253]), 10), vec![vec![254]]);
+	}
+
+	#[test]
+	fn paged_child_prefix_walking_works() {
+		let trie = test_trie();
+
+		let mut keys = Vec::new();
+		trie.apply_to_child_key_values_while(CHILD_KEY_1, CHILD_INFO_1, b"", Some(b"value3"), |key, value| {
+			keys.push((key.to_vec(), value.to_vec()));
+			true
+		});
+
+		assert_eq!(keys, vec![(b"value4".to_vec(), vec![124])]);
+	}
+
+	#[test]
+	fn paged_prefix_walking_by_next_key_matches_trie() {
+		let trie = test_trie();
+		let in_memory = crate::backend::InMemory::<Blake2Hasher>::from(
+			trie.pairs().into_iter().collect::<std::collections::BTreeMap<_, _>>(),
+		);
+
+		for (prefix, start_at) in vec![
+			(&b""[..], None),
+			(&b"value"[..], None),
+			(&b"value"[..], Some(&b"key"[..])),
+			(&b"value"[..], Some(&b"value1"[..])),
+			(&b"value1"[..], None),
+			(&b""[..], Some(&[200u8][..])),
+		] {
+			assert_eq!(
+				keys_while(&in_memory, prefix, start_at, 5),
+				keys_while(&trie, prefix, start_at, 5),
+			);
+		}
+	}
 }
@@ -229,19 +229,52 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> where H::Out:
 		};
 		let mut root = H::Out::default();
 		root.as_mut().copy_from_slice(&root_vec);
-		self.keys_values_with_prefix_inner(&root, prefix, |k, _v| f(k), Some(child_info))
+		self.keys_values_with_prefix_inner(&root, prefix, None, |k, _v| { f(k); true }, Some(child_info))
 	}
 
 	/// Execute given closure for all keys starting with prefix.
 	pub fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], mut f: F) {
-		self.keys_values_with_prefix_inner(&self.root, prefix, |k, _v| f(k), None)
+		self.keys_values_with_prefix_inner(&self.root, prefix, None, |k, _v| { f(k); true }, None)
 	}
 
+	/// Execute given closure for key and values starting with prefix and strictly greater
+	/// than `start_at`, until it returns `false`.
+	pub fn apply_to_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		self.keys_values_with_prefix_inner(&self.root, prefix, start_at, f, None)
+	}
+
+	/// Execute given closure for child key and values starting with prefix and strictly
+	/// greater than `start_at`, until it returns `false`.
+	pub fn apply_to_child_key_values_while<F: FnMut(&[u8], &[u8]) -> bool>(
+		&self,
+		storage_key: &[u8],
+		child_info: ChildInfo,
+		prefix: &[u8],
+		start_at: Option<&[u8]>,
+		f: F,
+	) {
+		let root_vec = match self.storage(storage_key) {
+			Ok(v) => v.unwrap_or(default_child_trie_root::<Layout<H>>(storage_key).encode()),
+			Err(e) => {
+				debug!(target: "trie", "Error while iterating child storage: {}", e);
+				return;
+			}
+		};
+		let mut root = H::Out::default();
+		root.as_mut().copy_from_slice(&root_vec);
+		self.keys_values_with_prefix_inner(&root, prefix, start_at, f, Some(child_info))
+	}
 
-	fn keys_values_with_prefix_inner<F: FnMut(&[u8], &[u8])>(
+	fn keys_values_with_prefix_inner<F: FnMut(&[u8], &[u8]) -> bool>(
 		&self,
 		root: &H::Out,
 		prefix: &[u8],
+		start_at: Option<&[u8]>,
 		mut f: F,
 		child_info: Option<ChildInfo>,
 	) {
@@ -255,16 +288,26 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> where H::Out:
 			let trie = TrieDB::<H>::new(db, root)?;
 			let mut iter = trie.iter()?;
 
-			iter.seek(prefix)?;
+			let seek_to = match start_at {
+				Some(start_at) if start_at > prefix => start_at,
+				_ => prefix,
+			};
+			iter.seek(seek_to)?;
 
 			for x in iter {
 				let (key, value) = x?;
 
+				if Some(&key[..]) == start_at {
+					continue;
+				}
+
 				if !key.starts_with(prefix) {
 					break;
 				}
 
-				f(&key, &value);
+				if !f(&key, &value) {
+					break;
+				}
 			}
 
 			Ok(())
@@ -282,8 +325,8 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> where H::Out:
 	}
 
 	/// Execute given closure for all key and values starting with prefix.
-	pub fn for_key_values_with_prefix<F: FnMut(&[u8], &[u8])>(&self, prefix: &[u8], f: F) {
-		self.keys_values_with_prefix_inner(&self.root, prefix, f, None)
+	pub fn for_key_values_with_prefix<F: FnMut(&[u8], &[u8])>(&self, prefix: &[u8], mut f: F) {
+		self.keys_values_with_prefix_inner(&self.root, prefix, None, |k, v| { f(k, v); true }, None)
 	}
 
 }
@@ -63,6 +63,12 @@ pub enum Error {
 	/// The pool is not accepting future transactions.
 	#[display(fmt="The pool is not accepting future transactions")]
 	RejectedFutureTransaction,
+	/// The sender of the transaction already has too many transactions in the pool.
+	#[display(fmt="Too many transactions from the same sender (limit: {})", limit)]
+	TooManyTransactionsFromSender {
+		/// Maximal number of transactions per sender.
+		limit: usize,
+	},
 }
 
 impl std::error::Error for Error {}
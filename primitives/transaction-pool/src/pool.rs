@@ -45,6 +45,8 @@ pub struct PoolStatus {
 	pub future: usize,
 	/// Sum of bytes of ready transaction encodings.
 	pub future_bytes: usize,
+	/// Transactions that left the pool without being included in a block, since it started.
+	pub evictions: EvictionStats,
 }
 
 impl PoolStatus {
@@ -54,6 +56,17 @@ impl PoolStatus {
 	}
 }
 
+/// Counters of the transactions that left the pool without being included in a block.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EvictionStats {
+	/// Transactions replaced by a transaction with a higher priority providing the same tags.
+	pub replaced: u64,
+	/// Transactions evicted because the pool was full.
+	pub evicted: u64,
+	/// Transactions rejected because their sender already had too many transactions in the pool.
+	pub rejected_sender_limit: u64,
+}
+
 /// Possible transaction status events.
 ///
 /// This events are being emitted by `TransactionPool` watchers,
@@ -70,6 +83,8 @@ impl PoolStatus {
 ///		- `Invalid`
 ///		- `Usurped`
 ///		- `Dropped`
+/// 4. Re-entering the pool:
+///		- `Retracted`
 ///
 /// The events will always be received in the order described above, however
 /// there might be cases where transactions alternate between `Future` and `Ready`
@@ -79,7 +94,9 @@ impl PoolStatus {
 ///
 /// Note that there are conditions that may cause transactions to reappear in the pool.
 /// 1. Due to possible forks, the transaction that ends up being in included
-/// in one block, may later re-enter the pool or be marked as invalid.
+/// in one block, may later re-enter the pool or be marked as invalid. If the pool is
+/// configured to keep watching transactions included in recent blocks, their watchers
+/// are notified with `Retracted` in such case. Otherwise the watchers end at `InBlock`.
 /// 2. Transaction `Dropped` at one point, may later re-enter the pool if some other
 /// transactions are removed.
 /// 3. `Invalid` transaction may become valid at some point in the future.
@@ -99,6 +116,8 @@ pub enum TransactionStatus<Hash, BlockHash> {
 	/// Transaction has been included in block with given hash.
 	#[serde(rename = "finalized")] // See #4438
 	InBlock(BlockHash),
+	/// The block this transaction was included in has been retracted.
+	Retracted(BlockHash),
 	/// Transaction has been replaced in the pool, by another transaction
 	/// that provides the same tags. (e.g. same (sender, nonce)).
 	Usurped(Hash),
@@ -183,6 +202,21 @@ pub trait TransactionPool: Send + Sync {
 		Self::Error
 	>> + Send + Unpin>;
 
+	/// Returns a future that imports one locally submitted transaction to the pool.
+	///
+	/// Local transactions are the ones submitted over RPC or by offchain workers.
+	/// Implementations may treat them specially, e.g. persist them across restarts.
+	fn submit_local(
+		&self,
+		at: &BlockId<Self::Block>,
+		xt: TransactionFor<Self>,
+	) -> Box<dyn Future<Output=Result<
+		TxHash<Self>,
+		Self::Error
+	>> + Send + Unpin> {
+		self.submit_one(at, xt)
+	}
+
 	/// Returns a future that import a single transaction and starts to watch their progress in the pool.
 	fn submit_and_watch(
 		&self,
@@ -239,7 +273,7 @@ impl<TPool: TransactionPool> OffchainSubmitTransaction<TPool::Block> for TPool {
 			extrinsic
 		);
 
-		let result = futures::executor::block_on(self.submit_one(&at, extrinsic));
+		let result = futures::executor::block_on(self.submit_local(&at, extrinsic));
 
 		result.map(|_| ())
 			.map_err(|e| log::warn!(
@@ -305,6 +339,14 @@ impl<Pool, Maintainer> TransactionPool for MaintainableTransactionPool<Pool, Mai
 		self.pool.submit_one(at, xt)
 	}
 
+	fn submit_local(
+		&self,
+		at: &BlockId<Self::Block>,
+		xt: TransactionFor<Self>,
+	) -> Box<dyn Future<Output=Result<TxHash<Self>, Self::Error>> + Send + Unpin> {
+		self.pool.submit_local(at, xt)
+	}
+
 	fn submit_and_watch(
 		&self,
 		at: &BlockId<Self::Block>,
@@ -241,7 +241,7 @@ impl<E, Backend, G: GenesisInit> TestClientBuilder<
 		Block: BlockT<Hash=<Blake2Hasher as Hasher>::Out>,
 	{
 		let executor = executor.into().unwrap_or_else(||
-			NativeExecutor::new(WasmExecutionMethod::Interpreted, None)
+			NativeExecutor::new(WasmExecutionMethod::Interpreted, None, 8)
 		);
 		let executor = LocalCallExecutor::new(self.backend.clone(), executor);
 
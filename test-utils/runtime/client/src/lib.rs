@@ -349,7 +349,7 @@ pub fn new_light() -> (
 	let storage = sc_client_db::light::LightStorage::new_test();
 	let blockchain = Arc::new(sc_client::light::blockchain::Blockchain::new(storage));
 	let backend = Arc::new(LightBackend::new(blockchain.clone()));
-	let executor = NativeExecutor::new(WasmExecutionMethod::Interpreted, None);
+	let executor = NativeExecutor::new(WasmExecutionMethod::Interpreted, None, 8);
 	let local_call_executor = sc_client::LocalCallExecutor::new(backend.clone(), executor);
 	let call_executor = LightExecutor::new(
 		backend.clone(),
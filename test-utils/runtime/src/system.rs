@@ -106,6 +106,7 @@ pub fn polish_block(block: &mut Block) {
 }
 
 pub fn execute_block(mut block: Block) {
+	frame_support::enter_span!("execute_block");
 	execute_block_with_state_root_handler(&mut block, Mode::Verify);
 }
 
@@ -350,7 +351,7 @@ mod tests {
 	);
 
 	fn executor() -> NativeExecutor<NativeDispatch> {
-		NativeExecutor::new(WasmExecutionMethod::Interpreted, None)
+		NativeExecutor::new(WasmExecutionMethod::Interpreted, None, 8)
 	}
 
 	fn new_test_ext() -> TestExternalities {
@@ -37,10 +37,12 @@ use sp_runtime::{
 	generic::BlockId,
 	traits,
 };
-use sp_core::hexdisplay::HexDisplay;
+use sp_api::Core;
+use sp_core::{Bytes, hexdisplay::HexDisplay};
 use sp_transaction_pool::{TransactionPool, InPoolTransaction};
 
 pub use frame_system_rpc_runtime_api::AccountNonceApi;
+pub use sp_block_builder::BlockBuilder as BlockBuilderApi;
 pub use self::gen_client::Client as SystemClient;
 
 /// Future that resolves to account nonce.
@@ -48,7 +50,7 @@ pub type FutureResult<T> = Box<dyn Future<Item = T, Error = Error> + Send>;
 
 /// System RPC methods.
 #[rpc]
-pub trait SystemApi<AccountId, Index> {
+pub trait SystemApi<BlockHash, AccountId, Index> {
 	/// Returns the next valid index (aka nonce) for given account.
 	///
 	/// This method takes into consideration all pending transactions
@@ -56,9 +58,17 @@ pub trait SystemApi<AccountId, Index> {
 	/// it fallbacks to query the index from the runtime (aka. state nonce).
 	#[rpc(name = "system_accountNextIndex", alias("account_nextIndex"))]
 	fn nonce(&self, account: AccountId) -> FutureResult<Index>;
+
+	/// Dry run an extrinsic at a given block, or the best block if none is given.
+	///
+	/// The extrinsic is applied on top of a new block built on the given one and the
+	/// SCALE-encoded `ApplyExtrinsicResult` is returned. Nothing is persisted.
+	#[rpc(name = "system_dryRun", alias("system_dryRunAt"))]
+	fn dry_run(&self, extrinsic: Bytes, at: Option<BlockHash>) -> FutureResult<Bytes>;
 }
 
 const RUNTIME_ERROR: i64 = 1;
+const DECODE_ERROR: i64 = 2;
 
 /// An implementation of System-specific RPC methods on full client.
 pub struct FullSystem<P: TransactionPool, C, B> {
@@ -78,12 +88,14 @@ impl<P: TransactionPool, C, B> FullSystem<P, C, B> {
 	}
 }
 
-impl<P, C, Block, AccountId, Index> SystemApi<AccountId, Index> for FullSystem<P, C, Block>
+impl<P, C, Block, AccountId, Index> SystemApi<<Block as traits::Block>::Hash, AccountId, Index>
+	for FullSystem<P, C, Block>
 where
 	C: traits::ProvideRuntimeApi,
 	C: HeaderBackend<Block>,
 	C: Send + Sync + 'static,
 	C::Api: AccountNonceApi<Block, AccountId, Index>,
+	C::Api: BlockBuilderApi<Block>,
 	P: TransactionPool + 'static,
 	Block: traits::Block,
 	AccountId: Clone + std::fmt::Display + Codec,
@@ -106,6 +118,52 @@ where
 
 		Box::new(result(get_nonce()))
 	}
+
+	fn dry_run(
+		&self,
+		extrinsic: Bytes,
+		at: Option<<Block as traits::Block>::Hash>,
+	) -> FutureResult<Bytes> {
+		let dry_run = || {
+			let api = self.client.runtime_api();
+			let parent_hash = at.unwrap_or_else(|| self.client.info().best_hash);
+			let parent_id = BlockId::hash(parent_hash);
+			let parent_number = self.client.number(parent_hash)
+				.and_then(|number| number.ok_or_else(|| ClientError::UnknownBlock(format!("{}", parent_hash))))
+				.map_err(|e| Error {
+					code: ErrorCode::ServerError(RUNTIME_ERROR),
+					message: "Unable to dry run extrinsic.".into(),
+					data: Some(format!("{:?}", e).into()),
+				})?;
+
+			let uxt: <Block as traits::Block>::Extrinsic = Decode::decode(&mut &*extrinsic)
+				.map_err(|e| Error {
+					code: ErrorCode::ServerError(DECODE_ERROR),
+					message: "Unable to dry run extrinsic.".into(),
+					data: Some(format!("{:?}", e).into()),
+				})?;
+
+			// Runtimes expect extrinsics to be applied within an initialized block.
+			let header = <<Block as traits::Block>::Header as traits::Header>::new(
+				parent_number + traits::One::one(),
+				Default::default(),
+				Default::default(),
+				parent_hash,
+				Default::default(),
+			);
+			let result = api.initialize_block(&parent_id, &header)
+				.and_then(|_| api.apply_extrinsic(&parent_id, uxt))
+				.map_err(|e| Error {
+					code: ErrorCode::ServerError(RUNTIME_ERROR),
+					message: "Unable to dry run extrinsic.".into(),
+					data: Some(format!("{:?}", e).into()),
+				})?;
+
+			Ok(result.encode().into())
+		};
+
+		Box::new(result(dry_run()))
+	}
 }
 
 /// An implementation of System-specific RPC methods on light client.
@@ -133,7 +191,8 @@ impl<P: TransactionPool, C, F, Block> LightSystem<P, C, F, Block> {
 	}
 }
 
-impl<P, C, F, Block, AccountId, Index> SystemApi<AccountId, Index> for LightSystem<P, C, F, Block>
+impl<P, C, F, Block, AccountId, Index> SystemApi<<Block as traits::Block>::Hash, AccountId, Index>
+	for LightSystem<P, C, F, Block>
 where
 	P: TransactionPool + 'static,
 	C: HeaderBackend<Block>,
@@ -178,6 +237,18 @@ where
 
 		Box::new(future_nonce)
 	}
+
+	fn dry_run(
+		&self,
+		_extrinsic: Bytes,
+		_at: Option<<Block as traits::Block>::Hash>,
+	) -> FutureResult<Bytes> {
+		Box::new(result(Err(Error {
+			code: ErrorCode::MethodNotFound,
+			message: "Unable to dry run extrinsic.".into(),
+			data: Some("Dry run is not available on light clients.".into()),
+		})))
+	}
 }
 
 /// Adjust account nonce from state, so that tx with the nonce will be
@@ -260,4 +331,32 @@ mod tests {
 		// then
 		assert_eq!(nonce.wait().unwrap(), 2);
 	}
+
+	#[test]
+	fn dry_run_should_apply_extrinsic_without_persisting() {
+		// given
+		let _ = env_logger::try_init();
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let pool = Arc::new(BasicPool::new(Default::default(), FullChainApi::new(client.clone())));
+
+		let accounts = FullSystem::new(client, pool);
+
+		let tx = Transfer {
+			from: AccountKeyring::Alice.into(),
+			to: AccountKeyring::Bob.into(),
+			amount: 5,
+			nonce: 0,
+		}.into_signed_tx();
+
+		// when
+		let first = accounts.dry_run(tx.encode().into(), None).wait().unwrap();
+		let second = accounts.dry_run(tx.encode().into(), None).wait().unwrap();
+		let invalid = accounts.dry_run(vec![0xff].into(), None).wait();
+
+		// then
+		let first: sp_runtime::ApplyExtrinsicResult = Decode::decode(&mut &*first).unwrap();
+		assert_eq!(first, Ok(Ok(())));
+		assert_eq!(first.encode(), second.0);
+		assert_eq!(invalid.unwrap_err().code, ErrorCode::ServerError(DECODE_ERROR));
+	}
 }
@@ -68,6 +68,17 @@ pub fn record_metrics_slice(metrics: &[(&str, f32)]) -> Result<(), Error> {
 	Ok(())
 }
 
+/// The key of the metric `name` with the given `labels`, e.g. `name{label="value"}`.
+///
+/// All the metrics with the same name are listed by a search for that name, whatever their labels.
+pub fn labeled_key(name: &str, labels: &[(&str, &str)]) -> String {
+	let labels = labels.iter()
+		.map(|(label, value)| format!("{}=\"{}\"", label, value.escape_default()))
+		.collect::<Vec<_>>();
+
+	format!("{}{{{}}}", name, labels.join(","))
+}
+
 /// Error type that can be returned by either `record_metrics` or `run_server`.
 #[derive(Debug, derive_more::Display, derive_more::From)]
 pub enum Error {
@@ -94,3 +105,20 @@ impl std::error::Error for Error {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn should_label_keys() {
+		// given
+		let labels = [("method", "Core_version"), ("quoted", "\"a\"")];
+
+		// when
+		let key = labeled_key("wasm_heap_peak_usage", &labels);
+
+		// then
+		assert_eq!(key, r#"wasm_heap_peak_usage{method="Core_version",quoted="\"a\""}"#);
+	}
+}
@@ -71,6 +71,30 @@
 //! Where `PROJECT_NAME` needs to be replaced by the name of the cargo project, e.g. `node-runtime` will
 //! be `NODE_RUNTIME`.
 //!
+//! ## Validation
+//!
+//! The compact WASM binary is validated after each build, failing the build if it:
+//!
+//! - imports a function that is not provided by `sc-executor` as a host function,
+//! - uses floating point,
+//! - has a start function,
+//! - exceeds the configured budget once compressed.
+//!
+//! A report of the size of the binary and of the code per crate, based on the name section, is written
+//! next to the compact binary in `<name>.compact.size-report.txt`. The build only refers to it when
+//! the budget is exceeded, as cargo doesn't show the output of build scripts.
+//!
+//! The validation is configured in the `Cargo.toml` of the project:
+//!
+//! ```toml
+//! [package.metadata.wasm-builder]
+//! # Host functions provided in addition to the ones of `sc-executor`, e.g. by the
+//! # `ExtendHostFunctions` of the node. A trailing `*` matches any function with the given prefix.
+//! extra-host-functions = ["ext_my_host_function_version_1", "ext_my_interface_*"]
+//! # Maximum size in bytes of the gzip compressed WASM binary.
+//! compressed-size-budget = 1048576
+//! ```
+//!
 //! ## Prerequisites:
 //!
 //! WASM builder requires the following prerequisities for building the WASM binary:
@@ -81,6 +105,7 @@
 use std::{env, fs, path::PathBuf, process::{Command, Stdio, self}};
 
 mod prerequisites;
+mod validation;
 mod wasm_project;
 
 /// Environment variable that tells us to skip building the wasm binary.
@@ -0,0 +1,440 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Validation and size analysis of the built WASM binary.
+
+use std::{collections::BTreeMap, fs, io::Write, path::Path};
+
+use toml::value::Table;
+
+use parity_wasm::elements::{
+	deserialize_file, serialize, External, FunctionType, ImportCountType, Instruction, Module, Type,
+	ValueType,
+};
+
+use sc_executor::sp_wasm_interface::{Signature, ValueType as HostValueType};
+
+use flate2::{Compression, write::GzEncoder};
+
+/// Name of the group of the functions that are not attributed to a crate.
+const OTHER_FUNCTIONS: &str = "<other>";
+
+/// Settings of the validation, read from the `[package.metadata.wasm-builder]` section of the
+/// project `Cargo.toml`.
+#[derive(Default)]
+struct Settings {
+	/// Host functions provided in addition to the ones of `sc-executor`.
+	///
+	/// A trailing `*` matches any function starting with the given prefix.
+	extra_host_functions: Vec<String>,
+	/// Maximum size in bytes of the compressed WASM binary.
+	compressed_size_budget: Option<u64>,
+}
+
+impl Settings {
+	fn read(cargo_manifest: &Path) -> Self {
+		let cargo_toml: Table = toml::from_str(
+			&fs::read_to_string(cargo_manifest).expect("File exists as checked before; qed")
+		).expect("Cargo manifest is a valid toml file; qed");
+
+		let metadata = match cargo_toml.get("package")
+			.and_then(|p| p.get("metadata"))
+			.and_then(|m| m.get("wasm-builder"))
+		{
+			Some(metadata) => metadata,
+			None => return Settings::default(),
+		};
+
+		Settings {
+			extra_host_functions: metadata.get("extra-host-functions")
+				.and_then(|f| f.as_array())
+				.map(|f| f.iter().filter_map(|f| f.as_str()).map(Into::into).collect())
+				.unwrap_or_default(),
+			compressed_size_budget: metadata.get("compressed-size-budget")
+				.and_then(|b| b.as_integer())
+				.map(|b| b as u64),
+		}
+	}
+
+	fn is_extra_host_function(&self, name: &str) -> bool {
+		self.extra_host_functions.iter().any(|f| if f.ends_with('*') {
+			name.starts_with(&f[..f.len() - 1])
+		} else {
+			name == f
+		})
+	}
+}
+
+/// Validate the compact WASM binary and write its size report next to it.
+///
+/// The binary may only import host functions provided by `sc-executor`, with their signatures,
+/// may not use floating point and may not have a start function. When a compressed size budget
+/// is configured, the binary may not exceed it.
+///
+/// # Returns
+/// Returns `Ok(())` if the binary is valid and `Err(ERR_MSG)` otherwise.
+pub fn check(cargo_manifest: &Path, wasm_binary: &Path, bloaty: &Path) -> Result<(), String> {
+	let settings = Settings::read(cargo_manifest);
+	let module = deserialize_file(wasm_binary)
+		.map_err(|e| format!("Failed to deserialize `{}`: {}", wasm_binary.display(), e))?;
+
+	let mut errors = Vec::new();
+	check_imports(&module, &settings, &mut errors);
+	check_floats(&module, &mut errors);
+	if module.start_section().is_some() {
+		errors.push("The WASM binary has a start function.".to_string());
+	}
+
+	let code = fs::read(wasm_binary).expect("The compact WASM binary exists; qed");
+	let compressed_size = compressed_size(&code);
+
+	let report = size_report(wasm_binary, bloaty, code.len(), compressed_size);
+	let report_file = wasm_binary.with_extension("size-report.txt");
+	fs::write(&report_file, &report).expect("Writing the size report can not fail; qed");
+
+	if let Some(budget) = settings.compressed_size_budget {
+		if compressed_size > budget {
+			errors.push(format!(
+				"The compressed WASM binary is {} bytes, exceeding the budget of {} bytes. \
+				See `{}` for the size per crate.",
+				compressed_size,
+				budget,
+				report_file.display(),
+			));
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(())
+	} else {
+		Err(format!(
+			"Validation of the WASM binary `{}` failed:\n  - {}",
+			wasm_binary.display(),
+			errors.join("\n  - "),
+		))
+	}
+}
+
+/// Check that every function imported by `module` is provided by the host, with the same
+/// signature.
+fn check_imports(module: &Module, settings: &Settings, errors: &mut Vec<String>) {
+	let host_functions = sc_executor::default_host_functions();
+	let types = module.type_section().map(|s| s.types()).unwrap_or_default();
+	let imports = module.import_section().map(|s| s.entries()).unwrap_or_default();
+
+	for import in imports {
+		if let External::Function(type_index) = import.external() {
+			let name = import.field();
+			if import.module() != "env" {
+				errors.push(format!("Imports `{}` from the unknown module `{}`.", name, import.module()));
+			} else if let Some(function) = host_functions.iter().find(|f| f.name() == name) {
+				let expected = function_type(&function.signature());
+				match types.get(*type_index as usize) {
+					Some(Type::Function(ty)) if *ty == expected => {},
+					Some(Type::Function(ty)) => errors.push(format!(
+						"Imports `{}` taking {:?} and returning {:?}, but the host function takes {:?} \
+						and returns {:?}.",
+						name,
+						ty.params(),
+						ty.return_type(),
+						expected.params(),
+						expected.return_type(),
+					)),
+					None => errors.push(format!("Imports `{}` with an unknown type.", name)),
+				}
+			} else if !settings.is_extra_host_function(name) {
+				errors.push(sc_executor::imports::missing_import_message(name, &host_functions));
+			}
+		}
+	}
+}
+
+/// The WASM type of the host function with the given `signature`.
+fn function_type(signature: &Signature) -> FunctionType {
+	let value_type = |ty: &HostValueType| match ty {
+		HostValueType::I32 => ValueType::I32,
+		HostValueType::I64 => ValueType::I64,
+		HostValueType::F32 => ValueType::F32,
+		HostValueType::F64 => ValueType::F64,
+	};
+
+	FunctionType::new(
+		signature.args.iter().map(value_type).collect(),
+		signature.return_value.as_ref().map(value_type),
+	)
+}
+
+/// Check that `module` does not use floating point.
+fn check_floats(module: &Module, errors: &mut Vec<String>) {
+	let is_float = |ty: &ValueType| match ty {
+		ValueType::F32 | ValueType::F64 => true,
+		_ => false,
+	};
+
+	let types = module.type_section().map(|s| s.types()).unwrap_or_default();
+	let float_signatures = types.iter().filter(|Type::Function(ty)|
+		ty.params().iter().any(is_float) || ty.return_type().as_ref().map_or(false, is_float)
+	).count();
+
+	let globals = module.global_section().map(|s| s.entries()).unwrap_or_default();
+	let float_globals = globals.iter().filter(|g| is_float(&g.global_type().content_type())).count();
+
+	let bodies = module.code_section().map(|s| s.bodies()).unwrap_or_default();
+	let float_functions = bodies.iter()
+		.filter(|body|
+			body.locals().iter().any(|l| is_float(&l.value_type()))
+				|| body.code().elements().iter().any(is_float_instruction)
+		)
+		.count();
+
+	if float_signatures + float_globals + float_functions > 0 {
+		errors.push(format!(
+			"The WASM binary uses floating point: {} function signature(s), {} global(s) and \
+			{} function(s) have floating point values.",
+			float_signatures,
+			float_globals,
+			float_functions,
+		));
+	}
+}
+
+fn is_float_instruction(instruction: &Instruction) -> bool {
+	use Instruction::*;
+
+	match instruction {
+		F32Load(..) | F64Load(..) | F32Store(..) | F64Store(..) | F32Const(_) | F64Const(_) |
+		F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge |
+		F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge |
+		F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt |
+		F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign |
+		F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt |
+		F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign |
+		I32TruncSF32 | I32TruncUF32 | I32TruncSF64 | I32TruncUF64 |
+		I64TruncSF32 | I64TruncUF32 | I64TruncSF64 | I64TruncUF64 |
+		F32ConvertSI32 | F32ConvertUI32 | F32ConvertSI64 | F32ConvertUI64 | F32DemoteF64 |
+		F64ConvertSI32 | F64ConvertUI32 | F64ConvertSI64 | F64ConvertUI64 | F64PromoteF32 |
+		I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => true,
+		_ => false,
+	}
+}
+
+/// Size of `code` once compressed.
+fn compressed_size(code: &[u8]) -> u64 {
+	let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+	encoder.write_all(code).expect("Writing to a `Vec` can not fail; qed");
+	encoder.finish().expect("Writing to a `Vec` can not fail; qed").len() as u64
+}
+
+/// Render the report of the size of the WASM binary, with the size of the code per crate.
+///
+/// The crates are found in the name section, which is usually only kept in the bloaty binary.
+fn size_report(wasm_binary: &Path, bloaty: &Path, size: usize, compressed_size: u64) -> String {
+	let mut report = format!(
+		"WASM binary `{}`: {} bytes, {} bytes compressed\n",
+		wasm_binary.display(),
+		size,
+		compressed_size,
+	);
+
+	let sizes = [wasm_binary, bloaty].iter().filter_map(|binary| code_size_per_crate(binary)).next();
+	match sizes {
+		Some((binary, sizes)) => {
+			let mut sizes = sizes.into_iter().collect::<Vec<_>>();
+			sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+			report.push_str(&format!("Code size per crate, from the names of `{}`:\n", binary));
+			for (krate, size) in sizes {
+				report.push_str(&format!("  {:>10}  {}\n", size, krate));
+			}
+		},
+		None => report.push_str("No name section found, the code size per crate is not available.\n"),
+	}
+
+	report
+}
+
+/// The code size per crate of the WASM binary at `path`, along with the name of the binary.
+///
+/// Returns `None` if the binary has no name section.
+fn code_size_per_crate(path: &Path) -> Option<(String, BTreeMap<String, usize>)> {
+	let module = deserialize_file(path).ok()?.parse_names().ok()?;
+	let names = module.names_section()?.functions()?.names();
+	let imported = module.import_count(ImportCountType::Function);
+	let bodies = module.code_section().map(|s| s.bodies()).unwrap_or_default();
+
+	let mut sizes = BTreeMap::new();
+	for (index, body) in bodies.iter().enumerate() {
+		let size = serialize(body.clone()).map(|b| b.len()).unwrap_or_default();
+		let krate = names.get((index + imported) as u32)
+			.and_then(|name| crate_of_symbol(name))
+			.unwrap_or(OTHER_FUNCTIONS)
+			.to_string();
+		*sizes.entry(krate).or_insert(0) += size;
+	}
+
+	Some((path.display().to_string(), sizes))
+}
+
+/// The crate defining the function with the given `symbol`, either demangled or mangled.
+///
+/// For trait implementations, e.g. `<T as core::convert::Into<U>>::into`, this is the crate of
+/// the first path found in the implementation.
+fn crate_of_symbol(symbol: &str) -> Option<&str> {
+	let prefix = if symbol.starts_with("_ZN") {
+		// Legacy mangling: the first segment is prefixed by its length and `::` is escaped as `..`.
+		let rest = &symbol[3..];
+		let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+		let len = rest[..digits].parse::<usize>().ok()?;
+		let segment = rest.get(digits..digits + len)?;
+		&segment[..segment.find("..").unwrap_or_else(|| segment.len())]
+	} else {
+		&symbol[..symbol.find("::")?]
+	};
+
+	let start = prefix.rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).map_or(0, |i| i + 1);
+	Some(&prefix[start..]).filter(|krate| !krate.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parity_wasm::elements::{
+		CodeSection, Func, FuncBody, FunctionSection, GlobalEntry, GlobalSection, GlobalType,
+		ImportEntry, ImportSection, InitExpr, Instructions, Section, TypeSection,
+	};
+
+	fn module_importing(name: &str, ty: FunctionType) -> Module {
+		Module::new(vec![
+			Section::Type(TypeSection::with_types(vec![Type::Function(ty)])),
+			Section::Import(ImportSection::with_entries(vec![
+				ImportEntry::new("env".into(), name.into(), External::Function(0)),
+			])),
+		])
+	}
+
+	#[test]
+	fn should_find_the_crate_of_symbols() {
+		assert_eq!(crate_of_symbol("sp_io::storage::get"), Some("sp_io"));
+		assert_eq!(crate_of_symbol("<T as core::convert::Into<U>>::into"), Some("core"));
+		assert_eq!(crate_of_symbol("_ZN5alloc7raw_vec11finish_grow17h0123456789abcdefE"), Some("alloc"));
+		assert_eq!(
+			crate_of_symbol("_ZN50_$LT$T$u20$as$u20$core..convert..Into$LT$U$GT$$GT$4into17h0123456789abcdefE"),
+			Some("core"),
+		);
+		assert_eq!(crate_of_symbol("memcpy"), None);
+		assert_eq!(crate_of_symbol("_ZN99core"), None);
+	}
+
+	#[test]
+	fn should_match_extra_host_functions() {
+		// given
+		let settings = Settings {
+			extra_host_functions: vec!["ext_custom_call".into(), "ext_offchain_*".into()],
+			compressed_size_budget: None,
+		};
+
+		// then
+		assert!(settings.is_extra_host_function("ext_custom_call"));
+		assert!(!settings.is_extra_host_function("ext_custom_call_version_2"));
+		assert!(settings.is_extra_host_function("ext_offchain_submit_transaction_version_1"));
+		assert!(!settings.is_extra_host_function("ext_storage_get_version_1"));
+	}
+
+	#[test]
+	fn should_check_the_signatures_of_the_imports() {
+		// given
+		let valid = module_importing(
+			"ext_misc_print_num_version_1",
+			FunctionType::new(vec![ValueType::I64], None),
+		);
+		let invalid = module_importing(
+			"ext_misc_print_num_version_1",
+			FunctionType::new(vec![ValueType::I32], None),
+		);
+		let extra = module_importing("ext_custom_call", FunctionType::new(vec![], None));
+		let settings = Settings {
+			extra_host_functions: vec!["ext_custom_call".into()],
+			compressed_size_budget: None,
+		};
+
+		// when
+		let mut valid_errors = Vec::new();
+		check_imports(&valid, &Settings::default(), &mut valid_errors);
+		let mut invalid_errors = Vec::new();
+		check_imports(&invalid, &Settings::default(), &mut invalid_errors);
+		let mut extra_errors = Vec::new();
+		check_imports(&extra, &settings, &mut extra_errors);
+		let mut missing_errors = Vec::new();
+		check_imports(&extra, &Settings::default(), &mut missing_errors);
+
+		// then
+		assert!(valid_errors.is_empty());
+		assert_eq!(invalid_errors, vec![
+			"Imports `ext_misc_print_num_version_1` taking [I32] and returning None, but the host \
+			function takes [I64] and returns None.".to_string(),
+		]);
+		assert!(extra_errors.is_empty());
+		assert_eq!(missing_errors, vec!["Export ext_custom_call not found".to_string()]);
+	}
+
+	#[test]
+	fn should_find_floats() {
+		// given
+		let float_free = Module::new(vec![
+			Section::Type(TypeSection::with_types(vec![
+				Type::Function(FunctionType::new(vec![ValueType::I32], Some(ValueType::I64))),
+			])),
+			Section::Function(FunctionSection::with_entries(vec![Func::new(0)])),
+			Section::Code(CodeSection::with_bodies(vec![
+				FuncBody::new(vec![], Instructions::new(vec![Instruction::I64Const(0), Instruction::End])),
+			])),
+		]);
+		let with_floats = Module::new(vec![
+			Section::Type(TypeSection::with_types(vec![
+				Type::Function(FunctionType::new(vec![ValueType::F32], None)),
+				Type::Function(FunctionType::new(vec![], None)),
+			])),
+			Section::Function(FunctionSection::with_entries(vec![Func::new(1)])),
+			Section::Global(GlobalSection::with_entries(vec![
+				GlobalEntry::new(
+					GlobalType::new(ValueType::F64, false),
+					InitExpr::new(vec![Instruction::F64Const(0), Instruction::End]),
+				),
+			])),
+			Section::Code(CodeSection::with_bodies(vec![
+				FuncBody::new(vec![], Instructions::new(vec![
+					Instruction::I32Const(0),
+					Instruction::F32ConvertSI32,
+					Instruction::Drop,
+					Instruction::End,
+				])),
+			])),
+		]);
+
+		// when
+		let mut float_free_errors = Vec::new();
+		check_floats(&float_free, &mut float_free_errors);
+		let mut with_floats_errors = Vec::new();
+		check_floats(&with_floats, &mut with_floats_errors);
+
+		// then
+		assert!(float_free_errors.is_empty());
+		assert_eq!(with_floats_errors, vec![
+			"The WASM binary uses floating point: 1 function signature(s), 1 global(s) and \
+			1 function(s) have floating point values.".to_string(),
+		]);
+	}
+}
@@ -73,7 +73,7 @@ impl Drop for WorkspaceLock {
 	}
 }
 
-/// Creates the WASM project, compiles the WASM binary and compacts the WASM binary.
+/// Creates the WASM project, compiles the WASM binary, compacts and validates the WASM binary.
 ///
 /// # Returns
 /// The path to the compact WASM binary and the bloaty WASM binary.
@@ -97,6 +97,11 @@ pub fn create_and_compile(
 		&wasm_workspace,
 	);
 
+	if let Err(err_msg) = crate::validation::check(cargo_manifest, &wasm_binary.0, &bloaty.0) {
+		eprintln!("{}", err_msg);
+		process::exit(1);
+	}
+
 	copy_wasm_to_target_directory(cargo_manifest, &wasm_binary);
 
 	generate_rerun_if_changed_instructions(cargo_manifest, &project, &wasm_workspace);